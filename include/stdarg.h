@@ -0,0 +1,18 @@
+#ifndef _STDARG_H
+#define _STDARG_H	1
+
+/* x86-64 System V layout */
+struct __va_list_tag {
+    unsigned int gp_offset;
+    unsigned int fp_offset;
+    void *overflow_arg_area;
+    void *reg_save_area;
+};
+typedef struct __va_list_tag va_list[1];
+
+#define va_start(ap, last) __builtin_va_start(ap, last)
+#define va_arg(ap, type) __builtin_va_arg(ap, type)
+#define va_end(ap) __builtin_va_end(ap)
+#define va_copy(dest, src) __builtin_va_copy(dest, src)
+
+#endif /* <stdarg.h> included.  */
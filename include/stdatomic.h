@@ -0,0 +1,60 @@
+#ifndef _STDATOMIC_H
+#define _STDATOMIC_H	1
+
+/* every atomic operation is sequentially consistent, whatever memory order is asked for */
+typedef int memory_order;
+#define memory_order_relaxed 0
+#define memory_order_consume 1
+#define memory_order_acquire 2
+#define memory_order_release 3
+#define memory_order_acq_rel 4
+#define memory_order_seq_cst 5
+
+typedef _Atomic _Bool atomic_bool;
+typedef _Atomic char atomic_char;
+typedef _Atomic signed char atomic_schar;
+typedef _Atomic unsigned char atomic_uchar;
+typedef _Atomic short atomic_short;
+typedef _Atomic unsigned short atomic_ushort;
+typedef _Atomic int atomic_int;
+typedef _Atomic unsigned int atomic_uint;
+typedef _Atomic long atomic_long;
+typedef _Atomic unsigned long atomic_ulong;
+typedef _Atomic long long atomic_llong;
+typedef _Atomic unsigned long long atomic_ullong;
+
+#define ATOMIC_VAR_INIT(value) (value)
+#define atomic_init(obj, value) __atomic_store_n(obj, value, memory_order_relaxed)
+#define kill_dependency(y) (y)
+
+#define atomic_thread_fence(order) __atomic_thread_fence(order)
+#define atomic_signal_fence(order) __atomic_signal_fence(order)
+
+#define atomic_store(obj, desired) __atomic_store_n(obj, desired, memory_order_seq_cst)
+#define atomic_store_explicit(obj, desired, order) __atomic_store_n(obj, desired, order)
+#define atomic_load(obj) __atomic_load_n(obj, memory_order_seq_cst)
+#define atomic_load_explicit(obj, order) __atomic_load_n(obj, order)
+#define atomic_exchange(obj, desired) __atomic_exchange_n(obj, desired, memory_order_seq_cst)
+#define atomic_exchange_explicit(obj, desired, order) __atomic_exchange_n(obj, desired, order)
+
+#define atomic_compare_exchange_strong(obj, expected, desired) \
+    __atomic_compare_exchange_n(obj, expected, desired, 0, memory_order_seq_cst, memory_order_seq_cst)
+#define atomic_compare_exchange_strong_explicit(obj, expected, desired, success, failure) \
+    __atomic_compare_exchange_n(obj, expected, desired, 0, success, failure)
+#define atomic_compare_exchange_weak(obj, expected, desired) \
+    __atomic_compare_exchange_n(obj, expected, desired, 1, memory_order_seq_cst, memory_order_seq_cst)
+#define atomic_compare_exchange_weak_explicit(obj, expected, desired, success, failure) \
+    __atomic_compare_exchange_n(obj, expected, desired, 1, success, failure)
+
+#define atomic_fetch_add(obj, arg) __atomic_fetch_add(obj, arg, memory_order_seq_cst)
+#define atomic_fetch_add_explicit(obj, arg, order) __atomic_fetch_add(obj, arg, order)
+#define atomic_fetch_sub(obj, arg) __atomic_fetch_sub(obj, arg, memory_order_seq_cst)
+#define atomic_fetch_sub_explicit(obj, arg, order) __atomic_fetch_sub(obj, arg, order)
+#define atomic_fetch_or(obj, arg) __atomic_fetch_or(obj, arg, memory_order_seq_cst)
+#define atomic_fetch_or_explicit(obj, arg, order) __atomic_fetch_or(obj, arg, order)
+#define atomic_fetch_xor(obj, arg) __atomic_fetch_xor(obj, arg, memory_order_seq_cst)
+#define atomic_fetch_xor_explicit(obj, arg, order) __atomic_fetch_xor(obj, arg, order)
+#define atomic_fetch_and(obj, arg) __atomic_fetch_and(obj, arg, memory_order_seq_cst)
+#define atomic_fetch_and_explicit(obj, arg, order) __atomic_fetch_and(obj, arg, order)
+
+#endif /* <stdatomic.h> included.  */
@@ -2,5 +2,7 @@
 #define _STDLIB_H	1
 
 void *malloc(long size);
+_Noreturn void abort();
+_Noreturn void exit(int status);
 
 #endif /* <stdlib.h> included.  */
@@ -81,6 +81,25 @@ pub enum BinaryOperationEnum {
     Comma,
 }
 
+impl AssignOperationEnum {
+    /// the operation performed by a compound assignment, None for `=`
+    pub fn binary_operation(&self) -> Option<BinaryOperationEnum> {
+        match self {
+            AssignOperationEnum::Naive => None,
+            AssignOperationEnum::Addition => Some(BinaryOperationEnum::Addition),
+            AssignOperationEnum::Subtraction => Some(BinaryOperationEnum::Subtraction),
+            AssignOperationEnum::Multiplication => Some(BinaryOperationEnum::Multiplication),
+            AssignOperationEnum::Division => Some(BinaryOperationEnum::Division),
+            AssignOperationEnum::Modulo => Some(BinaryOperationEnum::Modulo),
+            AssignOperationEnum::BitwiseAnd => Some(BinaryOperationEnum::BitwiseAnd),
+            AssignOperationEnum::BitwiseOr => Some(BinaryOperationEnum::BitwiseOr),
+            AssignOperationEnum::BitwiseXor => Some(BinaryOperationEnum::BitwiseXor),
+            AssignOperationEnum::LeftShift => Some(BinaryOperationEnum::LeftShift),
+            AssignOperationEnum::RightShift => Some(BinaryOperationEnum::RightShift),
+        }
+    }
+}
+
 impl Default for AssignOperation {
     fn default() -> Self {
         AssignOperation {
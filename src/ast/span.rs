@@ -1,6 +1,7 @@
+use serde::Serialize;
 use std::convert::From;
 
-#[derive(Default, Debug, Clone, Copy, PartialEq)]
+#[derive(Serialize, Default, Debug, Clone, Copy, PartialEq)]
 pub struct Span {
     pub start: usize,
     pub end: usize,
@@ -5,6 +5,7 @@ use super::operations::*;
 use super::span::*;
 use super::types::*;
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Serialize, Debug, PartialEq, Clone)]
 pub enum AST {
     GlobalDeclaration(Vec<Declaration>),
@@ -28,6 +29,7 @@ pub enum DeclarationEnum {
     FunctionDefinition(
         Vec<FunctionSpecifier>,
         StorageClassSpecifier,
+        Vec<Attribute>,
         /// return type
         Box<BasicType>,
         /// identifier
@@ -87,6 +89,36 @@ pub enum StatementEnum {
     Continue,
     Return(Option<Box<Expression>>),
     Goto(String),
+    // assembly statement
+    Asm(Box<AsmStatement>),
+}
+
+/// GNU inline assembly, `asm volatile ("..." : outputs : inputs : clobbers : labels)`.
+#[derive(Serialize, Debug, PartialEq, Clone)]
+pub struct AsmStatement {
+    /// the assembler template, with `%0`-style operand references unless it is basic
+    pub template: String,
+    #[serde(skip)]
+    pub template_span: Span,
+    /// basic assembly, without any colon, has no operands and its template is emitted as is
+    pub is_basic: bool,
+    pub is_volatile: bool,
+    pub outputs: Vec<AsmOperand>,
+    pub inputs: Vec<AsmOperand>,
+    /// clobbered registers, or `memory` and `cc`
+    pub clobbers: Vec<(String, Span)>,
+    /// labels the assembly may jump to, for `asm goto`
+    pub labels: Vec<String>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Clone)]
+pub struct AsmOperand {
+    /// symbolic name, referenced as `%[name]` in the template
+    pub name: Option<String>,
+    pub constraint: String,
+    pub expression: Expression,
+    #[serde(skip)]
+    pub span: Span,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -128,6 +160,24 @@ pub enum ExpressionEnum {
         Box<Expression>,
     ),
     SizeofType(BasicType),
+    GenericSelection(
+        /// controlling expression
+        Box<Expression>,
+        /// associations, None represents default association
+        Vec<(Option<BasicType>, Expression)>,
+    ),
+    VaArg(
+        /// va_list
+        Box<Expression>,
+        /// type of the argument
+        BasicType,
+    ),
+    Offsetof(
+        /// struct or union type
+        BasicType,
+        /// designator of the member, through nested members
+        Vec<String>,
+    ),
     MemberOfObject(
         /// object
         Box<Expression>,
@@ -154,13 +204,96 @@ pub enum ExpressionEnum {
     UnsignedLongConstant(u64),
     LongLongConstant(i64),
     UnsignedLongLongConstant(u64),
-    CharacterConstant(char),
+    CharacterConstant(
+        /// encoding prefix
+        EncodingPrefix,
+        /// value of the constant
+        i64,
+    ),
     FloatConstant(f32),
     DoubleConstant(f64),
-    StringLiteral(String),
+    StringLiteral(
+        /// encoding prefix
+        EncodingPrefix,
+        /// code units, without the terminating null character
+        Vec<u32>,
+    ),
     Empty,
 }
 
+impl ExpressionEnum {
+    /// value of a nonnegative integer constant, e.g. an array size or an attribute argument
+    pub fn integer_literal(&self) -> Option<u64> {
+        match *self {
+            ExpressionEnum::IntegerConstant(value) => u64::try_from(value).ok(),
+            ExpressionEnum::UnsignedIntegerConstant(value) => Some(value as u64),
+            ExpressionEnum::LongConstant(value) | ExpressionEnum::LongLongConstant(value) => {
+                u64::try_from(value).ok()
+            }
+            ExpressionEnum::UnsignedLongConstant(value)
+            | ExpressionEnum::UnsignedLongLongConstant(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq, Clone, Copy)]
+pub enum EncodingPrefix {
+    /// no prefix
+    Char,
+    /// `u8`
+    Utf8,
+    /// `L`
+    Wide,
+    /// `u`
+    Utf16,
+    /// `U`
+    Utf32,
+}
+
+impl EncodingPrefix {
+    /// type of the elements of a string literal with this prefix
+    pub fn element_type(&self) -> BaseType {
+        match self {
+            EncodingPrefix::Char | EncodingPrefix::Utf8 => {
+                BaseType::SignedInteger(IntegerType::Char)
+            }
+            EncodingPrefix::Wide => BaseType::SignedInteger(IntegerType::Int),
+            EncodingPrefix::Utf16 => BaseType::UnsignedInteger(IntegerType::Short),
+            EncodingPrefix::Utf32 => BaseType::UnsignedInteger(IntegerType::Int),
+        }
+    }
+
+    /// type of a character constant with this prefix, an unprefixed one is an `int` (C99
+    /// 6.4.4.4p10)
+    pub fn character_type(&self) -> BaseType {
+        match self {
+            EncodingPrefix::Char => BaseType::SignedInteger(IntegerType::Int),
+            _ => self.element_type(),
+        }
+    }
+
+    /// whether a string literal with this prefix can initialize an array of `element_type`
+    pub fn initializes(&self, element_type: &BaseType) -> bool {
+        matches!(
+            (self, element_type),
+            (
+                EncodingPrefix::Char | EncodingPrefix::Utf8,
+                BaseType::SignedInteger(IntegerType::Char)
+                    | BaseType::UnsignedInteger(IntegerType::Char)
+            ) | (
+                EncodingPrefix::Wide | EncodingPrefix::Utf32,
+                BaseType::SignedInteger(IntegerType::Int)
+                    | BaseType::UnsignedInteger(IntegerType::Int)
+            ) | (
+                EncodingPrefix::Utf16,
+                BaseType::SignedInteger(IntegerType::Short)
+                    | BaseType::UnsignedInteger(IntegerType::Short)
+            )
+        )
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct ForInitClause {
     pub node: ForInitClauseEnum,
@@ -188,7 +321,7 @@ pub enum StatementOrDeclarationEnum {
 impl Default for Statement {
     fn default() -> Self {
         Statement {
-            node: StatementEnum::Expression(Box::new(Default::default())),
+            node: StatementEnum::Expression(Box::default()),
             span: Default::default(),
         }
     }
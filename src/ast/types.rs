@@ -9,15 +9,18 @@ use serde::Serialize;
 pub struct Type {
     pub function_specifier: Vec<FunctionSpecifier>,
     pub storage_class_specifier: StorageClassSpecifier,
+    /// `_Thread_local`, alone or with `static` or `extern`
+    pub thread_local: bool,
     pub basic_type: BasicType,
+    pub attributes: Vec<Attribute>,
 }
 
-#[derive(Serialize, Debug, PartialEq, Clone)]
+#[derive(Serialize, Debug, PartialEq, Clone, Default)]
 pub enum StorageClassSpecifier {
     Typedef,
     Extern,
     Static,
-    ThreadLocal,
+    #[default]
     Auto,
     Register,
 }
@@ -36,6 +39,16 @@ pub enum FunctionSpecifier {
     Noreturn,
 }
 
+/// A GNU attribute, like `aligned(16)` in `__attribute__((aligned(16)))`.
+#[derive(Serialize, Debug, PartialEq, Clone)]
+pub struct Attribute {
+    /// name without surrounding double underscores, `packed` for `__packed__`
+    pub name: String,
+    pub arguments: Vec<Expression>,
+    #[serde(skip)]
+    pub span: Span,
+}
+
 #[derive(Serialize, Debug, PartialEq, Clone, Default)]
 pub struct BasicType {
     pub qualifier: Vec<TypeQualifier>,
@@ -94,6 +107,8 @@ pub enum IntegerType {
 pub struct StructMember {
     pub member_name: String,
     pub member_type: BasicType,
+    /// including those of the struct or union itself, see `Parse::build_struct_specifier`
+    pub attributes: Vec<Attribute>,
 }
 
 impl Default for BaseType {
@@ -102,15 +117,102 @@ impl Default for BaseType {
     }
 }
 
-impl<'ctx> BasicType {
+impl BasicType {
     pub fn is_const(&self) -> bool {
         self.qualifier
             .iter()
             .any(|x| matches!(x, TypeQualifier::Const))
     }
+
+    fn has_qualifiers_of(&self, rhs: &BasicType) -> bool {
+        rhs.qualifier.iter().all(|q| self.qualifier.contains(q))
+    }
+
+    /// Check that a pointer to `self` converts implicitly to a pointer to `dest`, from `from` to
+    /// `to`: the pointed-to type may gain qualifiers but not lose them, and the types it points to
+    /// in turn must be qualified alike (C99 6.5.16.1p1).
+    fn test_pointee_cast(
+        &self,
+        dest: &BasicType,
+        from: &BaseType,
+        to: &BaseType,
+        span: Span,
+        typedef_map: &HashMap<String, BasicType>,
+    ) -> Result<(), CE> {
+        if let Some(q) = self.qualifier.iter().find(|q| !dest.qualifier.contains(q)) {
+            return Err(CE::discards_qualifier(
+                from.to_string(),
+                to.to_string(),
+                q.to_string(),
+                span,
+            ));
+        }
+        let mut pointees = (self, dest);
+        while let (BaseType::Pointer(lhs), BaseType::Pointer(rhs)) = (
+            pointees.0.base_type.resolve(typedef_map),
+            pointees.1.base_type.resolve(typedef_map),
+        ) {
+            if !(lhs.has_qualifiers_of(rhs) && rhs.has_qualifiers_of(lhs)) {
+                return Err(CE::incompatible_pointer_types(
+                    from.to_string(),
+                    to.to_string(),
+                    span,
+                ));
+            }
+            pointees = (lhs, rhs);
+        }
+        Ok(())
+    }
+
+    /// Complete the type of an array declared like `char s[] = "abc";`, whose omitted length is
+    /// taken from the string literal, including the terminating null character.
+    pub(crate) fn complete_array_type(
+        &self,
+        var_name: &str,
+        initializer: &Option<Box<Expression>>,
+        span: Span,
+    ) -> Result<BasicType, CE> {
+        let mut var_type = self.to_owned();
+        if let BaseType::Array(_, ref mut dimensions) = var_type.base_type {
+            if dimensions[0].node == ExpressionEnum::Empty {
+                match initializer.as_ref().map(|initializer| &initializer.node) {
+                    Some(ExpressionEnum::StringLiteral(_, code_units)) => {
+                        dimensions[0].node =
+                            ExpressionEnum::IntegerConstant(code_units.len() as i32 + 1);
+                    }
+                    _ => return Err(CE::array_size_missing(var_name.to_string(), span)),
+                }
+            }
+        }
+        Ok(var_type)
+    }
 }
 
-impl<'ctx> BaseType {
+impl BaseType {
+    /// integer types, `_Bool` included; typedef names are not looked through
+    pub fn is_integer(&self) -> bool {
+        matches!(
+            self,
+            BaseType::Bool | BaseType::SignedInteger(_) | BaseType::UnsignedInteger(_)
+        )
+    }
+
+    pub fn is_arithmetic(&self) -> bool {
+        self.is_integer() || matches!(self, BaseType::Float | BaseType::Double)
+    }
+
+    pub fn is_scalar(&self) -> bool {
+        self.is_arithmetic() || matches!(self, BaseType::Pointer(_))
+    }
+
+    /// The type named by a typedef name, other types are kept.
+    fn resolve<'t>(&'t self, typedef_map: &'t HashMap<String, BasicType>) -> &'t BaseType {
+        match self {
+            BaseType::Identifier(name) => &typedef_map[name].base_type,
+            _ => self,
+        }
+    }
+
     fn cast_rank(&self, typedef_map: &HashMap<String, BasicType>) -> i32 {
         let true_self = match *self {
             BaseType::Identifier(ref name) => {
@@ -141,57 +243,97 @@ impl<'ctx> BaseType {
         }
     }
 
-    pub(crate) fn upcast(
-        lhs: &BaseType,
-        rhs: &BaseType,
-        typedef_map: &HashMap<String, BasicType>,
-    ) -> Result<BaseType, CE> {
-        if lhs.cast_rank(typedef_map) >= rhs.cast_rank(typedef_map) {
-            Ok(lhs.clone())
-        } else {
-            Ok(rhs.clone())
+    /// The integer conversion rank (C99 6.3.1.1p1), `_Bool` being the lowest.
+    fn integer_rank(&self) -> u8 {
+        match self {
+            BaseType::Bool => 0,
+            BaseType::SignedInteger(t) | BaseType::UnsignedInteger(t) => match t {
+                IntegerType::Char => 1,
+                IntegerType::Short => 2,
+                IntegerType::Int => 3,
+                IntegerType::Long => 4,
+                IntegerType::LongLong => 5,
+            },
+            _ => unreachable!(),
         }
     }
 
-    pub(crate) fn equal_discarding_qualifiers(
-        &self,
-        rhs: &BaseType,
-        typedef_map: &HashMap<String, BasicType>,
-    ) -> bool {
-        let true_self = match *self {
-            BaseType::Identifier(ref name) => {
-                if let Some(typedef) = typedef_map.get(name) {
-                    &typedef.base_type
-                } else {
-                    unreachable!()
-                }
+    /// The size in bytes of an integer type on x86-64.
+    pub(crate) fn integer_size(&self) -> u8 {
+        match self {
+            BaseType::Bool => 1,
+            BaseType::SignedInteger(t) | BaseType::UnsignedInteger(t) => match t {
+                IntegerType::Char => 1,
+                IntegerType::Short => 2,
+                IntegerType::Int => 4,
+                IntegerType::Long | IntegerType::LongLong => 8,
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    /// The integer promotions (C99 6.3.1.1p2): the integer types ranked below `int` are
+    /// promoted to `int`, which represents all their values, other types are kept.
+    pub fn promote(&self) -> BaseType {
+        match self {
+            BaseType::Bool
+            | BaseType::SignedInteger(IntegerType::Char | IntegerType::Short)
+            | BaseType::UnsignedInteger(IntegerType::Char | IntegerType::Short) => {
+                BaseType::SignedInteger(IntegerType::Int)
             }
-            _ => self,
-        };
-        let true_rhs = match *rhs {
-            BaseType::Identifier(ref name) => {
-                if let Some(typedef) = typedef_map.get(name) {
-                    &typedef.base_type
-                } else {
-                    unreachable!()
+            _ => self.to_owned(),
+        }
+    }
+
+    /// The common type of arithmetic operands by the usual arithmetic conversions
+    /// (C99 6.3.1.8).
+    pub fn usual_arithmetic_conversion(lhs: &BaseType, rhs: &BaseType) -> BaseType {
+        if *lhs == BaseType::Double || *rhs == BaseType::Double {
+            return BaseType::Double;
+        }
+        if *lhs == BaseType::Float || *rhs == BaseType::Float {
+            return BaseType::Float;
+        }
+        let (lhs, rhs) = (lhs.promote(), rhs.promote());
+        let (signed, unsigned) = match (&lhs, &rhs) {
+            _ if lhs == rhs => return lhs,
+            // with the same signedness, the operand of lesser rank is converted
+            (BaseType::SignedInteger(_), BaseType::SignedInteger(_))
+            | (BaseType::UnsignedInteger(_), BaseType::UnsignedInteger(_)) => {
+                return match lhs.integer_rank() >= rhs.integer_rank() {
+                    true => lhs,
+                    false => rhs,
                 }
             }
-            _ => rhs,
+            (BaseType::SignedInteger(_), _) => (lhs, rhs),
+            _ => (rhs, lhs),
         };
-
-        if true_self == true_rhs {
-            return true;
+        match signed {
+            _ if unsigned.integer_rank() >= signed.integer_rank() => unsigned,
+            // the signed type represents all the values of the unsigned one
+            _ if signed.integer_size() > unsigned.integer_size() => signed,
+            BaseType::SignedInteger(t) => BaseType::UnsignedInteger(t),
+            _ => unreachable!(),
         }
+    }
 
-        if let BaseType::Pointer(lhs_inner) = true_self {
-            if let BaseType::Pointer(rhs_inner) = true_rhs {
-                return lhs_inner
-                    .base_type
-                    .equal_discarding_qualifiers(&rhs_inner.base_type, typedef_map);
+    /// Whether `self` and `rhs` are the same type but for their own qualifiers: the types they
+    /// point to must be qualified alike.
+    pub(crate) fn equal_discarding_qualifiers(
+        &self,
+        rhs: &BaseType,
+        typedef_map: &HashMap<String, BasicType>,
+    ) -> bool {
+        match (self.resolve(typedef_map), rhs.resolve(typedef_map)) {
+            (BaseType::Pointer(lhs), BaseType::Pointer(rhs)) => {
+                lhs.has_qualifiers_of(rhs)
+                    && rhs.has_qualifiers_of(lhs)
+                    && lhs
+                        .base_type
+                        .equal_discarding_qualifiers(&rhs.base_type, typedef_map)
             }
+            (lhs, rhs) => lhs == rhs,
         }
-
-        false
     }
 
     pub fn test_cast(
@@ -226,9 +368,8 @@ impl<'ctx> BaseType {
             return Ok(());
         }
 
-        if let (BaseType::Pointer(_), BaseType::Pointer(_)) = (true_self, true_dest) {
-            // TODO: handle const
-            return Ok(());
+        if let (BaseType::Pointer(lhs_ptr), BaseType::Pointer(rhs_ptr)) = (true_self, true_dest) {
+            return lhs_ptr.test_pointee_cast(rhs_ptr, self, dest, span, typedef_map);
         }
 
         if let (BaseType::Array(lhs_type, lhs_expr), BaseType::Pointer(rhs_ptr)) =
@@ -241,6 +382,13 @@ impl<'ctx> BaseType {
                     span,
                 ));
             }
+            lhs_type.test_pointee_cast(rhs_ptr, self, dest, span, typedef_map)?;
+            if lhs_type
+                .base_type
+                .equal_discarding_qualifiers(&rhs_ptr.base_type, typedef_map)
+            {
+                return Ok(());
+            }
             //make sure they are both basic type(not pointer or array)
             lhs_type.base_type.cast_rank(typedef_map);
             rhs_ptr.base_type.cast_rank(typedef_map);
@@ -274,6 +422,17 @@ impl<'ctx> BaseType {
     }
 }
 
+impl fmt::Display for TypeQualifier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TypeQualifier::Const => write!(f, "const"),
+            TypeQualifier::Volatile => write!(f, "volatile"),
+            TypeQualifier::Restrict => write!(f, "restrict"),
+            TypeQualifier::Atomic => write!(f, "_Atomic"),
+        }
+    }
+}
+
 impl fmt::Display for BaseType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -292,16 +451,23 @@ impl fmt::Display for BaseType {
             BaseType::Float => write!(f, "float"),
             BaseType::Double => write!(f, "double"),
             BaseType::Pointer(inner) => {
-                write!(f, "{}", inner.base_type)?;
-                write!(f, "*")
+                let qualifiers = inner
+                    .qualifier
+                    .iter()
+                    .map(TypeQualifier::to_string)
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                match inner.base_type {
+                    _ if qualifiers.is_empty() => write!(f, "{}*", inner.base_type),
+                    BaseType::Pointer(_) => write!(f, "{} {}*", inner.base_type, qualifiers),
+                    _ => write!(f, "{} {}*", qualifiers, inner.base_type),
+                }
             }
+            BaseType::Struct(Some(name), _) => write!(f, "struct {}", name),
+            BaseType::Union(Some(name), _) => write!(f, "union {}", name),
+            BaseType::Struct(None, _) => write!(f, "struct <anonymous>"),
+            BaseType::Union(None, _) => write!(f, "union <anonymous>"),
             _ => write!(f, "{:?}", self),
         }
     }
 }
-
-impl Default for StorageClassSpecifier {
-    fn default() -> Self {
-        StorageClassSpecifier::Auto
-    }
-}
@@ -0,0 +1,208 @@
+use crate::ast::{BasicType, Span};
+use crate::generator::Generator;
+use crate::sema::TypedAsmStatement;
+use crate::utils::asm::{
+    parse_clobber, parse_constraint, translate_basic_template, translate_template,
+};
+use crate::utils::CompileErr as CE;
+use inkwell::attributes::{Attribute, AttributeLoc};
+use inkwell::types::{BasicMetadataTypeEnum, BasicType as _};
+use inkwell::values::{BasicMetadataValueEnum, BasicValueEnum, CallableValue, PointerValue};
+use std::convert::TryFrom;
+
+impl<'ctx> Generator<'ctx> {
+    /// Inline assembly is a call to an LLVM inline assembly value, whose constraints and
+    /// arguments follow the operands in the source so that the template refers to the same
+    /// operand numbers. Outputs in registers are returned by the call and stored to their
+    /// lvalues, operands in memory are passed by address, and `+` outputs are also read, by
+    /// inputs tied to them after all others.
+    pub(crate) fn gen_asm_statement(
+        &mut self,
+        asm: &TypedAsmStatement,
+        span: Span,
+    ) -> Result<(), CE> {
+        let mut constraints = vec![];
+        let mut args: Vec<BasicValueEnum<'ctx>> = vec![];
+        let mut tied_inputs = vec![];
+        let mut output_types = vec![];
+        let mut results: Vec<(BasicType, PointerValue<'ctx>)> = vec![];
+        // arguments passed by address
+        let mut indirect_args = vec![];
+
+        for (i, output) in asm.outputs.iter().enumerate() {
+            // the constraints are checked by the semantic analysis
+            let constraint = parse_constraint(&output.constraint, true, 0).unwrap();
+            let t = output.expression.expr_type.to_owned();
+            let ptr = self.get_lvalue(&output.expression)?;
+            let early_clobber = if constraint.is_early_clobber { "&" } else { "" };
+            if constraint.is_indirect() {
+                constraints.push(format!("=*{}{}", early_clobber, constraint.codes));
+                indirect_args.push(args.len());
+                args.push(ptr.into());
+                if constraint.is_read_write {
+                    tied_inputs.push((format!("*{}", constraint.codes), ptr.into()));
+                }
+            } else {
+                constraints.push(format!("={}{}", early_clobber, constraint.codes));
+                if constraint.is_read_write {
+                    let value = self.build_object_load(&t, ptr, "asm_operand");
+                    tied_inputs.push((i.to_string(), value));
+                }
+                results.push((t.to_owned(), ptr));
+            }
+            output_types.push(t);
+        }
+
+        for input in &asm.inputs {
+            let constraint = parse_constraint(&input.constraint, false, asm.outputs.len()).unwrap();
+            if let Some(index) = constraint.matching_output {
+                // an input sharing the register of an output has its type
+                let value = self.gen_expression(&input.expression)?;
+                let output_type = &output_types[index].base_type;
+                constraints.push(index.to_string());
+                args.push(self.cast_value(
+                    &input.expression.expr_type.base_type,
+                    &value,
+                    output_type,
+                    input.expression.span,
+                )?);
+            } else if constraint.is_indirect() {
+                let ptr = self.get_lvalue(&input.expression)?;
+                constraints.push(format!("*{}", constraint.codes));
+                indirect_args.push(args.len());
+                args.push(ptr.into());
+            } else {
+                let value = self.gen_expression(&input.expression)?;
+                let is_constant = match value {
+                    BasicValueEnum::IntValue(value) => value.is_const(),
+                    BasicValueEnum::FloatValue(value) => value.is_const(),
+                    BasicValueEnum::PointerValue(value) => value.is_const(),
+                    _ => false,
+                };
+                if !constraint.allows_register && !constraint.allows_memory && !is_constant {
+                    return Err(CE::invalid_asm_constraint(
+                        input.constraint.to_owned(),
+                        "the operand must be a constant".to_string(),
+                        input.expression.span,
+                    ));
+                }
+                constraints.push(constraint.codes);
+                args.push(value);
+            }
+        }
+
+        let label_blocks = asm
+            .labels
+            .iter()
+            .map(|label| self.label_block(label))
+            .collect::<Vec<_>>();
+        for label_block in &label_blocks {
+            constraints.push("X".to_string());
+            args.push(unsafe { label_block.get_address() }.unwrap().into());
+        }
+
+        for (constraint, value) in tied_inputs {
+            if constraint.starts_with('*') {
+                indirect_args.push(args.len());
+            }
+            constraints.push(constraint);
+            args.push(value);
+        }
+        for clobber in &asm.clobbers {
+            if let Ok(Some(register)) = parse_clobber(clobber) {
+                constraints.push(format!("~{{{}}}", register));
+            }
+        }
+        // like Clang, the direction flag and the x87 status word are clobbered too
+        constraints.extend(["~{dirflag}", "~{fpsr}", "~{flags}"].map(String::from));
+
+        let template = match asm.is_basic {
+            true => translate_basic_template(&asm.template),
+            false => {
+                let names = asm
+                    .outputs
+                    .iter()
+                    .chain(asm.inputs.iter())
+                    .map(|operand| operand.name.to_owned())
+                    .collect::<Vec<_>>();
+                translate_template(&asm.template, &names, &asm.labels).unwrap()
+            }
+        };
+
+        let param_types = args
+            .iter()
+            .map(|arg| BasicMetadataTypeEnum::from(arg.get_type()))
+            .collect::<Vec<_>>();
+        let result_types = results
+            .iter()
+            .map(|(t, _)| self.convert_llvm_type(&t.base_type, span))
+            .collect::<Result<Vec<_>, _>>()?;
+        let fn_type = match result_types.len() {
+            0 => self.context.void_type().fn_type(&param_types, false),
+            1 => result_types[0].fn_type(&param_types, false),
+            _ => self
+                .context
+                .struct_type(&result_types, false)
+                .fn_type(&param_types, false),
+        };
+        let inline_asm = self.context.create_inline_asm(
+            fn_type,
+            template,
+            constraints.join(","),
+            asm.is_volatile,
+            false,
+            None,
+            false,
+        );
+        let args = args
+            .into_iter()
+            .map(BasicMetadataValueEnum::from)
+            .collect::<Vec<_>>();
+        let call = self
+            .builder
+            .build_call(CallableValue::try_from(inline_asm).unwrap(), &args, "");
+        // newer versions of LLVM need the type of operands passed by address
+        let element_type = Attribute::get_named_enum_kind_id("elementtype");
+        if element_type != 0 {
+            for index in indirect_args {
+                let pointee_type = args[index]
+                    .into_pointer_value()
+                    .get_type()
+                    .get_element_type();
+                call.add_attribute(
+                    AttributeLoc::Param(index as u32),
+                    self.context
+                        .create_type_attribute(element_type, pointee_type),
+                );
+            }
+        }
+
+        if let Some(result) = call.try_as_basic_value().left() {
+            for (i, (t, ptr)) in results.iter().enumerate() {
+                let value = match results.len() {
+                    1 => result,
+                    _ => self
+                        .builder
+                        .build_extract_value(result.into_struct_value(), i as u32, "asm_result")
+                        .unwrap(),
+                };
+                self.build_object_store(t, *ptr, value);
+            }
+        }
+
+        if !label_blocks.is_empty() {
+            // LLVM-C cannot build `callbr`: the jumps of `asm goto` to the addresses of the labels
+            // are modeled by an indirect branch, which may also fall through
+            let func = self.current_function.as_ref().unwrap().0;
+            let fallthrough_block = self.context.append_basic_block(func, "asm_fallthrough");
+            let mut destinations = vec![fallthrough_block];
+            destinations.extend(label_blocks);
+            self.builder.build_indirect_branch(
+                unsafe { fallthrough_block.get_address() }.unwrap(),
+                &destinations,
+            );
+            self.builder.position_at_end(fallthrough_block);
+        }
+        Ok(())
+    }
+}
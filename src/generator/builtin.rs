@@ -0,0 +1,482 @@
+use crate::ast::{BaseType, BasicType, Span, TypeQualifier};
+use crate::generator::Generator;
+use crate::sema::TypedExpression;
+use crate::utils::builtin::{builtin_signature, is_overflow_builtin};
+use crate::utils::CompileErr as CE;
+use inkwell::types::{BasicType as _, FunctionType};
+use inkwell::values::{
+    BasicMetadataValueEnum, BasicValue, BasicValueEnum, FunctionValue, PointerValue,
+};
+use inkwell::{AddressSpace, AtomicOrdering, AtomicRMWBinOp, IntPredicate};
+
+/// Generates a call to a compiler builtin, given its name and arguments checked by the
+/// semantic analysis.
+type BuiltinGenerator<'ctx> =
+    fn(&Generator<'ctx>, &str, &[TypedExpression], Span) -> Result<BasicValueEnum<'ctx>, CE>;
+
+impl<'ctx> Generator<'ctx> {
+    /// The registry of compiler builtins, which are generated inline rather than called.
+    fn builtin_generator(name: &str) -> Option<BuiltinGenerator<'ctx>> {
+        Some(match name {
+            // the second argument of `va_start` only names the last fixed parameter
+            "__builtin_va_start" => |g, _, args, _| {
+                g.gen_va_intrinsic_call("llvm.va_start", &args[..1])
+                    .map(|_| g.void_value())
+            },
+            "__builtin_va_end" => |g, _, args, _| {
+                g.gen_va_intrinsic_call("llvm.va_end", args)
+                    .map(|_| g.void_value())
+            },
+            "__builtin_va_copy" => |g, _, args, _| {
+                g.gen_va_intrinsic_call("llvm.va_copy", args)
+                    .map(|_| g.void_value())
+            },
+            _ if Self::is_atomic_builtin_function(name) => Self::gen_atomic_builtin_call,
+            _ if is_overflow_builtin(name) => Self::gen_overflow_builtin_call,
+            _ if builtin_signature(name).is_some() => Self::gen_prototyped_builtin_call,
+            _ => return None,
+        })
+    }
+
+    fn is_atomic_builtin_function(name: &str) -> bool {
+        matches!(
+            name,
+            "__atomic_load_n"
+                | "__atomic_store_n"
+                | "__atomic_exchange_n"
+                | "__atomic_compare_exchange_n"
+                | "__atomic_fetch_add"
+                | "__atomic_fetch_sub"
+                | "__atomic_fetch_and"
+                | "__atomic_fetch_or"
+                | "__atomic_fetch_xor"
+                | "__atomic_thread_fence"
+                | "__atomic_signal_fence"
+        )
+    }
+
+    /// Generate a call to a compiler builtin, recognized by the semantic analysis.
+    pub(crate) fn gen_builtin_call(
+        &self,
+        name: &str,
+        args: &[TypedExpression],
+        span: Span,
+    ) -> Result<BasicValueEnum<'ctx>, CE> {
+        let generator = Self::builtin_generator(name).unwrap();
+        generator(self, name, args, span)
+    }
+
+    /// The builtins with a signature, mostly lowered to the LLVM intrinsic of the same name.
+    /// The arguments are converted to the parameter types.
+    fn gen_prototyped_builtin_call(
+        &self,
+        name: &str,
+        args: &[TypedExpression],
+        span: Span,
+    ) -> Result<BasicValueEnum<'ctx>, CE> {
+        let (_, return_type) = builtin_signature(name).unwrap();
+        let values = args
+            .iter()
+            .map(|arg| self.gen_expression(arg))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let i1_type = self.context.bool_type();
+        let value = match name {
+            "__builtin_expect" => {
+                // the expected value only guides the optimizer
+                let i64_type = self.context.i64_type();
+                let intrinsic = self.get_intrinsic(
+                    "llvm.expect.i64",
+                    i64_type.fn_type(&[i64_type.into(), i64_type.into()], false),
+                );
+                self.build_intrinsic_call(intrinsic, &values)
+            }
+            "__builtin_unreachable" | "__builtin_trap" => {
+                if name == "__builtin_trap" {
+                    let intrinsic = self
+                        .get_intrinsic("llvm.trap", self.context.void_type().fn_type(&[], false));
+                    self.builder.build_call(intrinsic, &[], "");
+                }
+                self.builder.build_unreachable();
+                // code after the call is unreachable, but still generated
+                let func = self.current_function.as_ref().unwrap().0;
+                let after_block = self.context.append_basic_block(func, "after_unreachable");
+                self.builder.position_at_end(after_block);
+                return Ok(self.void_value());
+            }
+            "__builtin_memcpy" => {
+                self.builder
+                    .build_memcpy(
+                        values[0].into_pointer_value(),
+                        1,
+                        values[1].into_pointer_value(),
+                        1,
+                        values[2].into_int_value(),
+                    )
+                    .unwrap();
+                values[0]
+            }
+            "__builtin_memset" => {
+                let i8_type = self.context.i8_type();
+                let i8_ptr_type = i8_type.ptr_type(AddressSpace::Generic);
+                let i64_type = self.context.i64_type();
+                let intrinsic = self.get_intrinsic(
+                    "llvm.memset.p0i8.i64",
+                    self.context.void_type().fn_type(
+                        &[
+                            i8_ptr_type.into(),
+                            i8_type.into(),
+                            i64_type.into(),
+                            i1_type.into(),
+                        ],
+                        false,
+                    ),
+                );
+                let byte = self.builder.build_int_truncate(
+                    values[1].into_int_value(),
+                    i8_type,
+                    "memset_byte",
+                );
+                self.builder.build_call(
+                    intrinsic,
+                    &[
+                        values[0].into(),
+                        byte.into(),
+                        values[2].into(),
+                        i1_type.const_zero().into(),
+                    ],
+                    "",
+                );
+                values[0]
+            }
+            _ => {
+                // bit operations on an unsigned integer of any width
+                let operand = values[0].into_int_value();
+                let int_type = operand.get_type();
+                let (intrinsic_name, zero_is_poison) = match name.trim_end_matches('l') {
+                    "__builtin_popcount" => ("ctpop", false),
+                    "__builtin_clz" => ("ctlz", true),
+                    "__builtin_ctz" => ("cttz", true),
+                    _ => ("bswap", false),
+                };
+                let intrinsic_name =
+                    format!("llvm.{}.i{}", intrinsic_name, int_type.get_bit_width());
+                let mut param_types = vec![int_type.into()];
+                let mut intrinsic_args = vec![operand.as_basic_value_enum()];
+                // the result is undefined for 0, as for GCC
+                if zero_is_poison {
+                    param_types.push(i1_type.into());
+                    intrinsic_args.push(i1_type.const_int(1, false).as_basic_value_enum());
+                }
+                let intrinsic =
+                    self.get_intrinsic(&intrinsic_name, int_type.fn_type(&param_types, false));
+                let result = self
+                    .build_intrinsic_call(intrinsic, &intrinsic_args)
+                    .into_int_value();
+                let return_type = self
+                    .convert_llvm_type(&return_type.base_type, span)?
+                    .into_int_type();
+                self.builder
+                    .build_int_truncate_or_bit_cast(result, return_type, name)
+                    .as_basic_value_enum()
+            }
+        };
+        Ok(value)
+    }
+
+    /// `__builtin_add_overflow` and the like, with the LLVM intrinsics checking the overflow
+    /// of the operation on the type the result is stored to, which the operands are converted
+    /// to.
+    fn gen_overflow_builtin_call(
+        &self,
+        name: &str,
+        args: &[TypedExpression],
+        _: Span,
+    ) -> Result<BasicValueEnum<'ctx>, CE> {
+        let mut operands = vec![];
+        for arg in &args[..2] {
+            operands.push(self.gen_expression(arg)?);
+        }
+        let result_ptr = self.gen_expression(&args[2])?;
+        let t = match args[2].expr_type.base_type {
+            BaseType::Pointer(ref t) => t.as_ref(),
+            _ => unreachable!(),
+        };
+        let is_signed = matches!(t.base_type, BaseType::SignedInteger(_));
+
+        let int_type = operands[0].into_int_value().get_type();
+        let operation = name
+            .trim_start_matches("__builtin_")
+            .trim_end_matches("_overflow");
+        let intrinsic = self.get_intrinsic(
+            &format!(
+                "llvm.{}{}.with.overflow.i{}",
+                if is_signed { "s" } else { "u" },
+                operation,
+                int_type.get_bit_width()
+            ),
+            self.context
+                .struct_type(&[int_type.into(), self.context.bool_type().into()], false)
+                .fn_type(&[int_type.into(), int_type.into()], false),
+        );
+        let result = self
+            .build_intrinsic_call(intrinsic, &operands)
+            .into_struct_value();
+        let value = self
+            .builder
+            .build_extract_value(result, 0, "overflow_result")
+            .unwrap();
+        self.build_object_store(t, result_ptr.into_pointer_value(), value);
+        Ok(self
+            .builder
+            .build_extract_value(result, 1, "overflowed")
+            .unwrap())
+    }
+
+    /// Declare an LLVM intrinsic the first time it is used.
+    fn get_intrinsic(&self, name: &str, fn_type: FunctionType<'ctx>) -> FunctionValue<'ctx> {
+        self.module
+            .get_function(name)
+            .unwrap_or_else(|| self.module.add_function(name, fn_type, None))
+    }
+
+    fn build_intrinsic_call(
+        &self,
+        intrinsic: FunctionValue<'ctx>,
+        args: &[BasicValueEnum<'ctx>],
+    ) -> BasicValueEnum<'ctx> {
+        let args = args
+            .iter()
+            .map(|&arg| BasicMetadataValueEnum::from(arg))
+            .collect::<Vec<_>>();
+        self.builder
+            .build_call(intrinsic, &args, "")
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+    }
+
+    /// The `__atomic_*` builtins of GCC, on which <stdatomic.h> is built. Every operation is
+    /// sequentially consistent, which satisfies any memory order, so the memory order arguments
+    /// are not evaluated.
+    fn gen_atomic_builtin_call(
+        &self,
+        name: &str,
+        args: &[TypedExpression],
+        _: Span,
+    ) -> Result<BasicValueEnum<'ctx>, CE> {
+        if let "__atomic_thread_fence" | "__atomic_signal_fence" = name {
+            // a signal handler runs in the same thread
+            let single_thread = (name == "__atomic_signal_fence") as i32;
+            self.builder
+                .build_fence(AtomicOrdering::SequentiallyConsistent, single_thread, "");
+            return Ok(self.void_value());
+        }
+
+        // the object is accessed as if it was declared `_Atomic`
+        let ptr = self.gen_expression(&args[0])?.into_pointer_value();
+        let mut t = match args[0].expr_type.base_type {
+            BaseType::Pointer(ref t) => t.as_ref().to_owned(),
+            _ => unreachable!(),
+        };
+        t.qualifier.push(TypeQualifier::Atomic);
+        let is_integer = t.base_type.is_integer();
+
+        let rmw_op = match name {
+            "__atomic_load_n" => return Ok(self.build_object_load(&t, ptr, "atomic_load")),
+            "__atomic_store_n" => {
+                let value = self.gen_expression(&args[1])?;
+                self.build_object_store(&t, ptr, value);
+                return Ok(self.void_value());
+            }
+            "__atomic_exchange_n" => {
+                let value = self.gen_expression(&args[1])?;
+                let rmw = match is_integer {
+                    true => Some((AtomicRMWBinOp::Xchg, value.into_int_value())),
+                    false => None,
+                };
+                let (old_v, _) = self.build_atomic_update(&t, ptr, rmw, |_| Ok(value))?;
+                return Ok(old_v);
+            }
+            "__atomic_compare_exchange_n" => {
+                // a strong compare-and-exchange is also a valid weak one
+                let expected_ptr = self.gen_expression(&args[1])?;
+                let desired = self.gen_expression(&args[2])?;
+                let exchanged = self.build_atomic_compare_exchange(
+                    &t,
+                    ptr,
+                    expected_ptr.into_pointer_value(),
+                    desired,
+                );
+                return Ok(exchanged.as_basic_value_enum());
+            }
+            "__atomic_fetch_add" => AtomicRMWBinOp::Add,
+            "__atomic_fetch_sub" => AtomicRMWBinOp::Sub,
+            "__atomic_fetch_and" => AtomicRMWBinOp::And,
+            "__atomic_fetch_or" => AtomicRMWBinOp::Or,
+            _ => AtomicRMWBinOp::Xor,
+        };
+        let value = self.gen_expression(&args[1])?.into_int_value();
+        Ok(self.build_atomic_rmw(&t, ptr, rmw_op, value))
+    }
+
+    /// Scalars are fetched by hand following the x86-64 System V ABI, as LLVM's `va_arg`
+    /// instruction is miscompiled at `-O0` when the `va_list` is not a local variable: the
+    /// pointer to it is reloaded from a stack slot never stored to, see
+    /// tests/stdarg/va_list_parameter.c.
+    pub(crate) fn gen_va_arg(
+        &self,
+        va_list: &TypedExpression,
+        argument_type: &BasicType,
+        span: Span,
+    ) -> Result<BasicValueEnum<'ctx>, CE> {
+        let va_list = self.gen_va_list(va_list)?;
+        let llvm_type = self.convert_llvm_type(&argument_type.base_type, span)?;
+        // (offset field, end of the register area, size of one register slot)
+        let register_area = match argument_type.base_type {
+            BaseType::Bool
+            | BaseType::SignedInteger(_)
+            | BaseType::UnsignedInteger(_)
+            | BaseType::Pointer(_) => (0, 48, 8),
+            BaseType::Float | BaseType::Double => (1, 176, 16),
+            _ => return Ok(self.builder.build_va_arg(va_list, llvm_type, "va_arg")),
+        };
+        let (offset_field, register_area_end, slot_size) = register_area;
+
+        let i32_type = self.context.i32_type();
+        let i8_type = self.context.i8_type();
+        let i8_ptr_type = i8_type.ptr_type(AddressSpace::Generic);
+        let va_list_tag_type = self.context.struct_type(
+            &[
+                i32_type.into(),
+                i32_type.into(),
+                i8_ptr_type.into(),
+                i8_ptr_type.into(),
+            ],
+            false,
+        );
+        let va_list_tag = self
+            .builder
+            .build_bitcast(
+                va_list,
+                va_list_tag_type.ptr_type(AddressSpace::Generic),
+                "va_list_tag",
+            )
+            .into_pointer_value();
+        let offset_ptr = self
+            .builder
+            .build_struct_gep(va_list_tag, offset_field, "offset_ptr")
+            .unwrap();
+        let offset = self
+            .builder
+            .build_load(offset_ptr, "offset")
+            .into_int_value();
+        let in_register = self.builder.build_int_compare(
+            IntPredicate::ULE,
+            offset,
+            i32_type.const_int(register_area_end - slot_size, false),
+            "in_register",
+        );
+
+        let func_val = self.current_function.as_ref().unwrap().0;
+        let register_block = self.context.append_basic_block(func_val, "va_arg_register");
+        let memory_block = self.context.append_basic_block(func_val, "va_arg_memory");
+        let after_block = self.context.append_basic_block(func_val, "after_va_arg");
+        self.builder
+            .build_conditional_branch(in_register, register_block, memory_block);
+
+        self.builder.position_at_end(register_block);
+        let reg_save_area_ptr = self
+            .builder
+            .build_struct_gep(va_list_tag, 3, "reg_save_area_ptr")
+            .unwrap();
+        let reg_save_area = self
+            .builder
+            .build_load(reg_save_area_ptr, "reg_save_area")
+            .into_pointer_value();
+        let register_address = unsafe {
+            self.builder
+                .build_gep(reg_save_area, &[offset], "register_address")
+        };
+        let next_offset =
+            self.builder
+                .build_int_add(offset, i32_type.const_int(slot_size, false), "next_offset");
+        self.builder.build_store(offset_ptr, next_offset);
+        self.builder.build_unconditional_branch(after_block);
+
+        self.builder.position_at_end(memory_block);
+        let overflow_area_ptr = self
+            .builder
+            .build_struct_gep(va_list_tag, 2, "overflow_arg_area_ptr")
+            .unwrap();
+        let memory_address = self
+            .builder
+            .build_load(overflow_area_ptr, "overflow_arg_area")
+            .into_pointer_value();
+        let next_memory_address = unsafe {
+            self.builder.build_gep(
+                memory_address,
+                &[i32_type.const_int(8, false)],
+                "next_overflow_arg_area",
+            )
+        };
+        self.builder
+            .build_store(overflow_area_ptr, next_memory_address);
+        self.builder.build_unconditional_branch(after_block);
+
+        self.builder.position_at_end(after_block);
+        let address = self.builder.build_phi(i8_ptr_type, "va_arg_address");
+        address.add_incoming(&[
+            (&register_address, register_block),
+            (&memory_address, memory_block),
+        ]);
+        let address = self.builder.build_bitcast(
+            address.as_basic_value(),
+            llvm_type.ptr_type(AddressSpace::Generic),
+            "va_arg_ptr",
+        );
+        Ok(self
+            .builder
+            .build_load(address.into_pointer_value(), "va_arg"))
+    }
+
+    /// `llvm.va_start`, `llvm.va_end` and `llvm.va_copy` take one `i8*` for each `va_list`
+    /// argument
+    fn gen_va_intrinsic_call(&self, intrinsic: &str, args: &[TypedExpression]) -> Result<(), CE> {
+        let intrinsic = self.get_va_intrinsic(intrinsic);
+        let args = args
+            .iter()
+            .map(|arg| Ok(BasicMetadataValueEnum::from(self.gen_va_list(arg)?)))
+            .collect::<Result<Vec<_>, CE>>()?;
+        self.builder.build_call(intrinsic, &args, "");
+        Ok(())
+    }
+
+    fn get_va_intrinsic(&self, intrinsic: &str) -> FunctionValue<'ctx> {
+        let i8_ptr_type = self
+            .context
+            .i8_type()
+            .ptr_type(AddressSpace::Generic)
+            .into();
+        let param_types = match intrinsic {
+            "llvm.va_copy" => vec![i8_ptr_type, i8_ptr_type],
+            _ => vec![i8_ptr_type],
+        };
+        let fn_type = self.context.void_type().fn_type(&param_types, false);
+        self.get_intrinsic(intrinsic, fn_type)
+    }
+
+    /// Get a `va_list` as an `i8*` pointing to its `__va_list_tag`, a `va_list` is an array
+    /// that decays to a pointer when passed to a function.
+    fn gen_va_list(&self, va_list: &TypedExpression) -> Result<PointerValue<'ctx>, CE> {
+        let va_list_value = self.gen_expression(va_list)?;
+        Ok(self
+            .builder
+            .build_bitcast(
+                va_list_value,
+                self.context.i8_type().ptr_type(AddressSpace::Generic),
+                "va_list",
+            )
+            .into_pointer_value())
+    }
+}
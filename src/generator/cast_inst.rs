@@ -7,14 +7,14 @@ impl<'ctx> Generator<'ctx> {
     pub fn gen_cast_llvm_instruction(&self, curr: &Ty, dest: &Ty, span: Span) -> Result<Op, CE> {
         let instruction = match curr {
             // char
-            Ty::UnsignedInteger(IT::Char) => match dest {
-                Ty::SignedInteger(_) => Op::SExt,
-                Ty::UnsignedInteger(_) => Op::ZExt,
+            Ty::SignedInteger(IT::Char) => match dest {
+                Ty::UnsignedInteger(IT::Char) => Op::BitCast,
+                Ty::SignedInteger(_) | Ty::UnsignedInteger(_) => Op::SExt,
                 Ty::Float | Ty::Double => Op::SIToFP,
                 Ty::Pointer(_) => Op::IntToPtr,
                 _ => return Err(CE::invalid_cast(curr.to_string(), dest.to_string(), span)),
             },
-            Ty::SignedInteger(IT::Char) => match dest {
+            Ty::UnsignedInteger(IT::Char) => match dest {
                 Ty::SignedInteger(IT::Char) => Op::BitCast,
                 Ty::SignedInteger(_) | Ty::UnsignedInteger(_) => Op::ZExt,
                 Ty::Float | Ty::Double => Op::UIToFP,
@@ -26,8 +26,7 @@ impl<'ctx> Generator<'ctx> {
             Ty::SignedInteger(IT::Short) => match dest {
                 Ty::SignedInteger(IT::Char) | Ty::UnsignedInteger(IT::Char) => Op::Trunc,
                 Ty::UnsignedInteger(IT::Short) => Op::BitCast,
-                Ty::SignedInteger(_) => Op::SExt,
-                Ty::UnsignedInteger(_) => Op::ZExt,
+                Ty::SignedInteger(_) | Ty::UnsignedInteger(_) => Op::SExt,
                 Ty::Float | Ty::Double => Op::SIToFP,
                 Ty::Pointer(_) => Op::IntToPtr,
                 _ => return Err(CE::invalid_cast(curr.to_string(), dest.to_string(), span)),
@@ -48,8 +47,8 @@ impl<'ctx> Generator<'ctx> {
                 | Ty::SignedInteger(IT::Short)
                 | Ty::UnsignedInteger(IT::Short) => Op::Trunc,
                 Ty::UnsignedInteger(IT::Int) => Op::BitCast,
-                Ty::SignedInteger(_) => Op::SExt,
-                Ty::UnsignedInteger(_) => Op::ZExt,
+                // a negative value converted to a wider unsigned type wraps around
+                Ty::SignedInteger(_) | Ty::UnsignedInteger(_) => Op::SExt,
                 Ty::Float | Ty::Double => Op::SIToFP,
                 Ty::Pointer(_) => Op::IntToPtr,
                 _ => return Err(CE::invalid_cast(curr.to_string(), dest.to_string(), span)),
@@ -74,9 +73,10 @@ impl<'ctx> Generator<'ctx> {
                 | Ty::UnsignedInteger(IT::Short)
                 | Ty::SignedInteger(IT::Int)
                 | Ty::UnsignedInteger(IT::Int) => Op::Trunc,
-                Ty::UnsignedInteger(IT::Long) => Op::BitCast,
+                Ty::UnsignedInteger(IT::Long)
+                | Ty::SignedInteger(IT::LongLong)
+                | Ty::UnsignedInteger(IT::LongLong) => Op::BitCast,
                 Ty::SignedInteger(_) => Op::SExt,
-                Ty::UnsignedInteger(_) => Op::ZExt,
                 Ty::Float | Ty::Double => Op::SIToFP,
                 Ty::Pointer(_) => Op::IntToPtr,
                 _ => return Err(CE::invalid_cast(curr.to_string(), dest.to_string(), span)),
@@ -88,8 +88,9 @@ impl<'ctx> Generator<'ctx> {
                 | Ty::UnsignedInteger(IT::Short)
                 | Ty::SignedInteger(IT::Int)
                 | Ty::UnsignedInteger(IT::Int) => Op::Trunc,
-                Ty::SignedInteger(IT::Long) => Op::BitCast,
-                Ty::SignedInteger(_) | Ty::UnsignedInteger(_) => Op::ZExt,
+                Ty::SignedInteger(IT::Long)
+                | Ty::SignedInteger(IT::LongLong)
+                | Ty::UnsignedInteger(IT::LongLong) => Op::BitCast,
                 Ty::Float | Ty::Double => Op::UIToFP,
                 Ty::Pointer(_) => Op::IntToPtr,
                 _ => return Err(CE::invalid_cast(curr.to_string(), dest.to_string(), span)),
@@ -102,10 +103,10 @@ impl<'ctx> Generator<'ctx> {
                 | Ty::SignedInteger(IT::Short)
                 | Ty::UnsignedInteger(IT::Short)
                 | Ty::SignedInteger(IT::Int)
-                | Ty::UnsignedInteger(IT::Int)
-                | Ty::SignedInteger(IT::Long)
-                | Ty::UnsignedInteger(IT::Long) => Op::Trunc,
-                Ty::UnsignedInteger(IT::LongLong) => Op::BitCast,
+                | Ty::UnsignedInteger(IT::Int) => Op::Trunc,
+                Ty::SignedInteger(IT::Long)
+                | Ty::UnsignedInteger(IT::Long)
+                | Ty::UnsignedInteger(IT::LongLong) => Op::BitCast,
                 Ty::Float | Ty::Double => Op::SIToFP,
                 Ty::Pointer(_) => Op::IntToPtr,
                 _ => return Err(CE::invalid_cast(curr.to_string(), dest.to_string(), span)),
@@ -116,10 +117,10 @@ impl<'ctx> Generator<'ctx> {
                 | Ty::SignedInteger(IT::Short)
                 | Ty::UnsignedInteger(IT::Short)
                 | Ty::SignedInteger(IT::Int)
-                | Ty::UnsignedInteger(IT::Int)
-                | Ty::SignedInteger(IT::Long)
-                | Ty::UnsignedInteger(IT::Long) => Op::Trunc,
-                Ty::SignedInteger(IT::LongLong) => Op::BitCast,
+                | Ty::UnsignedInteger(IT::Int) => Op::Trunc,
+                Ty::SignedInteger(IT::Long)
+                | Ty::UnsignedInteger(IT::Long)
+                | Ty::SignedInteger(IT::LongLong) => Op::BitCast,
                 Ty::Float | Ty::Double => Op::UIToFP,
                 Ty::Pointer(_) => Op::IntToPtr,
                 _ => return Err(CE::invalid_cast(curr.to_string(), dest.to_string(), span)),
@@ -1,630 +1,488 @@
-use crate::ast::StructMember;
 use crate::ast::{
-    AssignOperation, AssignOperationEnum, BaseType, BasicType, BinaryOperation,
-    BinaryOperationEnum, Expression, ExpressionEnum, IntegerType, Span, UnaryOperation,
-    UnaryOperationEnum,
+    AssignOperation, BaseType, BasicType, BinaryOperation, BinaryOperationEnum, EncodingPrefix,
+    ExpressionEnum, IntegerType, Span, TypeQualifier, UnaryOperation, UnaryOperationEnum,
 };
 use crate::generator::Generator;
+use crate::sema::{Symbol, TypedExpression, TypedExpressionEnum, ValueCategory};
 use crate::utils::CompileErr as CE;
+use inkwell::module::Linkage;
 use inkwell::values::{
-    AnyValue, BasicMetadataValueEnum, BasicValue, BasicValueEnum, FloatValue, IntValue,
+    ArrayValue, BasicMetadataValueEnum, BasicValue, BasicValueEnum, FloatValue, IntValue,
     PointerValue,
 };
-use inkwell::{FloatPredicate, IntPredicate};
-use std::cmp::Ordering;
-use std::fmt::Error;
+use inkwell::{AtomicRMWBinOp, FloatPredicate, IntPredicate};
 
 impl<'ctx> Generator<'ctx> {
+    /// Generate the value of an expression checked by the semantic analysis, whose operands
+    /// are already converted to the types the operations are performed in.
     pub(crate) fn gen_expression(
         &self,
-        expr: &Expression,
-    ) -> Result<(BaseType, BasicValueEnum<'ctx>), CE> {
+        expr: &TypedExpression,
+    ) -> Result<BasicValueEnum<'ctx>, CE> {
+        if expr.category == ValueCategory::LValue {
+            let ptr = self.get_lvalue(expr)?;
+            //if BaseType is Array, we just return the address of the array but don't load value!!
+            if let BaseType::Array(_, _) = expr.expr_type.base_type {
+                return Ok(ptr.as_basic_value_enum());
+            }
+            let name = match expr.node {
+                TypedExpressionEnum::MemberOfObject(_, _) => "member_of_object",
+                TypedExpressionEnum::MemberOfPointer(_, _) => "member_of_pointer",
+                TypedExpressionEnum::ArraySubscript(ref array, _) => {
+                    match array.expr_type.base_type {
+                        BaseType::Array(_, _) => "load_arr_subscript",
+                        _ => "dereference",
+                    }
+                }
+                TypedExpressionEnum::Unary(_, _) => "dereference",
+                _ => "load_val",
+            };
+            return Ok(self.build_object_load(&expr.expr_type, ptr, name));
+        }
+
         match expr.node {
-            ExpressionEnum::Empty => Ok((
-                BaseType::Void,
-                self.context
-                    .i8_type()
-                    .const_int(0_u64, false)
-                    .as_basic_value_enum(),
-            )),
-            ExpressionEnum::Assignment(ref op, ref lhs, ref rhs) => {
+            TypedExpressionEnum::Empty => Ok(self.void_value()),
+            TypedExpressionEnum::Assignment(ref op, ref lhs, ref rhs) => {
                 self.gen_assignment(op, lhs, rhs, expr.span)
             }
-            ExpressionEnum::Unary(ref op, ref expr) => self.gen_unary_expr(op, expr, expr.span),
-            ExpressionEnum::Binary(ref op, ref lhs, ref rhs) => {
-                self.gen_binary_expr(op, lhs, rhs, expr.span)
-            }
-            ExpressionEnum::FunctionCall(ref name, ref args) => {
-                self.gen_function_call(name, args, expr.span)
+            TypedExpressionEnum::Unary(ref op, ref operand) => {
+                self.gen_unary_expr(op, operand, expr.span)
             }
-            ExpressionEnum::MemberOfObject(ref obj, ref member) => {
-                let (t, p_v) = self.gen_member_of_object(obj, member, expr.span)?;
-                let val = if let BaseType::Array(_, _) = t.base_type {
-                    p_v.as_basic_value_enum()
-                } else {
-                    self.builder.build_load(p_v, "member_of_object")
-                };
-                Ok((t.base_type, val))
-            }
-            ExpressionEnum::MemberOfPointer(ref ptr, ref member) => {
-                let (t, p_v) = self.gen_member_of_pointer(ptr, member, expr.span)?;
-                let val = if let BaseType::Array(_, _) = t.base_type {
-                    p_v.as_basic_value_enum()
-                } else {
-                    self.builder.build_load(p_v, "member_of_pointer")
-                };
-                Ok((t.base_type, val))
-            }
-            ExpressionEnum::CharacterConstant(ref value) => Ok((
-                BaseType::SignedInteger(IntegerType::Char),
-                self.context
-                    .i8_type()
-                    .const_int(*value as u64, false)
-                    .as_basic_value_enum(),
-            )),
-            ExpressionEnum::IntegerConstant(ref value) => Ok((
-                BaseType::SignedInteger(IntegerType::Int),
-                self.context
-                    .i32_type()
-                    .const_int(*value as u64, false)
-                    .as_basic_value_enum(),
-            )),
-            ExpressionEnum::UnsignedIntegerConstant(ref value) => Ok((
-                BaseType::UnsignedInteger(IntegerType::Int),
-                self.context
-                    .i32_type()
-                    .const_int(*value as u64, false)
-                    .as_basic_value_enum(),
-            )),
-            ExpressionEnum::LongConstant(ref value)
-            | ExpressionEnum::LongLongConstant(ref value) => Ok((
-                BaseType::SignedInteger(IntegerType::Long),
-                self.context
-                    .i64_type()
-                    .const_int(*value as u64, false)
-                    .as_basic_value_enum(),
-            )),
-            ExpressionEnum::UnsignedLongConstant(ref value)
-            | ExpressionEnum::UnsignedLongLongConstant(ref value) => Ok((
-                BaseType::UnsignedInteger(IntegerType::Long),
-                self.context
-                    .i64_type()
-                    .const_int(*value as u64, false)
-                    .as_basic_value_enum(),
-            )),
-            ExpressionEnum::FloatConstant(ref value) => Ok((
-                BaseType::Float,
-                self.context
-                    .f32_type()
-                    .const_float(*value as f64)
-                    .as_basic_value_enum(),
-            )),
-            ExpressionEnum::DoubleConstant(ref value) => Ok((
-                BaseType::Float,
-                self.context
-                    .f64_type()
-                    .const_float(*value as f64)
-                    .as_basic_value_enum(),
-            )),
-            ExpressionEnum::Identifier(ref string_literal) => {
-                //if BaseType is Array, we just return Array type but don't load value!!
-                let deref = self.get_variable(string_literal, expr.span)?;
-                let val = if let BaseType::Array(_, _) = deref.0.base_type {
-                    deref.1.as_basic_value_enum()
-                } else {
-                    self.builder.build_load(deref.1, "load_val")
-                };
-                Ok((deref.0.base_type, val))
+            TypedExpressionEnum::Binary(ref op, ref lhs, ref rhs) => {
+                self.gen_binary_expr(op, lhs, rhs, expr.span)
             }
-            ExpressionEnum::StringLiteral(ref string) => Ok((
-                BaseType::Pointer(Box::new(BasicType {
-                    qualifier: vec![],
-                    base_type: BaseType::SignedInteger(IntegerType::Char),
-                })),
-                self.builder
-                    .build_global_string_ptr(string.as_str(), "str")
-                    .as_basic_value_enum(),
-            )),
-            ExpressionEnum::ArraySubscript(ref id_expr, ref idx_vec) => {
-                let (l_t, mut l_pv) = self.get_lvalue(id_expr)?;
-
-                //if type is pointer, get_variable will get the address of pointer, not the pointer point to!
-                //So if we want get the point to address, we need extra load action!
-                if let BaseType::Pointer(_) = l_t.base_type {
-                    // println!("{}", l_pv.get_type().print_to_string().to_string());
-                    l_pv = self
-                        .builder
-                        .build_load(l_pv, "dereference")
-                        .into_pointer_value();
-                }
-                // println!("{}", l_pv.get_type().print_to_string().to_string());
-                let (res_t, mut idx_int_val_vec) =
-                    self.process_arr_subscript(&l_t, idx_vec, expr.span)?;
-                //Pointer
-                if let BaseType::Pointer(_) = l_t.base_type {
-                    while !idx_int_val_vec.is_empty() {
-                        l_pv = unsafe {
-                            self.builder.build_gep(
-                                l_pv,
-                                [idx_int_val_vec[0]].as_ref(),
-                                "pointer_subscript",
-                            )
-                        };
-                        idx_int_val_vec.remove(0);
-                        if !idx_int_val_vec.is_empty() {
-                            l_pv = self
-                                .builder
-                                .build_load(l_pv, "dereference")
-                                .into_pointer_value()
-                        }
-                    }
-                    Ok((
-                        res_t.base_type,
-                        self.builder.build_load(l_pv, "dereference"),
-                    ))
-                } else {
-                    //Array
-                    if let BaseType::Array(_, _) = res_t.base_type {
-                        Ok((res_t.base_type, unsafe {
-                            self.builder
-                                .build_gep(l_pv, idx_int_val_vec.as_ref(), "arr_subscript")
-                                .as_basic_value_enum()
-                        }))
-                    } else {
-                        Ok((
-                            res_t.base_type,
-                            self.builder.build_load(
-                                unsafe {
-                                    self.builder.build_gep(
-                                        l_pv,
-                                        idx_int_val_vec.as_ref(),
-                                        "arr_subscript",
-                                    )
-                                },
-                                "load_arr_subscript",
-                            ),
-                        ))
+            TypedExpressionEnum::FunctionCall(ref function, ref args) => {
+                self.gen_function_call(function, args, expr.span)
+            }
+            TypedExpressionEnum::TypeCast(_, ref operand)
+            | TypedExpressionEnum::ImplicitCast(ref operand) => {
+                let value = self.gen_expression(operand)?;
+                match (&operand.expr_type.base_type, &expr.expr_type.base_type) {
+                    // the value of a cast to void is discarded
+                    (_, BaseType::Void) => Ok(value),
+                    // a function designator is already the address of the function
+                    (BaseType::Function(_, _, _), _) => Ok(value),
+                    (curr_type, dest_type) => {
+                        self.cast_value(curr_type, &value, dest_type, expr.span)
                     }
                 }
             }
-            ExpressionEnum::SizeofType(ref basic_type) => {
-                let t = self
-                    .calculate_size_of(&basic_type.base_type, &expr.span)
-                    .unwrap();
-                Ok((
-                    BaseType::SignedInteger(IntegerType::Int),
-                    self.context
-                        .i32_type()
-                        .const_int(t as u64, false)
-                        .as_basic_value_enum(),
-                ))
+            TypedExpressionEnum::Conditional(ref cond, ref true_expr, ref false_expr) => {
+                self.gen_conditional(cond, true_expr, false_expr)
             }
-            _ => Err(CE::unknown_expression(expr.span)),
-        }
-    }
-    fn calculate_size_of(&self, bt: &BaseType, span: &Span) -> Result<u32, CE> {
-        match bt {
-            BaseType::Void => Err(CE::invalid_size_of_type("void".to_string(), *span)),
-            BaseType::SignedInteger(int_type) | BaseType::UnsignedInteger(int_type) => {
-                match int_type {
-                    IntegerType::Int => Ok(4),
-                    IntegerType::Char => Ok(1),
-                    IntegerType::Short => Ok(2),
-                    IntegerType::Long => Ok(8),
-                    IntegerType::LongLong => Ok(8),
-                }
-            }
-            BaseType::Bool => Ok(1),
-            BaseType::Float => Ok(4),
-            BaseType::Double => Ok(8),
-            BaseType::Pointer(_) => Ok(8),
-            BaseType::Array(arr_bt, arr_expr) => {
-                let t = arr_expr
-                    .iter()
-                    .map(|expr| self.gen_expression(expr).unwrap().1.into_int_value())
-                    .fold(1, |mut v, i| {
-                        v *= i.get_zero_extended_constant().unwrap() as u32;
-                        v
-                    });
-                Ok(self.calculate_size_of(&arr_bt.base_type, span)? * t)
+            TypedExpressionEnum::SizeofType(ref basic_type) => {
+                self.gen_size_of(&basic_type.base_type, expr.span)
             }
-            BaseType::Function(_, _, _) => {
-                Err(CE::invalid_size_of_type("function".to_string(), *span))
-            }
-            BaseType::Struct(name, member) => {
-                let custom_member = self.global_struct_map.get(name.as_ref().unwrap());
-                let m: Vec<StructMember> = match custom_member {
-                    Some(v) => v.clone(),
-                    None => match member {
-                        Some(t) => t.clone(),
-                        None => Vec::new(),
-                    },
-                };
-                // TODO Do we need this ?
-                // if m.is_empty() {
-                //     return Err(CE::invalid_size_of_type("struct".to_string(), *span));
-                // }
-                Ok(m.iter().fold(0, |mut v, i| {
-                    v += self
-                        .calculate_size_of(&i.member_type.base_type, span)
-                        .unwrap();
-                    v
-                }))
+            TypedExpressionEnum::VaArg(ref va_list, ref argument_type) => {
+                self.gen_va_arg(va_list, argument_type, expr.span)
             }
-            BaseType::Union(_, member) => Ok(member
-                .clone()
+            TypedExpressionEnum::Offsetof(ref struct_type, ref members) => {
+                let mut offset = 0;
+                let mut member_type = struct_type.base_type.to_owned();
+                for member in members {
+                    let (member_offset, t) = self.member_offset(&member_type, member, expr.span)?;
+                    offset += member_offset;
+                    member_type = t.base_type;
+                }
+                Ok(self
+                    .context
+                    .i64_type()
+                    .const_int(offset as u64, false)
+                    .as_basic_value_enum())
+            }
+            // a member of a struct which is not an lvalue, e.g. returned by a function
+            TypedExpressionEnum::MemberOfObject(ref obj, ref member) => {
+                let struct_value = self.gen_expression(obj)?.into_struct_value();
+                let idx = self.find_member(&obj.expr_type.base_type, member, expr.span)?;
+                Ok(self
+                    .builder
+                    .build_extract_value(struct_value, idx, "member_of_object")
+                    .unwrap())
+            }
+            TypedExpressionEnum::Identifier(ref name, Symbol::Function(_)) => Ok(self
+                .module
+                .get_function(name)
                 .unwrap()
-                .iter()
-                .map(|i| {
-                    self.calculate_size_of(&i.member_type.base_type, span)
-                        .unwrap()
-                })
-                .max()
-                .unwrap()),
-            BaseType::Identifier(string_literal) => {
-                let deref = self.get_variable(string_literal, *span)?;
-                self.calculate_size_of(&deref.0.base_type, span)
+                .as_global_value()
+                .as_pointer_value()
+                .as_basic_value_enum()),
+            TypedExpressionEnum::Literal(ref literal) => {
+                self.gen_literal(literal, &expr.expr_type.base_type, expr.span)
             }
+            _ => Err(CE::unknown_expression(expr.span)),
         }
     }
-    fn process_arr_subscript(
+
+    /// The value of an expression of type void, which is never used.
+    pub(crate) fn void_value(&self) -> BasicValueEnum<'ctx> {
+        self.context
+            .i8_type()
+            .const_int(0_u64, false)
+            .as_basic_value_enum()
+    }
+
+    fn gen_literal(
         &self,
-        l_t: &BasicType,
-        idx_vec: &[Expression],
+        literal: &ExpressionEnum,
+        literal_type: &BaseType,
         span: Span,
-    ) -> Result<(BasicType, Vec<IntValue<'ctx>>), CE> {
-        let true_l_t = match l_t.base_type {
-            BaseType::Identifier(ref name) => {
-                if let Some(typedef) = self.typedef_map.get(name) {
-                    typedef
-                } else {
-                    unreachable!()
-                }
-            }
-            _ => l_t,
-        };
-        if let BaseType::Array(ref arr_t, arr_len_vec) = &true_l_t.base_type {
-            let res_t = match idx_vec.len().cmp(&arr_len_vec.len()) {
-                Ordering::Less => BaseType::Array(
-                    arr_t.clone(),
-                    (idx_vec.len()..arr_len_vec.len()).fold(vec![], |mut acc, i| {
-                        acc.push(arr_len_vec[i].clone());
-                        acc
-                    }),
-                ),
-                Ordering::Equal => arr_t.base_type.clone(),
-                Ordering::Greater => {
-                    return Err(CE::array_dimension_mismatch(
-                        arr_len_vec.len(),
-                        idx_vec.len(),
-                        span,
-                    ));
-                }
-            };
+    ) -> Result<BasicValueEnum<'ctx>, CE> {
+        let llvm_type = self.convert_llvm_type(literal_type, span)?;
+        let int_value = |value: u64| llvm_type.into_int_type().const_int(value, false);
+        Ok(match *literal {
+            ExpressionEnum::CharacterConstant(_, value) => int_value(value as u64),
+            ExpressionEnum::IntegerConstant(value) => int_value(value as u64),
+            ExpressionEnum::UnsignedIntegerConstant(value) => int_value(value as u64),
+            ExpressionEnum::LongConstant(value) => int_value(value as u64),
+            ExpressionEnum::UnsignedLongConstant(value) => int_value(value),
+            ExpressionEnum::LongLongConstant(value) => int_value(value as u64),
+            ExpressionEnum::UnsignedLongLongConstant(value) => int_value(value),
+            ExpressionEnum::FloatConstant(value) => {
+                return Ok(llvm_type
+                    .into_float_type()
+                    .const_float(value as f64)
+                    .as_basic_value_enum())
+            }
+            ExpressionEnum::DoubleConstant(value) => {
+                return Ok(llvm_type
+                    .into_float_type()
+                    .const_float(value)
+                    .as_basic_value_enum())
+            }
+            _ => return Err(CE::unknown_expression(span)),
+        }
+        .as_basic_value_enum())
+    }
 
-            let mut idx_int_val_vec = vec![self.context.i32_type().const_zero()];
-            idx_int_val_vec.extend(
-                idx_vec
-                    .iter()
-                    .map(|expr| self.gen_expression(expr).unwrap().1.into_int_value()),
-            );
-            Ok((
-                BasicType {
-                    qualifier: true_l_t.qualifier.clone(),
-                    base_type: res_t,
-                },
-                idx_int_val_vec,
-            ))
-        } else if let BaseType::Pointer(_) = true_l_t.base_type {
-            let mut res_t = &true_l_t.base_type;
+    /// Build the array holding the code units of a string literal, padded with null characters
+    /// up to `length`.
+    pub(crate) fn gen_string_constant(
+        &self,
+        prefix: EncodingPrefix,
+        code_units: &[u32],
+        length: usize,
+        span: Span,
+    ) -> Result<ArrayValue<'ctx>, CE> {
+        let element_type = self
+            .convert_llvm_type(&prefix.element_type(), span)?
+            .into_int_type();
+        let mut elements = code_units
+            .iter()
+            .map(|&code_unit| element_type.const_int(code_unit as u64, false))
+            .collect::<Vec<_>>();
+        elements.resize(length, element_type.const_zero());
+        Ok(element_type.const_array(&elements))
+    }
 
-            let mut idx_int_val_vec = vec![];
+    /// Initialize an array of character type, e.g. `char s[4] = "abc";`, whose length is
+    /// checked by the semantic analysis.
+    pub(crate) fn gen_string_initializer(
+        &self,
+        array_type: &BaseType,
+        prefix: EncodingPrefix,
+        code_units: &[u32],
+        span: Span,
+    ) -> Result<ArrayValue<'ctx>, CE> {
+        let length = match array_type {
+            BaseType::Array(_, dimensions) => dimensions[0].node.integer_literal().unwrap(),
+            _ => unreachable!(),
+        };
+        // the terminating null character is dropped if there is no room for it
+        self.gen_string_constant(prefix, code_units, length as usize, span)
+    }
 
-            let mut not_match = false;
-            idx_int_val_vec.extend(idx_vec.iter().map(|expr| {
-                match res_t {
-                    BaseType::Pointer(p) => {
-                        res_t = &p.base_type;
-                    }
-                    _ => {
-                        not_match = true;
-                    }
-                }
-                self.gen_expression(expr).unwrap().1.into_int_value()
-            }));
-            if not_match {
-                //NOT found the expect number now!
-                return Err(CE::pointer_dimension_mismatch(0, idx_vec.len(), span));
-            }
-            Ok((
-                BasicType {
-                    qualifier: true_l_t.qualifier.clone(),
-                    base_type: res_t.clone(),
-                },
-                idx_int_val_vec,
-            ))
-        } else {
-            unreachable!()
-        }
+    fn gen_size_of(&self, t: &BaseType, span: Span) -> Result<BasicValueEnum<'ctx>, CE> {
+        let (size, _) = self.type_layout(t, span)?;
+        Ok(self
+            .context
+            .i64_type()
+            .const_int(size as u64, false)
+            .as_basic_value_enum())
     }
 
     fn gen_unary_expr(
         &self,
         op: &UnaryOperation,
-        expr: &Expression,
+        operand: &TypedExpression,
         span: Span,
-    ) -> Result<(BaseType, BasicValueEnum<'ctx>), CE> {
-        let (expr_type, expr_value) = self.gen_expression(expr)?;
-
+    ) -> Result<BasicValueEnum<'ctx>, CE> {
         match op.node {
-            UnaryOperationEnum::UnaryPlus => match expr_type {
-                BaseType::Bool
-                | BaseType::SignedInteger(_)
-                | BaseType::UnsignedInteger(_)
-                | BaseType::Float
-                | BaseType::Double => Ok((expr_type, expr_value)),
-                _ => Err(CE::invalid_unary(span)),
-            },
-            UnaryOperationEnum::UnaryMinus => match expr_type {
-                BaseType::Bool | BaseType::SignedInteger(_) | BaseType::UnsignedInteger(_) => Ok((
-                    expr_type,
-                    self.builder
-                        .build_int_neg(expr_value.into_int_value(), "int_neg")
-                        .as_basic_value_enum(),
-                )),
-                BaseType::Float | BaseType::Double => Ok((
-                    expr_type,
-                    self.builder
-                        .build_float_neg(expr_value.into_float_value(), "float_neg")
-                        .as_basic_value_enum(),
-                )),
-                _ => Err(CE::invalid_unary(span)),
-            },
-            UnaryOperationEnum::BitwiseNot => match expr_type {
-                BaseType::SignedInteger(_) | BaseType::UnsignedInteger(_) => Ok((
-                    expr_type,
-                    self.builder
-                        .build_not(expr_value.into_int_value(), "bitwise_not")
-                        .as_basic_value_enum(),
-                )),
-                _ => Err(CE::invalid_unary(span)),
-            },
-            UnaryOperationEnum::LogicalNot => match expr_type {
-                BaseType::SignedInteger(_) => {
-                    let llvm_type = self.convert_llvm_type(&expr_type, span)?;
-                    let result_int = self.builder.build_int_compare(
-                        IntPredicate::EQ,
-                        llvm_type.into_int_type().const_int(0_u64, true),
-                        expr_value.into_int_value(),
-                        "logical_not_result_int",
-                    );
-
-                    let llvm_type = self.convert_llvm_type(&BaseType::Bool, span)?;
-                    Ok((
-                        BaseType::Bool,
-                        self.builder
-                            .build_int_cast(
-                                result_int,
-                                llvm_type.into_int_type(),
-                                "cast_logical_not_result_to_bool",
-                            )
-                            .as_basic_value_enum(),
-                    ))
-                }
-                _ => Err(CE::invalid_unary(span)),
-            },
             UnaryOperationEnum::Reference => {
-                let (t, ptr) = self.get_lvalue(expr)?;
-                Ok((BaseType::Pointer(Box::new(t)), ptr.as_basic_value_enum()))
-            }
-            UnaryOperationEnum::Dereference => match expr_type {
-                BaseType::Pointer(ref t) => Ok((
-                    t.base_type.clone(),
-                    self.builder
-                        .build_load(expr_value.into_pointer_value(), "dereference")
-                        .as_basic_value_enum(),
-                )),
-                _ => Err(CE::invalid_unary(span)),
-            },
-            UnaryOperationEnum::PostfixDecrement | UnaryOperationEnum::PostfixIncrement => {
-                match expr_type {
-                    BaseType::SignedInteger(_)
-                    | BaseType::UnsignedInteger(_)
-                    | BaseType::Double
-                    | BaseType::Float
-                    | BaseType::Pointer(_) => {
-                        let assign_op = if op.node == UnaryOperationEnum::PostfixIncrement {
-                            AssignOperation {
-                                node: AssignOperationEnum::Addition,
-                                span: op.span,
-                            }
-                        } else {
-                            AssignOperation {
-                                node: AssignOperationEnum::Subtraction,
-                                span: op.span,
-                            }
-                        };
-                        let _ = self.gen_assignment(
-                            &assign_op,
-                            expr,
-                            &Box::new(Expression {
-                                node: ExpressionEnum::IntegerConstant(1),
-                                span: op.span,
-                            }),
-                            span,
-                        )?;
-                        Ok((expr_type, expr_value))
-                    }
-                    _ => Err(CE::invalid_unary(span)),
-                }
-            }
-            UnaryOperationEnum::PrefixIncrement | UnaryOperationEnum::PrefixDecrement => {
-                match expr_type {
-                    BaseType::SignedInteger(_)
-                    | BaseType::UnsignedInteger(_)
-                    | BaseType::Double
-                    | BaseType::Float
-                    | BaseType::Pointer(_) => {
-                        let assign_op = if op.node == UnaryOperationEnum::PostfixIncrement {
-                            AssignOperation {
-                                node: AssignOperationEnum::Addition,
-                                span: op.span,
-                            }
-                        } else {
-                            AssignOperation {
-                                node: AssignOperationEnum::Subtraction,
-                                span: op.span,
-                            }
-                        };
-                        self.gen_assignment(
-                            &assign_op,
-                            expr,
-                            &Box::new(Expression {
-                                node: ExpressionEnum::IntegerConstant(1),
-                                span: op.span,
-                            }),
-                            span,
-                        )
-                    }
-                    _ => Err(CE::invalid_unary(span)),
-                }
+                return match operand.expr_type.base_type {
+                    BaseType::Function(_, _, _) => self.gen_expression(operand),
+                    _ => Ok(self.get_lvalue(operand)?.as_basic_value_enum()),
+                };
             }
+            // the operand of sizeof is not evaluated
             UnaryOperationEnum::SizeofExpr => {
-                let t = self.calculate_size_of(&expr_type, &expr.span).unwrap();
-                Ok((
-                    BaseType::SignedInteger(IntegerType::Int),
-                    self.context
-                        .i32_type()
-                        .const_int(t as u64, false)
-                        .as_basic_value_enum(),
-                ))
+                return self.gen_size_of(&operand.expr_type.base_type, span);
+            }
+            UnaryOperationEnum::PostfixIncrement
+            | UnaryOperationEnum::PostfixDecrement
+            | UnaryOperationEnum::PrefixIncrement
+            | UnaryOperationEnum::PrefixDecrement => {
+                return self.gen_increment_or_decrement(op, operand, span);
             }
+            _ => {}
+        }
+
+        // the dereference of a function pointer designates the function, which is the pointer
+        let expr_value = self.gen_expression(operand)?;
+        match op.node {
+            UnaryOperationEnum::UnaryPlus | UnaryOperationEnum::Dereference => Ok(expr_value),
+            UnaryOperationEnum::UnaryMinus => match operand.expr_type.base_type {
+                BaseType::Float | BaseType::Double => Ok(self
+                    .builder
+                    .build_float_neg(expr_value.into_float_value(), "float_neg")
+                    .as_basic_value_enum()),
+                _ => Ok(self
+                    .builder
+                    .build_int_neg(expr_value.into_int_value(), "int_neg")
+                    .as_basic_value_enum()),
+            },
+            UnaryOperationEnum::BitwiseNot => Ok(self
+                .builder
+                .build_not(expr_value.into_int_value(), "bitwise_not")
+                .as_basic_value_enum()),
+            UnaryOperationEnum::LogicalNot => {
+                let value = self
+                    .builder
+                    .build_not(self.build_truth_value(expr_value), "logical_not");
+                Ok(self.build_int_from_truth_value(value))
+            }
+            _ => unreachable!(),
         }
     }
 
-    fn gen_binary_expr(
+    /// The `int` result of a comparison or a logical operator, from its `i1`.
+    fn build_int_from_truth_value(&self, value: IntValue<'ctx>) -> BasicValueEnum<'ctx> {
+        self.builder
+            .build_int_z_extend(value, self.context.i32_type(), "truth_value_to_int")
+            .as_basic_value_enum()
+    }
+
+    /// Whether a scalar value is nonzero, as an `i1`.
+    pub(crate) fn build_truth_value(&self, value: BasicValueEnum<'ctx>) -> IntValue<'ctx> {
+        match value {
+            BasicValueEnum::IntValue(v) if v.get_type().get_bit_width() == 1 => v,
+            BasicValueEnum::IntValue(v) => self.builder.build_int_compare(
+                IntPredicate::NE,
+                v,
+                v.get_type().const_zero(),
+                "truth_value",
+            ),
+            BasicValueEnum::FloatValue(v) => self.builder.build_float_compare(
+                FloatPredicate::UNE,
+                v,
+                v.get_type().const_zero(),
+                "truth_value",
+            ),
+            BasicValueEnum::PointerValue(v) => self.builder.build_is_not_null(v, "truth_value"),
+            _ => unreachable!(),
+        }
+    }
+
+    /// `++` and `--`, whose operand is evaluated only once
+    fn gen_increment_or_decrement(
         &self,
-        op: &BinaryOperation,
-        lhs: &Expression,
-        rhs: &Expression,
+        op: &UnaryOperation,
+        operand: &TypedExpression,
         span: Span,
-    ) -> Result<(BaseType, BasicValueEnum<'ctx>), CE> {
-        let (ref l_t, l_v) = self.gen_expression(lhs)?;
-        let (ref r_t, r_v) = self.gen_expression(rhs)?;
+    ) -> Result<BasicValueEnum<'ctx>, CE> {
+        let l_pv = self.get_lvalue(operand)?;
 
-        //TODO FIXME!! so dirty!
-        let mut visit = 0;
-        let tmp = match l_t {
-            BaseType::SignedInteger(_) | BaseType::UnsignedInteger(_) => {
-                if let BaseType::Pointer(_) = r_t {
-                    visit = 1;
-                    self.build_point_binary_op(op, r_v.into_pointer_value(), l_v.into_int_value())
-                } else if let BaseType::Array(array_type, array_vec) = r_t {
-                    visit = 1;
-                    if array_vec.len() != 1 {
-                        Err(CE::array_dimension_mismatch(1, array_vec.len(), span))
-                    } else {
-                        let point_v = self.cast_value(
-                            r_t,
-                            &r_v,
-                            &BaseType::Pointer(array_type.clone()),
-                            rhs.span,
-                        )?;
-                        self.build_point_binary_op(
-                            op,
-                            point_v.into_pointer_value(),
-                            l_v.into_int_value(),
-                        )
-                    }
-                } else {
-                    Err(CE::plain_error("".to_string(), span))
-                }
-            }
-            BaseType::Pointer(_) => match r_t {
-                BaseType::SignedInteger(_) => {
-                    visit = 2;
-                    self.build_point_binary_op(op, l_v.into_pointer_value(), r_v.into_int_value())
+        let bin_op = BinaryOperation {
+            node: match op.node {
+                UnaryOperationEnum::PrefixIncrement | UnaryOperationEnum::PostfixIncrement => {
+                    BinaryOperationEnum::Addition
                 }
-                BaseType::UnsignedInteger(_) => {
-                    visit = 2;
-                    self.build_point_binary_op(op, l_v.into_pointer_value(), r_v.into_int_value())
-                }
-                _ => Err(CE::plain_error("".to_string(), span)),
+                _ => BinaryOperationEnum::Subtraction,
             },
-            BaseType::Array(array_type, array_vec) => {
-                visit = 2;
-                if array_vec.len() != 1 {
-                    Err(CE::array_dimension_mismatch(1, array_vec.len(), span))
-                } else {
-                    let point_v = self.cast_value(
-                        l_t,
-                        &l_v,
-                        &BaseType::Pointer(array_type.clone()),
-                        lhs.span,
-                    )?;
-                    self.build_point_binary_op(
-                        op,
-                        point_v.into_pointer_value(),
-                        r_v.into_int_value(),
-                    )
-                }
-            }
-            _ => Err(CE::plain_error("".to_string(), span)),
+            span: op.span,
         };
-        if visit > 0 {
-            return match tmp {
-                Ok(t) => {
-                    if visit == 1 {
-                        Ok((r_t.clone(), t))
-                    } else {
-                        Ok((l_t.clone(), t))
-                    }
-                }
-                Err(e) => Err(e),
-            };
+        // adding one in the type of the operand gives the same result as in a wider type
+        let (one_t, one_v) = match operand.expr_type.base_type {
+            BaseType::Pointer(_) => (
+                BaseType::SignedInteger(IntegerType::Int),
+                self.context
+                    .i32_type()
+                    .const_int(1, false)
+                    .as_basic_value_enum(),
+            ),
+            ref t @ (BaseType::Float | BaseType::Double) => (
+                t.to_owned(),
+                self.convert_llvm_type(t, span)?
+                    .into_float_type()
+                    .const_float(1.0)
+                    .as_basic_value_enum(),
+            ),
+            ref t => (
+                t.to_owned(),
+                self.convert_llvm_type(t, span)?
+                    .into_int_type()
+                    .const_int(1, false)
+                    .as_basic_value_enum(),
+            ),
+        };
+        let (old_v, new_v) =
+            self.gen_read_modify_write(&operand.expr_type, l_pv, &bin_op, &one_t, one_v, span)?;
+
+        match op.node {
+            UnaryOperationEnum::PostfixIncrement | UnaryOperationEnum::PostfixDecrement => {
+                Ok(old_v)
+            }
+            _ => Ok(new_v),
         }
+    }
 
-        let cast_t = BaseType::upcast(l_t, r_t, &self.typedef_map)?;
-        let l_cast_v = self.cast_value(l_t, &l_v, &cast_t, lhs.span)?;
-        let r_cast_v = self.cast_value(r_t, &r_v, &cast_t, lhs.span)?;
+    /// Replace the value of the object at `l_pv` with the result of `op` applied to it and to
+    /// the right operand, performed in the type `r_t` of the right operand unless the object
+    /// is a pointer, and converted back to the type of the object. Return the old and the new
+    /// values. Atomic objects are updated atomically.
+    fn gen_read_modify_write(
+        &self,
+        l_t: &BasicType,
+        l_pv: PointerValue<'ctx>,
+        op: &BinaryOperation,
+        r_t: &BaseType,
+        r_v: BasicValueEnum<'ctx>,
+        span: Span,
+    ) -> Result<(BasicValueEnum<'ctx>, BasicValueEnum<'ctx>), CE> {
+        let update = |old_v: BasicValueEnum<'ctx>| {
+            if let BaseType::Pointer(_) = l_t.base_type {
+                return self.build_point_binary_op(
+                    op,
+                    old_v.into_pointer_value(),
+                    r_v.into_int_value(),
+                );
+            }
+            let old_v = self.cast_value(&l_t.base_type, &old_v, r_t, span)?;
+            let new_v = self.gen_binary_values(op, r_t, old_v, r_v, span)?;
+            self.cast_value(r_t, &new_v, &l_t.base_type, span)
+        };
+        if !self.is_qualified(l_t, TypeQualifier::Atomic) {
+            let old_v = self.build_object_load(l_t, l_pv, "load_val");
+            let new_v = update(old_v)?;
+            self.build_object_store(l_t, l_pv, new_v);
+            return Ok((old_v, new_v));
+        }
 
-        match cast_t {
-            BaseType::Void => Err(CE::plain_error(
-                "Invalid void type for binary operation".to_string(),
-                span,
+        // integer addition, subtraction and bitwise operations give the same result whether
+        // done in the type of the object or in a wider one, so a single instruction does
+        let rmw_op = match op.node {
+            BinaryOperationEnum::Addition => Some(AtomicRMWBinOp::Add),
+            BinaryOperationEnum::Subtraction => Some(AtomicRMWBinOp::Sub),
+            BinaryOperationEnum::BitwiseAnd => Some(AtomicRMWBinOp::And),
+            BinaryOperationEnum::BitwiseOr => Some(AtomicRMWBinOp::Or),
+            BinaryOperationEnum::BitwiseXor => Some(AtomicRMWBinOp::Xor),
+            _ => None,
+        };
+        let rmw = match rmw_op {
+            Some(rmw_op) if l_t.base_type.is_integer() && r_t.is_integer() => Some((
+                rmw_op,
+                self.cast_value(r_t, &r_v, &l_t.base_type, span)?
+                    .into_int_value(),
             )),
-            BaseType::SignedInteger(_) | BaseType::UnsignedInteger(_) | BaseType::Bool => {
-                let int_or_bool_value = self.build_int_binary_op(
+            _ => None,
+        };
+        self.build_atomic_update(l_t, l_pv, rmw, update)
+    }
+
+    fn gen_binary_expr(
+        &self,
+        op: &BinaryOperation,
+        lhs: &TypedExpression,
+        rhs: &TypedExpression,
+        span: Span,
+    ) -> Result<BasicValueEnum<'ctx>, CE> {
+        let l_v = self.gen_expression(lhs)?;
+        let r_v = self.gen_expression(rhs)?;
+        let value = match (&op.node, &lhs.expr_type.base_type, &rhs.expr_type.base_type) {
+            // the left operand is evaluated only for its side effects
+            (BinaryOperationEnum::Comma, _, _) => Ok(r_v),
+            (BinaryOperationEnum::LogicalAnd, _, _) => Ok(self
+                .builder
+                .build_and(
+                    self.build_truth_value(l_v),
+                    self.build_truth_value(r_v),
+                    "logical_and",
+                )
+                .as_basic_value_enum()),
+            (BinaryOperationEnum::LogicalOr, _, _) => Ok(self
+                .builder
+                .build_or(
+                    self.build_truth_value(l_v),
+                    self.build_truth_value(r_v),
+                    "logical_or",
+                )
+                .as_basic_value_enum()),
+            (BinaryOperationEnum::Subtraction, BaseType::Pointer(_), BaseType::Pointer(_)) => {
+                Ok(self
+                    .builder
+                    .build_ptr_diff(
+                        l_v.into_pointer_value(),
+                        r_v.into_pointer_value(),
+                        "ptr_diff",
+                    )
+                    .as_basic_value_enum())
+            }
+            // pointer comparisons, an integer operand being a null pointer constant
+            (_, BaseType::Pointer(_), _) | (_, _, BaseType::Pointer(_)) if is_comparison(op) => {
+                let i64_type = self.context.i64_type();
+                let as_int = |v: BasicValueEnum<'ctx>| match v {
+                    BasicValueEnum::PointerValue(v) => {
+                        self.builder.build_ptr_to_int(v, i64_type, "ptr_to_int")
+                    }
+                    v => self
+                        .builder
+                        .build_int_cast(v.into_int_value(), i64_type, "cast"),
+                };
+                self.gen_binary_values(
                     op,
-                    l_cast_v.into_int_value(),
-                    r_cast_v.into_int_value(),
+                    &BaseType::UnsignedInteger(IntegerType::Long),
+                    as_int(l_v).as_basic_value_enum(),
+                    as_int(r_v).as_basic_value_enum(),
                     span,
-                )?;
-                // If value is bool
-                if int_or_bool_value
-                    .into_int_value()
-                    .get_type()
-                    .get_bit_width()
-                    == 1
-                {
-                    Ok((BaseType::Bool, int_or_bool_value))
-                } else {
-                    Ok((cast_t, int_or_bool_value))
-                }
+                )
             }
+            (_, BaseType::Pointer(_), _) => {
+                self.build_point_binary_op(op, l_v.into_pointer_value(), r_v.into_int_value())
+            }
+            (_, _, BaseType::Pointer(_)) => {
+                self.build_point_binary_op(op, r_v.into_pointer_value(), l_v.into_int_value())
+            }
+            // arithmetic operands are converted to their common type
+            (_, l_t, _) => self.gen_binary_values(op, l_t, l_v, r_v, span),
+        }?;
+        match op.node {
+            BinaryOperationEnum::LogicalAnd | BinaryOperationEnum::LogicalOr => {
+                Ok(self.build_int_from_truth_value(value.into_int_value()))
+            }
+            _ if is_comparison(op) => Ok(self.build_int_from_truth_value(value.into_int_value())),
+            _ => Ok(value),
+        }
+    }
+
+    /// Apply a binary operator to operands of the same arithmetic type `t`
+    fn gen_binary_values(
+        &self,
+        op: &BinaryOperation,
+        t: &BaseType,
+        l_v: BasicValueEnum<'ctx>,
+        r_v: BasicValueEnum<'ctx>,
+        span: Span,
+    ) -> Result<BasicValueEnum<'ctx>, CE> {
+        match t {
             BaseType::Float | BaseType::Double => {
-                let float_or_bool_value = self.build_float_binary_op(
-                    op,
-                    l_cast_v.into_float_value(),
-                    r_cast_v.into_float_value(),
-                )?;
-                // If value is bool(always float or int1)
-                if float_or_bool_value.is_int_value() {
-                    return Ok((BaseType::Bool, float_or_bool_value));
-                }
-                Ok((cast_t, float_or_bool_value))
+                self.build_float_binary_op(op, l_v.into_float_value(), r_v.into_float_value())
             }
-            _ => unimplemented!(),
+            _ => self.build_int_binary_op(
+                op,
+                l_v.into_int_value(),
+                r_v.into_int_value(),
+                !matches!(t, BaseType::UnsignedInteger(_) | BaseType::Bool),
+                span,
+            ),
         }
     }
 
@@ -637,60 +495,74 @@ impl<'ctx> Generator<'ctx> {
     ) -> Result<BasicValueEnum<'ctx>, CE> {
         let result_v = match op.node {
             BinaryOperationEnum::Addition => unsafe {
-                let idx_int_val_vec = vec![rhs];
-                self.builder
-                    .build_gep(lhs, idx_int_val_vec.as_ref(), "pointer add")
+                self.builder.build_gep(lhs, &[rhs], "pointer add")
             },
             BinaryOperationEnum::Subtraction => unsafe {
-                let idx_int_val_vec = vec![rhs.const_neg()];
-                self.builder
-                    .build_gep(lhs, idx_int_val_vec.as_ref(), "pointer add")
+                let rhs = self.builder.build_int_neg(rhs, "int_neg");
+                self.builder.build_gep(lhs, &[rhs], "pointer add")
             },
-
-            // logical
-            //TODO 先不管logical操作了QAQ
-            _ => unimplemented!(),
+            _ => return Err(CE::invalid_binary(op.span)),
         };
         Ok(result_v.as_basic_value_enum())
     }
+
     fn build_int_binary_op(
         &self,
         op: &BinaryOperation,
         lhs: IntValue<'ctx>,
         rhs: IntValue<'ctx>,
+        is_signed: bool,
         span: Span,
     ) -> Result<BasicValueEnum<'ctx>, CE> {
+        let predicate = |signed, unsigned| match is_signed {
+            true => signed,
+            false => unsigned,
+        };
         let result_v = match op.node {
             // arithmetic
             BinaryOperationEnum::Addition => self.builder.build_int_add(lhs, rhs, "int_add"),
             BinaryOperationEnum::Subtraction => self.builder.build_int_sub(lhs, rhs, "int_sub"),
             BinaryOperationEnum::Multiplication => self.builder.build_int_mul(lhs, rhs, "int_mul"),
-            BinaryOperationEnum::Division => self.builder.build_int_signed_div(lhs, rhs, "int_div"),
-            BinaryOperationEnum::Modulo => self.builder.build_int_signed_rem(lhs, rhs, "int_mod"),
+            BinaryOperationEnum::Division => match is_signed {
+                true => self.builder.build_int_signed_div(lhs, rhs, "int_div"),
+                false => self.builder.build_int_unsigned_div(lhs, rhs, "int_div"),
+            },
+            BinaryOperationEnum::Modulo => match is_signed {
+                true => self.builder.build_int_signed_rem(lhs, rhs, "int_mod"),
+                false => self.builder.build_int_unsigned_rem(lhs, rhs, "int_mod"),
+            },
             BinaryOperationEnum::BitwiseAnd => self.builder.build_and(lhs, rhs, "int_and"),
             BinaryOperationEnum::BitwiseOr => self.builder.build_or(lhs, rhs, "int_or"),
             BinaryOperationEnum::BitwiseXor => self.builder.build_xor(lhs, rhs, "int_xor"),
             BinaryOperationEnum::LeftShift => self.builder.build_left_shift(lhs, rhs, "int_shl"),
-            BinaryOperationEnum::RightShift => {
-                self.builder.build_right_shift(lhs, rhs, true, "int_shr")
-            }
+            BinaryOperationEnum::RightShift => self
+                .builder
+                .build_right_shift(lhs, rhs, is_signed, "int_shr"),
             // comparison
-            BinaryOperationEnum::LessThan => {
-                self.builder
-                    .build_int_compare(IntPredicate::SLT, lhs, rhs, "int_lt")
-            }
-            BinaryOperationEnum::LessThanOrEqual => {
-                self.builder
-                    .build_int_compare(IntPredicate::SLE, lhs, rhs, "int_le")
-            }
-            BinaryOperationEnum::GreaterThan => {
-                self.builder
-                    .build_int_compare(IntPredicate::SGT, lhs, rhs, "int_gt")
-            }
-            BinaryOperationEnum::GreaterThanOrEqual => {
-                self.builder
-                    .build_int_compare(IntPredicate::SGE, lhs, rhs, "int_ge")
-            }
+            BinaryOperationEnum::LessThan => self.builder.build_int_compare(
+                predicate(IntPredicate::SLT, IntPredicate::ULT),
+                lhs,
+                rhs,
+                "int_lt",
+            ),
+            BinaryOperationEnum::LessThanOrEqual => self.builder.build_int_compare(
+                predicate(IntPredicate::SLE, IntPredicate::ULE),
+                lhs,
+                rhs,
+                "int_le",
+            ),
+            BinaryOperationEnum::GreaterThan => self.builder.build_int_compare(
+                predicate(IntPredicate::SGT, IntPredicate::UGT),
+                lhs,
+                rhs,
+                "int_gt",
+            ),
+            BinaryOperationEnum::GreaterThanOrEqual => self.builder.build_int_compare(
+                predicate(IntPredicate::SGE, IntPredicate::UGE),
+                lhs,
+                rhs,
+                "int_ge",
+            ),
             BinaryOperationEnum::Equal => {
                 self.builder
                     .build_int_compare(IntPredicate::EQ, lhs, rhs, "int_eq")
@@ -699,445 +571,295 @@ impl<'ctx> Generator<'ctx> {
                 self.builder
                     .build_int_compare(IntPredicate::NE, lhs, rhs, "int_ne")
             }
-            // logical
-            //TODO FIXME 这里and 和 or 是完全错误的，这个build_int_cast只是截取了最后一位！！
-            BinaryOperationEnum::LogicalAnd => self.builder.build_and(
-                self.builder
-                    .build_int_cast(lhs, self.context.bool_type(), "cast i32 to i1_1"),
-                self.builder
-                    .build_int_cast(rhs, self.context.bool_type(), "cast i32 to i1_2"),
-                "logical int and",
-            ),
-            BinaryOperationEnum::LogicalOr => self.builder.build_or(
-                self.builder
-                    .build_int_cast(lhs, self.context.bool_type(), "cast i32 to i1_1"),
-                self.builder
-                    .build_int_cast(rhs, self.context.bool_type(), "cast i32 to i1_2"),
-                "logical int or",
-            ),
-
             _ => return Err(CE::invalid_binary(span)),
         };
 
         Ok(result_v.as_basic_value_enum())
     }
+
     fn build_float_binary_op(
         &self,
         op: &BinaryOperation,
         lhs: FloatValue<'ctx>,
         rhs: FloatValue<'ctx>,
     ) -> Result<BasicValueEnum<'ctx>, CE> {
+        let compare = |predicate, name| {
+            Ok(self
+                .builder
+                .build_float_compare(predicate, lhs, rhs, name)
+                .as_basic_value_enum())
+        };
         let result_f = match op.node {
             // arithmetic
-            BinaryOperationEnum::Addition => {
-                Ok(self.builder.build_float_add(lhs, rhs, "float add"))
-            }
-            BinaryOperationEnum::Subtraction => {
-                Ok(self.builder.build_float_sub(lhs, rhs, "float sub"))
-            }
+            BinaryOperationEnum::Addition => self.builder.build_float_add(lhs, rhs, "float add"),
+            BinaryOperationEnum::Subtraction => self.builder.build_float_sub(lhs, rhs, "float sub"),
             BinaryOperationEnum::Multiplication => {
-                Ok(self.builder.build_float_mul(lhs, rhs, "float mul"))
-            }
-            BinaryOperationEnum::Division => {
-                Ok(self.builder.build_float_div(lhs, rhs, "float div"))
-            }
-            BinaryOperationEnum::Modulo => Ok(self.builder.build_float_rem(lhs, rhs, "float mod")),
-            _ => Err(Error),
-        };
-        //return FloatValue
-        if let Ok(result_f) = result_f {
-            return Ok(result_f.as_basic_value_enum());
-        }
-        let result_i = match op.node {
-            BinaryOperationEnum::LessThan => {
-                self.builder
-                    .build_float_compare(FloatPredicate::OLT, lhs, rhs, "float lt")
+                self.builder.build_float_mul(lhs, rhs, "float mul")
             }
+            BinaryOperationEnum::Division => self.builder.build_float_div(lhs, rhs, "float div"),
+            BinaryOperationEnum::Modulo => self.builder.build_float_rem(lhs, rhs, "float mod"),
+            // comparison
+            BinaryOperationEnum::LessThan => return compare(FloatPredicate::OLT, "float lt"),
             BinaryOperationEnum::LessThanOrEqual => {
-                self.builder
-                    .build_float_compare(FloatPredicate::OLE, lhs, rhs, "float le")
-            }
-            BinaryOperationEnum::GreaterThan => {
-                self.builder
-                    .build_float_compare(FloatPredicate::OGT, lhs, rhs, "float gt")
+                return compare(FloatPredicate::OLE, "float le")
             }
+            BinaryOperationEnum::GreaterThan => return compare(FloatPredicate::OGT, "float gt"),
             BinaryOperationEnum::GreaterThanOrEqual => {
-                self.builder
-                    .build_float_compare(FloatPredicate::OGE, lhs, rhs, "float ge")
-            }
-            BinaryOperationEnum::Equal => {
-                self.builder
-                    .build_float_compare(FloatPredicate::OEQ, lhs, rhs, "float eq")
+                return compare(FloatPredicate::OGE, "float ge")
             }
-            BinaryOperationEnum::NotEqual => {
-                self.builder
-                    .build_float_compare(FloatPredicate::ONE, lhs, rhs, "float ne")
-            }
-            // logical
-            //TODO FIXME 这里and 和 or 是完全错误的，这个build_int_cast只是截取了最后一位！！
-            BinaryOperationEnum::LogicalAnd => self.builder.build_and(
-                self.builder.build_int_cast(
-                    self.builder
-                        .build_cast(
-                            self.gen_cast_llvm_instruction(
-                                &BaseType::Double,
-                                &BaseType::SignedInteger(IntegerType::Int),
-                                op.span,
-                            )?,
-                            lhs,
-                            self.context.i32_type(),
-                            "cast float to i32",
-                        )
-                        .into_int_value(),
-                    self.context.bool_type(),
-                    "cast float to i1",
-                ),
-                self.builder.build_int_cast(
-                    self.builder
-                        .build_cast(
-                            self.gen_cast_llvm_instruction(
-                                &BaseType::Double,
-                                &BaseType::SignedInteger(IntegerType::Int),
-                                op.span,
-                            )?,
-                            rhs,
-                            self.context.i32_type(),
-                            "cast float to i32",
-                        )
-                        .into_int_value(),
-                    self.context.bool_type(),
-                    "cast float to i1",
-                ),
-                "logical float and",
-            ),
-            BinaryOperationEnum::LogicalOr => self.builder.build_or(
-                self.builder.build_int_cast(
-                    self.builder
-                        .build_cast(
-                            self.gen_cast_llvm_instruction(
-                                &BaseType::Double,
-                                &BaseType::SignedInteger(IntegerType::Int),
-                                op.span,
-                            )?,
-                            lhs,
-                            self.context.i32_type(),
-                            "cast float to i32",
-                        )
-                        .into_int_value(),
-                    self.context.bool_type(),
-                    "cast float to i1",
-                ),
-                self.builder.build_int_cast(
-                    self.builder
-                        .build_cast(
-                            self.gen_cast_llvm_instruction(
-                                &BaseType::Double,
-                                &BaseType::SignedInteger(IntegerType::Int),
-                                op.span,
-                            )?,
-                            rhs,
-                            self.context.i32_type(),
-                            "cast float to i32",
-                        )
-                        .into_int_value(),
-                    self.context.bool_type(),
-                    "cast float to i1",
-                ),
-                "logical float or",
-            ),
+            BinaryOperationEnum::Equal => return compare(FloatPredicate::OEQ, "float eq"),
+            BinaryOperationEnum::NotEqual => return compare(FloatPredicate::UNE, "float ne"),
             _ => return Err(CE::invalid_binary(op.span)),
         };
-        //return IntValue
-        Ok(result_i.as_basic_value_enum())
+        Ok(result_f.as_basic_value_enum())
     }
-    pub(crate) fn gen_assignment(
+
+    fn gen_assignment(
         &self,
         op: &AssignOperation,
-        lhs: &Expression,
-        rhs: &Expression,
+        lhs: &TypedExpression,
+        rhs: &TypedExpression,
         span: Span,
-    ) -> Result<(BaseType, BasicValueEnum<'ctx>), CE> {
-        let (l_t, l_pv) = self.get_lvalue(lhs)?;
-
-        let (r_t, r_v) = if let AssignOperationEnum::Naive = op.node {
-            self.gen_expression(rhs)?
-        } else {
-            self.gen_binary_expr(
-                &BinaryOperation {
-                    node: match op.node {
-                        AssignOperationEnum::Addition => BinaryOperationEnum::Addition,
-                        AssignOperationEnum::Subtraction => BinaryOperationEnum::Subtraction,
-                        AssignOperationEnum::Multiplication => BinaryOperationEnum::Multiplication,
-                        AssignOperationEnum::Division => BinaryOperationEnum::Division,
-                        AssignOperationEnum::Modulo => BinaryOperationEnum::Modulo,
-                        AssignOperationEnum::BitwiseAnd => BinaryOperationEnum::BitwiseAnd,
-                        AssignOperationEnum::BitwiseOr => BinaryOperationEnum::BitwiseOr,
-                        AssignOperationEnum::BitwiseXor => BinaryOperationEnum::BitwiseXor,
-                        AssignOperationEnum::LeftShift => BinaryOperationEnum::LeftShift,
-                        AssignOperationEnum::RightShift => BinaryOperationEnum::RightShift,
-                        AssignOperationEnum::Naive => unreachable!(),
-                    },
+    ) -> Result<BasicValueEnum<'ctx>, CE> {
+        let l_pv = self.get_lvalue(lhs)?;
+        let r_v = self.gen_expression(rhs)?;
+
+        match op.node.binary_operation() {
+            None => {
+                self.build_object_store(&lhs.expr_type, l_pv, r_v);
+                Ok(r_v)
+            }
+            Some(binary_op) => {
+                // the left operand of a compound assignment is evaluated only once
+                let binary_op = BinaryOperation {
+                    node: binary_op,
                     span: op.span,
-                },
-                lhs,
-                rhs,
-                span,
-            )?
-        };
-
-        r_t.test_cast(&l_t.base_type, rhs.span, &self.typedef_map)?;
-
-        let cast_v = self.cast_value(&r_t, &r_v, &l_t.base_type, rhs.span)?;
+                };
+                Ok(self
+                    .gen_read_modify_write(
+                        &lhs.expr_type,
+                        l_pv,
+                        &binary_op,
+                        &rhs.expr_type.base_type,
+                        r_v,
+                        span,
+                    )?
+                    .1)
+            }
+        }
+    }
 
-        self.builder.build_store(l_pv, cast_v);
+    /// Only the selected operand is evaluated, both are already converted to the type of
+    /// the result.
+    fn gen_conditional(
+        &self,
+        cond: &TypedExpression,
+        true_expr: &TypedExpression,
+        false_expr: &TypedExpression,
+    ) -> Result<BasicValueEnum<'ctx>, CE> {
+        let cond_v = self.build_truth_value(self.gen_expression(cond)?);
+        let func = match self.current_function {
+            Some((func, _)) => func,
+            // a constant initializer
+            None => {
+                return Ok(self.builder.build_select(
+                    cond_v,
+                    self.gen_expression(true_expr)?,
+                    self.gen_expression(false_expr)?,
+                    "conditional",
+                ))
+            }
+        };
 
-        Ok((l_t.base_type, cast_v))
+        let true_block = self.context.append_basic_block(func, "cond_true");
+        let false_block = self.context.append_basic_block(func, "cond_false");
+        let after_block = self.context.append_basic_block(func, "after_cond");
+        self.builder
+            .build_conditional_branch(cond_v, true_block, false_block);
+
+        self.builder.position_at_end(true_block);
+        let true_v = self.gen_expression(true_expr)?;
+        let true_end = self.builder.get_insert_block().unwrap();
+        self.builder.build_unconditional_branch(after_block);
+
+        self.builder.position_at_end(false_block);
+        let false_v = self.gen_expression(false_expr)?;
+        let false_end = self.builder.get_insert_block().unwrap();
+        self.builder.build_unconditional_branch(after_block);
+
+        self.builder.position_at_end(after_block);
+        let phi = self.builder.build_phi(true_v.get_type(), "conditional");
+        phi.add_incoming(&[(&true_v, true_end), (&false_v, false_end)]);
+        Ok(phi.as_basic_value())
     }
 
-    fn get_lvalue(&self, lhs: &Expression) -> Result<(BasicType, PointerValue<'ctx>), CE> {
+    pub(crate) fn get_lvalue(&self, lhs: &TypedExpression) -> Result<PointerValue<'ctx>, CE> {
         match lhs.node {
-            ExpressionEnum::Identifier(ref id) => Ok(self.get_variable(id, lhs.span)?),
-            ExpressionEnum::Unary(ref op, ref unary_operation) => {
-                // we need lhs expression's type!!! But now we don't have a function only get the type
-                //TODO FIXME! It's really dirty
-                let (t, _) = self.gen_expression(lhs)?;
-                let (l_t, l_v) = self.gen_expression(unary_operation)?;
-                if let UnaryOperationEnum::Dereference = op.node {
-                    if let BaseType::Pointer(_) = l_t {
-                        Ok((
-                            BasicType {
-                                qualifier: vec![],
-                                base_type: t,
-                            },
-                            l_v.into_pointer_value(),
-                        ))
-                    } else {
-                        Err(CE::invalid_dereference(
-                            l_v.print_to_string().to_string(),
-                            lhs.span,
-                        ))
-                    }
-                } else if l_v.is_pointer_value() {
-                    Ok((
-                        BasicType {
-                            qualifier: vec![],
-                            base_type: t,
-                        },
-                        l_v.into_pointer_value(),
-                    ))
-                } else {
-                    Err(CE::invalid_left_value(
-                        l_v.print_to_string().to_string(),
-                        lhs.span,
-                    ))
-                }
-            }
-            ExpressionEnum::ArraySubscript(ref id_expr, ref idx_vec) => {
-                if let ExpressionEnum::Identifier(ref id) = id_expr.node {
-                    let (t, pv) = self.get_variable(id, lhs.span)?;
-                    self.gen_array_subscript(&t, pv, idx_vec, &lhs.span)
-                } else if let ExpressionEnum::MemberOfObject(ref id_expr, ref member_id) =
-                    id_expr.node
-                {
-                    let (t, pv) = self.gen_member_of_object(id_expr, member_id, lhs.span)?;
-                    self.gen_array_subscript(&t, pv, idx_vec, &lhs.span)
-                } else {
-                    unreachable!()
-                }
-            }
-            ExpressionEnum::MemberOfObject(ref id_expr, ref member_id) => {
-                let (t, p_v) = self.gen_member_of_object(id_expr, member_id, lhs.span)?;
-                Ok((t, p_v))
-            }
-            ExpressionEnum::MemberOfPointer(ref id_expr, ref member_id) => {
-                let (t, p_v) = self.gen_member_of_pointer(id_expr, member_id, lhs.span)?;
-                Ok((t, p_v))
-            }
-            _ => panic!(),
+            TypedExpressionEnum::Identifier(ref id, _) => Ok(self.get_variable(id, lhs.span)?.1),
+            // only a dereference is an lvalue
+            TypedExpressionEnum::Unary(_, ref operand) => {
+                Ok(self.gen_expression(operand)?.into_pointer_value())
+            }
+            TypedExpressionEnum::ArraySubscript(ref array, ref indexes) => {
+                self.gen_array_subscript(array, indexes)
+            }
+            TypedExpressionEnum::MemberOfObject(ref obj, ref member) => {
+                let p_v = self.get_lvalue(obj)?;
+                let idx = self.find_member(&obj.expr_type.base_type, member, lhs.span)?;
+                Ok(self
+                    .builder
+                    .build_struct_gep(p_v, idx, "member_of_object")
+                    .unwrap())
+            }
+            TypedExpressionEnum::MemberOfPointer(ref ptr, ref member) => {
+                let ptr_value = self.gen_expression(ptr)?.into_pointer_value();
+                let struct_type = match ptr.expr_type.base_type {
+                    BaseType::Pointer(ref struct_type) => &struct_type.base_type,
+                    _ => unreachable!(),
+                };
+                let idx = self.find_member(struct_type, member, lhs.span)?;
+                Ok(self
+                    .builder
+                    .build_struct_gep(ptr_value, idx, "member_of_pointer")
+                    .unwrap())
+            }
+            TypedExpressionEnum::Literal(ExpressionEnum::StringLiteral(prefix, ref code_units)) => {
+                let value =
+                    self.gen_string_constant(prefix, code_units, code_units.len() + 1, lhs.span)?;
+                let global_value = self.module.add_global(value.get_type(), None, "str");
+                global_value.set_linkage(Linkage::Private);
+                global_value.set_unnamed_addr(true);
+                global_value.set_constant(true);
+                global_value.set_initializer(&value);
+                Ok(global_value.as_pointer_value())
+            }
+            _ => Err(CE::invalid_left_value(self.source_text(lhs.span), lhs.span)),
         }
     }
+
+    /// The address of an element of an array or of the object a pointer points to. Indexes of
+    /// an array, which may be multidimensional, go in a single `getelementptr`, a pointer is
+    /// loaded and indexed once per index.
     fn gen_array_subscript(
         &self,
-        t: &BasicType,
-        mut pv: PointerValue<'ctx>,
-        idx_vec: &[Expression],
-        span: &Span,
-    ) -> Result<(BasicType, PointerValue<'ctx>), CE> {
-        //if type is pointer, get_variable will get the address of pointer, not the pointer point to!
-        //So if we want get the point to address, we need extra load action!
-        if let BaseType::Pointer(_) = t.base_type {
-            pv = self
-                .builder
-                .build_load(pv, "dereference")
-                .into_pointer_value()
-        }
-        let (res_t, mut idx_int_val_vec) = self.process_arr_subscript(t, idx_vec, *span)?;
-        //Pointer
-        if let BaseType::Pointer(_) = t.base_type {
-            while !idx_int_val_vec.is_empty() {
-                pv = unsafe {
-                    self.builder
-                        .build_gep(pv, [idx_int_val_vec[0]].as_ref(), "ptr_subscript")
-                };
-                idx_int_val_vec.remove(0);
-                if !idx_int_val_vec.is_empty() {
-                    pv = self
-                        .builder
-                        .build_load(pv, "dereference")
-                        .into_pointer_value()
+        array: &TypedExpression,
+        indexes: &[TypedExpression],
+    ) -> Result<PointerValue<'ctx>, CE> {
+        let mut t = array.expr_type.base_type.to_owned();
+        let mut pv = match (&t, array.category) {
+            (BaseType::Array(_, _), _) => self.get_lvalue(array)?,
+            (_, ValueCategory::LValue) => self
+                .build_object_load(&array.expr_type, self.get_lvalue(array)?, "dereference")
+                .into_pointer_value(),
+            (_, ValueCategory::RValue) => self.gen_expression(array)?.into_pointer_value(),
+        };
+        let mut indexes = indexes.iter();
+        loop {
+            match t {
+                BaseType::Array(element_type, dimensions) => {
+                    // an array is indexed through the pointer to it, which takes an extra
+                    // leading zero
+                    let mut idx_int_val_vec = vec![self.context.i32_type().const_zero()];
+                    for index in indexes.by_ref().take(dimensions.len()) {
+                        idx_int_val_vec.push(self.gen_expression(index)?.into_int_value());
+                    }
+                    let count = idx_int_val_vec.len() - 1;
+                    pv = unsafe {
+                        self.builder
+                            .build_gep(pv, idx_int_val_vec.as_ref(), "arr_subscript")
+                    };
+                    t = match count < dimensions.len() {
+                        true => BaseType::Array(element_type, dimensions[count..].to_vec()),
+                        false => element_type.base_type,
+                    };
                 }
+                BaseType::Pointer(element_type) => {
+                    let index = self.gen_expression(indexes.next().unwrap())?;
+                    pv = unsafe {
+                        self.builder
+                            .build_gep(pv, &[index.into_int_value()], "pointer_subscript")
+                    };
+                    t = element_type.base_type;
+                }
+                _ => unreachable!(),
+            }
+            if indexes.len() == 0 {
+                return Ok(pv);
+            }
+            //if type is pointer, we have the address of pointer, not the pointer point to!
+            //So if we want get the point to address, we need extra load action!
+            if let BaseType::Pointer(_) = t {
+                pv = self
+                    .builder
+                    .build_load(pv, "dereference")
+                    .into_pointer_value();
             }
-            Ok((res_t, pv))
-        } else {
-            //array
-            Ok((res_t, unsafe {
-                self.builder
-                    .build_gep(pv, idx_int_val_vec.as_ref(), "arr_subscript")
-            }))
         }
     }
+
     fn gen_function_call(
         &self,
-        name: &Expression,
-        args: &[Expression],
+        function: &TypedExpression,
+        args: &[TypedExpression],
         span: Span,
-    ) -> Result<(BaseType, BasicValueEnum<'ctx>), CE> {
-        if let ExpressionEnum::Identifier(ref id) = name.node {
-            let (ret_t, args_t, is_variadic) = self.function_map.get(id).unwrap().to_owned();
-            let fv = self.module.get_function(id).unwrap();
-
-            if args.len() != fv.get_type().count_param_types() as usize
-                && !(is_variadic && args.len() > fv.get_type().count_param_types() as usize)
-            {
-                return Err(CE::parameter_count_mismatch(
-                    id.to_string(),
-                    fv.get_type().count_param_types() as usize,
-                    args.len(),
-                    span,
-                ));
-            }
-
-            let mut casted_args = Vec::with_capacity(args.len());
-
-            for (i, e) in args.iter().enumerate() {
-                let t = args_t.get(i);
-
-                match t {
-                    Some(t) => {
-                        let (e_t, e_v) = self.gen_expression(e)?;
-
-                        e_t.test_cast(&t.base_type, e.span, &self.typedef_map)?;
-                        let cast_v = self.cast_value(&e_t, &e_v, &t.base_type, e.span)?;
-
-                        casted_args.push(BasicMetadataValueEnum::from(cast_v));
-                    }
-                    None => {
-                        // variadic
-                        let (_, e_v) = self.gen_expression(e)?;
-
-                        casted_args.push(BasicMetadataValueEnum::from(e_v));
-                    }
-                }
-            }
-
-            let ret_v = self
-                .builder
-                .build_call(fv, casted_args.as_slice(), id)
-                .try_as_basic_value()
-                .left();
-
-            if ret_t.base_type == BaseType::Void && ret_v.is_none()
-                || ret_t.base_type != BaseType::Void && ret_v.is_some()
-            {
-                Ok((
-                    ret_t.base_type,
-                    ret_v.unwrap_or_else(|| {
-                        self.context.i32_type().const_zero().as_basic_value_enum()
-                    }),
-                ))
-            } else {
-                unreachable!()
-            }
-        } else {
-            unreachable!()
-        }
+    ) -> Result<BasicValueEnum<'ctx>, CE> {
+        let id = match function.node {
+            TypedExpressionEnum::Identifier(ref id, Symbol::Builtin) => {
+                return self.gen_builtin_call(id, args, span);
+            }
+            TypedExpressionEnum::ImplicitCast(ref function) => match function.node {
+                TypedExpressionEnum::Identifier(ref id, Symbol::Function(_)) => id,
+                // calls through function pointers are not supported
+                _ => return Err(CE::unknown_expression(span)),
+            },
+            _ => return Err(CE::unknown_expression(span)),
+        };
+        let fv = self.module.get_function(id).unwrap();
+
+        let args = args
+            .iter()
+            .map(|arg| Ok(BasicMetadataValueEnum::from(self.gen_expression(arg)?)))
+            .collect::<Result<Vec<_>, CE>>()?;
+
+        let ret_v = self
+            .builder
+            .build_call(fv, args.as_slice(), id)
+            .try_as_basic_value()
+            .left();
+        Ok(ret_v.unwrap_or_else(|| self.context.i32_type().const_zero().as_basic_value_enum()))
     }
 
-    pub(crate) fn gen_member_of_object(
+    /// The LLVM field of a member of a struct type
+    pub(crate) fn find_member(
         &self,
-        obj: &Expression,
-        member: &String,
+        struct_type: &BaseType,
+        member: &str,
         span: Span,
-    ) -> Result<(BasicType, PointerValue<'ctx>), CE> {
-        let (t, p_v) = self.get_lvalue(obj)?;
-        if let BaseType::Struct(ref name, _) = t.base_type {
-            let members = self
-                .global_struct_map
-                .get(name.clone().unwrap().as_str())
-                .unwrap();
+    ) -> Result<u32, CE> {
+        if let BaseType::Struct(ref name, ref members) = struct_type {
+            let members = self.members_of_struct(name, members, span)?;
             let idx = members
                 .iter()
-                .map(|x| x.clone().member_name)
-                .position(|x| x == *member);
-            if let Some(idx) = idx {
-                Ok((
-                    members.get(idx).unwrap().member_type.clone(),
-                    self.builder
-                        .build_struct_gep(p_v, idx as u32, "member_of_object")
-                        .unwrap(),
-                ))
-            } else {
-                Err(CE::struct_member_not_found(
-                    name.clone().unwrap(),
-                    member.to_string(),
-                    span,
-                ))
-            }
+                .position(|x| x.member_name == member)
+                .unwrap();
+            Ok(match self.struct_layout(&members, span)? {
+                Some(layout) => layout.field_indices[idx],
+                None => idx as u32,
+            })
         } else {
-            unimplemented!()
+            Err(CE::get_member_from_not_struct(member.to_string(), span))
         }
     }
+}
 
-    pub(crate) fn gen_member_of_pointer(
-        &self,
-        ptr: &Expression,
-        member: &String,
-        span: Span,
-    ) -> Result<(BasicType, PointerValue<'ctx>), CE> {
-        let (ptr_type, ptr_value) = self.gen_expression(ptr)?;
-        if let BaseType::Pointer(struct_type) = ptr_type {
-            if let BaseType::Struct(ref name, _) = struct_type.base_type {
-                let members = self
-                    .global_struct_map
-                    .get(name.clone().unwrap().as_str())
-                    .unwrap();
-                let idx = members
-                    .iter()
-                    .map(|x| x.clone().member_name)
-                    .position(|x| x == *member);
-                if let Some(idx) = idx {
-                    Ok((
-                        members.get(idx).unwrap().member_type.clone(),
-                        self.builder
-                            .build_struct_gep(
-                                ptr_value.into_pointer_value(),
-                                idx as u32,
-                                "member_of_pointer",
-                            )
-                            .unwrap(),
-                    ))
-                } else {
-                    Err(CE::struct_member_not_found(
-                        name.clone().unwrap(),
-                        member.to_string(),
-                        span,
-                    ))
-                }
-            } else {
-                Err(CE::get_member_from_not_struct(member.clone(), span))
-            }
-        } else {
-            Err(CE::invalid_dereference(member.clone(), span))
-        }
-    }
+fn is_comparison(op: &BinaryOperation) -> bool {
+    matches!(
+        op.node,
+        BinaryOperationEnum::LessThan
+            | BinaryOperationEnum::LessThanOrEqual
+            | BinaryOperationEnum::GreaterThan
+            | BinaryOperationEnum::GreaterThanOrEqual
+            | BinaryOperationEnum::Equal
+            | BinaryOperationEnum::NotEqual
+    )
 }
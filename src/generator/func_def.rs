@@ -1,9 +1,12 @@
-use crate::ast::{
-    AssignOperation, AssignOperationEnum, BaseType, BasicType, Declaration, DeclarationEnum,
-    Expression, ExpressionEnum, Span, Statement, StatementEnum, StatementOrDeclarationEnum,
-};
+use crate::ast::{BaseType, BasicType, ExpressionEnum, Span, StorageClassSpecifier, Type};
 use crate::generator::Generator;
+use crate::sema::{
+    TypedDeclaration, TypedDeclarationEnum, TypedExpression, TypedExpressionEnum, TypedStatement,
+    TypedStatementEnum, TypedStatementOrDeclaration,
+};
 use crate::utils::CompileErr as CE;
+use inkwell::attributes::{Attribute, AttributeLoc};
+use inkwell::types::BasicTypeEnum;
 use inkwell::values::{BasicValue, PointerValue};
 use std::collections::HashMap;
 
@@ -13,14 +16,14 @@ impl<'ctx> Generator<'ctx> {
         return_type: &BasicType,
         func_name: &str,
         func_param: &[(BasicType, Option<String>)],
-        func_body: &Statement,
+        func_body: &TypedStatement,
         span: Span,
     ) -> Result<(), Vec<CE>> {
         let func = self.module.get_function(func_name).unwrap();
         self.val_map_block_stack.push(HashMap::new());
 
-        let func_ty = self.function_map.get(func_name).unwrap().to_owned();
-        self.current_function = Some((func, func_ty.0));
+        self.current_function = Some((func, return_type.to_owned()));
+        self.label_blocks.clear();
 
         let mut func_param_alloca = Vec::new();
         let mut errors: Vec<CE> = Vec::new();
@@ -62,33 +65,26 @@ impl<'ctx> Generator<'ctx> {
 
             func_param_alloca.push(alloca);
 
-            if func_param[i].1.is_some() {
-                if let Err(e) = self.insert_to_val_map(
-                    &func_param[i].0,
-                    func_param[i].1.as_ref().unwrap(),
-                    alloca,
-                    span,
-                ) {
-                    errors.push(e);
-                }
+            if let Some(ref name) = func_param[i].1 {
+                self.insert_to_val_map(&func_param[i].0, name, alloca);
             }
         }
 
         // store params on the stack
         for (i, param) in func.get_param_iter().enumerate() {
-            self.builder.build_store(func_param_alloca[i], param);
+            self.build_object_store(&func_param[i].0, func_param_alloca[i], param);
         }
 
         // generate IR for each statement or declaration in function body
-        if let StatementEnum::Compound(ref state_or_decl) = func_body.node {
+        if let TypedStatementEnum::Compound(ref state_or_decl) = func_body.node {
             errors.extend(
                 state_or_decl
                     .iter()
-                    .map(|element| match element.node {
-                        StatementOrDeclarationEnum::Statement(ref state) => {
+                    .map(|element| match element {
+                        TypedStatementOrDeclaration::Statement(ref state) => {
                             self.gen_statement(state)
                         }
-                        StatementOrDeclarationEnum::LocalDeclaration(ref decl) => {
+                        TypedStatementOrDeclaration::LocalDeclaration(ref decl) => {
                             self.gen_decl_in_fn(decl)
                         }
                     })
@@ -99,6 +95,12 @@ impl<'ctx> Generator<'ctx> {
         }
 
         // build terminator for any block that is not terminated
+        let noreturn = func
+            .get_enum_attribute(
+                AttributeLoc::Function,
+                Attribute::get_named_enum_kind_id("noreturn"),
+            )
+            .is_some();
         let mut iter_block = func.get_first_basic_block();
         while iter_block.is_some() {
             let block = iter_block.unwrap();
@@ -106,12 +108,27 @@ impl<'ctx> Generator<'ctx> {
                 let terminator_builder = self.context.create_builder();
                 terminator_builder.position_at_end(block);
                 match return_type.base_type {
+                    // the end of a `_Noreturn` function is checked to be unreachable
+                    _ if noreturn => {
+                        terminator_builder.build_unreachable();
+                    }
                     BaseType::Void => {
                         terminator_builder.build_return(None);
                     }
+                    // `main` returns 0, other functions an undefined value, which is
+                    // warned about by the semantic analysis
                     _ => {
-                        let null_val = self.context.i32_type().const_zero();
-                        terminator_builder.build_return(Some(&null_val));
+                        let llvm_type = func.get_type().get_return_type().unwrap();
+                        let value = match (func_name, llvm_type) {
+                            ("main", _) => llvm_type.const_zero(),
+                            (_, BasicTypeEnum::ArrayType(t)) => t.get_undef().into(),
+                            (_, BasicTypeEnum::FloatType(t)) => t.get_undef().into(),
+                            (_, BasicTypeEnum::IntType(t)) => t.get_undef().into(),
+                            (_, BasicTypeEnum::PointerType(t)) => t.get_undef().into(),
+                            (_, BasicTypeEnum::StructType(t)) => t.get_undef().into(),
+                            (_, BasicTypeEnum::VectorType(t)) => t.get_undef().into(),
+                        };
+                        terminator_builder.build_return(Some(&value));
                     }
                 }
             }
@@ -138,53 +155,117 @@ impl<'ctx> Generator<'ctx> {
         var_type: &BasicType,
         identifier: &str,
         ptr: PointerValue<'ctx>,
-        span: Span,
-    ) -> Result<(), CE> {
+    ) {
         let local_map = self.val_map_block_stack.last_mut().unwrap();
+        local_map.insert(identifier.to_string(), (var_type.clone(), ptr));
+    }
 
-        if local_map.contains_key(identifier) {
-            return Err(CE::duplicated_variable(identifier.to_string(), span));
+    /// Declarations are checked by the semantic analysis: the types of the variables are
+    /// complete, and their initializers are converted to them.
+    pub(crate) fn gen_decl_in_fn(&mut self, decl: &TypedDeclaration) -> Result<(), CE> {
+        let (var_type, identifier, expr) = match decl.node {
+            TypedDeclarationEnum::Declaration(ref var_type, ref identifier, ref expr) => {
+                (var_type, identifier, expr)
+            }
+            TypedDeclarationEnum::FunctionDefinition(..) => {
+                return Err(CE::plain_error(
+                    "FunctionDefinition cannot exist in function".to_string(),
+                    decl.span,
+                ))
+            }
+        };
+        self.define_struct(&var_type.basic_type.base_type);
+        let identifier = match identifier {
+            Some(identifier) => identifier,
+            None => return Ok(()),
+        };
+        match (
+            &var_type.storage_class_specifier,
+            &var_type.basic_type.base_type,
+        ) {
+            (StorageClassSpecifier::Typedef, _) => return Ok(()),
+            (_, BaseType::Function(ref return_type, ref params_type, is_variadic)) => {
+                return self.gen_function_proto(
+                    &var_type.storage_class_specifier,
+                    return_type,
+                    identifier,
+                    params_type,
+                    *is_variadic,
+                    decl.span,
+                );
+            }
+            (StorageClassSpecifier::Static | StorageClassSpecifier::Extern, _) => {
+                return self.gen_static_decl_in_fn(var_type, identifier, expr, decl.span);
+            }
+            _ => {}
         }
 
-        local_map.insert(identifier.to_string(), (var_type.clone(), ptr));
+        let basic_type = &var_type.basic_type;
+        let llvm_type = self.convert_llvm_type(&basic_type.base_type, decl.span)?;
+        let p_val = self.builder.build_alloca(llvm_type, identifier);
+        if let Some(alignment) =
+            self.variable_alignment(&basic_type.base_type, &var_type.attributes, decl.span)?
+        {
+            p_val
+                .as_instruction_value()
+                .unwrap()
+                .set_alignment(alignment)
+                .unwrap();
+        }
+        self.insert_to_val_map(basic_type, identifier, p_val);
+        if let Some(expr) = expr {
+            match (&basic_type.base_type, &expr.node) {
+                (
+                    BaseType::Array(_, _),
+                    TypedExpressionEnum::Literal(ExpressionEnum::StringLiteral(prefix, code_units)),
+                ) => {
+                    let value = self.gen_string_initializer(
+                        &basic_type.base_type,
+                        *prefix,
+                        code_units,
+                        expr.span,
+                    )?;
+                    self.builder.build_store(p_val, value);
+                }
+                _ => {
+                    let value = self.gen_expression(expr)?;
+                    self.build_object_store(basic_type, p_val, value);
+                }
+            }
+        }
         Ok(())
     }
 
-    pub(crate) fn gen_decl_in_fn(&mut self, decl: &Declaration) -> Result<(), CE> {
-        if let DeclarationEnum::Declaration(ref var_type, ref identifier, ref expr) = decl.node {
-            let llvm_type = self.convert_llvm_type(
-                &self.extend_struct_type(var_type.basic_type.base_type.to_owned(), decl.span)?,
-                decl.span,
-            )?;
-            let p_val = self
-                .builder
-                .build_alloca(llvm_type, &identifier.to_owned().unwrap());
-            self.insert_to_val_map(
-                &var_type.basic_type,
-                &identifier.to_owned().unwrap(),
-                p_val,
-                decl.span,
-            )?;
-            if let Some(ref expr) = expr {
-                self.gen_assignment(
-                    &AssignOperation {
-                        node: AssignOperationEnum::Naive,
-                        span: expr.span,
-                    },
-                    &Box::new(Expression {
-                        node: ExpressionEnum::Identifier(identifier.to_owned().unwrap()),
-                        span: expr.span,
-                    }),
-                    expr,
-                    decl.span,
-                )?;
+    /// A block scope variable declared `static` is a global only visible in its block, named
+    /// after its function, and one declared `extern` refers to the file scope variable.
+    fn gen_static_decl_in_fn(
+        &mut self,
+        var_type: &Type,
+        identifier: &str,
+        expr: &Option<Box<TypedExpression>>,
+        span: Span,
+    ) -> Result<(), CE> {
+        let (basic_type, ptr) = match var_type.storage_class_specifier {
+            StorageClassSpecifier::Extern => match self.global_variable_map.get(identifier) {
+                Some(global) => global.to_owned(),
+                None => match self.module.get_global(identifier) {
+                    Some(global) => (var_type.basic_type.to_owned(), global.as_pointer_value()),
+                    None => {
+                        let (basic_type, global) =
+                            self.gen_static_variable(var_type, identifier, identifier, expr, span)?;
+                        (basic_type, global.as_pointer_value())
+                    }
+                },
+            },
+            _ => {
+                let function_name = self.current_function.as_ref().unwrap().0.get_name();
+                let llvm_name = format!("{}.{}", function_name.to_str().unwrap(), identifier);
+                let (basic_type, global) =
+                    self.gen_static_variable(var_type, identifier, &llvm_name, expr, span)?;
+                (basic_type, global.as_pointer_value())
             }
-            Ok(())
-        } else {
-            Err(CE::plain_error(
-                "FunctionDefinition cannot exist in function".to_string(),
-                decl.span,
-            ))
-        }
+        };
+        self.insert_to_val_map(&basic_type, identifier, ptr);
+        Ok(())
     }
 }
@@ -1,23 +1,29 @@
 use crate::ast::{
-    BaseType, BasicType as BT, DeclarationEnum, Expression, IntegerType, Span,
-    StorageClassSpecifier, Type, AST,
+    self, BaseType, BasicType as BT, ExpressionEnum, FunctionSpecifier, IntegerType, Span,
+    StorageClassSpecifier, Type,
 };
 use crate::generator::Generator;
+use crate::preprocess::SourceMap;
+use crate::sema::{
+    TypedAST, TypedDeclaration, TypedDeclarationEnum, TypedExpression, TypedExpressionEnum,
+};
 use crate::utils::CompileErr as CE;
+use inkwell::attributes::{Attribute, AttributeLoc};
 use inkwell::context::Context;
 use inkwell::module::Linkage;
 use inkwell::types::{BasicMetadataTypeEnum, BasicType, BasicTypeEnum, FunctionType};
-use inkwell::values::{BasicValueEnum, PointerValue};
-use inkwell::AddressSpace;
-use std::collections::{HashMap, VecDeque};
-use std::ops::Deref;
+use inkwell::values::{BasicValue, BasicValueEnum, GlobalValue, PointerValue};
+use inkwell::{AddressSpace, GlobalVisibility, ThreadLocalMode};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::path::Path;
 
 impl<'ctx> Generator<'ctx> {
     // new LLVM context
-    pub fn new(context: &'ctx Context, source_path: &'ctx str, code: &'ctx str) -> Generator<'ctx> {
-        use codespan_reporting::files::SimpleFiles;
-
+    pub fn new(
+        context: &'ctx Context,
+        source_path: &'ctx str,
+        source_map: &'ctx SourceMap,
+    ) -> Generator<'ctx> {
         let module_name = Path::new(source_path)
             .file_stem()
             .unwrap()
@@ -31,11 +37,8 @@ impl<'ctx> Generator<'ctx> {
         let global_map: HashMap<String, (BT, PointerValue<'ctx>)> = HashMap::new();
         val_map_block_stack.push(global_map); // push global variable hashmap
 
-        let mut files = SimpleFiles::new();
-        files.add(source_path, code);
-
         Generator {
-            files,
+            source_map,
             module_name,
             context,
             module,
@@ -45,14 +48,19 @@ impl<'ctx> Generator<'ctx> {
             current_function: None,
             break_labels: VecDeque::new(),
             continue_labels: VecDeque::new(),
-            function_map: HashMap::new(),
+            label_blocks: HashMap::new(),
             global_variable_map: HashMap::new(),
-            typedef_map: HashMap::new(),
+            tls_model: ThreadLocalMode::GeneralDynamicTLSModel,
         }
     }
 
-    pub fn gen(&mut self, ast: &AST) {
-        let AST::GlobalDeclaration(ref declarations) = ast.deref();
+    /// Select how thread-local variables are accessed, general-dynamic by default.
+    pub fn set_tls_model(&mut self, tls_model: ThreadLocalMode) {
+        self.tls_model = tls_model;
+    }
+
+    pub fn gen(&mut self, ast: &TypedAST) {
+        let TypedAST::GlobalDeclaration(ref declarations) = ast;
 
         let mut err: Vec<CE> = vec![];
 
@@ -62,93 +70,99 @@ impl<'ctx> Generator<'ctx> {
                 .iter()
                 .map(|declaration| -> Result<(), CE> {
                     match declaration.node {
-                        DeclarationEnum::Declaration(
+                        TypedDeclarationEnum::Declaration(
                             ref type_info,
                             ref identifier,
                             ref initializer,
-                        ) => match type_info.basic_type.base_type {
-                            BaseType::Function(ref return_type, ref params_type, is_variadic) => {
-                                self.gen_function_proto(
-                                    &type_info.storage_class_specifier,
-                                    return_type,
-                                    identifier.as_ref().unwrap(),
-                                    params_type,
-                                    is_variadic,
-                                    declaration.span,
-                                )
-                            }
-                            BaseType::Struct(ref name, ref members) => {
-                                if members.is_some() {
-                                    if self
-                                        .global_struct_map
-                                        .contains_key(name.clone().unwrap().as_str())
-                                    {
-                                        return Err(CE::duplicated_struct_definition(
-                                            name.clone().unwrap(),
-                                            declaration.span,
-                                        ));
-                                    }
-                                    self.global_struct_map
-                                        .insert(name.clone().unwrap(), members.clone().unwrap());
+                        ) => {
+                            self.define_struct(&type_info.basic_type.base_type);
+                            let identifier = match identifier {
+                                Some(identifier) => identifier,
+                                None => return Ok(()),
+                            };
+                            match type_info.basic_type.base_type {
+                                _ if type_info.storage_class_specifier
+                                    == StorageClassSpecifier::Typedef =>
+                                {
                                     Ok(())
-                                } else {
-                                    let mut type_info = type_info.clone();
-                                    type_info.basic_type.base_type = self.extend_struct_type(
-                                        type_info.basic_type.base_type.to_owned(),
+                                }
+                                BaseType::Function(
+                                    ref return_type,
+                                    ref params_type,
+                                    is_variadic,
+                                ) => {
+                                    self.gen_function_proto(
+                                        &type_info.storage_class_specifier,
+                                        return_type,
+                                        identifier,
+                                        params_type,
+                                        is_variadic,
                                         declaration.span,
                                     )?;
-                                    self.gen_global_variable(
-                                        &type_info,
-                                        identifier.as_ref().unwrap(),
-                                        initializer,
-                                        declaration.span,
-                                    )
+                                    self.add_function_attributes(
+                                        identifier,
+                                        &type_info.function_specifier,
+                                        &type_info.attributes,
+                                    );
+                                    Ok(())
                                 }
+                                _ => self.gen_global_variable(
+                                    type_info,
+                                    identifier,
+                                    initializer,
+                                    declaration.span,
+                                ),
                             }
-                            _ => self.gen_global_variable(
-                                type_info,
-                                identifier.as_ref().unwrap(),
-                                initializer,
-                                declaration.span,
-                            ),
-                        },
-                        DeclarationEnum::FunctionDefinition(
-                            _,
+                        }
+                        TypedDeclarationEnum::FunctionDefinition(
+                            ref function_specifier,
                             ref storage_class,
+                            ref attributes,
                             ref return_type,
                             ref identifier,
                             ref params_type,
-                            ref is_variadic,
+                            is_variadic,
                             _,
                         ) => {
-                            if !self.function_map.contains_key(identifier) {
-                                self.gen_function_proto(
-                                    storage_class,
-                                    return_type,
-                                    identifier,
-                                    params_type
-                                        .iter()
-                                        .map(|param| param.0.clone())
-                                        .collect::<Vec<_>>()
-                                        .as_slice(),
-                                    *is_variadic,
-                                    declaration.span,
-                                )
-                            } else {
-                                Ok(())
-                            }
+                            self.gen_function_proto(
+                                storage_class,
+                                return_type,
+                                identifier,
+                                params_type
+                                    .iter()
+                                    .map(|param| param.0.clone())
+                                    .collect::<Vec<_>>()
+                                    .as_slice(),
+                                is_variadic,
+                                declaration.span,
+                            )?;
+                            self.add_function_attributes(
+                                identifier,
+                                function_specifier,
+                                attributes,
+                            );
+                            Ok(())
                         }
                     }
                 })
-                .filter_map(|result| if result.is_err() { result.err() } else { None }),
+                .filter_map(|result| result.err()),
         );
 
+        // an inline definition does not provide the external definition of its function, which
+        // another translation unit may provide instead (C99 6.7.4)
+        for name in Self::inline_definitions(declarations) {
+            if let Some(func) = self.module.get_function(&name) {
+                func.set_linkage(Linkage::LinkOnceODR);
+            }
+        }
+
         // second-time scanning, gen func definitions
         err.extend(
             declarations
                 .iter()
                 .map(|declaration| -> Result<(), Vec<CE>> {
-                    if let DeclarationEnum::FunctionDefinition(
+                    if let TypedDeclarationEnum::FunctionDefinition(
+                        _,
                         _,
                         _,
                         ref return_type,
@@ -176,15 +190,41 @@ impl<'ctx> Generator<'ctx> {
                 .collect::<Vec<_>>(),
         );
 
+        // a weak function is `extern_weak` until it is defined
+        for func in
+            std::iter::successors(self.module.get_first_function(), |f| f.get_next_function())
+        {
+            if func.get_linkage() == Linkage::ExternalWeak && func.count_basic_blocks() > 0 {
+                func.set_linkage(Linkage::WeakAny);
+            }
+        }
+        self.gen_constructors(declarations);
+
         if !err.is_empty() {
             err.iter().for_each(|err| {
-                self.gen_err_output(0, err);
+                self.gen_err_output(err);
             });
             std::process::exit(err.len() as i32);
         }
     }
 
-    fn gen_function_proto(
+    /// Record the members of a struct or union a declaration defines, at any scope, as struct
+    /// and union tags are checked to be unique by the semantic analysis.
+    pub(crate) fn define_struct(&mut self, t: &BaseType) {
+        match t {
+            BaseType::Struct(Some(name), Some(members))
+            | BaseType::Union(Some(name), Some(members)) => {
+                self.global_struct_map
+                    .insert(name.to_owned(), members.to_owned());
+            }
+            BaseType::Pointer(t) | BaseType::Array(t, _) => self.define_struct(&t.base_type),
+            _ => {}
+        }
+    }
+
+    /// Declare a function, unless it has already been declared. Its parameter types are
+    /// adjusted by the semantic analysis.
+    pub(crate) fn gen_function_proto(
         &mut self,
         storage_class: &StorageClassSpecifier,
         ret_type: &BT,
@@ -193,23 +233,15 @@ impl<'ctx> Generator<'ctx> {
         is_variadic: bool,
         span: Span,
     ) -> Result<(), CE> {
-        if self.function_map.contains_key(func_name) {
-            return Err(CE::duplicated_function(func_name.to_string(), span));
-        }
-        if self.global_variable_map.contains_key(func_name)
-            || self.typedef_map.contains_key(func_name)
-        {
-            return Err(CE::redefinition_symbol(func_name.to_string(), span));
+        if self.module.get_function(func_name).is_some() {
+            return Ok(());
         }
 
         // function parameter should be added in this llvm_func_type
-        let mut llvm_params: Vec<BasicTypeEnum<'ctx>> = Vec::new();
-        let mut params: Vec<BT> = Vec::new();
-
-        for param in func_param {
-            params.push(param.to_owned());
-            llvm_params.push(self.convert_llvm_type(&param.base_type, span)?);
-        }
+        let llvm_params = func_param
+            .iter()
+            .map(|param| self.convert_llvm_type(&param.base_type, span))
+            .collect::<Result<Vec<_>, _>>()?;
 
         let llvm_func_ty = self.gen_return_type(ret_type, &llvm_params, is_variadic, span)?;
 
@@ -217,19 +249,192 @@ impl<'ctx> Generator<'ctx> {
             StorageClassSpecifier::Static => Some(Linkage::Internal),
             StorageClassSpecifier::Extern => Some(Linkage::External),
             StorageClassSpecifier::Auto => None,
-            StorageClassSpecifier::Typedef => unimplemented!(),
             _ => unreachable!(),
         };
 
         // create function
         self.module.add_function(func_name, llvm_func_ty, linkage);
-        self.function_map.insert(
-            func_name.to_owned(),
-            (ret_type.to_owned(), params, is_variadic),
-        );
         Ok(())
     }
 
+    /// `inline` hints the optimizer to inline the function, and `_Noreturn` tells it that calls
+    /// to the function do not return. GNU attributes of the function are mapped likewise, `weak`
+    /// makes it `extern_weak` until it is defined.
+    fn add_function_attributes(
+        &self,
+        func_name: &str,
+        function_specifier: &[FunctionSpecifier],
+        attributes: &[ast::Attribute],
+    ) {
+        let func = self.module.get_function(func_name).unwrap();
+        let names = function_specifier
+            .iter()
+            .map(|specifier| match specifier {
+                FunctionSpecifier::Inline => "inlinehint",
+                FunctionSpecifier::Noreturn => "noreturn",
+            })
+            .chain(
+                attributes
+                    .iter()
+                    .filter_map(|attribute| match attribute.name.as_str() {
+                        "noinline" => Some("noinline"),
+                        "always_inline" => Some("alwaysinline"),
+                        "cold" => Some("cold"),
+                        "noreturn" => Some("noreturn"),
+                        _ => None,
+                    }),
+            );
+        for name in names {
+            let attribute = self
+                .context
+                .create_enum_attribute(Attribute::get_named_enum_kind_id(name), 0);
+            func.add_attribute(AttributeLoc::Function, attribute);
+        }
+        if attributes.iter().any(|attribute| attribute.name == "weak")
+            && func.count_basic_blocks() == 0
+        {
+            func.set_linkage(Linkage::ExternalWeak);
+        }
+        self.set_global_attributes(func.as_global_value(), attributes);
+    }
+
+    /// Apply the `section`, `visibility` and `aligned` attributes of a function or a global
+    /// variable.
+    fn set_global_attributes(&self, global: GlobalValue<'ctx>, attributes: &[ast::Attribute]) {
+        for attribute in attributes {
+            match attribute.name.as_str() {
+                "section" => global.set_section(&Self::string_argument(attribute)),
+                // LLVM has no internal visibility, which is hidden with further guarantees
+                "visibility" => {
+                    global.set_visibility(match Self::string_argument(attribute).as_str() {
+                        "hidden" | "internal" => GlobalVisibility::Hidden,
+                        "protected" => GlobalVisibility::Protected,
+                        _ => GlobalVisibility::Default,
+                    })
+                }
+                "aligned" => global.set_alignment(self.alignment_of(&[attribute.to_owned()], 1)),
+                _ => {}
+            }
+        }
+    }
+
+    /// The string literal argument of an attribute, checked by the semantic analysis.
+    fn string_argument(attribute: &ast::Attribute) -> String {
+        match attribute.arguments[0].node {
+            ExpressionEnum::StringLiteral(_, ref code_units) => code_units
+                .iter()
+                .filter_map(|&code_unit| char::from_u32(code_unit))
+                .collect(),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Functions with the `constructor` attribute are called before `main`, in the order of
+    /// their priorities, by listing them in `llvm.global_ctors`.
+    fn gen_constructors(&self, declarations: &[TypedDeclaration]) {
+        let mut constructors: Vec<(u64, String)> = vec![];
+        for declaration in declarations {
+            let (attributes, name) = match declaration.node {
+                TypedDeclarationEnum::Declaration(ref type_info, Some(ref name), _) => {
+                    (&type_info.attributes, name)
+                }
+                TypedDeclarationEnum::FunctionDefinition(_, _, ref attributes, _, ref name, ..) => {
+                    (attributes, name)
+                }
+                _ => continue,
+            };
+            for attribute in attributes.iter().filter(|a| a.name == "constructor") {
+                let priority = match attribute.arguments.first() {
+                    Some(argument) => argument.node.integer_literal().unwrap(),
+                    None => 65535,
+                };
+                if !constructors.iter().any(|(_, n)| n == name) {
+                    constructors.push((priority, name.to_owned()));
+                }
+            }
+        }
+        if constructors.is_empty() {
+            return;
+        }
+
+        let i32_type = self.context.i32_type();
+        let func_ptr_type = self
+            .context
+            .void_type()
+            .fn_type(&[], false)
+            .ptr_type(AddressSpace::Generic);
+        let data_ptr_type = self.context.i8_type().ptr_type(AddressSpace::Generic);
+        let entry_type = self.context.struct_type(
+            &[
+                i32_type.as_basic_type_enum(),
+                func_ptr_type.as_basic_type_enum(),
+                data_ptr_type.as_basic_type_enum(),
+            ],
+            false,
+        );
+        let entries = constructors
+            .iter()
+            .filter_map(|(priority, name)| {
+                let func = self.module.get_function(name)?;
+                Some(
+                    entry_type.const_named_struct(&[
+                        i32_type.const_int(*priority, false).as_basic_value_enum(),
+                        func.as_global_value()
+                            .as_pointer_value()
+                            .const_cast(func_ptr_type)
+                            .as_basic_value_enum(),
+                        data_ptr_type.const_null().as_basic_value_enum(),
+                    ]),
+                )
+            })
+            .collect::<Vec<_>>();
+        let value = entry_type.const_array(&entries);
+        let global_ctors = self
+            .module
+            .add_global(value.get_type(), None, "llvm.global_ctors");
+        global_ctors.set_linkage(Linkage::Appending);
+        global_ctors.set_initializer(&value);
+    }
+
+    /// Functions with external linkage defined by an inline definition: every file scope
+    /// declaration of them is `inline`, and none is `extern`.
+    fn inline_definitions(declarations: &[TypedDeclaration]) -> HashSet<String> {
+        let mut defined = HashSet::new();
+        let mut not_inline = HashSet::new();
+        for declaration in declarations {
+            let (function_specifier, storage_class, name) = match declaration.node {
+                TypedDeclarationEnum::Declaration(ref type_info, Some(ref name), _) => {
+                    match type_info.basic_type.base_type {
+                        BaseType::Function(..) => (
+                            &type_info.function_specifier,
+                            &type_info.storage_class_specifier,
+                            name,
+                        ),
+                        _ => continue,
+                    }
+                }
+                TypedDeclarationEnum::FunctionDefinition(
+                    ref function_specifier,
+                    ref storage_class,
+                    _,
+                    _,
+                    ref name,
+                    ..,
+                ) => {
+                    defined.insert(name.to_owned());
+                    (function_specifier, storage_class, name)
+                }
+                _ => continue,
+            };
+            if !function_specifier.contains(&FunctionSpecifier::Inline)
+                || *storage_class != StorageClassSpecifier::Auto
+            {
+                not_inline.insert(name.to_owned());
+            }
+        }
+        defined.difference(&not_inline).cloned().collect()
+    }
+
     // add void type as return type
     fn gen_return_type(
         &mut self,
@@ -262,9 +467,13 @@ impl<'ctx> Generator<'ctx> {
         dest_type: &BaseType,
         span: Span,
     ) -> Result<BasicValueEnum<'ctx>, CE> {
-        if curr_type.equal_discarding_qualifiers(dest_type, &self.typedef_map) {
+        if curr_type.equal_discarding_qualifiers(dest_type, &HashMap::new()) {
             return Ok(curr_val.to_owned());
         }
+        // any scalar converts to `_Bool` by comparing it with zero
+        if *dest_type == BaseType::Bool {
+            return Ok(self.build_truth_value(*curr_val).as_basic_value_enum());
+        }
 
         let llvm_type = self.convert_llvm_type(dest_type, span)?;
 
@@ -304,65 +513,102 @@ impl<'ctx> Generator<'ctx> {
         &mut self,
         var_type: &Type,
         var_name: &str,
-        ptr_to_init: &Option<Box<Expression>>,
+        ptr_to_init: &Option<Box<TypedExpression>>,
         span: Span,
     ) -> Result<(), CE> {
-        if self.global_variable_map.contains_key(var_name) {
-            return Err(CE::duplicated_global_variable(var_name.to_string(), span));
-        } else if self.function_map.contains_key(var_name)
-            || self.typedef_map.contains_key(var_name)
+        let (basic_type, global_value) =
+            self.gen_static_variable(var_type, var_name, var_name, ptr_to_init, span)?;
+        self.global_variable_map.insert(
+            var_name.to_string(),
+            (basic_type, global_value.as_pointer_value()),
+        );
+
+        Ok(())
+    }
+
+    /// Generate a variable with static or thread storage duration, `llvm_name` naming it in the
+    /// module. An `extern` variable without an initializer is only declared, as it is defined
+    /// elsewhere. The initializer is already converted to the type of the variable.
+    pub(crate) fn gen_static_variable(
+        &self,
+        var_type: &Type,
+        var_name: &str,
+        llvm_name: &str,
+        ptr_to_init: &Option<Box<TypedExpression>>,
+        span: Span,
+    ) -> Result<(BT, GlobalValue<'ctx>), CE> {
+        let basic_type = var_type.basic_type.clone();
+        let llvm_type = self.convert_llvm_type(&basic_type.base_type, span)?;
+        let global_value = self.module.add_global(llvm_type, None, llvm_name);
+        if var_type.thread_local {
+            global_value.set_thread_local_mode(Some(self.tls_model));
+        }
+        self.set_global_attributes(global_value, &var_type.attributes);
+        if let Some(alignment) =
+            self.variable_alignment(&basic_type.base_type, &var_type.attributes, span)?
         {
-            return Err(CE::duplicated_symbol(var_name.to_string(), span));
+            global_value.set_alignment(alignment);
         }
-
-        if var_type.storage_class_specifier == StorageClassSpecifier::Typedef {
-            let mut true_type = var_type.basic_type.clone();
-            if let BaseType::Identifier(type_name) = &true_type.base_type {
-                if self.typedef_map.contains_key(type_name) {
-                    true_type = self.typedef_map[type_name].clone();
-                } else {
-                    return Err(CE::missing_typedef(type_name.to_string(), span));
-                }
-            }
-            self.typedef_map.insert(var_name.to_owned(), true_type);
-            return Ok(());
+        if basic_type.is_const() {
+            global_value.set_constant(true);
         }
 
-        let llvm_type = self.convert_llvm_type(&var_type.basic_type.base_type, span)?;
-        let global_value = self.module.add_global(llvm_type, None, var_name);
-        global_value.set_linkage(Linkage::Common);
-
-        if var_type.basic_type.is_const() {
-            global_value.set_constant(true);
+        if var_type
+            .attributes
+            .iter()
+            .any(|attribute| attribute.name == "weak")
+        {
+            global_value.set_linkage(Linkage::WeakAny);
+        } else if var_type.storage_class_specifier == StorageClassSpecifier::Static {
+            global_value.set_linkage(Linkage::Internal);
+        } else if ptr_to_init.is_none() {
+            if var_type.storage_class_specifier == StorageClassSpecifier::Extern {
+                return Ok((basic_type, global_value));
+            }
+            // tentative definition, common symbols must be zero-initialized, and can not be
+            // thread-local
+            if !var_type.thread_local {
+                global_value.set_linkage(Linkage::Common);
+            }
         }
 
         match ptr_to_init {
-            Some(ptr_to_init) => {
-                let (e_t, e_v) = self.gen_expression(&**ptr_to_init)?;
-                e_t.test_cast(
-                    &var_type.basic_type.base_type,
-                    ptr_to_init.span,
-                    &self.typedef_map,
-                )?;
-                let value_after_cast =
-                    self.cast_value(&e_t, &e_v, &var_type.basic_type.base_type, ptr_to_init.span)?;
-
-                global_value.set_initializer(&value_after_cast);
-            }
+            Some(ptr_to_init) => match (&basic_type.base_type, &ptr_to_init.node) {
+                (
+                    BaseType::Array(_, _),
+                    TypedExpressionEnum::Literal(ExpressionEnum::StringLiteral(prefix, code_units)),
+                ) => {
+                    let value = self.gen_string_initializer(
+                        &basic_type.base_type,
+                        *prefix,
+                        code_units,
+                        ptr_to_init.span,
+                    )?;
+                    global_value.set_initializer(&value);
+                }
+                _ => {
+                    let value_after_cast = self.gen_expression(ptr_to_init)?;
+                    let is_constant = match value_after_cast {
+                        BasicValueEnum::IntValue(v) => v.is_const(),
+                        BasicValueEnum::FloatValue(v) => v.is_const(),
+                        BasicValueEnum::PointerValue(v) => v.is_const(),
+                        _ => true,
+                    };
+                    if !is_constant {
+                        return Err(CE::non_constant_initializer(
+                            var_name.to_string(),
+                            ptr_to_init.span,
+                        ));
+                    }
+
+                    global_value.set_initializer(&value_after_cast);
+                }
+            },
             None => {
                 global_value.set_initializer(&llvm_type.const_zero());
             }
         }
-
-        self.global_variable_map.insert(
-            var_name.to_string(),
-            (
-                var_type.basic_type.to_owned(),
-                global_value.as_pointer_value(),
-            ),
-        );
-
-        Ok(())
+        Ok((basic_type, global_value))
     }
 
     pub(crate) fn convert_llvm_type(
@@ -370,17 +616,7 @@ impl<'ctx> Generator<'ctx> {
         base_type: &BaseType,
         span: Span,
     ) -> Result<BasicTypeEnum<'ctx>, CE> {
-        let true_type = match base_type {
-            BaseType::Identifier(typedef_name) => {
-                if let Some(true_type) = self.typedef_map.get(typedef_name) {
-                    &true_type.base_type
-                } else {
-                    return Err(CE::missing_typedef(typedef_name.to_string(), span));
-                }
-            }
-            _ => base_type,
-        };
-        Ok(match *true_type {
+        Ok(match *base_type {
             BaseType::Bool => self.context.bool_type().as_basic_type_enum(),
             BaseType::SignedInteger(IntegerType::Char) => {
                 self.context.i8_type().as_basic_type_enum()
@@ -421,14 +657,7 @@ impl<'ctx> Generator<'ctx> {
             BaseType::Array(ref basic_type, ref size) => size
                 .iter()
                 .rev()
-                .map(|x| {
-                    self.gen_expression(x)
-                        .unwrap()
-                        .1
-                        .into_int_value()
-                        .get_zero_extended_constant()
-                        .unwrap() as u32
-                })
+                .map(|x| x.node.integer_literal().unwrap_or(0) as u32)
                 .fold(
                     self.convert_llvm_type(&basic_type.base_type, span)?,
                     |acc, len| acc.array_type(len).as_basic_type_enum(),
@@ -450,6 +679,11 @@ impl<'ctx> Generator<'ctx> {
                         .map(|x| self.convert_llvm_type(&x.base_type, span))
                         .collect::<Result<Vec<_>, _>>()?;
                 }
+                if let Some(layout) =
+                    self.struct_layout(&self.members_of_struct(name, members, span)?, span)?
+                {
+                    return Ok(layout.llvm_type.as_basic_type_enum());
+                }
                 self.context
                     .struct_type(member_types.as_slice(), false)
                     .as_basic_type_enum()
@@ -0,0 +1,252 @@
+use crate::ast::{Attribute, BaseType, BasicType as BT, IntegerType, Span, StructMember};
+use crate::generator::Generator;
+use crate::utils::CompileErr as CE;
+use inkwell::types::{BasicType, BasicTypeEnum, StructType};
+
+/// The layout of a struct with `packed` or `aligned` members. LLVM can not express it with the
+/// alignments of the member types, so its LLVM type is a packed struct with explicit padding.
+pub(crate) struct StructLayout<'ctx> {
+    pub(crate) llvm_type: StructType<'ctx>,
+    /// the LLVM field of each member
+    pub(crate) field_indices: Vec<u32>,
+    pub(crate) size: u32,
+    pub(crate) alignment: u32,
+}
+
+impl<'ctx> Generator<'ctx> {
+    /// The members of a struct, from its definition or from the struct map.
+    pub(crate) fn members_of_struct(
+        &self,
+        name: &Option<String>,
+        members: &Option<Vec<StructMember>>,
+        span: Span,
+    ) -> Result<Vec<StructMember>, CE> {
+        match members {
+            Some(members) => Ok(members.to_owned()),
+            None => match self.global_struct_map.get(name.as_ref().unwrap()) {
+                Some(members) => Ok(members.to_owned()),
+                None => Err(CE::struct_not_found(name.clone().unwrap(), span)),
+            },
+        }
+    }
+
+    /// The layout of a struct, unless none of its members is `packed` or `aligned` and the
+    /// natural LLVM struct type lays it out.
+    pub(crate) fn struct_layout(
+        &self,
+        members: &[StructMember],
+        span: Span,
+    ) -> Result<Option<StructLayout<'ctx>>, CE> {
+        if !members.iter().any(|member| {
+            member
+                .attributes
+                .iter()
+                .any(|attribute| matches!(attribute.name.as_str(), "packed" | "aligned"))
+        }) {
+            return Ok(None);
+        }
+
+        let mut fields: Vec<BasicTypeEnum<'ctx>> = vec![];
+        let mut field_indices = vec![];
+        let mut offset = 0;
+        let mut alignment = 1;
+        for member in members {
+            let (member_size, natural_alignment) =
+                self.type_layout(&member.member_type.base_type, span)?;
+            let member_alignment = self.alignment_of(&member.attributes, natural_alignment);
+            let padding = align_to(offset, member_alignment) - offset;
+            if padding > 0 {
+                fields.push(
+                    self.context
+                        .i8_type()
+                        .array_type(padding)
+                        .as_basic_type_enum(),
+                );
+            }
+            field_indices.push(fields.len() as u32);
+            fields.push(self.convert_llvm_type(&member.member_type.base_type, span)?);
+            offset += padding + member_size;
+            alignment = alignment.max(member_alignment);
+        }
+        let size = align_to(offset, alignment);
+        if size > offset {
+            fields.push(
+                self.context
+                    .i8_type()
+                    .array_type(size - offset)
+                    .as_basic_type_enum(),
+            );
+        }
+
+        Ok(Some(StructLayout {
+            llvm_type: self.context.struct_type(&fields, true),
+            field_indices,
+            size,
+            alignment,
+        }))
+    }
+
+    /// The size and alignment of an object of type `base_type`.
+    pub(crate) fn type_layout(&self, base_type: &BaseType, span: Span) -> Result<(u32, u32), CE> {
+        Ok(match base_type {
+            BaseType::Bool
+            | BaseType::Void
+            | BaseType::SignedInteger(IntegerType::Char)
+            | BaseType::UnsignedInteger(IntegerType::Char) => (1, 1),
+            BaseType::SignedInteger(IntegerType::Short)
+            | BaseType::UnsignedInteger(IntegerType::Short) => (2, 2),
+            BaseType::SignedInteger(IntegerType::Int)
+            | BaseType::UnsignedInteger(IntegerType::Int)
+            | BaseType::Float => (4, 4),
+            BaseType::SignedInteger(_)
+            | BaseType::UnsignedInteger(_)
+            | BaseType::Double
+            | BaseType::Pointer(_)
+            | BaseType::Function(..) => (8, 8),
+            BaseType::Array(element_type, dimensions) => {
+                let (size, alignment) = self.type_layout(&element_type.base_type, span)?;
+                let length = dimensions.iter().fold(1, |length, dimension| {
+                    length * dimension.node.integer_literal().unwrap() as u32
+                });
+                (size * length, alignment)
+            }
+            BaseType::Struct(name, members) => {
+                let members = self.members_of_struct(name, members, span)?;
+                if let Some(layout) = self.struct_layout(&members, span)? {
+                    return Ok((layout.size, layout.alignment));
+                }
+                let (mut offset, mut alignment) = (0, 1);
+                for member in members {
+                    let (member_size, member_alignment) =
+                        self.type_layout(&member.member_type.base_type, span)?;
+                    offset = align_to(offset, member_alignment) + member_size;
+                    alignment = alignment.max(member_alignment);
+                }
+                (align_to(offset, alignment), alignment)
+            }
+            BaseType::Union(name, members) => {
+                let (mut size, mut alignment) = (0, 1);
+                for member in self.members_of_struct(name, members, span)? {
+                    let (member_size, member_alignment) =
+                        self.type_layout(&member.member_type.base_type, span)?;
+                    size = size.max(member_size);
+                    alignment =
+                        alignment.max(self.alignment_of(&member.attributes, member_alignment));
+                }
+                (align_to(size, alignment), alignment)
+            }
+            BaseType::Identifier(_) => unreachable!(),
+        })
+    }
+
+    /// The offset and the type of a member of a struct or union.
+    pub(crate) fn member_offset(
+        &self,
+        base_type: &BaseType,
+        member_name: &str,
+        span: Span,
+    ) -> Result<(u32, BT), CE> {
+        let (name, members, is_union) = match base_type {
+            BaseType::Struct(name, members) => (name, members, false),
+            BaseType::Union(name, members) => (name, members, true),
+            _ => {
+                return Err(CE::get_member_from_not_struct(
+                    member_name.to_string(),
+                    span,
+                ))
+            }
+        };
+        let members = self.members_of_struct(name, members, span)?;
+        let mut offset = 0;
+        for member in members {
+            let (member_size, natural_alignment) =
+                self.type_layout(&member.member_type.base_type, span)?;
+            if !is_union {
+                offset = align_to(
+                    offset,
+                    self.alignment_of(&member.attributes, natural_alignment),
+                );
+            }
+            if member.member_name == member_name {
+                return Ok((offset, member.member_type));
+            }
+            if !is_union {
+                offset += member_size;
+            }
+        }
+        Err(CE::struct_member_not_found(
+            name.clone().unwrap_or_default(),
+            member_name.to_string(),
+            span,
+        ))
+    }
+
+    /// The alignment of a variable of type `base_type`, when it is not the alignment of its LLVM
+    /// type: it has an `aligned` attribute, or its type contains a struct with an explicit
+    /// layout.
+    pub(crate) fn variable_alignment(
+        &self,
+        base_type: &BaseType,
+        attributes: &[Attribute],
+        span: Span,
+    ) -> Result<Option<u32>, CE> {
+        let mut element_type = base_type;
+        while let BaseType::Array(ref t, _) = element_type {
+            element_type = &t.base_type;
+        }
+        let explicit_layout = match element_type {
+            BaseType::Struct(name, members) => self
+                .struct_layout(&self.members_of_struct(name, members, span)?, span)?
+                .is_some(),
+            _ => false,
+        };
+        if !explicit_layout
+            && !attributes
+                .iter()
+                .any(|attribute| attribute.name == "aligned")
+        {
+            return Ok(None);
+        }
+        let (_, natural_alignment) = self.type_layout(base_type, span)?;
+        // `packed` does not apply to variables
+        let aligned = attributes
+            .iter()
+            .filter(|attribute| attribute.name == "aligned")
+            .cloned()
+            .collect::<Vec<_>>();
+        Ok(Some(self.alignment_of(&aligned, natural_alignment)))
+    }
+
+    /// The alignment given by `packed` and `aligned` attributes to something whose alignment is
+    /// `natural_alignment` otherwise. `packed` lowers the alignment to 1, or to its argument from
+    /// `#pragma pack`. `aligned` can only increase the alignment, and defaults to the largest
+    /// alignment of any type.
+    pub(crate) fn alignment_of(&self, attributes: &[Attribute], natural_alignment: u32) -> u32 {
+        let mut alignment = natural_alignment;
+        for attribute in attributes
+            .iter()
+            .filter(|attribute| attribute.name == "packed")
+        {
+            let packed = match attribute.arguments.first() {
+                Some(argument) => argument.node.integer_literal().unwrap() as u32,
+                None => 1,
+            };
+            alignment = alignment.min(packed);
+        }
+        for attribute in attributes
+            .iter()
+            .filter(|attribute| attribute.name == "aligned")
+        {
+            let aligned = match attribute.arguments.first() {
+                Some(argument) => argument.node.integer_literal().unwrap() as u32,
+                None => 16,
+            };
+            alignment = alignment.max(aligned);
+        }
+        alignment
+    }
+}
+
+fn align_to(offset: u32, alignment: u32) -> u32 {
+    offset.div_ceil(alignment) * alignment
+}
@@ -0,0 +1,262 @@
+use crate::ast::{BasicType, TypeQualifier};
+use crate::generator::Generator;
+use crate::utils::CompileErr as CE;
+use inkwell::types::{BasicType as _, BasicTypeEnum};
+use inkwell::values::{BasicValue, BasicValueEnum, InstructionValue, IntValue, PointerValue};
+use inkwell::{AddressSpace, AtomicOrdering, AtomicRMWBinOp};
+
+impl<'ctx> Generator<'ctx> {
+    /// Load the value of an object of type `t`. Accesses to volatile objects are kept by the
+    /// optimizer, and atomic objects are read with a sequentially consistent atomic load.
+    pub(crate) fn build_object_load(
+        &self,
+        t: &BasicType,
+        ptr: PointerValue<'ctx>,
+        name: &str,
+    ) -> BasicValueEnum<'ctx> {
+        if !self.is_qualified(t, TypeQualifier::Atomic) {
+            let value = self.builder.build_load(ptr, name);
+            self.set_volatile(t, value.as_instruction_value().unwrap());
+            return value;
+        }
+        let (storage_ptr, value_type) = self.atomic_storage_pointer(ptr);
+        let value = self.builder.build_load(storage_ptr, name);
+        self.set_atomic(t, value.as_instruction_value().unwrap(), value.get_type());
+        self.value_from_atomic_storage(value, value_type)
+    }
+
+    /// Store `value` to an object of type `t`, see `build_object_load`.
+    pub(crate) fn build_object_store(
+        &self,
+        t: &BasicType,
+        ptr: PointerValue<'ctx>,
+        value: BasicValueEnum<'ctx>,
+    ) {
+        if !self.is_qualified(t, TypeQualifier::Atomic) {
+            let instruction = self.builder.build_store(ptr, value);
+            self.set_volatile(t, instruction);
+            return;
+        }
+        let (storage_ptr, _) = self.atomic_storage_pointer(ptr);
+        let value = self.atomic_storage_value(value);
+        let instruction = self.builder.build_store(storage_ptr, value);
+        self.set_atomic(t, instruction, value.get_type());
+    }
+
+    /// Atomically replace the value of the atomic object at `ptr` with `update(old value)`,
+    /// returning the old and the new values. Updates LLVM can do in a single instruction are
+    /// given by `rmw`, the other ones are done in a compare-and-exchange loop.
+    pub(crate) fn build_atomic_update<F>(
+        &self,
+        t: &BasicType,
+        ptr: PointerValue<'ctx>,
+        rmw: Option<(AtomicRMWBinOp, IntValue<'ctx>)>,
+        update: F,
+    ) -> Result<(BasicValueEnum<'ctx>, BasicValueEnum<'ctx>), CE>
+    where
+        F: Fn(BasicValueEnum<'ctx>) -> Result<BasicValueEnum<'ctx>, CE>,
+    {
+        let ordering = AtomicOrdering::SequentiallyConsistent;
+        if let Some((op, operand)) = rmw {
+            let old_v = self.build_atomic_rmw(t, ptr, op, operand);
+            // the new value is computed again, as `atomicrmw` only yields the old one
+            return Ok((old_v, update(old_v)?));
+        }
+
+        let (storage_ptr, value_type) = self.atomic_storage_pointer(ptr);
+        let function = self.current_function.as_ref().unwrap().0;
+        let before_block = self.builder.get_insert_block().unwrap();
+        let initial = self.builder.build_load(storage_ptr, "atomic_initial");
+        self.set_atomic(
+            t,
+            initial.as_instruction_value().unwrap(),
+            initial.get_type(),
+        );
+
+        let update_block = self.context.append_basic_block(function, "atomic_update");
+        let after_block = self
+            .context
+            .append_basic_block(function, "after_atomic_update");
+        self.builder.build_unconditional_branch(update_block);
+
+        self.builder.position_at_end(update_block);
+        let expected = self
+            .builder
+            .build_phi(initial.get_type(), "atomic_expected");
+        let old_v = self.value_from_atomic_storage(expected.as_basic_value(), value_type);
+        let new_v = update(old_v)?;
+        let result = self
+            .builder
+            .build_cmpxchg(
+                storage_ptr,
+                expected.as_basic_value(),
+                self.atomic_storage_value(new_v),
+                ordering,
+                ordering,
+            )
+            .unwrap();
+        self.set_volatile(t, result.as_instruction_value().unwrap());
+        let current = self
+            .builder
+            .build_extract_value(result, 0, "atomic_current")
+            .unwrap();
+        let exchanged = self
+            .builder
+            .build_extract_value(result, 1, "atomic_exchanged")
+            .unwrap()
+            .into_int_value();
+        expected.add_incoming(&[
+            (&initial, before_block),
+            (&current, self.builder.get_insert_block().unwrap()),
+        ]);
+        self.builder
+            .build_conditional_branch(exchanged, after_block, update_block);
+
+        self.builder.position_at_end(after_block);
+        Ok((old_v, new_v))
+    }
+
+    /// A single `atomicrmw` instruction on an integer object, yielding its old value.
+    pub(crate) fn build_atomic_rmw(
+        &self,
+        t: &BasicType,
+        ptr: PointerValue<'ctx>,
+        op: AtomicRMWBinOp,
+        operand: IntValue<'ctx>,
+    ) -> BasicValueEnum<'ctx> {
+        let old_v = self
+            .builder
+            .build_atomicrmw(op, ptr, operand, AtomicOrdering::SequentiallyConsistent)
+            .unwrap()
+            .as_basic_value_enum();
+        self.set_volatile(t, old_v.as_instruction_value().unwrap());
+        old_v
+    }
+
+    /// Replace the value of the object at `ptr` with `desired` if it equals the value at
+    /// `expected_ptr`, otherwise store the value found to `expected_ptr`; whether the value was
+    /// replaced is returned.
+    pub(crate) fn build_atomic_compare_exchange(
+        &self,
+        t: &BasicType,
+        ptr: PointerValue<'ctx>,
+        expected_ptr: PointerValue<'ctx>,
+        desired: BasicValueEnum<'ctx>,
+    ) -> IntValue<'ctx> {
+        let ordering = AtomicOrdering::SequentiallyConsistent;
+        let (storage_ptr, value_type) = self.atomic_storage_pointer(ptr);
+        let expected = self.builder.build_load(expected_ptr, "atomic_expected");
+        let result = self
+            .builder
+            .build_cmpxchg(
+                storage_ptr,
+                self.atomic_storage_value(expected),
+                self.atomic_storage_value(desired),
+                ordering,
+                ordering,
+            )
+            .unwrap();
+        self.set_volatile(t, result.as_instruction_value().unwrap());
+        let current = self
+            .builder
+            .build_extract_value(result, 0, "atomic_current")
+            .unwrap();
+        // when the value was replaced, the one found is the expected one anyway
+        self.builder.build_store(
+            expected_ptr,
+            self.value_from_atomic_storage(current, value_type),
+        );
+        self.builder
+            .build_extract_value(result, 1, "atomic_exchanged")
+            .unwrap()
+            .into_int_value()
+    }
+
+    /// Make a load or store of the storage type sequentially consistent. It is aligned to its
+    /// size, as misaligned atomic accesses are turned into calls to libatomic.
+    fn set_atomic(
+        &self,
+        t: &BasicType,
+        instruction: InstructionValue<'ctx>,
+        storage_type: BasicTypeEnum<'ctx>,
+    ) {
+        let size = match storage_type {
+            BasicTypeEnum::IntType(t) => t.get_bit_width() / 8,
+            _ => 8,
+        };
+        instruction.set_alignment(size).unwrap();
+        instruction
+            .set_atomic_ordering(AtomicOrdering::SequentiallyConsistent)
+            .unwrap();
+        self.set_volatile(t, instruction);
+    }
+
+    fn set_volatile(&self, t: &BasicType, instruction: InstructionValue<'ctx>) {
+        if self.is_qualified(t, TypeQualifier::Volatile) {
+            instruction.set_volatile(true).unwrap();
+        }
+    }
+
+    /// LLVM only compares and exchanges integers of at least 8 bits and pointers: `_Bool` is
+    /// accessed as an `i8`, and floating types as integers of the same size.
+    fn atomic_storage_type(&self, value_type: BasicTypeEnum<'ctx>) -> BasicTypeEnum<'ctx> {
+        match value_type {
+            BasicTypeEnum::IntType(t) if t.get_bit_width() == 1 => {
+                self.context.i8_type().as_basic_type_enum()
+            }
+            BasicTypeEnum::FloatType(t) if t == self.context.f32_type() => {
+                self.context.i32_type().as_basic_type_enum()
+            }
+            BasicTypeEnum::FloatType(_) => self.context.i64_type().as_basic_type_enum(),
+            t => t,
+        }
+    }
+
+    /// The pointer `ptr` cast to point to the storage type, and the type it pointed to.
+    pub(crate) fn atomic_storage_pointer(
+        &self,
+        ptr: PointerValue<'ctx>,
+    ) -> (PointerValue<'ctx>, BasicTypeEnum<'ctx>) {
+        let value_type = BasicTypeEnum::try_from(ptr.get_type().get_element_type()).unwrap();
+        let storage_type = self.atomic_storage_type(value_type);
+        if storage_type == value_type {
+            return (ptr, value_type);
+        }
+        let storage_ptr = self.builder.build_pointer_cast(
+            ptr,
+            storage_type.ptr_type(AddressSpace::Generic),
+            "atomic_storage",
+        );
+        (storage_ptr, value_type)
+    }
+
+    pub(crate) fn atomic_storage_value(&self, value: BasicValueEnum<'ctx>) -> BasicValueEnum<'ctx> {
+        let storage_type = self.atomic_storage_type(value.get_type());
+        match value {
+            BasicValueEnum::IntValue(v) if v.get_type().get_bit_width() == 1 => self
+                .builder
+                .build_int_z_extend(v, storage_type.into_int_type(), "atomic_storage")
+                .as_basic_value_enum(),
+            BasicValueEnum::FloatValue(v) => {
+                self.builder
+                    .build_bitcast(v, storage_type, "atomic_storage")
+            }
+            v => v,
+        }
+    }
+
+    pub(crate) fn value_from_atomic_storage(
+        &self,
+        value: BasicValueEnum<'ctx>,
+        value_type: BasicTypeEnum<'ctx>,
+    ) -> BasicValueEnum<'ctx> {
+        match value_type {
+            BasicTypeEnum::IntType(t) if t.get_bit_width() == 1 => self
+                .builder
+                .build_int_truncate(value.into_int_value(), t, "atomic_value")
+                .as_basic_value_enum(),
+            BasicTypeEnum::FloatType(t) => self.builder.build_bitcast(value, t, "atomic_value"),
+            _ => value,
+        }
+    }
+}
@@ -1,23 +1,28 @@
 use crate::ast::BasicType as BT;
 use crate::ast::StructMember;
-use codespan_reporting::files::SimpleFiles;
+use crate::preprocess::SourceMap;
 use inkwell::basic_block::BasicBlock;
 use inkwell::builder::Builder;
 use inkwell::context::Context;
 use inkwell::module::Module;
 use inkwell::values::{FunctionValue, PointerValue};
+use inkwell::ThreadLocalMode;
 use std::collections::{HashMap, VecDeque};
 
+mod asm;
+mod builtin;
 mod cast_inst;
 mod expr;
 mod func_def;
 pub mod gen;
+mod layout;
+mod memory;
 mod out;
 mod stmt;
 mod utils;
 
 pub struct Generator<'ctx> {
-    files: SimpleFiles<&'ctx str, &'ctx str>,
+    source_map: &'ctx SourceMap,
     module_name: &'ctx str,
     context: &'ctx Context,
     module: Module<'ctx>,
@@ -37,10 +42,10 @@ pub struct Generator<'ctx> {
     break_labels: VecDeque<BasicBlock<'ctx>>,
     // continue labels (in loop statements)
     continue_labels: VecDeque<BasicBlock<'ctx>>,
-    // hashset for functions
-    function_map: HashMap<String, (BT, Vec<BT>, bool)>,
+    // blocks of the labels in the current function
+    label_blocks: HashMap<String, BasicBlock<'ctx>>,
     // hashset for global variable
     global_variable_map: HashMap<String, (BT, PointerValue<'ctx>)>,
-    // hashset for typedef
-    typedef_map: HashMap<String, BT>,
+    // TLS model of thread-local variables
+    tls_model: ThreadLocalMode,
 }
@@ -1,35 +1,42 @@
-use crate::ast::{
-    Expression, ExpressionEnum, ForInitClause, ForInitClauseEnum, Span, Statement, StatementEnum,
-    StatementOrDeclaration, StatementOrDeclarationEnum,
-};
+use crate::ast::{BaseType, Span};
 use crate::generator::Generator;
+use crate::sema::{
+    TypedExpression, TypedForInitClause, TypedStatement, TypedStatementEnum,
+    TypedStatementOrDeclaration,
+};
 use crate::utils::CompileErr as CE;
+use inkwell::basic_block::BasicBlock;
 use std::collections::HashMap;
 
 impl<'ctx> Generator<'ctx> {
-    pub(crate) fn gen_statement(&mut self, statement: &Statement) -> Result<(), CE> {
+    pub(crate) fn gen_statement(&mut self, statement: &TypedStatement) -> Result<(), CE> {
         match statement.node {
-            StatementEnum::Compound(ref state_or_decl) => {
+            TypedStatementEnum::Compound(ref state_or_decl) => {
                 self.gen_compound_statement(state_or_decl)?
             }
-            StatementEnum::While(ref cond, ref body) => {
-                self.gen_while_statement(cond, body, false)?
+            TypedStatementEnum::While(ref cond, ref body) => {
+                self.gen_while_statement(Some(cond), body, None, false)?
             }
-            StatementEnum::DoWhile(ref body, ref cond) => {
-                self.gen_while_statement(cond, body, true)?
+            TypedStatementEnum::DoWhile(ref body, ref cond) => {
+                self.gen_while_statement(Some(cond), body, None, true)?
             }
-            StatementEnum::For(ref init, ref cond, ref iter, ref body) => {
+            TypedStatementEnum::For(ref init, ref cond, ref iter, ref body) => {
                 self.gen_for_statement(init, cond, iter, body)?
             }
-            StatementEnum::Break => self.gen_break_statement(statement.span)?,
-            StatementEnum::Continue => self.gen_continue_statement(statement.span)?,
-            StatementEnum::If(ref cond, ref then_stmt, ref else_stmt) => {
+            TypedStatementEnum::Break => self.gen_break_statement(statement.span)?,
+            TypedStatementEnum::Continue => self.gen_continue_statement(statement.span)?,
+            TypedStatementEnum::If(ref cond, ref then_stmt, ref else_stmt) => {
                 self.gen_if_statement(cond, then_stmt, else_stmt)?
             }
-            StatementEnum::Return(ref expr) => self.gen_return_statement(expr)?,
-            StatementEnum::Expression(ref expr) => {
+            TypedStatementEnum::Return(ref expr) => self.gen_return_statement(expr)?,
+            TypedStatementEnum::Expression(ref expr) => {
                 self.gen_expression(expr)?;
             }
+            TypedStatementEnum::Labeled(ref label, ref statement) => {
+                self.gen_labeled_statement(label, statement)?
+            }
+            TypedStatementEnum::Goto(ref label) => self.gen_goto_statement(label),
+            TypedStatementEnum::Asm(ref asm) => self.gen_asm_statement(asm, statement.span)?,
             _ => {
                 dbg!(statement);
                 unimplemented!()
@@ -38,16 +45,19 @@ impl<'ctx> Generator<'ctx> {
         Ok(())
     }
 
-    fn gen_compound_statement(&mut self, statements: &[StatementOrDeclaration]) -> Result<(), CE> {
+    fn gen_compound_statement(
+        &mut self,
+        statements: &[TypedStatementOrDeclaration],
+    ) -> Result<(), CE> {
         self.val_map_block_stack.push(HashMap::new());
 
         // generate IR for each statement or declaration in function body
         for element in statements {
-            match element.node {
-                StatementOrDeclarationEnum::Statement(ref state) => {
+            match element {
+                TypedStatementOrDeclaration::Statement(ref state) => {
                     self.gen_statement(state)?;
                 }
-                StatementOrDeclarationEnum::LocalDeclaration(ref decl) => {
+                TypedStatementOrDeclaration::LocalDeclaration(ref decl) => {
                     self.gen_decl_in_fn(decl)?;
                 }
             }
@@ -57,10 +67,13 @@ impl<'ctx> Generator<'ctx> {
         Ok(())
     }
 
+    /// A `while` or `do while` loop, and the loop of a `for` statement, which has `iter` and
+    /// may have no condition.
     fn gen_while_statement(
         &mut self,
-        cond: &Expression,
-        body: &Statement,
+        cond: Option<&TypedExpression>,
+        body: &TypedStatement,
+        iter: Option<&TypedExpression>,
         is_do_while: bool,
     ) -> Result<(), CE> {
         let func_val = self.current_function.as_ref().unwrap().0;
@@ -76,7 +89,10 @@ impl<'ctx> Generator<'ctx> {
 
         self.builder.build_unconditional_branch(before_while_block);
         self.builder.position_at_end(before_while_block);
-        let condition_val_int_val = self.gen_expression(cond)?.1.into_int_value();
+        let condition_val_int_val = match cond {
+            Some(cond) => self.build_truth_value(self.gen_expression(cond)?),
+            None => self.context.bool_type().const_all_ones(),
+        };
         if self.no_terminator() {
             if is_do_while {
                 self.builder.build_unconditional_branch(while_block);
@@ -93,6 +109,11 @@ impl<'ctx> Generator<'ctx> {
 
         // body must be Statement::Compound
         self.gen_statement(body)?;
+        if let Some(iter) = iter {
+            if self.no_terminator() {
+                self.gen_expression(iter)?;
+            }
+        }
         if self.no_terminator() {
             if is_do_while {
                 self.builder.build_conditional_branch(
@@ -115,70 +136,26 @@ impl<'ctx> Generator<'ctx> {
 
     fn gen_for_statement(
         &mut self,
-        init: &Option<Box<ForInitClause>>,
-        cond: &Option<Box<Expression>>,
-        iter: &Option<Box<Expression>>,
-        body: &Statement,
+        init: &Option<Box<TypedForInitClause>>,
+        cond: &Option<Box<TypedExpression>>,
+        iter: &Option<Box<TypedExpression>>,
+        body: &TypedStatement,
     ) -> Result<(), CE> {
-        let mut new_block: Vec<StatementOrDeclaration> = vec![];
-        if let Some(ref init) = init {
-            match &init.node {
-                ForInitClauseEnum::Expression(ref expr) => {
-                    new_block.push(StatementOrDeclaration {
-                        node: StatementOrDeclarationEnum::Statement(Statement {
-                            node: StatementEnum::Expression(Box::new(expr.to_owned())),
-                            span: init.span,
-                        }),
-                        span: init.span,
-                    });
-                }
-                ForInitClauseEnum::ForDeclaration(decl) => {
-                    new_block.append(
-                        decl.iter()
-                            .map(|d| StatementOrDeclaration {
-                                node: StatementOrDeclarationEnum::LocalDeclaration(d.to_owned()),
-                                span: d.span,
-                            })
-                            .collect::<Vec<StatementOrDeclaration>>()
-                            .as_mut(),
-                    );
+        // the variables declared by the init clause are only visible in the loop
+        self.val_map_block_stack.push(HashMap::new());
+        match init.as_deref() {
+            Some(TypedForInitClause::Expression(ref expr)) => {
+                self.gen_expression(expr)?;
+            }
+            Some(TypedForInitClause::ForDeclaration(ref decl)) => {
+                for d in decl {
+                    self.gen_decl_in_fn(d)?;
                 }
             }
+            None => {}
         }
-        let mut new_body = vec![StatementOrDeclaration {
-            node: StatementOrDeclarationEnum::Statement(body.to_owned()),
-            span: body.span,
-        }];
-        if let Some(iter) = iter {
-            new_body.push(StatementOrDeclaration {
-                node: StatementOrDeclarationEnum::Statement(Statement {
-                    node: StatementEnum::Expression(iter.to_owned()),
-                    span: iter.span,
-                }),
-                span: iter.span,
-            });
-        }
-        let new_cond = match cond {
-            Some(cond) => cond.to_owned(),
-            None => Box::new(Expression {
-                node: ExpressionEnum::Empty,
-                span: Span::default(),
-            }),
-        };
-        new_block.push(StatementOrDeclaration {
-            node: StatementOrDeclarationEnum::Statement(Statement {
-                node: StatementEnum::While(
-                    new_cond.clone(),
-                    Box::new(Statement {
-                        node: StatementEnum::Compound(new_body),
-                        span: body.span,
-                    }),
-                ),
-                span: new_cond.span,
-            }),
-            span: new_cond.span,
-        });
-        self.gen_compound_statement(&new_block)?;
+        self.gen_while_statement(cond.as_deref(), body, iter.as_deref(), false)?;
+        self.val_map_block_stack.pop();
         Ok(())
     }
 
@@ -200,11 +177,39 @@ impl<'ctx> Generator<'ctx> {
         Ok(())
     }
 
+    fn gen_labeled_statement(&mut self, label: &str, statement: &TypedStatement) -> Result<(), CE> {
+        let label_block = self.label_block(label);
+        if self.no_terminator() {
+            self.builder.build_unconditional_branch(label_block);
+        }
+        self.builder.position_at_end(label_block);
+        self.gen_statement(statement)
+    }
+
+    fn gen_goto_statement(&mut self, label: &str) {
+        let label_block = self.label_block(label);
+        self.builder.build_unconditional_branch(label_block);
+        // the statements after the jump are unreachable unless labeled
+        let func_val = self.current_function.as_ref().unwrap().0;
+        let after_block = self.context.append_basic_block(func_val, "after_goto");
+        self.builder.position_at_end(after_block);
+    }
+
+    /// The block starting at a label, created when the label is first defined or jumped to.
+    pub(crate) fn label_block(&mut self, label: &str) -> BasicBlock<'ctx> {
+        let func_val = self.current_function.as_ref().unwrap().0;
+        let context = self.context;
+        *self
+            .label_blocks
+            .entry(label.to_owned())
+            .or_insert_with(|| context.append_basic_block(func_val, label))
+    }
+
     fn gen_if_statement(
         &mut self,
-        cond: &Expression,
-        then_stmt: &Statement,
-        else_stmt: &Option<Box<Statement>>,
+        cond: &TypedExpression,
+        then_stmt: &TypedStatement,
+        else_stmt: &Option<Box<TypedStatement>>,
     ) -> Result<(), CE> {
         let func_val = self.current_function.as_ref().unwrap().0;
 
@@ -212,7 +217,7 @@ impl<'ctx> Generator<'ctx> {
         let else_block = self.context.append_basic_block(func_val, "else_block");
         let after_block = self.context.append_basic_block(func_val, "after_block");
 
-        let cond_int_value = self.gen_expression(cond)?.1.into_int_value();
+        let cond_int_value = self.build_truth_value(self.gen_expression(cond)?);
         self.builder
             .build_conditional_branch(cond_int_value, if_block, else_block);
 
@@ -235,30 +240,21 @@ impl<'ctx> Generator<'ctx> {
         Ok(())
     }
 
-    fn gen_return_statement(&mut self, expr: &Option<Box<Expression>>) -> Result<(), CE> {
-        if expr.is_none() {
-            self.builder.build_return(None);
-            return Ok(());
-        }
-
-        let func_return_type = self
-            .current_function
-            .as_ref()
-            .unwrap()
-            .to_owned()
-            .1
-            .base_type;
-        let (e_t, e_v) = self.gen_expression(&expr.to_owned().unwrap())?;
-
-        e_t.test_cast(
-            &func_return_type,
-            expr.as_ref().unwrap().span,
-            &self.typedef_map,
-        )?;
+    /// The value is already converted to the return type of the function.
+    fn gen_return_statement(&mut self, expr: &Option<Box<TypedExpression>>) -> Result<(), CE> {
+        let expr = match expr {
+            Some(expr) => expr,
+            None => {
+                self.builder.build_return(None);
+                return Ok(());
+            }
+        };
 
-        let return_val =
-            self.cast_value(&e_t, &e_v, &func_return_type, expr.as_ref().unwrap().span)?;
-        self.builder.build_return(Some(&return_val));
+        let return_val = self.gen_expression(expr)?;
+        match self.current_function.as_ref().unwrap().1.base_type {
+            BaseType::Void => self.builder.build_return(None),
+            _ => self.builder.build_return(Some(&return_val)),
+        };
 
         Ok(())
     }
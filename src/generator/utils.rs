@@ -1,4 +1,4 @@
-use crate::ast::{BaseType, Span};
+use crate::ast::{BasicType, Span, TypeQualifier};
 use crate::generator::Generator;
 use crate::utils::CompileErr as CE;
 
@@ -9,29 +9,25 @@ impl<'ctx> Generator<'ctx> {
         terminator.is_none()
     }
 
-    pub(crate) fn gen_err_output(&self, file_id: usize, e: &CE) {
+    pub(crate) fn gen_err_output(&self, e: &CE) {
         use codespan_reporting::term;
         use codespan_reporting::term::termcolor::{ColorChoice, StandardStream};
 
-        let diagnostic = e.to_diagnostic(file_id);
+        let diagnostic = e.to_diagnostic(self.source_map.file_id(e.span().start));
 
         let writer = StandardStream::stderr(ColorChoice::Always);
         let config = term::Config::default();
 
-        term::emit(&mut writer.lock(), &config, &self.files, &diagnostic).expect("unreachable");
+        term::emit(&mut writer.lock(), &config, self.source_map, &diagnostic).expect("unreachable");
     }
 
-    pub(crate) fn extend_struct_type(&self, t: BaseType, span: Span) -> Result<BaseType, CE> {
-        match t {
-            BaseType::Struct(ref name, ref _members) => {
-                let members = self.global_struct_map.get(name.as_ref().unwrap());
-                if members.is_none() {
-                    return Err(CE::struct_not_found(name.clone().unwrap(), span));
-                }
-                let members = members.unwrap().clone();
-                Ok(BaseType::Struct(name.clone(), Some(members)))
-            }
-            _ => Ok(t),
-        }
+    /// The source code covered by `span`, used to name an expression in diagnostics.
+    pub(crate) fn source_text(&self, span: Span) -> String {
+        let source = self.source_map.code();
+        source[span.start..span.end].to_string()
+    }
+
+    pub(crate) fn is_qualified(&self, t: &BasicType, qualifier: TypeQualifier) -> bool {
+        t.qualifier.contains(&qualifier)
     }
 }
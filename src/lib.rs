@@ -7,6 +7,7 @@ pub mod ast;
 pub mod generator;
 pub mod parse;
 pub mod preprocess;
+pub mod sema;
 pub mod visual;
 
 pub mod utils;
@@ -4,22 +4,25 @@ extern crate pest_derive;
 extern crate core;
 
 use cc99::compile_result;
-use clap::{ArgGroup, Parser};
-use inkwell::{context::Context, OptimizationLevel};
-use std::fs;
+use clap::{ArgGroup, ArgMatches, CommandFactory, FromArgMatches, Parser};
+use inkwell::targets::TargetMachine;
+use inkwell::{context::Context, OptimizationLevel, ThreadLocalMode};
 use std::io::{stdin, Read};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Output};
+use std::{env, fs};
 
 mod ast;
 mod generator;
 mod parse;
 mod preprocess;
+mod sema;
 mod utils;
 
 use generator::*;
 use parse::*;
 use preprocess::*;
+use sema::*;
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
@@ -40,7 +43,7 @@ struct Args {
     #[clap(short = 'E', long, display_order = 3)]
     expand: bool,
 
-    /// Preprocess and parse; do not compile, assemble or link
+    /// Preprocess, parse and type check; do not compile, assemble or link
     #[clap(short, long, display_order = 4)]
     parse: bool,
 
@@ -64,17 +67,105 @@ struct Args {
     #[clap(short = 'O', long, default_value = "0", display_order = 2)]
     opt_level: u32,
 
-    /// Add the directory <dir>,<dir>,<dir>(from left to right) to the list of directories to be searched for header files during preprocessing
+    /// Add the directory <dir>,<dir>,<dir>(from left to right) to the list of directories to be searched for header files during preprocessing, like -I
     #[clap(short, long, display_order = 1)]
     include: Option<String>,
+
+    /// Add <DIR> to the directories searched for header files, before the system directories
+    #[clap(
+        short = 'I',
+        value_name = "DIR",
+        multiple_occurrences = true,
+        display_order = 1
+    )]
+    include_dir: Vec<String>,
+
+    /// Add <DIR> to the system directories searched for header files, after the -I directories
+    #[clap(
+        long,
+        value_name = "DIR",
+        multiple_occurrences = true,
+        display_order = 1
+    )]
+    isystem: Vec<String>,
+
+    /// Add <DIR> to the directories searched for header files included with quotes only, before the -I directories
+    #[clap(
+        long,
+        value_name = "DIR",
+        multiple_occurrences = true,
+        display_order = 1
+    )]
+    iquote: Vec<String>,
+
+    /// Define <MACRO> as <VALUE>, or as 1 without a value
+    #[clap(
+        short = 'D',
+        value_name = "MACRO[=VALUE]",
+        multiple_occurrences = true,
+        display_order = 1
+    )]
+    define: Vec<String>,
+
+    /// Undefine <MACRO>, -D and -U are processed in the order they are given
+    #[clap(
+        short = 'U',
+        value_name = "MACRO",
+        multiple_occurrences = true,
+        display_order = 1
+    )]
+    undefine: Vec<String>,
+
+    /// Code generation option, -ftls-model=<model> selects the global-dynamic (default), local-dynamic, initial-exec or local-exec model of thread-local variables
+    #[clap(short = 'f', multiple_occurrences = true, display_order = 9)]
+    flag: Vec<String>,
+
+    /// Write a rule of make with the files the source depends on instead of preprocessing, like -M
+    #[clap(long = "M", display_order = 10)]
+    dependencies: bool,
+
+    /// Like -M, without the headers in system directories and the files they include, like -MM
+    #[clap(long = "MM", display_order = 10)]
+    user_dependencies: bool,
+
+    /// Write the rule of -M to a .d file besides the output, like -MD
+    #[clap(long = "MD", display_order = 10)]
+    write_dependencies: bool,
+
+    /// Write the rule of -MM to a .d file besides the output, like -MMD
+    #[clap(long = "MMD", display_order = 10)]
+    write_user_dependencies: bool,
+
+    /// Write the rule of -M, -MM, -MD or -MMD to <FILE>, like -MF
+    #[clap(long = "MF", value_name = "FILE", display_order = 10)]
+    dependency_file: Option<String>,
+
+    /// Make <TARGET> the target of the rule instead of the object file, like -MT
+    #[clap(
+        long = "MT",
+        value_name = "TARGET",
+        multiple_occurrences = true,
+        display_order = 10
+    )]
+    dependency_target: Vec<String>,
+
+    /// Add a rule without dependencies for each header, so that make does not fail once it is removed, like -MP
+    #[clap(long = "MP", display_order = 10)]
+    phony_targets: bool,
 }
 
 fn main() {
-    let args = Args::parse();
-    let include_dirs: Vec<&str> = match args.include {
-        Some(ref includes) => includes.split(',').collect(),
-        None => Default::default(),
-    };
+    let matches = Args::command().get_matches_from(gcc_options(env::args()));
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let mut include_paths = IncludePaths::new();
+    include_paths.quote = args.iquote.iter().map(PathBuf::from).collect();
+    include_paths.user = args.include_dir.iter().map(PathBuf::from).collect();
+    if let Some(ref includes) = args.include {
+        include_paths
+            .user
+            .extend(includes.split(',').map(PathBuf::from));
+    }
+    include_paths.system = args.isystem.iter().map(PathBuf::from).collect();
     if args.visual {
         let mut buffer = String::new();
         let size = stdin().read_to_string(&mut buffer);
@@ -92,7 +183,7 @@ fn main() {
     }
     let basename = Path::new(&args.file).file_stem().unwrap().to_str().unwrap();
     let output_file = match args.output {
-        Some(output) => output,
+        Some(ref output) => output.clone(),
         None => {
             if args.expand {
                 format!("{}.expand.c", basename)
@@ -119,12 +210,74 @@ fn main() {
             std::process::exit(1);
         }
     };
+    let mut tls_model = ThreadLocalMode::GeneralDynamicTLSModel;
+    for flag in &args.flag {
+        match flag.split_once('=') {
+            Some(("tls-model", model)) => {
+                tls_model = match model {
+                    "global-dynamic" => ThreadLocalMode::GeneralDynamicTLSModel,
+                    "local-dynamic" => ThreadLocalMode::LocalDynamicTLSModel,
+                    "initial-exec" => ThreadLocalMode::InitialExecTLSModel,
+                    "local-exec" => ThreadLocalMode::LocalExecTLSModel,
+                    _ => {
+                        eprintln!("Invalid TLS model {}", model);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            _ => {
+                eprintln!("Unknown option -f{}", flag);
+                std::process::exit(1);
+            }
+        }
+    }
 
     // preprocess
-    let code = preprocess_file(&args.file, &include_dirs).unwrap_or_else(|e| {
-        eprintln!("Preprocess failed:\n{}", e);
-        std::process::exit(1);
-    });
+    let macros = command_line_macros(&matches);
+    let (code, dependencies) =
+        preprocess_file(&args.file, &include_paths, &macros).unwrap_or_else(|e| {
+            e.emit();
+            std::process::exit(1);
+        });
+
+    // dependencies
+    let write_dependencies = args.write_dependencies || args.write_user_dependencies;
+    if args.dependencies || args.user_dependencies || write_dependencies {
+        let dependencies = match args.user_dependencies || args.write_user_dependencies {
+            true => dependencies
+                .into_iter()
+                .filter(|dependency| !dependency.is_system)
+                .collect(),
+            false => dependencies,
+        };
+        // like GCC, the target is the object file, or the output of -MD when it is given
+        let targets = match args.dependency_target.is_empty() {
+            true => match (write_dependencies && !args.expand, &args.output) {
+                (true, Some(output)) => vec![escape(output)],
+                _ => vec![escape(&format!("{}.o", basename))],
+            },
+            false => args.dependency_target.clone(),
+        };
+        let rule = make_rule(&targets, &dependencies, args.phony_targets);
+        if write_dependencies {
+            let file = match (&args.dependency_file, &args.output) {
+                (Some(file), _) => file.clone(),
+                (None, Some(output)) => Path::new(output)
+                    .with_extension("d")
+                    .to_string_lossy()
+                    .to_string(),
+                (None, None) => format!("{}.d", basename),
+            };
+            write_or_exit(&file, &rule);
+        } else {
+            // -M and -MM only preprocess, writing the rule instead of the code
+            match args.dependency_file.or(args.output) {
+                Some(file) => write_or_exit(&file, &rule),
+                None => print!("{}", rule),
+            }
+            std::process::exit(0);
+        }
+    }
 
     if args.expand {
         fs::write(&output_file, &code).unwrap_or_else(|_| {
@@ -132,22 +285,41 @@ fn main() {
             std::process::exit(1);
         });
     } else {
+        // the diagnostics point to the files and lines given by the line markers
+        let source_map = SourceMap::new(&args.file, &code);
+        let code = source_map.code();
+
         // parse
-        let ast = Parse::new().parse(&code).unwrap_or_else(|e| {
-            eprintln!("Parse failed:\n{}", e);
+        let ast = Parse::new().parse(code).unwrap_or_else(|e| {
+            match e.downcast::<pest::error::Error<parse::Rule>>() {
+                Ok(e) => eprintln!("Parse failed:\n{}", source_map.locate_pest_error(*e)),
+                Err(e) => eprintln!("Parse failed:\n{}", e),
+            }
             std::process::exit(1);
         });
 
+        // semantic analysis
+        let mut sema = Sema::new(code);
+        let result = sema.analyze(&ast);
+        print_errors(&source_map, sema.warnings());
+        let typed_ast = result.unwrap_or_else(|errors| {
+            print_errors(&source_map, &errors);
+            std::process::exit(errors.len() as i32);
+        });
+
         if args.parse {
-            fs::write(&output_file, &serde_json::to_string(&ast).unwrap()).unwrap_or_else(|_| {
-                eprintln!("Unable to write file {}", output_file);
-                std::process::exit(1);
-            });
+            fs::write(&output_file, serde_json::to_string(&typed_ast).unwrap()).unwrap_or_else(
+                |_| {
+                    eprintln!("Unable to write file {}", output_file);
+                    std::process::exit(1);
+                },
+            );
         } else {
             // code_gen
             let context = Context::create();
-            let mut code_gen = Generator::new(&context, &args.file, &code);
-            code_gen.gen(&ast);
+            let mut code_gen = Generator::new(&context, &args.file, &source_map);
+            code_gen.set_tls_model(tls_model);
+            code_gen.gen(&typed_ast);
 
             if args.bitcode {
                 // generate LLVM bitcode
@@ -178,7 +350,7 @@ fn main() {
                     // generate binary
                     let object_file = basename.to_string() + ".o";
                     link(&object_file, &output_file, Compiler::Clang)
-                        .map_err(|_| link(&object_file, &output_file, Compiler::GNU))
+                        .map_err(|_| link(&object_file, &output_file, Compiler::Gnu))
                         .map_or_else(
                             |e| {
                                 if let Err(e) = e {
@@ -204,24 +376,88 @@ fn main() {
     }
 }
 
+/// The arguments with the options of GCC made of a single dash and a long name, like `-isystem`
+/// and `-MD`, written with two dashes for clap.
+fn gcc_options(args: impl Iterator<Item = String>) -> Vec<String> {
+    args.map(|arg| {
+        if ["-M", "-MM", "-MD", "-MMD", "-MP"].contains(&arg.as_str()) {
+            return format!("-{}", arg);
+        }
+        for option in ["-isystem", "-iquote", "-MF", "-MT"] {
+            if let Some(value) = arg.strip_prefix(option) {
+                return match value.is_empty() {
+                    true => format!("-{}", option),
+                    false => format!("-{}={}", option, value),
+                };
+            }
+        }
+        arg
+    })
+    .collect()
+}
+
+/// The target macros followed by the macros of -D and -U, in the order they are given.
+fn command_line_macros(matches: &ArgMatches) -> Vec<CommandLineMacro> {
+    let mut macros = vec![];
+    for (id, to_macro) in [
+        (
+            "define",
+            CommandLineMacro::define as fn(&str) -> CommandLineMacro,
+        ),
+        ("undefine", |name: &str| {
+            CommandLineMacro::Undefine(name.to_string())
+        }),
+    ] {
+        if let (Some(indices), Some(values)) = (matches.indices_of(id), matches.values_of(id)) {
+            macros.extend(indices.zip(values.map(to_macro)));
+        }
+    }
+    macros.sort_by_key(|(index, _)| *index);
+
+    let triple = TargetMachine::get_default_triple();
+    let mut result = target_macros(triple.as_str().to_str().unwrap());
+    result.extend(macros.into_iter().map(|(_, macro_)| macro_));
+    result
+}
+
+fn write_or_exit(file: &str, content: &str) {
+    fs::write(file, content).unwrap_or_else(|_| {
+        eprintln!("Unable to write file {}", file);
+        std::process::exit(1);
+    });
+}
+
+fn print_errors(source_map: &SourceMap, errors: &[utils::CompileErr]) {
+    use codespan_reporting::term;
+    use codespan_reporting::term::termcolor::{ColorChoice, StandardStream};
+
+    let writer = StandardStream::stderr(ColorChoice::Always);
+    let config = term::Config::default();
+    for error in errors {
+        term::emit(
+            &mut writer.lock(),
+            &config,
+            source_map,
+            &error.to_diagnostic(source_map.file_id(error.span().start)),
+        )
+        .expect("unreachable");
+    }
+}
+
 #[derive(Debug)]
 enum Compiler {
-    GNU,
+    Gnu,
     Clang,
 }
 
 fn link(source_file: &str, target_file: &str, compiler: Compiler) -> Result<Output, String> {
     let args = vec!["-o", target_file, source_file];
     let output = match compiler {
-        Compiler::GNU => Command::new("gcc").args(args).output(),
+        Compiler::Gnu => Command::new("gcc").args(args).output(),
         Compiler::Clang => Command::new("clang").arg("-no-pie").args(args).output(),
     };
     match output {
         Ok(output) => Ok(output),
-        Err(e) => Err(format!(
-            "Failed to link using {:?}: {}",
-            compiler,
-            e.to_string()
-        )),
+        Err(e) => Err(format!("Failed to link using {:?}: {}", compiler, e)),
     }
 }
@@ -64,13 +64,14 @@ impl Parse {
                 .extend(derived_type.basic_type.qualifier);
             let parameters = parameter_types
                 .into_iter()
-                .zip(parameter_names.into_iter())
+                .zip(parameter_names)
                 .collect::<Vec<_>>();
 
             ast.push(Declaration {
                 node: DeclarationEnum::FunctionDefinition(
                     derived_type.function_specifier,
                     derived_type.storage_class_specifier,
+                    derived_type.attributes,
                     return_type,
                     identifier,
                     parameters,
@@ -124,7 +125,9 @@ impl Parse {
         let span = pair.as_span();
         let mut qualifier: Vec<TypeQualifier> = Default::default();
         let mut storage_class_specifier: Vec<StorageClassSpecifier> = Default::default();
+        let mut thread_local = false;
         let mut function_specifier: Vec<FunctionSpecifier> = Default::default();
+        let mut attributes: Vec<Attribute> = Default::default();
         let mut base_type: BaseType = Default::default();
         for token in pair.into_inner() {
             match token.as_rule() {
@@ -140,7 +143,7 @@ impl Parse {
                         storage_class_specifier.push(StorageClassSpecifier::Static);
                     }
                     Rule::thread_local_ => {
-                        storage_class_specifier.push(StorageClassSpecifier::ThreadLocal);
+                        thread_local = true;
                     }
                     Rule::auto_ => {
                         storage_class_specifier.push(StorageClassSpecifier::Auto);
@@ -163,6 +166,9 @@ impl Parse {
                 Rule::type_specifier => {
                     base_type = self.build_type_specifier(ast, token)?;
                 }
+                Rule::attribute_specifier => {
+                    attributes.extend(self.build_attribute_specifier(token)?);
+                }
                 _ => unreachable!(),
             }
         }
@@ -178,7 +184,7 @@ impl Parse {
                     span,
                 )));
             }
-            if storage_class_specifier[0] == StorageClassSpecifier::ThreadLocal {
+            if thread_local {
                 return Err(Box::new(pest::error::Error::<Rule>::new_from_span(
                     ErrorVariant::CustomError {
                         message: "thread local storage class specifier is not allowed for function"
@@ -189,6 +195,22 @@ impl Parse {
             }
         }
 
+        if thread_local
+            && !matches!(
+                storage_class_specifier.first(),
+                None | Some(StorageClassSpecifier::Static) | Some(StorageClassSpecifier::Extern)
+            )
+        {
+            return Err(Box::new(pest::error::Error::<Rule>::new_from_span(
+                ErrorVariant::CustomError {
+                    message: "thread local storage class specifier can only be combined with \
+                              static or extern"
+                        .to_string(),
+                },
+                span,
+            )));
+        }
+
         Ok(Type {
             function_specifier,
             storage_class_specifier: if !storage_class_specifier.is_empty() {
@@ -196,10 +218,12 @@ impl Parse {
             } else {
                 Default::default()
             },
+            thread_local,
             basic_type: BasicType {
                 qualifier,
                 base_type,
             },
+            attributes,
         })
     }
 
@@ -210,27 +234,28 @@ impl Parse {
     ) -> Result<BaseType, Box<dyn Error>> {
         let mut is_signed = true;
         let mut integer_type = IntegerType::Int;
-        let token = pair.into_inner().next().unwrap();
-        match token.as_rule() {
-            Rule::void_ => return Ok(BaseType::Void),
-            Rule::signed_ => is_signed = true,
-            Rule::unsigned_ => is_signed = false,
-            Rule::char_ => integer_type = IntegerType::Char,
-            Rule::short_ => integer_type = IntegerType::Short,
-            Rule::int_ => integer_type = IntegerType::Int,
-            Rule::long_ => {
-                integer_type = match integer_type {
-                    IntegerType::Int => IntegerType::Long,
-                    IntegerType::Long => IntegerType::LongLong,
-                    _ => unreachable!(),
+        for token in pair.into_inner() {
+            match token.as_rule() {
+                Rule::void_ => return Ok(BaseType::Void),
+                Rule::signed_ => is_signed = true,
+                Rule::unsigned_ => is_signed = false,
+                Rule::char_ => integer_type = IntegerType::Char,
+                Rule::short_ => integer_type = IntegerType::Short,
+                Rule::int_ => integer_type = IntegerType::Int,
+                Rule::long_ => {
+                    integer_type = match integer_type {
+                        IntegerType::Int => IntegerType::Long,
+                        IntegerType::Long => IntegerType::LongLong,
+                        _ => unreachable!(),
+                    }
                 }
+                Rule::bool_ => return Ok(BaseType::Bool),
+                Rule::float_ => return Ok(BaseType::Float),
+                Rule::double_ => return Ok(BaseType::Double),
+                Rule::identifier => return Ok(BaseType::Identifier(token.as_str().to_string())),
+                Rule::struct_specifier => return self.build_struct_specifier(ast, token),
+                _ => unreachable!(),
             }
-            Rule::bool_ => return Ok(BaseType::Bool),
-            Rule::float_ => return Ok(BaseType::Float),
-            Rule::double_ => return Ok(BaseType::Double),
-            Rule::identifier => return Ok(BaseType::Identifier(token.as_str().to_string())),
-            Rule::struct_specifier => return self.build_struct_specifier(ast, token),
-            _ => unreachable!(),
         }
         if is_signed {
             Ok(BaseType::SignedInteger(integer_type))
@@ -269,6 +294,11 @@ impl Parse {
                         }
                     }
                 }
+                Rule::attribute_specifier => {
+                    derived_type
+                        .attributes
+                        .extend(self.build_attribute_specifier(token)?);
+                }
                 Rule::assignment_expression => {
                     initializer = Some(Box::new(self.build_assignment_expression(token)?));
                 }
@@ -350,6 +380,13 @@ impl Parse {
                 Rule::identifier => {
                     *identifier = token.as_str().to_string();
                 }
+                Rule::unspecified_dimension => {
+                    // completed by the initializer
+                    dimensions.push(Expression {
+                        node: ExpressionEnum::Empty,
+                        span: Span::from(token.as_span()),
+                    });
+                }
                 Rule::assignment_expression => {
                     dimensions.push(self.build_assignment_expression(token)?);
                 }
@@ -418,6 +455,8 @@ impl Parse {
                         token,
                     )?;
                 }
+                // none of the supported attributes applies to parameters
+                Rule::attribute_specifier => {}
                 _ => unreachable!(),
             }
         }
@@ -434,7 +473,9 @@ impl Parse {
         let mut derived_type = Type {
             function_specifier: Default::default(),
             storage_class_specifier: Default::default(),
+            thread_local: false,
             basic_type: basic_type.to_owned(),
+            attributes: Default::default(),
         };
         for token in pair.into_inner() {
             match token.as_rule() {
@@ -465,6 +506,7 @@ impl Parse {
         let mut identifier: Option<String> = None;
         let mut struct_declaration = false;
         let mut struct_members: Vec<StructMember> = Default::default();
+        let mut attributes: Vec<Attribute> = Default::default();
 
         for token in pair.into_inner() {
             match token.as_rule() {
@@ -477,6 +519,9 @@ impl Parse {
                 Rule::identifier => {
                     identifier = Some(token.as_str().to_string());
                 }
+                Rule::attribute_specifier => {
+                    attributes.extend(self.build_attribute_specifier(token)?);
+                }
                 Rule::struct_declaration => {
                     struct_declaration = true;
                     for sub_token in token.into_inner() {
@@ -550,17 +595,10 @@ impl Parse {
                                             struct_members.push(StructMember {
                                                 member_type: member_type.basic_type,
                                                 member_name,
+                                                attributes: member_type.attributes,
                                             });
                                         }
-                                        DeclarationEnum::FunctionDefinition(
-                                            _,
-                                            _,
-                                            _,
-                                            _,
-                                            _,
-                                            _,
-                                            _,
-                                        ) => {
+                                        DeclarationEnum::FunctionDefinition(..) => {
                                             unreachable!();
                                         }
                                     }
@@ -574,6 +612,30 @@ impl Parse {
             }
         }
 
+        // `packed` on a struct or union packs each of its members, and its other attributes, like
+        // `aligned`, have the same effect on the layout as on its first member
+        for (i, member) in struct_members.iter_mut().enumerate() {
+            member.attributes.extend(
+                attributes
+                    .iter()
+                    .filter(|attribute| i == 0 || attribute.name == "packed")
+                    .cloned(),
+            );
+        }
+        // `#pragma pack(n)` packs the members to at most `n` bytes
+        if let Some(pack) = self.pack {
+            for member in struct_members.iter_mut() {
+                member.attributes.push(Attribute {
+                    name: "packed".to_string(),
+                    arguments: vec![Expression {
+                        node: ExpressionEnum::IntegerConstant(pack as i32),
+                        span: Span::from(span.clone()),
+                    }],
+                    span: Span::from(span.clone()),
+                });
+            }
+        }
+
         let struct_definition = match is_struct {
             true => BaseType::Struct(
                 identifier.clone(),
@@ -601,10 +663,12 @@ impl Parse {
                     Type {
                         function_specifier: Default::default(),
                         storage_class_specifier: Default::default(),
+                        thread_local: false,
                         basic_type: BasicType {
                             qualifier: Default::default(),
                             base_type: struct_definition,
                         },
+                        attributes: Default::default(),
                     },
                     None,
                     None,
@@ -619,6 +683,70 @@ impl Parse {
         })
     }
 
+    pub fn build_pragma_pack(&mut self, pair: Pair<'_, Rule>) {
+        let alignment = |pair: Pair<'_, Rule>| pair.as_str().parse::<u32>().ok();
+        match pair.into_inner().next() {
+            Some(token) => match token.as_rule() {
+                Rule::pack_push => {
+                    self.pack_stack.push(self.pack);
+                    if let Some(token) = token.into_inner().next() {
+                        self.pack = alignment(token);
+                    }
+                }
+                Rule::pack_pop => {
+                    self.pack = self.pack_stack.pop().flatten();
+                }
+                Rule::pack_alignment => {
+                    self.pack = alignment(token);
+                }
+                _ => unreachable!(),
+            },
+            None => self.pack = None,
+        }
+    }
+
+    fn build_attribute_specifier(
+        &mut self,
+        pair: Pair<'_, Rule>,
+    ) -> Result<Vec<Attribute>, Box<dyn Error>> {
+        let mut attributes: Vec<Attribute> = Default::default();
+        for token in pair.into_inner() {
+            match token.as_rule() {
+                Rule::attribute_ => {}
+                Rule::attribute => {
+                    let span = Span::from(token.as_span());
+                    let mut name: String = Default::default();
+                    let mut arguments: Vec<Expression> = Default::default();
+                    for sub_token in token.into_inner() {
+                        match sub_token.as_rule() {
+                            Rule::attribute_name => {
+                                let raw_name = sub_token.as_str();
+                                name = match raw_name
+                                    .strip_prefix("__")
+                                    .and_then(|name| name.strip_suffix("__"))
+                                {
+                                    Some(name) if !name.is_empty() => name.to_string(),
+                                    _ => raw_name.to_string(),
+                                };
+                            }
+                            Rule::assignment_expression => {
+                                arguments.push(self.build_assignment_expression(sub_token)?);
+                            }
+                            _ => unreachable!(),
+                        }
+                    }
+                    attributes.push(Attribute {
+                        name,
+                        arguments,
+                        span,
+                    });
+                }
+                _ => unreachable!(),
+            }
+        }
+        Ok(attributes)
+    }
+
     fn build_type_qualifier(
         &mut self,
         pair: Pair<'_, Rule>,
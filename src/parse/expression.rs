@@ -332,6 +332,9 @@ impl Parse {
     ) -> Result<Expression, Box<dyn Error>> {
         let mut expression = Default::default();
         let mut object_or_pointer = true; // true if object, false otherwise
+        let (code, start) = (pair.as_str(), pair.as_span().start());
+        // the end of the `]` after the index ending at `end`, which the subscript spans
+        let bracket_end = |end: usize| end + code[end - start..].find(']').unwrap() + 1;
         for token in pair.into_inner() {
             let token_span = token.as_span();
             match token.as_rule() {
@@ -380,17 +383,18 @@ impl Parse {
                 Rule::expression => {
                     let expr_span = expression.span;
                     let expr = self.build_expression(token)?;
+                    let end = bracket_end(token_span.end());
                     expression = match expression.node {
                         ExpressionEnum::ArraySubscript(base, ref mut index) => {
                             index.push(expr);
                             Expression {
                                 node: ExpressionEnum::ArraySubscript(base, index.to_owned()),
-                                span: Span::new(expression.span.start, token_span.end()),
+                                span: Span::new(expression.span.start, end),
                             }
                         }
                         _ => Expression {
                             node: ExpressionEnum::ArraySubscript(Box::new(expression), vec![expr]),
-                            span: Span::new(expr_span.start, token_span.end()),
+                            span: Span::new(expr_span.start, end),
                         },
                     }
                 }
@@ -450,11 +454,110 @@ impl Parse {
             }),
             Rule::constant => self.build_constant(token),
             Rule::string_literal => self.build_string_literal(token),
+            Rule::generic_selection => self.build_generic_selection(token),
+            Rule::builtin_va_arg => self.build_builtin_va_arg(token),
+            Rule::builtin_offsetof => self.build_builtin_offsetof(token),
             Rule::expression => self.build_expression(token),
             _ => unreachable!(),
         }
     }
 
+    fn build_builtin_va_arg(&mut self, pair: Pair<'_, Rule>) -> Result<Expression, Box<dyn Error>> {
+        let span = pair.as_span();
+        let mut va_list: Expression = Default::default();
+        let mut argument_type: BasicType = Default::default();
+        for token in pair.into_inner() {
+            match token.as_rule() {
+                Rule::builtin_va_arg_ => {}
+                Rule::assignment_expression => {
+                    va_list = self.build_assignment_expression(token)?;
+                }
+                Rule::type_name => {
+                    argument_type = self.build_type_name(token)?;
+                }
+                _ => unreachable!(),
+            }
+        }
+        Ok(Expression {
+            node: ExpressionEnum::VaArg(Box::new(va_list), argument_type),
+            span: Span::from(span),
+        })
+    }
+
+    fn build_builtin_offsetof(
+        &mut self,
+        pair: Pair<'_, Rule>,
+    ) -> Result<Expression, Box<dyn Error>> {
+        let span = pair.as_span();
+        let mut struct_type: BasicType = Default::default();
+        let mut members: Vec<String> = Default::default();
+        for token in pair.into_inner() {
+            match token.as_rule() {
+                Rule::builtin_offsetof_ => {}
+                Rule::type_name => {
+                    struct_type = self.build_type_name(token)?;
+                }
+                Rule::identifier => {
+                    members.push(token.as_str().to_owned());
+                }
+                _ => unreachable!(),
+            }
+        }
+        Ok(Expression {
+            node: ExpressionEnum::Offsetof(struct_type, members),
+            span: Span::from(span),
+        })
+    }
+
+    fn build_generic_selection(
+        &mut self,
+        pair: Pair<'_, Rule>,
+    ) -> Result<Expression, Box<dyn Error>> {
+        let span = pair.as_span();
+        let mut controlling_expression: Expression = Default::default();
+        let mut associations: Vec<(Option<BasicType>, Expression)> = Default::default();
+        for token in pair.into_inner() {
+            let token_span = token.as_span();
+            match token.as_rule() {
+                Rule::generic_ => {}
+                Rule::assignment_expression => {
+                    controlling_expression = self.build_assignment_expression(token)?;
+                }
+                Rule::generic_association => {
+                    let mut association_type: Option<BasicType> = None;
+                    let mut expression: Expression = Default::default();
+                    for sub_token in token.into_inner() {
+                        match sub_token.as_rule() {
+                            Rule::default_ => {}
+                            Rule::type_name => {
+                                association_type = Some(self.build_type_name(sub_token)?);
+                            }
+                            Rule::assignment_expression => {
+                                expression = self.build_assignment_expression(sub_token)?;
+                            }
+                            _ => unreachable!(),
+                        }
+                    }
+                    if association_type.is_none() && associations.iter().any(|x| x.0.is_none()) {
+                        return Err(Box::new(pest::error::Error::<Rule>::new_from_span(
+                            ErrorVariant::CustomError {
+                                message: "duplicate default association in generic selection"
+                                    .to_string(),
+                            },
+                            token_span,
+                        )));
+                    }
+                    associations.push((association_type, expression));
+                }
+                _ => unreachable!(),
+            }
+        }
+        Ok(Expression {
+            node: ExpressionEnum::GenericSelection(Box::new(controlling_expression), associations),
+            span: Span::from(span),
+        })
+    }
+
     fn build_type_name(&mut self, pair: Pair<'_, Rule>) -> Result<BasicType, Box<dyn Error>> {
         let span = pair.as_span();
         let mut fake_ast: Vec<Declaration> = Default::default();
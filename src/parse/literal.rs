@@ -9,28 +9,62 @@ impl Parse {
         pair: Pair<'_, Rule>,
     ) -> Result<Expression, Box<dyn Error>> {
         let span = pair.as_span();
-        let mut string_literal: String = Default::default();
+        let (prefix, elements) = self.build_literal_elements(pair)?;
+        Ok(Expression {
+            node: ExpressionEnum::StringLiteral(
+                prefix,
+                encode_literal(prefix, elements, span.clone())?,
+            ),
+            span: Span::from(span),
+        })
+    }
+
+    fn build_literal_elements(
+        &mut self,
+        pair: Pair<'_, Rule>,
+    ) -> Result<(EncodingPrefix, Vec<LiteralElement>), Box<dyn Error>> {
+        let mut prefix = EncodingPrefix::Char;
+        let mut elements = Vec::new();
         for token in pair.into_inner() {
             match token.as_rule() {
+                Rule::character_prefix | Rule::string_prefix => {
+                    prefix = match token.as_str() {
+                        "u8" => EncodingPrefix::Utf8,
+                        "L" => EncodingPrefix::Wide,
+                        "u" => EncodingPrefix::Utf16,
+                        "U" => EncodingPrefix::Utf32,
+                        _ => unreachable!(),
+                    };
+                }
                 Rule::char_no_escape => {
-                    string_literal.push_str(token.as_str());
+                    elements.push(LiteralElement::Character(
+                        token.as_str().chars().next().unwrap(),
+                    ));
                 }
                 Rule::escape_sequence => {
-                    string_literal.push(self.build_escape_sequence(token)?);
+                    elements.push(self.build_escape_sequence(token)?);
                 }
                 _ => unreachable!(),
             }
         }
-        Ok(Expression {
-            node: ExpressionEnum::StringLiteral(string_literal),
-            span: Span::from(span),
-        })
+        Ok((prefix, elements))
     }
 
-    fn build_escape_sequence(&mut self, pair: Pair<'_, Rule>) -> Result<char, Box<dyn Error>> {
+    fn build_escape_sequence(
+        &mut self,
+        pair: Pair<'_, Rule>,
+    ) -> Result<LiteralElement, Box<dyn Error>> {
         let span = pair.as_span();
         let escape_sequence = pair.as_str();
-        Ok(match escape_sequence {
+        let error = |message: &str| {
+            Box::new(pest::error::Error::<Rule>::new_from_span(
+                ErrorVariant::CustomError {
+                    message: message.to_string(),
+                },
+                span.clone(),
+            ))
+        };
+        Ok(LiteralElement::Character(match escape_sequence {
             "\\'" => '\'',
             "\\\"" => '\"',
             "\\?" => '?',
@@ -43,35 +77,28 @@ impl Parse {
             "\\t" => '\t',
             "\\v" => '\x0b',
             _ => {
-                if escape_sequence == "\\0" {
-                    return Ok('\0');
-                }
-                if escape_sequence.starts_with("\\x") {
-                    let hex_string = escape_sequence.chars().skip(2).collect::<String>();
-                    let hex_value = u8::from_str_radix(&hex_string, 16)?;
-                    return Ok(char::from(hex_value));
+                if let Some(hex_string) = escape_sequence.strip_prefix("\\x") {
+                    return match u32::from_str_radix(hex_string, 16) {
+                        Ok(value) => Ok(LiteralElement::CodeUnit(value)),
+                        Err(_) => Err(error("hex escape sequence out of range")),
+                    };
                 }
                 if escape_sequence.starts_with("\\u") || escape_sequence.starts_with("\\U") {
-                    let hex_string = escape_sequence.chars().skip(2).collect::<String>();
-                    let hex_value = u32::from_str_radix(&hex_string, 16)?;
-                    let c = char::from_u32(hex_value);
-                    match c {
-                        Some(c) => return Ok(c),
-                        None => {
-                            return Err(Box::new(pest::error::Error::<Rule>::new_from_span(
-                                ErrorVariant::CustomError {
-                                    message: "invalid unicode character".to_string(),
-                                },
-                                span,
-                            )));
-                        }
+                    let hex_value = u32::from_str_radix(&escape_sequence[2..], 16)?;
+                    // C99 6.4.3, only `$`, `@` and `` ` `` may be named below U+00A0
+                    if hex_value < 0xa0 && ![0x24, 0x40, 0x60].contains(&hex_value) {
+                        return Err(error("invalid universal character name"));
+                    }
+                    match char::from_u32(hex_value) {
+                        Some(c) => c,
+                        None => return Err(error("invalid unicode character")),
                     }
+                } else {
+                    let oct_value = u32::from_str_radix(&escape_sequence[1..], 8)?;
+                    return Ok(LiteralElement::CodeUnit(oct_value));
                 }
-                let oct_string = escape_sequence.chars().skip(1).collect::<String>();
-                let oct_value = u8::from_str_radix(&oct_string, 8)?;
-                return Ok(char::from(oct_value));
             }
-        })
+        }))
     }
 
     pub fn build_constant(&mut self, pair: Pair<'_, Rule>) -> Result<Expression, Box<dyn Error>> {
@@ -90,200 +117,103 @@ impl Parse {
     ) -> Result<Expression, Box<dyn Error>> {
         let span = pair.as_span();
         let mut is_decimal_base = false;
-        let mut number: i128 = Default::default();
+        let mut number: Option<u128> = None;
+        let mut suffix = None;
         for token in pair.into_inner() {
+            let number_str = token.as_str();
             match token.as_rule() {
                 Rule::decimal_constant => {
                     is_decimal_base = true;
-                    number = match token.as_str().to_string().parse::<i128>() {
-                        Ok(number) => number,
-                        Err(_) => {
-                            return Err(Box::new(pest::error::Error::<Rule>::new_from_span(
-                                ErrorVariant::CustomError {
-                                    message: "integer constant overflow".to_string(),
-                                },
-                                span,
-                            )));
-                        }
-                    };
+                    number = number_str.parse::<u128>().ok();
                 }
                 Rule::octal_constant => {
-                    let number_str = token.as_str();
                     number = match number_str.len() {
                         0 => unreachable!(),
-                        1 => 0,
-                        _ => match i128::from_str_radix(&number_str[1..number_str.len()], 8) {
-                            Ok(number) => number,
-                            Err(_) => {
-                                return Err(Box::new(pest::error::Error::<Rule>::new_from_span(
-                                    ErrorVariant::CustomError {
-                                        message: "integer constant overflow".to_string(),
-                                    },
-                                    span,
-                                )));
-                            }
-                        },
+                        1 => Some(0),
+                        _ => u128::from_str_radix(&number_str[1..], 8).ok(),
                     }
                 }
                 Rule::hex_constant => {
-                    let number_str = token.as_str();
-                    number = match i128::from_str_radix(&number_str[2..number_str.len()], 16) {
-                        Ok(number) => number,
-                        Err(_) => {
-                            return Err(Box::new(pest::error::Error::<Rule>::new_from_span(
-                                ErrorVariant::CustomError {
-                                    message: "integer constant overflow".to_string(),
-                                },
-                                span,
-                            )));
-                        }
-                    };
+                    number = u128::from_str_radix(&number_str[2..], 16).ok();
                 }
                 Rule::binary_constant => {
-                    let number_str = token.as_str();
-                    number = match i128::from_str_radix(&number_str[2..number_str.len()], 2) {
-                        Ok(number) => number,
-                        Err(_) => {
-                            return Err(Box::new(pest::error::Error::<Rule>::new_from_span(
-                                ErrorVariant::CustomError {
-                                    message: "integer constant overflow".to_string(),
-                                },
-                                span,
-                            )));
-                        }
-                    };
+                    number = u128::from_str_radix(&number_str[2..], 2).ok();
+                }
+                Rule::integer_suffix => {
+                    suffix = Some(token.into_inner().next().unwrap().as_rule());
                 }
-                Rule::integer_suffix => match token.into_inner().next().unwrap().as_rule() {
-                    Rule::ull_ => match number.try_into() {
-                        Ok(number) => {
-                            return Ok(Expression {
-                                node: ExpressionEnum::UnsignedLongLongConstant(number),
-                                span: Span::from(span),
-                            });
-                        }
-                        Err(_) => {
-                            return Err(Box::new(pest::error::Error::<Rule>::new_from_span(
-                                ErrorVariant::CustomError {
-                                    message: "integer constant overflow".to_string(),
-                                },
-                                span,
-                            )));
-                        }
-                    },
-                    Rule::ll_ => {
-                        if let Ok(number) = number.try_into() {
-                            return Ok(Expression {
-                                node: ExpressionEnum::LongLongConstant(number),
-                                span: Span::from(span),
-                            });
-                        }
-                        if !is_decimal_base {
-                            if let Ok(number) = number.try_into() {
-                                return Ok(Expression {
-                                    node: ExpressionEnum::UnsignedLongLongConstant(number),
-                                    span: Span::from(span),
-                                });
-                            }
-                        }
-                        return Err(Box::new(pest::error::Error::<Rule>::new_from_span(
-                            ErrorVariant::CustomError {
-                                message: "integer constant overflow".to_string(),
-                            },
-                            span,
-                        )));
-                    }
-                    Rule::ul_ => {
-                        if let Ok(number) = number.try_into() {
-                            return Ok(Expression {
-                                node: ExpressionEnum::UnsignedLongConstant(number),
-                                span: Span::from(span),
-                            });
-                        }
-                        return Err(Box::new(pest::error::Error::<Rule>::new_from_span(
-                            ErrorVariant::CustomError {
-                                message: "integer constant overflow".to_string(),
-                            },
-                            span,
-                        )));
-                    }
-                    Rule::l_ => {
-                        if let Ok(number) = number.try_into() {
-                            return Ok(Expression {
-                                node: ExpressionEnum::LongConstant(number),
-                                span: Span::from(span),
-                            });
-                        }
-                        if !is_decimal_base {
-                            if let Ok(number) = number.try_into() {
-                                return Ok(Expression {
-                                    node: ExpressionEnum::UnsignedLongConstant(number),
-                                    span: Span::from(span),
-                                });
-                            }
-                        }
-                        return Err(Box::new(pest::error::Error::<Rule>::new_from_span(
-                            ErrorVariant::CustomError {
-                                message: "integer constant overflow".to_string(),
-                            },
-                            span,
-                        )));
-                    }
-                    Rule::u_ => {
-                        if let Ok(number) = number.try_into() {
-                            return Ok(Expression {
-                                node: ExpressionEnum::UnsignedIntegerConstant(number),
-                                span: Span::from(span),
-                            });
-                        }
-                        if let Ok(number) = number.try_into() {
-                            return Ok(Expression {
-                                node: ExpressionEnum::UnsignedLongConstant(number),
-                                span: Span::from(span),
-                            });
-                        }
-                        return Err(Box::new(pest::error::Error::<Rule>::new_from_span(
-                            ErrorVariant::CustomError {
-                                message: "integer constant overflow".to_string(),
-                            },
-                            span,
-                        )));
-                    }
-                    _ => unreachable!(),
-                },
                 _ => unreachable!(),
             }
         }
-        if let Ok(number) = number.try_into() {
-            return Ok(Expression {
-                node: ExpressionEnum::IntegerConstant(number),
-                span: Span::from(span),
-            });
-        }
-        if !is_decimal_base {
-            if let Ok(number) = number.try_into() {
-                return Ok(Expression {
-                    node: ExpressionEnum::UnsignedIntegerConstant(number),
-                    span: Span::from(span),
-                });
+
+        // candidate types in order, as listed in C99 6.4.4.1, with `true` for unsigned
+        use IntegerType::*;
+        let candidates: &[(bool, IntegerType)] = match (suffix, is_decimal_base) {
+            (None, true) => &[(false, Int), (false, Long), (false, LongLong)],
+            (None, false) => &[
+                (false, Int),
+                (true, Int),
+                (false, Long),
+                (true, Long),
+                (false, LongLong),
+                (true, LongLong),
+            ],
+            (Some(Rule::u_), _) => &[(true, Int), (true, Long), (true, LongLong)],
+            (Some(Rule::l_), true) => &[(false, Long), (false, LongLong)],
+            (Some(Rule::l_), false) => &[
+                (false, Long),
+                (true, Long),
+                (false, LongLong),
+                (true, LongLong),
+            ],
+            (Some(Rule::ul_), _) => &[(true, Long), (true, LongLong)],
+            (Some(Rule::ll_), true) => &[(false, LongLong)],
+            (Some(Rule::ll_), false) => &[(false, LongLong), (true, LongLong)],
+            (Some(Rule::ull_), _) => &[(true, LongLong)],
+            _ => unreachable!(),
+        };
+
+        if let Some(number) = number {
+            for (is_unsigned, integer_type) in candidates {
+                let node = match (is_unsigned, integer_type) {
+                    (false, Int) => number.try_into().ok().map(ExpressionEnum::IntegerConstant),
+                    (true, Int) => number
+                        .try_into()
+                        .ok()
+                        .map(ExpressionEnum::UnsignedIntegerConstant),
+                    (false, Long) => number.try_into().ok().map(ExpressionEnum::LongConstant),
+                    (true, Long) => number
+                        .try_into()
+                        .ok()
+                        .map(ExpressionEnum::UnsignedLongConstant),
+                    (false, LongLong) => {
+                        number.try_into().ok().map(ExpressionEnum::LongLongConstant)
+                    }
+                    (true, LongLong) => number
+                        .try_into()
+                        .ok()
+                        .map(ExpressionEnum::UnsignedLongLongConstant),
+                    _ => unreachable!(),
+                };
+                if let Some(node) = node {
+                    return Ok(Expression {
+                        node,
+                        span: Span::from(span),
+                    });
+                }
             }
         }
-        if let Ok(number) = number.try_into() {
-            return Ok(Expression {
-                node: ExpressionEnum::LongConstant(number),
-                span: Span::from(span),
-            });
-        }
-        if !is_decimal_base {
-            if let Ok(number) = number.try_into() {
-                return Ok(Expression {
-                    node: ExpressionEnum::UnsignedLongLongConstant(number),
-                    span: Span::from(span),
-                });
+
+        // gcc falls back to __int128 here, which we do not have
+        let message = match number {
+            Some(number) if u64::try_from(number).is_ok() => {
+                "integer constant is so large that it is unsigned, add a `u` suffix"
             }
-        }
+            _ => "integer constant is too large for its type",
+        };
         Err(Box::new(pest::error::Error::<Rule>::new_from_span(
             ErrorVariant::CustomError {
-                message: "integer constant overflow".to_string(),
+                message: message.to_string(),
             },
             span,
         )))
@@ -294,17 +224,30 @@ impl Parse {
         pair: Pair<'_, Rule>,
     ) -> Result<Expression, Box<dyn Error>> {
         let span = pair.as_span();
-        let token = pair.into_inner().next().unwrap();
+        let (prefix, elements) = self.build_literal_elements(pair)?;
+        let code_units = encode_literal(prefix, elements, span.clone())?;
+        let value = match (prefix, code_units.as_slice()) {
+            // a single char is converted from `char` to `int`
+            (EncodingPrefix::Char, &[code_unit]) => code_unit as u8 as i8 as i64,
+            // multi-character constants take the value as gcc does, the last char is the lowest byte
+            (EncodingPrefix::Char, code_units) if code_units.len() <= 4 => code_units
+                .iter()
+                .fold(0u32, |value, code_unit| (value << 8) | code_unit)
+                as i32
+                as i64,
+            (EncodingPrefix::Wide, &[code_unit]) => code_unit as i32 as i64,
+            (_, &[code_unit]) => code_unit as i64,
+            _ => {
+                return Err(Box::new(pest::error::Error::<Rule>::new_from_span(
+                    ErrorVariant::CustomError {
+                        message: "character constant too long for its type".to_string(),
+                    },
+                    span,
+                )));
+            }
+        };
         Ok(Expression {
-            node: match token.as_rule() {
-                Rule::char_no_escape => {
-                    ExpressionEnum::CharacterConstant(token.as_str().chars().next().unwrap())
-                }
-                Rule::escape_sequence => {
-                    ExpressionEnum::CharacterConstant(self.build_escape_sequence(token)?)
-                }
-                _ => unreachable!(),
-            },
+            node: ExpressionEnum::CharacterConstant(prefix, value),
             span: Span::from(span),
         })
     }
@@ -326,12 +269,12 @@ impl Parse {
         pair: Pair<'_, Rule>,
     ) -> Result<Expression, Box<dyn Error>> {
         let span = pair.as_span();
-        let mut number: f64 = Default::default();
+        let mut number_str = "";
         let mut is_double = true;
         for token in pair.into_inner() {
             match token.as_rule() {
                 Rule::decimal_floating_constant_no_suffix => {
-                    number = token.as_str().to_string().parse::<f64>()?;
+                    number_str = token.as_str();
                 }
                 Rule::floating_suffix => {
                     is_double = match token.into_inner().next().unwrap().as_rule() {
@@ -344,9 +287,10 @@ impl Parse {
             }
         }
         Ok(Expression {
+            // parse directly into the target type, so that the value is rounded only once
             node: match is_double {
-                false => ExpressionEnum::FloatConstant(number as f32),
-                true => ExpressionEnum::DoubleConstant(number),
+                false => ExpressionEnum::FloatConstant(number_str.parse::<f32>()?),
+                true => ExpressionEnum::DoubleConstant(number_str.parse::<f64>()?),
             },
             span: Span::from(span),
         })
@@ -354,8 +298,185 @@ impl Parse {
 
     fn build_hex_floating_constant(
         &mut self,
-        _pair: Pair<'_, Rule>,
+        pair: Pair<'_, Rule>,
     ) -> Result<Expression, Box<dyn Error>> {
-        unimplemented!()
+        let span = pair.as_span();
+        let mut number_str = "";
+        let mut is_double = true;
+        for token in pair.into_inner() {
+            match token.as_rule() {
+                Rule::hex_floating_constant_no_suffix => {
+                    number_str = token.as_str();
+                }
+                Rule::floating_suffix => {
+                    is_double = match token.into_inner().next().unwrap().as_rule() {
+                        Rule::f_ => false,
+                        Rule::l_ => true,
+                        _ => unreachable!(),
+                    };
+                }
+                _ => {}
+            }
+        }
+
+        // split "0x<significand>p<exponent>"
+        let (significand, exponent) =
+            number_str[2..].split_at(number_str.find(['p', 'P']).unwrap() - 2);
+        let exponent = match exponent[1..].parse::<i64>() {
+            Ok(exponent) => exponent,
+            // so many digits in the exponent can only mean zero, infinity or overflow
+            Err(_) if exponent[1..].starts_with('-') => i64::MIN / 2,
+            Err(_) => i64::MAX / 2,
+        };
+
+        // accumulate the significand as an integer, the value is `mantissa * 2^binary_exponent`
+        let mut mantissa: u128 = 0;
+        let mut binary_exponent = exponent;
+        let mut sticky = false;
+        let mut after_point = false;
+        for c in significand.chars() {
+            if c == '.' {
+                after_point = true;
+                continue;
+            }
+            let digit = c.to_digit(16).unwrap() as u128;
+            if mantissa >> 124 == 0 {
+                mantissa = (mantissa << 4) | digit;
+                if after_point {
+                    binary_exponent -= 4;
+                }
+            } else {
+                // no more room, the digit only matters for rounding
+                sticky |= digit != 0;
+                if !after_point {
+                    binary_exponent += 4;
+                }
+            }
+        }
+
+        let overflow = || {
+            Box::new(pest::error::Error::<Rule>::new_from_span(
+                ErrorVariant::CustomError {
+                    message: "floating constant overflow".to_string(),
+                },
+                span.clone(),
+            ))
+        };
+        Ok(Expression {
+            node: match is_double {
+                false => ExpressionEnum::FloatConstant(f32::from_bits(
+                    round_to_binary_float(mantissa, binary_exponent, sticky, 24, 127)
+                        .ok_or_else(overflow)? as u32,
+                )),
+                true => ExpressionEnum::DoubleConstant(f64::from_bits(
+                    round_to_binary_float(mantissa, binary_exponent, sticky, 53, 1023)
+                        .ok_or_else(overflow)?,
+                )),
+            },
+            span: Span::from(span),
+        })
+    }
+}
+
+/// An element of a character constant or string literal, before being encoded.
+enum LiteralElement {
+    /// a source character or universal character name, encoded according to the prefix
+    Character(char),
+    /// an octal or hexadecimal escape sequence, which specifies a code unit directly
+    CodeUnit(u32),
+}
+
+/// Encode the elements of a character constant or string literal into code units:
+/// UTF-8 for `char`, UTF-16 for `char16_t` and UTF-32 for `char32_t` and `wchar_t`.
+fn encode_literal(
+    prefix: EncodingPrefix,
+    elements: Vec<LiteralElement>,
+    span: pest::Span<'_>,
+) -> Result<Vec<u32>, Box<dyn Error>> {
+    let max_code_unit = match prefix {
+        EncodingPrefix::Char | EncodingPrefix::Utf8 => u8::MAX as u32,
+        EncodingPrefix::Utf16 => u16::MAX as u32,
+        EncodingPrefix::Wide | EncodingPrefix::Utf32 => u32::MAX,
+    };
+    let mut code_units = Vec::with_capacity(elements.len());
+    for element in elements {
+        match element {
+            LiteralElement::CodeUnit(code_unit) if code_unit > max_code_unit => {
+                return Err(Box::new(pest::error::Error::<Rule>::new_from_span(
+                    ErrorVariant::CustomError {
+                        message: "escape sequence out of range".to_string(),
+                    },
+                    span,
+                )));
+            }
+            LiteralElement::CodeUnit(code_unit) => code_units.push(code_unit),
+            LiteralElement::Character(c) => match prefix {
+                EncodingPrefix::Char | EncodingPrefix::Utf8 => {
+                    code_units.extend(c.encode_utf8(&mut [0; 4]).bytes().map(u32::from))
+                }
+                EncodingPrefix::Utf16 => {
+                    code_units.extend(c.encode_utf16(&mut [0; 2]).iter().map(|&u| u as u32))
+                }
+                EncodingPrefix::Wide | EncodingPrefix::Utf32 => code_units.push(c as u32),
+            },
+        }
+    }
+    Ok(code_units)
+}
+
+/// Round `mantissa * 2^exponent` (plus a bit of something if `sticky` is set) to the nearest
+/// IEEE 754 binary float with `precision` significant bits, ties to even.
+/// Returns the bit pattern of the result, or None if it is too large to be represented.
+fn round_to_binary_float(
+    mantissa: u128,
+    exponent: i64,
+    sticky: bool,
+    precision: u32,
+    bias: i64,
+) -> Option<u64> {
+    if mantissa == 0 {
+        return Some(0);
+    }
+    let length = (128 - mantissa.leading_zeros()) as i64;
+    // the value lies in [2^msb_exponent, 2^(msb_exponent + 1))
+    let msb_exponent = length - 1 + exponent;
+    let min_exponent = 1 - bias;
+    let max_exponent = bias;
+
+    // number of significant bits that can be kept, fewer for subnormal numbers
+    let kept_bits = match msb_exponent < min_exponent {
+        true => precision as i64 - (min_exponent - msb_exponent),
+        false => precision as i64,
+    };
+    if kept_bits < 0 {
+        return Some(0);
+    }
+
+    let shift = length - kept_bits;
+    let mut kept = if shift > 0 {
+        let kept = mantissa.checked_shr(shift as u32).unwrap_or(0);
+        let remainder = mantissa & ((1u128 << (shift - 1) << 1).wrapping_sub(1));
+        let half = 1u128 << (shift - 1);
+        let round_up = remainder > half || (remainder == half && (sticky || kept & 1 == 1));
+        kept + round_up as u128
+    } else {
+        // exact, any sticky digits are far below half an ulp
+        mantissa << -shift
+    };
+
+    if msb_exponent < min_exponent {
+        // subnormal, a carry out of the kept bits turns it into the smallest normal number
+        return Some(kept as u64);
+    }
+
+    let mut msb_exponent = msb_exponent;
+    if kept >> precision != 0 {
+        kept >>= 1;
+        msb_exponent += 1;
+    }
+    if msb_exponent > max_exponent {
+        return None;
     }
+    let fraction = kept as u64 - (1u64 << (precision - 1));
+    Some((((msb_exponent + bias) as u64) << (precision - 1)) | fraction)
 }
@@ -14,7 +14,12 @@ use super::ast::*;
 struct CC99Parser;
 
 #[derive(Default)]
-pub struct Parse {}
+pub struct Parse {
+    /// the maximum alignment of struct members, set by `#pragma pack`
+    pack: Option<u32>,
+    /// the alignments saved by `#pragma pack(push)`
+    pack_stack: Vec<Option<u32>>,
+}
 
 impl Parse {
     pub fn new() -> Parse {
@@ -35,6 +40,9 @@ impl Parse {
                 Rule::function_definition => {
                     self.build_function_definition(&mut ast, token)?;
                 }
+                Rule::pragma_pack => {
+                    self.build_pragma_pack(token);
+                }
                 Rule::EOI => {}
                 _ => unreachable!(),
             }
@@ -58,6 +66,8 @@ mod tests {
                         Type {
                             function_specifier: vec![],
                             storage_class_specifier: StorageClassSpecifier::Static,
+                            thread_local: false,
+                            attributes: vec![],
                             basic_type: BasicType {
                                 qualifier: vec![TypeQualifier::Const],
                                 base_type: BaseType::Pointer(Box::new(BasicType {
@@ -76,6 +86,8 @@ mod tests {
                         Type {
                             function_specifier: vec![],
                             storage_class_specifier: StorageClassSpecifier::Static,
+                            thread_local: false,
+                            attributes: vec![],
                             basic_type: BasicType {
                                 qualifier: vec![TypeQualifier::Const],
                                 base_type: Default::default(),
@@ -100,6 +112,8 @@ mod tests {
                     Type {
                         function_specifier: vec!(FunctionSpecifier::Inline),
                         storage_class_specifier: StorageClassSpecifier::Static,
+                        thread_local: false,
+                        attributes: vec![],
                         basic_type: BasicType {
                             qualifier: vec![],
                             base_type: BaseType::Function(
@@ -143,6 +157,8 @@ mod tests {
                         Type {
                             function_specifier: vec!(),
                             storage_class_specifier: StorageClassSpecifier::Auto,
+                            thread_local: false,
+                            attributes: vec![],
                             basic_type: BasicType {
                                 qualifier: vec![],
                                 base_type: BaseType::Struct(
@@ -150,6 +166,7 @@ mod tests {
                                     Some(vec![
                                         StructMember {
                                             member_name: "y".to_string(),
+                                            attributes: vec![],
                                             member_type: BasicType {
                                                 qualifier: vec![TypeQualifier::Const],
                                                 base_type: Default::default(),
@@ -157,6 +174,7 @@ mod tests {
                                         },
                                         StructMember {
                                             member_name: "z".to_string(),
+                                            attributes: vec![],
                                             member_type: BasicType {
                                                 qualifier: vec![],
                                                 base_type: BaseType::Float,
@@ -176,6 +194,8 @@ mod tests {
                         Type {
                             function_specifier: vec!(),
                             storage_class_specifier: StorageClassSpecifier::Typedef,
+                            thread_local: false,
+                            attributes: vec![],
                             basic_type: BasicType {
                                 qualifier: vec![],
                                 base_type: BaseType::Struct(Some("Xxx".to_string()), None),
@@ -199,6 +219,7 @@ mod tests {
                 node: DeclarationEnum::FunctionDefinition(
                     vec![FunctionSpecifier::Inline],
                     StorageClassSpecifier::Auto,
+                    vec![],
                     Box::new(BasicType {
                         qualifier: vec![TypeQualifier::Const],
                         base_type: BaseType::Pointer(Box::new(BasicType {
@@ -244,6 +265,8 @@ mod tests {
                     Type {
                         function_specifier: vec![],
                         storage_class_specifier: StorageClassSpecifier::Auto,
+                        thread_local: false,
+                        attributes: vec![],
                         basic_type: BasicType {
                             qualifier: vec![],
                             base_type: BaseType::Array(
@@ -275,4 +298,286 @@ mod tests {
             },]))
         );
     }
+
+    #[test]
+    fn hex_floating_constant() {
+        let code = r#"
+double a = 0x1.8p3;
+double b = 0x.8p1;
+double c = 0x1p-1074;
+double d = 0x1.fffffffffffff8p0;
+double e = 0x10.8P-1l;
+float f = 0x1.000001p0f;
+float g = 0x1.000003p0F;
+float h = 0x1p-149f;
+"#;
+        let expected = vec![
+            ExpressionEnum::DoubleConstant(12.0),
+            ExpressionEnum::DoubleConstant(1.0),
+            ExpressionEnum::DoubleConstant(f64::from_bits(1)),
+            ExpressionEnum::DoubleConstant(2.0),
+            ExpressionEnum::DoubleConstant(8.25),
+            ExpressionEnum::FloatConstant(1.0),
+            ExpressionEnum::FloatConstant(1.0 + 2.0 / 8388608.0),
+            ExpressionEnum::FloatConstant(f32::from_bits(1)),
+        ];
+        let AST::GlobalDeclaration(declarations) = *Parse::new().parse(code).unwrap();
+        let initializers = declarations
+            .into_iter()
+            .map(|declaration| match declaration.node {
+                DeclarationEnum::Declaration(_, _, Some(initializer)) => initializer.node,
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(expected, initializers);
+
+        assert!(Parse::new().parse("float x = 0x1p128f;").is_err());
+        assert!(Parse::new().parse("double x = 0x1.8;").is_err());
+    }
+
+    #[test]
+    fn integer_constant_type() {
+        let code = r#"
+long a = 2147483647;
+long b = 2147483648;
+long c = 0xFFFFFFFF;
+long d = 0x100000000;
+long e = 0xFFFFFFFFFFFFFFFF;
+long f = 037777777777;
+long g = 10u;
+long h = 4294967296u;
+long i = 10l;
+long j = 0xFFFFFFFFFFFFFFFFl;
+long k = 10ul;
+long l = 10ll;
+long m = 0x8000000000000000LL;
+long n = 10uLL;
+long o = 0b101;
+"#;
+        let expected = vec![
+            ExpressionEnum::IntegerConstant(2147483647),
+            ExpressionEnum::LongConstant(2147483648),
+            ExpressionEnum::UnsignedIntegerConstant(0xFFFFFFFF),
+            ExpressionEnum::LongConstant(0x100000000),
+            ExpressionEnum::UnsignedLongConstant(0xFFFFFFFFFFFFFFFF),
+            ExpressionEnum::UnsignedIntegerConstant(0xFFFFFFFF),
+            ExpressionEnum::UnsignedIntegerConstant(10),
+            ExpressionEnum::UnsignedLongConstant(4294967296),
+            ExpressionEnum::LongConstant(10),
+            ExpressionEnum::UnsignedLongConstant(0xFFFFFFFFFFFFFFFF),
+            ExpressionEnum::UnsignedLongConstant(10),
+            ExpressionEnum::LongLongConstant(10),
+            ExpressionEnum::UnsignedLongLongConstant(0x8000000000000000),
+            ExpressionEnum::UnsignedLongLongConstant(10),
+            ExpressionEnum::IntegerConstant(5),
+        ];
+        let AST::GlobalDeclaration(declarations) = *Parse::new().parse(code).unwrap();
+        let initializers = declarations
+            .into_iter()
+            .map(|declaration| match declaration.node {
+                DeclarationEnum::Declaration(_, _, Some(initializer)) => initializer.node,
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(expected, initializers);
+
+        assert!(Parse::new().parse("long x = 9223372036854775808;").is_err());
+        assert!(Parse::new().parse("long x = 0x10000000000000000;").is_err());
+        assert!(Parse::new().parse("long x = 10lL;").is_err());
+    }
+
+    #[test]
+    fn prefixed_literal() {
+        let code = r#"
+int a = 'a';
+int b = '\xff';
+int c = 'ab';
+int d = L'\u00e9';
+int e = u'\u00e9';
+int f = U'\U0001F600';
+char *g = "\u00e9\101";
+char *h = u8"\xff";
+char *i = u"\U0001F600";
+char *j = U"\U0001F600";
+char *k = L"\x100";
+"#;
+        let expected = vec![
+            ExpressionEnum::CharacterConstant(EncodingPrefix::Char, 97),
+            ExpressionEnum::CharacterConstant(EncodingPrefix::Char, -1),
+            ExpressionEnum::CharacterConstant(EncodingPrefix::Char, 0x6162),
+            ExpressionEnum::CharacterConstant(EncodingPrefix::Wide, 0xe9),
+            ExpressionEnum::CharacterConstant(EncodingPrefix::Utf16, 0xe9),
+            ExpressionEnum::CharacterConstant(EncodingPrefix::Utf32, 0x1f600),
+            ExpressionEnum::StringLiteral(EncodingPrefix::Char, vec![0xc3, 0xa9, 0x41]),
+            ExpressionEnum::StringLiteral(EncodingPrefix::Utf8, vec![0xff]),
+            ExpressionEnum::StringLiteral(EncodingPrefix::Utf16, vec![0xd83d, 0xde00]),
+            ExpressionEnum::StringLiteral(EncodingPrefix::Utf32, vec![0x1f600]),
+            ExpressionEnum::StringLiteral(EncodingPrefix::Wide, vec![0x100]),
+        ];
+        let AST::GlobalDeclaration(declarations) = *Parse::new().parse(code).unwrap();
+        let initializers = declarations
+            .into_iter()
+            .map(|declaration| match declaration.node {
+                DeclarationEnum::Declaration(_, _, Some(initializer)) => initializer.node,
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(expected, initializers);
+
+        assert!(Parse::new().parse("char *x = \"\\x100\";").is_err());
+        assert!(Parse::new().parse("int x = 'abcde';").is_err());
+        assert!(Parse::new().parse("int x = u'\\U0001F600';").is_err());
+        assert!(Parse::new().parse("char *x = \"\\u0041\";").is_err());
+    }
+
+    #[test]
+    fn pragma_pack() {
+        let code = r#"
+#pragma pack(push, 2)
+struct A { char c; };
+#pragma pack(pop)
+struct B { char c; };
+#pragma pack(4)
+union C { char c; };
+#pragma pack()
+struct D { char c; };
+"#;
+        let AST::GlobalDeclaration(declarations) = *Parse::new().parse(code).unwrap();
+        let packs = declarations
+            .iter()
+            .map(|declaration| match declaration.node {
+                DeclarationEnum::Declaration(ref type_info, _, _) => {
+                    match type_info.basic_type.base_type {
+                        BaseType::Struct(_, Some(ref members))
+                        | BaseType::Union(_, Some(ref members)) => members[0]
+                            .attributes
+                            .iter()
+                            .map(|attribute| match attribute.arguments[0].node {
+                                ExpressionEnum::IntegerConstant(pack) => pack,
+                                _ => unreachable!(),
+                            })
+                            .collect::<Vec<_>>(),
+                        _ => unreachable!(),
+                    }
+                }
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(packs, vec![vec![2], vec![], vec![4], vec![]]);
+    }
+
+    #[test]
+    fn attribute() {
+        let code = r#"
+__attribute__((noinline, __cold__)) static int f(const char *, ...) __attribute((format(printf, 1, 2)));
+struct __attribute__((packed)) S { char c; int x __attribute__((aligned(8))); } __attribute__((aligned));
+"#;
+        let AST::GlobalDeclaration(declarations) = *Parse::new().parse(code).unwrap();
+        let attributes = |type_info: &Type| {
+            type_info
+                .attributes
+                .iter()
+                .map(|attribute| (attribute.name.to_owned(), attribute.arguments.len()))
+                .collect::<Vec<_>>()
+        };
+        let names = |names: &[(&str, usize)]| {
+            names
+                .iter()
+                .map(|&(name, arguments)| (name.to_string(), arguments))
+                .collect::<Vec<_>>()
+        };
+
+        match declarations[0].node {
+            DeclarationEnum::Declaration(ref type_info, _, _) => assert_eq!(
+                attributes(type_info),
+                names(&[("noinline", 0), ("cold", 0), ("format", 3)])
+            ),
+            _ => unreachable!(),
+        }
+        match declarations[1].node {
+            DeclarationEnum::Declaration(ref type_info, _, _) => {
+                match type_info.basic_type.base_type {
+                    BaseType::Struct(_, Some(ref members)) => {
+                        let members = members
+                            .iter()
+                            .map(|member| {
+                                member
+                                    .attributes
+                                    .iter()
+                                    .map(|attribute| attribute.name.as_str())
+                                    .collect::<Vec<_>>()
+                            })
+                            .collect::<Vec<_>>();
+                        assert_eq!(
+                            members,
+                            vec![vec!["packed", "aligned"], vec!["aligned", "packed"]]
+                        );
+                    }
+                    _ => unreachable!(),
+                }
+            }
+            _ => unreachable!(),
+        }
+
+        assert!(Parse::new()
+            .parse("int __attribute__((aligned(8)) x;")
+            .is_err());
+        assert!(Parse::new().parse("int x __attribute__(aligned);").is_err());
+    }
+
+    #[test]
+    fn asm() {
+        let code = r#"
+void f(int x) {
+    asm("nop");
+    __asm__ __volatile__("movl %[in], %0" "\n\t" : "=r"(x) : [in] "r"(x) : "memory");
+    asm("" ::: "cc");
+    asm goto("jmp %l0" : : : : out);
+out:
+    return;
+}
+"#;
+        let AST::GlobalDeclaration(declarations) = *Parse::new().parse(code).unwrap();
+        let statements = match declarations[0].node {
+            DeclarationEnum::FunctionDefinition(.., ref body) => match body.node {
+                StatementEnum::Compound(ref items) => items
+                    .iter()
+                    .filter_map(|item| match item.node {
+                        StatementOrDeclarationEnum::Statement(Statement {
+                            node: StatementEnum::Asm(ref asm),
+                            ..
+                        }) => Some(asm.to_owned()),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>(),
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        };
+        let summary = statements
+            .iter()
+            .map(|asm| {
+                (
+                    asm.template.as_str(),
+                    asm.is_basic,
+                    asm.is_volatile,
+                    asm.outputs.len(),
+                    asm.inputs.len(),
+                    asm.clobbers.len(),
+                    asm.labels.len(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                ("nop", true, true, 0, 0, 0, 0),
+                ("movl %[in], %0\n\t", false, true, 1, 1, 1, 0),
+                ("", false, true, 0, 0, 1, 0),
+                ("jmp %l0", false, true, 0, 0, 0, 1),
+            ]
+        );
+        assert_eq!(statements[1].inputs[0].name, Some("in".to_string()));
+        assert!(Parse::new().parse("int asm;").is_err());
+    }
 }
@@ -1,3 +1,4 @@
+use pest::error::ErrorVariant;
 use pest::iterators::Pair;
 
 use super::*;
@@ -8,6 +9,7 @@ impl Parse {
         match token.as_rule() {
             Rule::labeled_statement => self.build_labeled_statement(token),
             Rule::case_statement => self.build_case_statement(token),
+            Rule::asm_statement => self.build_asm_statement(token),
             Rule::expression_statement => self.build_expression_statement(token),
             Rule::compound_statement => self.build_compound_statement(token),
             Rule::selection_statement => self.build_selection_statement(token),
@@ -129,7 +131,7 @@ impl Parse {
                                     span: Span::from(token_span.clone()),
                                 });
                             }
-                            DeclarationEnum::FunctionDefinition(_, _, _, _, _, _, _) => {
+                            DeclarationEnum::FunctionDefinition(_, _, _, _, _, _, _, _) => {
                                 unreachable!();
                             }
                         }
@@ -411,4 +413,123 @@ impl Parse {
             span: Span::from(span),
         })
     }
+
+    fn build_asm_statement(&mut self, pair: Pair<'_, Rule>) -> Result<Statement, Box<dyn Error>> {
+        let span = pair.as_span();
+        let mut asm = AsmStatement {
+            template: Default::default(),
+            template_span: Default::default(),
+            is_basic: true,
+            is_volatile: false,
+            outputs: Default::default(),
+            inputs: Default::default(),
+            clobbers: Default::default(),
+            labels: Default::default(),
+        };
+        let mut is_goto = false;
+        for token in pair.into_inner() {
+            match token.as_rule() {
+                Rule::asm_ => {}
+                Rule::asm_qualifier => match token.as_str() {
+                    "volatile" | "__volatile__" => asm.is_volatile = true,
+                    "goto" => is_goto = true,
+                    _ => {}
+                },
+                Rule::asm_template => {
+                    asm.template_span = Span::from(token.as_span());
+                    for sub_token in token.into_inner() {
+                        asm.template.push_str(&self.build_asm_string(sub_token)?);
+                    }
+                }
+                Rule::asm_outputs | Rule::asm_inputs => {
+                    let operands = match token.as_rule() {
+                        Rule::asm_outputs => &mut asm.outputs,
+                        _ => &mut asm.inputs,
+                    };
+                    for sub_token in token.into_inner() {
+                        operands.push(self.build_asm_operand(sub_token)?);
+                    }
+                }
+                Rule::asm_clobbers => {
+                    for sub_token in token.into_inner() {
+                        let clobber_span = Span::from(sub_token.as_span());
+                        asm.clobbers
+                            .push((self.build_asm_string(sub_token)?, clobber_span));
+                    }
+                }
+                Rule::asm_labels => {
+                    if !is_goto {
+                        return Err(Box::new(pest::error::Error::<Rule>::new_from_span(
+                            ErrorVariant::CustomError {
+                                message: "labels are only allowed in asm goto".to_string(),
+                            },
+                            token.as_span(),
+                        )));
+                    }
+                    for sub_token in token.into_inner() {
+                        asm.labels.push(sub_token.as_str().to_owned());
+                    }
+                }
+                _ => unreachable!(),
+            }
+        }
+        // a colon after the template makes it extended assembly, even without operands
+        asm.is_basic = !span.as_str()[asm.template_span.end - span.start()..].contains(':');
+        // like GCC, assembly without outputs is assumed to have side effects
+        if asm.outputs.is_empty() || is_goto {
+            asm.is_volatile = true;
+        }
+        Ok(Statement {
+            node: StatementEnum::Asm(Box::new(asm)),
+            span: Span::from(span),
+        })
+    }
+
+    fn build_asm_operand(&mut self, pair: Pair<'_, Rule>) -> Result<AsmOperand, Box<dyn Error>> {
+        let span = Span::from(pair.as_span());
+        let mut name = None;
+        let mut constraint: String = Default::default();
+        let mut expression: Expression = Default::default();
+        for token in pair.into_inner() {
+            match token.as_rule() {
+                Rule::identifier => {
+                    name = Some(token.as_str().to_owned());
+                }
+                Rule::string_literal => {
+                    constraint = self.build_asm_string(token)?;
+                }
+                Rule::expression => {
+                    expression = self.build_expression(token)?;
+                }
+                _ => unreachable!(),
+            }
+        }
+        Ok(AsmOperand {
+            name,
+            constraint,
+            expression,
+            span,
+        })
+    }
+
+    /// The text of a string literal in inline assembly, which has no encoding prefix.
+    fn build_asm_string(&mut self, pair: Pair<'_, Rule>) -> Result<String, Box<dyn Error>> {
+        let span = pair.as_span();
+        match self.build_string_literal(pair)?.node {
+            ExpressionEnum::StringLiteral(EncodingPrefix::Char, code_units) => {
+                Ok(String::from_utf8(
+                    code_units
+                        .iter()
+                        .map(|&code_unit| code_unit as u8)
+                        .collect(),
+                )?)
+            }
+            _ => Err(Box::new(pest::error::Error::<Rule>::new_from_span(
+                ErrorVariant::CustomError {
+                    message: "encoding prefix is not allowed in asm".to_string(),
+                },
+                span,
+            ))),
+        }
+    }
 }
@@ -0,0 +1,394 @@
+use super::error::SpanError;
+use super::phase4::Rule;
+use pest::iterators::Pair;
+use pest::Span;
+use std::error::Error;
+
+/// An integer in a preprocessor expression, where all signed types act as `intmax_t` and all
+/// unsigned types as `uintmax_t`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Value {
+    Signed(i64),
+    Unsigned(u64),
+}
+
+impl Value {
+    fn is_nonzero(self) -> bool {
+        match self {
+            Value::Signed(value) => value != 0,
+            Value::Unsigned(value) => value != 0,
+        }
+    }
+
+    fn from_bool(value: bool) -> Value {
+        Value::Signed(value as i64)
+    }
+}
+
+/// Evaluate the condition of `#if` or `#elif`, whose macros are already expanded and whose
+/// `defined` operators are already replaced. Errors point to `span`, the condition in the
+/// directive.
+pub fn evaluate_condition<'a>(
+    tokens: Vec<Pair<'a, Rule>>,
+    span: Span<'a>,
+) -> Result<bool, Box<dyn Error>> {
+    let mut evaluator = Evaluator {
+        tokens,
+        position: 0,
+        span,
+    };
+    if evaluator.tokens.is_empty() {
+        return Err(evaluator.error("#if with no expression".to_string()));
+    }
+    let value = evaluator.evaluate_expression(true)?;
+    match evaluator.peek() {
+        Some(token) => Err(evaluator.error(format!("missing binary operator before `{}`", token))),
+        None => Ok(value.is_nonzero()),
+    }
+}
+
+struct Evaluator<'a> {
+    /// the tokens of the condition, without whitespaces
+    tokens: Vec<Pair<'a, Rule>>,
+    position: usize,
+    span: Span<'a>,
+}
+
+impl<'a> Evaluator<'a> {
+    fn error(&self, message: String) -> Box<dyn Error> {
+        SpanError::at(message, self.span.as_str())
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.position).map(|token| token.as_str())
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), Box<dyn Error>> {
+        match self.peek() {
+            Some(token) if token == expected => {
+                self.position += 1;
+                Ok(())
+            }
+            Some(token) => Err(self.error(format!("expected `{}`, found `{}`", expected, token))),
+            None => Err(self.error(format!(
+                "expected `{}` at the end of the expression",
+                expected
+            ))),
+        }
+    }
+
+    /// Operands which are not `evaluated`, like the right operand of `0 &&`, may divide by zero.
+    fn evaluate_expression(&mut self, evaluated: bool) -> Result<Value, Box<dyn Error>> {
+        let mut value = self.evaluate_conditional(evaluated)?;
+        while self.peek() == Some(",") {
+            self.position += 1;
+            value = self.evaluate_conditional(evaluated)?;
+        }
+        Ok(value)
+    }
+
+    fn evaluate_conditional(&mut self, evaluated: bool) -> Result<Value, Box<dyn Error>> {
+        let condition = self.evaluate_binary(1, evaluated)?;
+        if self.peek() != Some("?") {
+            return Ok(condition);
+        }
+        self.position += 1;
+        let then_value = self.evaluate_expression(evaluated && condition.is_nonzero())?;
+        self.expect(":")?;
+        let else_value = self.evaluate_conditional(evaluated && !condition.is_nonzero())?;
+        // the result has the common type of both operands
+        let (then_value, else_value) = convert(then_value, else_value);
+        Ok(match condition.is_nonzero() {
+            true => then_value,
+            false => else_value,
+        })
+    }
+
+    /// Binary operators are parsed by precedence climbing, from `||` at 1 to `*` at 10.
+    fn evaluate_binary(
+        &mut self,
+        precedence: u8,
+        evaluated: bool,
+    ) -> Result<Value, Box<dyn Error>> {
+        let mut lhs = self.evaluate_unary(evaluated)?;
+        while let Some(operator) = self.peek() {
+            let operator_precedence = match binary_precedence(operator) {
+                Some(operator_precedence) if operator_precedence >= precedence => {
+                    operator_precedence
+                }
+                _ => break,
+            };
+            self.position += 1;
+            lhs = match operator {
+                "&&" => {
+                    let rhs = self
+                        .evaluate_binary(operator_precedence + 1, evaluated && lhs.is_nonzero())?;
+                    Value::from_bool(lhs.is_nonzero() && rhs.is_nonzero())
+                }
+                "||" => {
+                    let rhs = self
+                        .evaluate_binary(operator_precedence + 1, evaluated && !lhs.is_nonzero())?;
+                    Value::from_bool(lhs.is_nonzero() || rhs.is_nonzero())
+                }
+                _ => {
+                    let rhs = self.evaluate_binary(operator_precedence + 1, evaluated)?;
+                    self.apply_binary(operator, lhs, rhs, evaluated)?
+                }
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn apply_binary(
+        &self,
+        operator: &str,
+        lhs: Value,
+        rhs: Value,
+        evaluated: bool,
+    ) -> Result<Value, Box<dyn Error>> {
+        // shifts keep the type of the left operand
+        if let "<<" | ">>" = operator {
+            let amount = match rhs {
+                Value::Signed(amount) => amount,
+                Value::Unsigned(amount) => amount.min(64) as i64,
+            };
+            // shifting by a negative amount shifts the other way, as GCC does
+            let left = (operator == "<<") == (amount >= 0);
+            let amount = amount.unsigned_abs().min(64) as u32;
+            return Ok(match (lhs, left) {
+                (Value::Signed(value), true) => {
+                    Value::Signed(value.checked_shl(amount).unwrap_or(0))
+                }
+                (Value::Signed(value), false) => {
+                    Value::Signed(value.checked_shr(amount).unwrap_or(value >> 63))
+                }
+                (Value::Unsigned(value), true) => {
+                    Value::Unsigned(value.checked_shl(amount).unwrap_or(0))
+                }
+                (Value::Unsigned(value), false) => {
+                    Value::Unsigned(value.checked_shr(amount).unwrap_or(0))
+                }
+            });
+        }
+
+        let (lhs, rhs) = convert(lhs, rhs);
+        if let ("/" | "%", Value::Signed(0) | Value::Unsigned(0)) = (operator, rhs) {
+            return match evaluated {
+                true => Err(self.error("division by zero in #if".to_string())),
+                false => Ok(Value::Signed(0)),
+            };
+        }
+        Ok(match (lhs, rhs) {
+            (Value::Signed(lhs), Value::Signed(rhs)) => match operator {
+                "*" => Value::Signed(lhs.wrapping_mul(rhs)),
+                "/" => Value::Signed(lhs.wrapping_div(rhs)),
+                "%" => Value::Signed(lhs.wrapping_rem(rhs)),
+                "+" => Value::Signed(lhs.wrapping_add(rhs)),
+                "-" => Value::Signed(lhs.wrapping_sub(rhs)),
+                "&" => Value::Signed(lhs & rhs),
+                "^" => Value::Signed(lhs ^ rhs),
+                "|" => Value::Signed(lhs | rhs),
+                _ => Value::from_bool(compare(operator, lhs, rhs)),
+            },
+            (Value::Unsigned(lhs), Value::Unsigned(rhs)) => match operator {
+                "*" => Value::Unsigned(lhs.wrapping_mul(rhs)),
+                "/" => Value::Unsigned(lhs / rhs),
+                "%" => Value::Unsigned(lhs % rhs),
+                "+" => Value::Unsigned(lhs.wrapping_add(rhs)),
+                "-" => Value::Unsigned(lhs.wrapping_sub(rhs)),
+                "&" => Value::Unsigned(lhs & rhs),
+                "^" => Value::Unsigned(lhs ^ rhs),
+                "|" => Value::Unsigned(lhs | rhs),
+                _ => Value::from_bool(compare(operator, lhs, rhs)),
+            },
+            _ => unreachable!(),
+        })
+    }
+
+    fn evaluate_unary(&mut self, evaluated: bool) -> Result<Value, Box<dyn Error>> {
+        let token = match self.tokens.get(self.position) {
+            Some(token) => token.clone(),
+            None => {
+                return Err(
+                    self.error("expected an operand at the end of the expression".to_string())
+                )
+            }
+        };
+        self.position += 1;
+        match token.as_rule() {
+            Rule::punctuator => match token.as_str() {
+                "(" => {
+                    let value = self.evaluate_expression(evaluated)?;
+                    self.expect(")")?;
+                    Ok(value)
+                }
+                "+" => self.evaluate_unary(evaluated),
+                "-" => Ok(match self.evaluate_unary(evaluated)? {
+                    Value::Signed(value) => Value::Signed(value.wrapping_neg()),
+                    Value::Unsigned(value) => Value::Unsigned(value.wrapping_neg()),
+                }),
+                "~" => Ok(match self.evaluate_unary(evaluated)? {
+                    Value::Signed(value) => Value::Signed(!value),
+                    Value::Unsigned(value) => Value::Unsigned(!value),
+                }),
+                "!" => Ok(Value::from_bool(
+                    !self.evaluate_unary(evaluated)?.is_nonzero(),
+                )),
+                operator => Err(self.error(format!("operator `{}` has no left operand", operator))),
+            },
+            // identifiers which are not macros, and keywords, are 0
+            Rule::identifier | Rule::keyword => Ok(Value::Signed(0)),
+            Rule::constant => {
+                let constant = token.into_inner().next().unwrap();
+                match constant.as_rule() {
+                    Rule::integer_constant => self.integer_constant(constant),
+                    Rule::character_constant => self.character_constant(constant),
+                    _ => {
+                        Err(self.error("floating constant in preprocessor expression".to_string()))
+                    }
+                }
+            }
+            _ => Err(self.error(format!(
+                "token `{}` is not valid in preprocessor expressions",
+                token.as_str()
+            ))),
+        }
+    }
+
+    fn integer_constant(&self, pair: Pair<'a, Rule>) -> Result<Value, Box<dyn Error>> {
+        let mut value = 0;
+        let mut is_unsigned = false;
+        for token in pair.into_inner() {
+            let digits = token.as_str();
+            let parsed = match token.as_rule() {
+                Rule::decimal_constant => digits.parse::<u64>(),
+                Rule::octal_constant => u64::from_str_radix(digits, 8),
+                Rule::hex_constant => u64::from_str_radix(&digits[2..], 16),
+                Rule::binary_constant => u64::from_str_radix(&digits[2..], 2),
+                Rule::integer_suffix => {
+                    is_unsigned = matches!(
+                        token.into_inner().next().unwrap().as_rule(),
+                        Rule::u_ | Rule::ul_ | Rule::ull_
+                    );
+                    continue;
+                }
+                _ => unreachable!(),
+            };
+            value = match parsed {
+                Ok(value) => value,
+                // `0x` has no digits
+                Err(_) if digits.len() == 2 => {
+                    return Err(self.error(format!("invalid integer constant `{}`", digits)))
+                }
+                Err(_) => return Err(self.error("integer constant is too large".to_string())),
+            };
+        }
+        // constants too large for `intmax_t` are unsigned
+        match is_unsigned || value > i64::MAX as u64 {
+            true => Ok(Value::Unsigned(value)),
+            false => Ok(Value::Signed(value as i64)),
+        }
+    }
+
+    /// Character constants have the value they have in the program.
+    fn character_constant(&self, pair: Pair<'a, Rule>) -> Result<Value, Box<dyn Error>> {
+        let mut prefix = None;
+        let mut code_units: Vec<u32> = vec![];
+        for token in pair.into_inner() {
+            match token.as_rule() {
+                Rule::character_prefix => prefix = Some(token.as_str()),
+                Rule::char_no_escape => {
+                    let c = token.as_str().chars().next().unwrap();
+                    match prefix {
+                        // a plain char constant is encoded in UTF-8
+                        None => code_units.extend(c.to_string().bytes().map(u32::from)),
+                        Some(_) => code_units.push(c as u32),
+                    }
+                }
+                Rule::escape_sequence => code_units.push(self.escape_sequence(token.as_str())?),
+                _ => unreachable!(),
+            }
+        }
+        match (prefix, code_units.as_slice()) {
+            (None, &[code_unit]) => Ok(Value::Signed(code_unit as u8 as i8 as i64)),
+            // the last char is the lowest byte, as GCC does
+            (None, code_units) if code_units.len() <= 4 => Ok(Value::Signed(
+                code_units
+                    .iter()
+                    .fold(0u32, |value, code_unit| (value << 8) | (code_unit & 0xff))
+                    as i32 as i64,
+            )),
+            (Some("L"), &[code_unit]) => Ok(Value::Signed(code_unit as i32 as i64)),
+            (Some("u"), &[code_unit]) => Ok(Value::Unsigned(code_unit as u16 as u64)),
+            (Some(_), &[code_unit]) => Ok(Value::Unsigned(code_unit as u64)),
+            _ => Err(self.error("character constant too long for its type".to_string())),
+        }
+    }
+
+    fn escape_sequence(&self, escape_sequence: &str) -> Result<u32, Box<dyn Error>> {
+        Ok(match escape_sequence {
+            "\\'" => '\'' as u32,
+            "\\\"" => '"' as u32,
+            "\\?" => '?' as u32,
+            "\\\\" => '\\' as u32,
+            "\\a" => 0x07,
+            "\\b" => 0x08,
+            "\\f" => 0x0c,
+            "\\n" => 0x0a,
+            "\\r" => 0x0d,
+            "\\t" => 0x09,
+            "\\v" => 0x0b,
+            _ => {
+                let (digits, radix) = match escape_sequence.as_bytes()[1] {
+                    b'x' | b'u' | b'U' => (&escape_sequence[2..], 16),
+                    _ => (&escape_sequence[1..], 8),
+                };
+                match u32::from_str_radix(digits, radix) {
+                    Ok(value) => value,
+                    Err(_) => return Err(self.error("escape sequence out of range".to_string())),
+                }
+            }
+        })
+    }
+}
+
+fn binary_precedence(operator: &str) -> Option<u8> {
+    match operator {
+        "||" => Some(1),
+        "&&" => Some(2),
+        "|" => Some(3),
+        "^" => Some(4),
+        "&" => Some(5),
+        "==" | "!=" => Some(6),
+        "<" | ">" | "<=" | ">=" => Some(7),
+        "<<" | ">>" => Some(8),
+        "+" | "-" => Some(9),
+        "*" | "/" | "%" => Some(10),
+        _ => None,
+    }
+}
+
+/// The usual arithmetic conversions, a signed operand is converted to unsigned with the other.
+fn convert(lhs: Value, rhs: Value) -> (Value, Value) {
+    match (lhs, rhs) {
+        (Value::Signed(lhs), Value::Unsigned(rhs)) => {
+            (Value::Unsigned(lhs as u64), Value::Unsigned(rhs))
+        }
+        (Value::Unsigned(lhs), Value::Signed(rhs)) => {
+            (Value::Unsigned(lhs), Value::Unsigned(rhs as u64))
+        }
+        _ => (lhs, rhs),
+    }
+}
+
+fn compare<T: PartialOrd>(operator: &str, lhs: T, rhs: T) -> bool {
+    match operator {
+        "==" => lhs == rhs,
+        "!=" => lhs != rhs,
+        "<" => lhs < rhs,
+        ">" => lhs > rhs,
+        "<=" => lhs <= rhs,
+        ">=" => lhs >= rhs,
+        _ => unreachable!(),
+    }
+}
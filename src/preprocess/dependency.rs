@@ -0,0 +1,62 @@
+/// A file read by the preprocessor, which the rules of make written with `-M` depend on.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Dependency {
+    /// the path of the file, as it was found
+    pub path: String,
+    /// whether it is found in a system directory or included by such a file, which `-MM` leaves
+    /// out
+    pub is_system: bool,
+}
+
+/// The maximum width of the lines of a rule, after which the names go on the next line, as GCC
+/// writes them.
+const MAX_COLUMN: usize = 72;
+
+/// The rule of make with `targets` depending on `dependencies`, like GCC writes with `-M`, where
+/// the targets are written as they are given. With `phony`, every header also gets a rule of its
+/// own, so that make does not fail when the header is removed.
+pub fn make_rule(targets: &[String], dependencies: &[Dependency], phony: bool) -> String {
+    let mut result = String::new();
+    let mut column = 0;
+    let mut write = |result: &mut String, name: &str| {
+        if column > 0 {
+            if column + name.len() > MAX_COLUMN {
+                result.push_str(" \\\n");
+                column = 0;
+            }
+            result.push(' ');
+            column += 1;
+        }
+        result.push_str(name);
+        column += name.len();
+    };
+    for target in targets {
+        write(&mut result, target);
+    }
+    result.push(':');
+    for dependency in dependencies {
+        write(&mut result, &escape(&dependency.path));
+    }
+    result.push('\n');
+    if phony {
+        // the first dependency is the source file
+        for dependency in dependencies.iter().skip(1) {
+            result.push_str(&format!("{}:\n", escape(&dependency.path)));
+        }
+    }
+    result
+}
+
+/// The name of `path` in a rule of make.
+pub fn escape(path: &str) -> String {
+    let mut result = String::new();
+    for c in path.chars() {
+        match c {
+            ' ' | '\t' | '#' => result.push('\\'),
+            '$' => result.push('$'),
+            _ => {}
+        }
+        result.push(c);
+    }
+    result
+}
@@ -0,0 +1,179 @@
+use codespan_reporting::diagnostic::{Diagnostic, Label};
+use codespan_reporting::files::SimpleFile;
+use codespan_reporting::term;
+use codespan_reporting::term::termcolor::{ColorChoice, StandardStream};
+use pest::error::{Error as PestError, ErrorVariant, InputLocation};
+use pest::RuleType;
+use std::error::Error;
+use std::fmt;
+use std::ops::Range;
+
+/// An error or a warning of the preprocessor.
+#[derive(Debug, Clone)]
+pub struct PreprocessError {
+    pub message: String,
+    pub is_warning: bool,
+    /// where it is, unless it is not in a file, like a file that can not be read
+    pub location: Option<Location>,
+    /// the files including the file of the location, with the line of their `#include`, the
+    /// innermost first
+    pub included_from: Vec<(String, usize)>,
+}
+
+/// A place in a file, with the code of the file to show it.
+#[derive(Debug, Clone)]
+pub struct Location {
+    pub file: String,
+    pub code: String,
+    pub span: Range<usize>,
+}
+
+impl PreprocessError {
+    pub fn new(message: String) -> PreprocessError {
+        PreprocessError {
+            message,
+            is_warning: false,
+            location: None,
+            included_from: vec![],
+        }
+    }
+
+    pub fn at(mut self, file: &str, code: &str, span: Range<usize>) -> PreprocessError {
+        self.location = Some(Location {
+            file: file.to_string(),
+            code: code.to_string(),
+            span,
+        });
+        self
+    }
+
+    /// The error of a pest parser on `code` of `file`.
+    pub fn from_pest<R: RuleType>(error: &PestError<R>, file: &str, code: &str) -> PreprocessError {
+        let span = match error.location {
+            InputLocation::Pos(position) => position..position,
+            InputLocation::Span((start, end)) => start..end,
+        };
+        PreprocessError::new(pest_message(error)).at(file, code, span)
+    }
+
+    /// The line and the column of the location, counted from 1.
+    pub fn line_col(&self) -> Option<(usize, usize)> {
+        self.location.as_ref().map(|location| {
+            let before = &location.code[..location.span.start.min(location.code.len())];
+            let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+            (
+                before.matches('\n').count() + 1,
+                before[line_start..].chars().count() + 1,
+            )
+        })
+    }
+
+    pub fn to_diagnostic(&self) -> Diagnostic<()> {
+        let diagnostic = match self.is_warning {
+            true => Diagnostic::warning(),
+            false => Diagnostic::error(),
+        }
+        .with_message(self.message.clone())
+        .with_notes(
+            self.included_from
+                .iter()
+                .map(|(file, line)| format!("in file included from {}:{}", file, line))
+                .collect(),
+        );
+        match self.location {
+            Some(ref location) => {
+                diagnostic.with_labels(vec![Label::primary((), location.span.clone())])
+            }
+            None => diagnostic,
+        }
+    }
+
+    /// Print the diagnostic to stderr.
+    pub fn emit(&self) {
+        let writer = StandardStream::stderr(ColorChoice::Always);
+        let config = term::Config::default();
+        let (file, code) = match self.location {
+            Some(ref location) => (location.file.as_str(), location.code.as_str()),
+            None => ("", ""),
+        };
+        term::emit(
+            &mut writer.lock(),
+            &config,
+            &SimpleFile::new(file, code),
+            &self.to_diagnostic(),
+        )
+        .expect("unreachable");
+    }
+}
+
+impl fmt::Display for PreprocessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (file, line) in self.included_from.iter().rev() {
+            writeln!(f, "In file included from {}:{}:", file, line)?;
+        }
+        if let (Some(location), Some((line, column))) = (&self.location, self.line_col()) {
+            write!(f, "{}:{}:{}: ", location.file, line, column)?;
+        }
+        match self.is_warning {
+            true => write!(f, "warning: {}", self.message),
+            false => write!(f, "error: {}", self.message),
+        }
+    }
+}
+
+impl Error for PreprocessError {}
+
+/// An error at a span of some code, which is located in its file by the state of the
+/// preprocessor, as the code may be a file or the expansion of a macro.
+#[derive(Debug)]
+pub(super) struct SpanError {
+    pub message: String,
+    /// the addresses of the text of the span
+    pub text: Range<usize>,
+}
+
+impl SpanError {
+    /// The error at `text`, a slice of the code.
+    pub fn at(message: String, text: &str) -> Box<dyn Error> {
+        let start = text.as_ptr() as usize;
+        Box::new(SpanError {
+            message,
+            text: start..start + text.len(),
+        })
+    }
+}
+
+impl fmt::Display for SpanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for SpanError {}
+
+/// The message of a pest error, like `expected identifier`.
+pub(super) fn pest_message<R: RuleType>(error: &PestError<R>) -> String {
+    let rules = |rules: &[R]| {
+        rules
+            .iter()
+            .map(|rule| format!("{:?}", rule))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    match error.variant {
+        ErrorVariant::ParsingError {
+            ref positives,
+            ref negatives,
+        } => match (negatives.is_empty(), positives.is_empty()) {
+            (true, true) => "unknown parsing error".to_string(),
+            (true, false) => format!("expected {}", rules(positives)),
+            (false, true) => format!("unexpected {}", rules(negatives)),
+            (false, false) => format!(
+                "unexpected {}; expected {}",
+                rules(negatives),
+                rules(positives)
+            ),
+        },
+        ErrorVariant::CustomError { ref message } => message.clone(),
+    }
+}
@@ -0,0 +1,307 @@
+use super::error::SpanError;
+use super::phase4::{Macro, State};
+use super::token::*;
+use std::cmp::Ordering;
+use std::error::Error;
+use std::rc::Rc;
+use typed_arena::Arena;
+
+/// The arguments of a function-like macro.
+type Arguments<'a> = Vec<Vec<Token<'a>>>;
+
+/// Expands the macros of tokens with Prosser's algorithm. Each token is read once: the expansion
+/// of a macro takes its place in the input, to be rescanned with the rest of it, and carries the
+/// macro in the hide sets of its tokens, so that the macro is not expanded again in them.
+pub struct Expander<'s, 'a> {
+    state: &'s mut State<'a>,
+    code_arena: &'a Arena<String>,
+}
+
+impl<'s, 'a> Expander<'s, 'a> {
+    pub fn new(state: &'s mut State<'a>, code_arena: &'a Arena<String>) -> Expander<'s, 'a> {
+        Expander { state, code_arena }
+    }
+
+    pub fn expand(&mut self, tokens: Vec<Token<'a>>) -> Result<Vec<Token<'a>>, Box<dyn Error>> {
+        // the tokens left to read, the next one last
+        let mut input = tokens.into_iter().rev().collect::<Vec<_>>();
+        let mut output = vec![];
+        while let Some(token) = input.pop() {
+            // `__LINE__` and the errors are on the line of the last token read from the file
+            if token.hide_set.is_empty() {
+                self.state.move_to_token(&token);
+            }
+            if token.kind != Kind::Identifier || token.hide_set.contains(token.text) {
+                output.push(token);
+                continue;
+            }
+            if let Some(text) = self.state.expand_dynamic_macro(token.text) {
+                let kind = match text.starts_with('"') {
+                    true => Kind::StringLiteral,
+                    false => Kind::Constant,
+                };
+                output.push(Token {
+                    kind,
+                    text: self.code_arena.alloc(text),
+                    ..token
+                });
+                continue;
+            }
+            // the newlines in the invocation of a macro, which may span lines
+            let mut newlines = 0;
+            let expansion = match self.state.defined.get(token.text).cloned() {
+                Some(Macro::Object(body)) => {
+                    let hide_set = with(&token.hide_set, token.text);
+                    self.substitute(&body, &[], vec![], &hide_set)?
+                }
+                // a function-like macro is only called by its name followed by `(`
+                Some(Macro::Function(params, is_variadic, body))
+                    if input.last().is_some_and(|next| next.is("(")) =>
+                {
+                    let open = input.pop().unwrap();
+                    newlines = open.space.matches('\n').count();
+                    let (args, close) =
+                        arguments(&mut input, &token, &params, is_variadic, &mut newlines)?;
+                    let hide_set =
+                        with(&intersection(&token.hide_set, &close.hide_set), token.text);
+                    self.substitute(&body, &params, args, &hide_set)?
+                }
+                _ => {
+                    output.push(token);
+                    continue;
+                }
+            };
+            if expansion.is_empty() {
+                newlines += token.space.matches('\n').count();
+            }
+            // like GCC, the newlines are put after the expansion, to keep the tokens after it on
+            // their lines
+            if let Some(next) = input.last_mut().filter(|_| newlines > 0) {
+                next.space =
+                    self.code_arena
+                        .alloc(format!("{}{}", "\n".repeat(newlines), next.space));
+            }
+            // the expansion is where the name of the macro was
+            let mut expansion = expansion.into_iter();
+            let first = expansion.next().map(|first| Token {
+                space: token.space,
+                ..first
+            });
+            input.extend(expansion.rev().chain(first));
+        }
+        Ok(output)
+    }
+
+    /// The body of a macro with its parameters replaced by `args`, with `hide_set` added to its
+    /// tokens.
+    fn substitute(
+        &mut self,
+        body: &[Token<'a>],
+        params: &[&'a str],
+        args: Arguments<'a>,
+        hide_set: &HideSet<'a>,
+    ) -> Result<Vec<Token<'a>>, Box<dyn Error>> {
+        let parameter = |token: &Token| match token.kind {
+            Kind::Identifier => params.iter().position(|param| *param == token.text),
+            _ => None,
+        };
+        // the arguments are fully expanded once, when they are used out of `#` and `##`
+        let mut expanded: Vec<Option<Vec<Token<'a>>>> = vec![None; args.len()];
+        let mut result: Vec<Token<'a>> = vec![];
+        let mut index = 0;
+        while index < body.len() {
+            let token = &body[index];
+            let next = body.get(index + 1);
+            index += 1;
+
+            // the macros are checked when they are defined, so that in a function-like macro `#` is
+            // followed by a parameter, and `##` is between two tokens
+            if let Some(param) = next.filter(|_| token.is("#")).and_then(parameter) {
+                index += 1;
+                let text = self.code_arena.alloc(stringize(&args[param]));
+                result.push(Token {
+                    kind: Kind::StringLiteral,
+                    text,
+                    ..token.clone()
+                });
+                continue;
+            }
+            if token.is("##") {
+                let rhs = &body[index];
+                index += 1;
+                let mut rhs_tokens = match parameter(rhs) {
+                    // like GCC, `, ## __VA_ARGS__` leaves out the comma without variable arguments
+                    Some(param)
+                        if rhs.text == "__VA_ARGS__"
+                            && result.last().is_some_and(|last| last.is(",")) =>
+                    {
+                        match args[param].is_empty() {
+                            true => {
+                                result.pop();
+                            }
+                            false => result.extend(spaced(args[param].clone(), rhs.space)),
+                        }
+                        continue;
+                    }
+                    Some(param) => spaced(args[param].clone(), rhs.space),
+                    None => vec![rhs.clone()],
+                }
+                .into_iter();
+                match (result.pop(), rhs_tokens.next()) {
+                    (Some(lhs), Some(rhs)) if lhs.kind == Kind::Placemarker => result.push(rhs),
+                    (Some(lhs), Some(rhs)) => result.push(self.paste(lhs, rhs)?),
+                    (lhs, rhs) => result.extend(lhs.or(rhs)),
+                }
+                result.extend(rhs_tokens);
+                continue;
+            }
+            if let Some(param) = parameter(token) {
+                let operand = next.is_some_and(|next| next.is("##"));
+                let tokens = match (operand, &expanded[param]) {
+                    // the operands of `##` are not expanded
+                    (true, _) => args[param].clone(),
+                    (false, Some(tokens)) => tokens.clone(),
+                    (false, None) => {
+                        let tokens = self.expand(args[param].clone())?;
+                        expanded[param] = Some(tokens.clone());
+                        tokens
+                    }
+                };
+                match (tokens.is_empty(), operand) {
+                    (true, true) => result.push(Token {
+                        kind: Kind::Placemarker,
+                        ..token.clone()
+                    }),
+                    (true, false) => {}
+                    (false, _) => result.extend(spaced(tokens, token.space)),
+                }
+                continue;
+            }
+            result.push(token.clone());
+        }
+        Ok(result
+            .into_iter()
+            .filter(|token| token.kind != Kind::Placemarker)
+            .map(|token| Token {
+                hide_set: union(&token.hide_set, hide_set),
+                ..token
+            })
+            .collect())
+    }
+
+    /// The token made by `##` from `lhs` and `rhs`.
+    fn paste(&mut self, lhs: Token<'a>, rhs: Token<'a>) -> Result<Token<'a>, Box<dyn Error>> {
+        let text = self
+            .code_arena
+            .alloc(format!("{}{}", lhs.text, rhs.text))
+            .as_str();
+        match lex(text).as_deref() {
+            Some([token]) => Ok(Token {
+                space: lhs.space,
+                hide_set: lhs.hide_set,
+                ..token.clone()
+            }),
+            _ => Err(SpanError::at(
+                format!(
+                    "pasting `{}` and `{}` does not give a valid preprocessing token",
+                    lhs.text, rhs.text
+                ),
+                lhs.text,
+            )),
+        }
+    }
+}
+
+/// Read the arguments of the function-like macro `name` from `input` after its `(`, and the `)`
+/// after them. The newlines between them are counted in `newlines`, and become spaces.
+fn arguments<'a>(
+    input: &mut Vec<Token<'a>>,
+    name: &Token<'a>,
+    params: &[&str],
+    is_variadic: bool,
+    newlines: &mut usize,
+) -> Result<(Arguments<'a>, Token<'a>), Box<dyn Error>> {
+    let mut args = vec![vec![]];
+    let mut depth = 0;
+    let close = loop {
+        let mut token = input.pop().ok_or_else(|| {
+            SpanError::at(
+                format!("unterminated argument list invoking macro `{}`", name.text),
+                name.text,
+            )
+        })?;
+        if token.space.contains('\n') {
+            *newlines += token.space.matches('\n').count();
+            token.space = " ";
+        }
+        if token.is("(") {
+            depth += 1;
+        } else if token.is(")") {
+            if depth == 0 {
+                break token;
+            }
+            depth -= 1;
+        } else if token.is(",") && depth == 0 && !(is_variadic && args.len() == params.len()) {
+            // the commas of the variable arguments are kept
+            args.push(vec![]);
+            continue;
+        }
+        args.last_mut().unwrap().push(token);
+    };
+    // a macro without parameters is called with an empty argument
+    if params.is_empty() && args.len() == 1 && args[0].is_empty() {
+        args.clear();
+    }
+    // the variable arguments may be left out
+    if is_variadic && args.len() + 1 == params.len() {
+        args.push(vec![]);
+    }
+    let count = params.len() - is_variadic as usize;
+    match args.len().cmp(&params.len()) {
+        Ordering::Less => Err(SpanError::at(
+            format!(
+                "macro `{}` requires {} arguments, but only {} given",
+                name.text,
+                count,
+                args.len()
+            ),
+            name.text,
+        )),
+        Ordering::Greater => Err(SpanError::at(
+            format!(
+                "macro `{}` passed {} arguments, but takes just {}",
+                name.text,
+                args.len(),
+                count
+            ),
+            name.text,
+        )),
+        Ordering::Equal => Ok((args, close)),
+    }
+}
+
+/// The tokens of an argument, put where the first one has `space` before it.
+fn spaced<'a>(mut tokens: Vec<Token<'a>>, space: &'a str) -> Vec<Token<'a>> {
+    if let Some(first) = tokens.first_mut() {
+        first.space = space;
+    }
+    tokens
+}
+
+fn with<'a>(hide_set: &HideSet<'a>, name: &'a str) -> HideSet<'a> {
+    let mut hide_set = (**hide_set).clone();
+    hide_set.insert(name);
+    Rc::new(hide_set)
+}
+
+fn union<'a>(lhs: &HideSet<'a>, rhs: &HideSet<'a>) -> HideSet<'a> {
+    match (lhs.is_empty(), rhs.is_empty()) {
+        (true, _) => rhs.clone(),
+        (_, true) => lhs.clone(),
+        _ => Rc::new(lhs.union(rhs).copied().collect()),
+    }
+}
+
+fn intersection<'a>(lhs: &HideSet<'a>, rhs: &HideSet<'a>) -> HideSet<'a> {
+    Rc::new(lhs.intersection(rhs).copied().collect())
+}
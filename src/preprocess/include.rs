@@ -0,0 +1,118 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// The directories searched for included files. Like GCC, `#include "file"` is searched in the
+/// directory of the including file, then the `quote` directories, then the directories of
+/// `#include <file>`: the `user` directories, the `system` directories and the headers of cc99.
+#[derive(Debug, Clone, Default)]
+pub struct IncludePaths {
+    /// `-iquote` directories
+    pub quote: Vec<PathBuf>,
+    /// `-I` directories
+    pub user: Vec<PathBuf>,
+    /// `-isystem` directories
+    pub system: Vec<PathBuf>,
+    /// the directory of the headers of cc99, like `stdio.h`
+    pub standard: Option<PathBuf>,
+}
+
+/// Where an included file is found.
+pub struct Found {
+    pub path: PathBuf,
+    /// the index of its directory among the directories of `#include <file>`, where
+    /// `#include_next` goes on searching, unless it is relative to the including file or in a
+    /// `quote` directory
+    pub directory: Option<usize>,
+    /// whether it is in a `system` directory or in the headers of cc99
+    pub is_system: bool,
+}
+
+impl IncludePaths {
+    /// The search path with the headers of cc99, and without other directories.
+    pub fn new() -> IncludePaths {
+        IncludePaths {
+            standard: standard_include_dir(),
+            ..Default::default()
+        }
+    }
+
+    /// The directories of `#include <file>`, in order.
+    fn angled(&self) -> impl Iterator<Item = &Path> {
+        self.user
+            .iter()
+            .chain(self.system.iter())
+            .chain(self.standard.iter())
+            .map(PathBuf::as_path)
+    }
+
+    /// Find the file of `#include "name"` when `quoted`, `#include <name>` otherwise. The files
+    /// included by `includer`, in `includer_directory`, are searched in its directory first.
+    pub fn find(&self, name: &str, quoted: bool, includer_directory: &Path) -> Option<Found> {
+        if Path::new(name).is_absolute() {
+            return match Path::new(name).is_file() {
+                true => Some(Found {
+                    path: PathBuf::from(name),
+                    directory: None,
+                    is_system: false,
+                }),
+                false => None,
+            };
+        }
+        if quoted {
+            let found = std::iter::once(includer_directory)
+                .chain(self.quote.iter().map(PathBuf::as_path))
+                .map(|directory| directory.join(name))
+                .find(|path| path.is_file());
+            if let Some(path) = found {
+                return Some(Found {
+                    path,
+                    directory: None,
+                    is_system: false,
+                });
+            }
+        }
+        self.find_next(name, 0)
+    }
+
+    /// Find the file of `#include_next` in the directories of `#include <file>` from the
+    /// directory at `start`.
+    pub fn find_next(&self, name: &str, start: usize) -> Option<Found> {
+        self.angled()
+            .enumerate()
+            .skip(start)
+            .map(|(index, directory)| (index, directory.join(name)))
+            .find(|(_, path)| path.is_file())
+            .map(|(index, path)| Found {
+                path,
+                directory: Some(index),
+                is_system: index >= self.user.len(),
+            })
+    }
+}
+
+/// The directory of the headers of cc99: `CC99_INCLUDE` when it is set, or the `include`
+/// directory next to the executable, under its installation prefix as `include/cc99`, or in the
+/// source tree it is built in.
+fn standard_include_dir() -> Option<PathBuf> {
+    if let Some(directory) = env::var_os("CC99_INCLUDE") {
+        return Some(PathBuf::from(directory));
+    }
+    let executable_dir = env::current_exe()
+        .ok()
+        .and_then(|executable| executable.parent().map(Path::to_path_buf));
+    executable_dir
+        .iter()
+        .flat_map(|directory| {
+            [
+                directory.join("include"),
+                directory.join("../include/cc99"),
+                // `target/<profile>` of the source tree
+                directory.join("../../include"),
+            ]
+        })
+        .chain(std::iter::once(
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("include"),
+        ))
+        .find(|directory| directory.join("stdio.h").is_file())
+        .map(|directory| directory.canonicalize().unwrap_or(directory))
+}
@@ -2,46 +2,126 @@ use pest::Parser;
 use std::collections::HashMap;
 use std::error::Error;
 use std::fs;
+use std::path::Path;
 use typed_arena::Arena;
 
+mod condition;
+mod dependency;
+mod error;
+mod expand;
+mod include;
 mod phase2;
 mod phase3;
 mod phase4;
 mod phase6;
+mod predefined;
+mod source_map;
+mod token;
 
+pub use dependency::{escape, make_rule, Dependency};
+pub use error::PreprocessError;
+use error::*;
+pub use include::IncludePaths;
 use phase2::*;
 use phase3::*;
 use phase4::*;
 use phase6::*;
+pub use predefined::target_macros;
+pub use source_map::SourceMap;
 
-pub fn preprocess_file(path: &str, include_dirs: &[&str]) -> Result<String, Box<dyn Error>> {
-    let source_content =
-        fs::read_to_string(path).unwrap_or_else(|_| panic!("Unable to read source file {}", path));
-    preprocess(&source_content, include_dirs)
+/// A macro defined with `-D` or undefined with `-U` on the command line.
+#[derive(Debug, PartialEq, Clone)]
+pub enum CommandLineMacro {
+    Define(String, String),
+    Undefine(String),
 }
 
-pub fn preprocess(code: &str, include_dirs: &[&str]) -> Result<String, Box<dyn Error>> {
+impl CommandLineMacro {
+    /// The macro of `-DNAME=value`, where `-DNAME` defines `NAME` as 1.
+    pub fn define(argument: &str) -> CommandLineMacro {
+        match argument.split_once('=') {
+            Some((name, value)) => CommandLineMacro::Define(name.to_string(), value.to_string()),
+            None => CommandLineMacro::Define(argument.to_string(), "1".to_string()),
+        }
+    }
+}
+
+pub fn preprocess_file(
+    path: &str,
+    include_paths: &IncludePaths,
+    macros: &[CommandLineMacro],
+) -> Result<(String, Vec<Dependency>), PreprocessError> {
+    let source_content = fs::read_to_string(path)
+        .map_err(|error| PreprocessError::new(format!("{}: {}", path, error)))?;
+    preprocess(&source_content, path, include_paths, macros)
+}
+
+/// Preprocess the code of the file named `file_name`, after defining and undefining `macros` in
+/// order. The files read to preprocess it, its file first, are returned with the code.
+pub fn preprocess(
+    code: &str,
+    file_name: &str,
+    include_paths: &IncludePaths,
+    macros: &[CommandLineMacro],
+) -> Result<(String, Vec<Dependency>), PreprocessError> {
     let code = phase2(code);
-    let code = phase3(&code)?;
+    let code =
+        phase3(&code).map_err(|error| PreprocessError::from_pest(&error, file_name, &code))?;
 
-    let mut defined: HashMap<String, Macro> = Default::default();
+    let mut state = State::default();
     let code_arena = Arena::new();
-    let code = phase4(&code, &mut defined, include_dirs, &code_arena)?;
+    // the predefined and command line macros are defined by directives before the code
+    let directives = predefined::standard_macros()
+        .iter()
+        .chain(macros.iter())
+        .map(|macro_| match macro_ {
+            CommandLineMacro::Define(name, value) => format!("#define {} {}\n", name, value),
+            CommandLineMacro::Undefine(name) => format!("#undef {}\n", name),
+        })
+        .collect::<String>();
+    state.include_stack.push("<command-line>".to_string());
+    phase4(
+        code_arena.alloc(directives),
+        &mut state,
+        include_paths,
+        &code_arena,
+    )?;
+    state.include_stack.pop();
+
+    state.include_stack.push(file_name.to_string());
+    state.file = fs::canonicalize(file_name).unwrap_or_else(|_| file_name.into());
+    state.directory = Path::new(file_name)
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    state.add_dependency(file_name.to_string());
+    // like GCC, the code starts with a line marker naming the file
+    let marker = state.line_marker(1, None);
+    let code = marker + &phase4(&code, &mut state, include_paths, &code_arena)?;
 
-    let code = phase6(&code)?;
-    Ok(code)
+    // the code has been rewritten by now, so the error is not shown in it
+    let code = phase6(&code).map_err(|error| PreprocessError {
+        location: None,
+        ..PreprocessError::from_pest(&error, file_name, &code)
+    })?;
+    Ok((code, state.dependencies))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use codespan_reporting::files::Files;
+    use std::path::PathBuf;
 
     #[test]
     #[should_panic]
     fn process_comments_fail() {
         let code = r#"/* "#;
-        let include_dirs = vec![];
-        println!("result: {}", preprocess(code, &include_dirs).unwrap());
+        let include_paths = IncludePaths::new();
+        println!(
+            "result: {}",
+            preprocess(code, "test.c", &include_paths, &[]).unwrap().0
+        );
     }
 
     #[test]
@@ -57,17 +137,23 @@ int main() {
     return 0;
 }
 "#;
-        let expected = r#"
+        let expected = r#"# 1 "test.c"
+
 int main() {
     '"'; "//";
     
      
-     
+    
+
+
     return 0;
 }
 "#;
-        let include_dirs = vec![];
-        assert_eq!(expected, preprocess(code, &include_dirs).unwrap());
+        let include_paths = IncludePaths::new();
+        assert_eq!(
+            expected,
+            preprocess(code, "test.c", &include_paths, &[]).unwrap().0
+        );
     }
 
     #[test]
@@ -76,11 +162,16 @@ int main() {
 int main() { \
 }
 "#;
-        let expected = r#"
+        let expected = r#"# 1 "test.c"
+
 int main() { }
+
 "#;
-        let include_dirs = vec![];
-        assert_eq!(expected, preprocess(code, &include_dirs).unwrap());
+        let include_paths = IncludePaths::new();
+        assert_eq!(
+            expected,
+            preprocess(code, "test.c", &include_paths, &[]).unwrap().0
+        );
     }
 
     #[test]
@@ -92,14 +183,19 @@ int main() {
     return 0;
 }
 "#;
-        let expected = r#"
+        let expected = r#"# 1 "test.c"
+
 int main() {
     
+
     return 0;
 }
 "#;
-        let include_dirs = vec![];
-        assert_eq!(expected, preprocess(code, &include_dirs).unwrap());
+        let include_paths = IncludePaths::new();
+        assert_eq!(
+            expected,
+            preprocess(code, "test.c", &include_paths, &[]).unwrap().0
+        );
     }
 
     #[test]
@@ -109,13 +205,46 @@ int main() {
     char *x = "x" "y""z";
 }
 "#;
-        let expected = r#"
+        let expected = r#"# 1 "test.c"
+
 int main() {
     char *x = "xyz";
 }
 "#;
-        let include_dirs = vec![];
-        assert_eq!(expected, preprocess(code, &include_dirs).unwrap());
+        let include_paths = IncludePaths::new();
+        assert_eq!(
+            expected,
+            preprocess(code, "test.c", &include_paths, &[]).unwrap().0
+        );
+    }
+
+    #[test]
+    fn combine_prefixed_strings() {
+        let code = r#"
+int main() {
+    int n = sizeof("ab" L"cd");
+    wchar_t *w = "a" L"b" "c";
+    char *u = u8"x" "y";
+    char *x = "\x1" "2" "\x1234";
+    char *o = "\1" "" "2";
+}
+"#;
+        let expected = r#"# 1 "test.c"
+
+int main() {
+    int n = sizeof(L"abcd");
+    wchar_t *w = L"abc";
+    char *u = u8"xy";
+    char *x = "\x1\062\x1234";
+    char *o = "\1\062";
+}
+"#;
+        let include_paths = IncludePaths::new();
+        assert_eq!(
+            expected,
+            preprocess(code, "test.c", &include_paths, &[]).unwrap().0
+        );
+        assert!(preprocess(r#"u"a" U"b""#, "test.c", &include_paths, &[]).is_err());
     }
 
     #[test]
@@ -127,13 +256,19 @@ int main() {
     return x;
 }
 "#;
-        let expected = r#"
+        let expected = r#"# 1 "test.c"
+
+
+
 int main() {
     return 0;
 }
 "#;
-        let include_dirs = vec![];
-        assert_eq!(expected, preprocess(code, &include_dirs).unwrap());
+        let include_paths = IncludePaths::new();
+        assert_eq!(
+            expected,
+            preprocess(code, "test.c", &include_paths, &[]).unwrap().0
+        );
     }
 
     #[test]
@@ -150,11 +285,71 @@ int main() {
         #endif
         int main() { return x; }
 "#;
-        let expected = r#"
-                        int main() { return 0; }
+        let expected = "# 1 \"test.c\"\n\n        \n        \n\n\n\n\n\n\n\n        int main() { return 0; }\n";
+        let include_paths = IncludePaths::new();
+        assert_eq!(
+            expected,
+            preprocess(code, "test.c", &include_paths, &[]).unwrap().0
+        );
+    }
+
+    #[test]
+    fn process_constant_expression() {
+        let code = r#"
+#define VERSION 3
+#define TWICE(x) ((x) * 2)
+#if VERSION >= 2 && !defined(NOPE) && defined VERSION
+int a;
+#endif
+#if 0
+int b;
+#elif TWICE(VERSION) == 6 && 'A' == 65 && '\377' < 0
+int c;
+#endif
+#if 0 && 1 / 0 || UNKNOWN ? 0 : 0x10 >> 4
+int d;
+#endif
+#if -1 < 0u || 1 << 63 > 0
+int e;
+#endif
+"#;
+        let expected = r#"# 1 "test.c"
+
+
+
+
+int a;
+
+
+
+
+int c;
+
+
+int d;
+
+
+
+
 "#;
-        let include_dirs = vec![];
-        assert_eq!(expected, preprocess(code, &include_dirs).unwrap());
+        let include_paths = IncludePaths::new();
+        assert_eq!(
+            expected,
+            preprocess(code, "test.c", &include_paths, &[]).unwrap().0
+        );
+    }
+
+    #[test]
+    fn process_constant_expression_fail() {
+        let include_paths = IncludePaths::new();
+        for condition in ["1 / (2 - 2)", "1 +", "1 2", "(1", "1.5", "defined", "\"s\""] {
+            let code = format!("#if {}\n#endif\n", condition);
+            assert!(
+                preprocess(&code, "test.c", &include_paths, &[]).is_err(),
+                "{}",
+                condition
+            );
+        }
     }
 
     #[test]
@@ -172,16 +367,392 @@ int main() {
     return x(0)m_4();
 }
 "#;
-        let expected = r#"
+        let expected = r#"# 1 "test.c"
+
+
+
+
+
+
 int main() {
     ij;
     "sadf";
-    2,-(3*4,7)," ,";
-    return 00;
+    "2, -(3*4, 7), \" ,\"";
+    return 0 0;
 }
 "#;
-        let include_dirs = vec![];
-        assert_eq!(expected, preprocess(code, &include_dirs).unwrap());
+        let include_paths = IncludePaths::new();
+        assert_eq!(
+            expected,
+            preprocess(code, "test.c", &include_paths, &[]).unwrap().0
+        );
+    }
+
+    #[test]
+    fn process_function_define_with_spaces() {
+        let code = r#"
+#define add(a, b) a + b
+int main() {
+    return add(1, 2);
+}
+"#;
+        let expected = r#"# 1 "test.c"
+
+
+int main() {
+    return 1 + 2;
+}
+"#;
+        let include_paths = IncludePaths::new();
+        assert_eq!(
+            expected,
+            preprocess(code, "test.c", &include_paths, &[]).unwrap().0
+        );
+    }
+
+    #[test]
+    fn process_multiline_invocations() {
+        let code = r#"
+#define ADD(a, b) a + b
+#define x(a) a
+int main() {
+    int i = ADD(1,
+        2);
+    int j = x
+    (__LINE__);
+    return x(
+    0) + i + j;
+}
+"#;
+        let expected = r#"# 1 "test.c"
+
+
+
+int main() {
+    int i = 1 + 2
+;
+    int j = 8
+;
+    return 0
+ + i + j;
+}
+"#;
+        let include_paths = IncludePaths::new();
+        assert_eq!(
+            expected,
+            preprocess(code, "test.c", &include_paths, &[]).unwrap().0
+        );
+    }
+
+    #[test]
+    fn process_include_macros() {
+        let code = r#"
+#include <stdarg.h>
+#include <stdarg.h>
+#ifdef _STDARG_H
+va_start(ap, count);
+#endif
+"#;
+        let include_paths = IncludePaths::new();
+        let (result, _) = preprocess(code, "test.c", &include_paths, &[]).unwrap();
+        assert_eq!(result.matches("struct __va_list_tag {").count(), 1);
+        assert!(result.contains("__builtin_va_start(ap, count);"));
+    }
+
+    #[test]
+    fn process_line_markers() {
+        let code = r#"#include <stdarg.h>
+/* a comment
+   on two lines */ int a = __LINE__;
+#line 100 "renamed.c"
+char *file = __FILE__;
+int b = \
+    __LINE__;
+"#;
+        let include_paths = IncludePaths::new();
+        let stdarg = include_paths.standard.as_ref().unwrap().join("stdarg.h");
+        let stdarg = stdarg.to_str().unwrap();
+        let (result, _) = preprocess(code, "test.c", &include_paths, &[]).unwrap();
+        assert!(result.starts_with(&format!("# 1 \"test.c\"\n# 1 \"{}\" 1\n", stdarg)));
+        assert!(result.contains("\n# 2 \"test.c\" 2\n\n int a = 3;\n"));
+        assert!(result
+            .ends_with("# 100 \"renamed.c\"\nchar *file = \"renamed.c\";\nint b =     101;\n\n"));
+
+        let source_map = SourceMap::new("test.c", &result);
+        assert!(!source_map.code().contains("# "));
+        let locate = |text: &str| {
+            let offset = source_map.code().find(text).unwrap();
+            let file_id = source_map.file_id(offset);
+            (
+                source_map.name(file_id).unwrap(),
+                source_map.location(file_id, offset).unwrap().line_number,
+            )
+        };
+        assert_eq!(locate("int a"), ("test.c", 3));
+        assert_eq!(locate("char *file"), ("renamed.c", 100));
+        assert_eq!(locate("struct __va_list_tag"), (stdarg, 5));
+
+        // the file name of the first marker is not concatenated with the code after it
+        let (result, _) = preprocess("\"a\";\n", "test.c", &include_paths, &[]).unwrap();
+        assert_eq!(result, "# 1 \"test.c\"\n\"a\";\n");
+    }
+
+    #[test]
+    fn process_include_paths() {
+        let root = std::env::temp_dir().join(format!("cc99-include-{}", std::process::id()));
+        for (file, code) in [
+            (
+                "src/main.c",
+                "#include \"local.h\"\n#include <x.h>\n#include \"x.h\"\n",
+            ),
+            ("src/local.h", "int local;\n"),
+            ("user/x.h", "int user;\n#include_next <x.h>\n"),
+            ("system/x.h", "int system;\n"),
+            ("quote/x.h", "int quote;\n"),
+        ] {
+            fs::create_dir_all(root.join(file).parent().unwrap()).unwrap();
+            fs::write(root.join(file), code).unwrap();
+        }
+
+        let mut include_paths = IncludePaths::new();
+        include_paths.user.push(root.join("user"));
+        include_paths.system.push(root.join("system"));
+        include_paths.quote.push(root.join("quote"));
+        let main = root.join("src/main.c");
+        let result = preprocess_file(main.to_str().unwrap(), &include_paths, &[]);
+        fs::remove_dir_all(&root).unwrap();
+
+        let (result, dependencies) = result.unwrap();
+        let declarations = result
+            .lines()
+            .filter(|line| line.starts_with("int"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            declarations,
+            vec!["int local;", "int user;", "int system;", "int quote;"]
+        );
+        let dependency = |path: PathBuf, is_system| Dependency {
+            path: path.to_str().unwrap().to_string(),
+            is_system,
+        };
+        assert_eq!(
+            dependencies,
+            vec![
+                dependency(main, false),
+                dependency(root.join("src/local.h"), false),
+                dependency(root.join("user/x.h"), false),
+                dependency(root.join("system/x.h"), true),
+                dependency(root.join("quote/x.h"), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn combine_strings_across_includes() {
+        let root = std::env::temp_dir().join(format!("cc99-strings-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(
+            root.join("main.c"),
+            "const char *t = \"x\"\n#include \"s.h\"\nint n;\n",
+        )
+        .unwrap();
+        fs::write(root.join("s.h"), "\"y\";\n").unwrap();
+        let main = root.join("main.c");
+        let result = preprocess_file(main.to_str().unwrap(), &IncludePaths::new(), &[]);
+        fs::remove_dir_all(&root).unwrap();
+
+        let (result, _) = result.unwrap();
+        let header = root.join("s.h");
+        assert!(result.ends_with(&format!(
+            "const char *t = \"xy\"\n# 1 \"{}\" 1\n;\n# 3 \"{}\" 2\nint n;\n",
+            header.to_str().unwrap(),
+            main.to_str().unwrap()
+        )));
+    }
+
+    #[test]
+    fn write_make_rule() {
+        let dependencies =
+            ["main.c", "a.h", "sub/b c.h", "/usr/include/stdio.h"].map(|path| Dependency {
+                path: path.to_string(),
+                is_system: path.starts_with("/usr"),
+            });
+        assert_eq!(
+            make_rule(&["main.o".to_string()], &dependencies, true),
+            "main.o: main.c a.h sub/b\\ c.h /usr/include/stdio.h\na.h:\nsub/b\\ c.h:\n/usr/include/stdio.h:\n"
+        );
+        let long = Dependency {
+            path: "include/".repeat(8) + "long.h",
+            is_system: false,
+        };
+        assert_eq!(
+            make_rule(
+                &["foo".to_string(), "bar".to_string()],
+                &[dependencies[0].clone(), long.clone()],
+                false
+            ),
+            format!("foo bar: main.c \\\n {}\n", long.path)
+        );
+    }
+
+    #[test]
+    fn process_errors() {
+        let root = std::env::temp_dir().join(format!("cc99-errors-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("main.c"), "int a;\n#include \"a.h\"\n").unwrap();
+        fs::write(root.join("a.h"), "int b;\n\n#include \"missing.h\"\n").unwrap();
+        let main = root.join("main.c").to_str().unwrap().to_string();
+        let result = preprocess_file(&main, &IncludePaths::new(), &[]);
+        fs::remove_dir_all(&root).unwrap();
+
+        let error = result.unwrap_err();
+        assert_eq!(error.message, "header file `missing.h` not found");
+        assert!(error.location.as_ref().unwrap().file.ends_with("a.h"));
+        assert_eq!(error.line_col(), Some((3, 1)));
+        assert_eq!(error.included_from, vec![(main.clone(), 2)]);
+        assert!(error
+            .to_string()
+            .starts_with(&format!("In file included from {}:2:\n", main)));
+
+        let error = preprocess_file(&main, &IncludePaths::new(), &[]).unwrap_err();
+        assert!(error.location.is_none());
+        assert!(error.message.starts_with(&main));
+
+        let include_paths = IncludePaths::new();
+        let error = preprocess("int a;\n#foo\n", "test.c", &include_paths, &[]).unwrap_err();
+        assert_eq!(error.message, "invalid preprocessing directive #foo");
+        assert_eq!(error.line_col(), Some((2, 1)));
+
+        for (code, message, line_col) in [
+            ("int a;\n#endif\n", "#endif without #if", (2, 2)),
+            ("# else\n", "#else without #if", (1, 3)),
+            (
+                "#ifdef A\n#else\n#elif B\n#endif\n",
+                "#elif after #else",
+                (3, 2),
+            ),
+            (
+                "#if A\n#ifndef B\n#endif\nint a;\n",
+                "unterminated #if",
+                (1, 2),
+            ),
+        ] {
+            let error = preprocess(code, "test.c", &include_paths, &[]).unwrap_err();
+            assert_eq!(error.message, message);
+            assert_eq!(error.line_col(), Some(line_col));
+        }
+
+        // like GCC, an error in the expansion of a macro is shown where it is in the macro
+        let code = "#define F(x) x\n#define G F(1, 2)\nint a = G;\n";
+        let error = preprocess(code, "test.c", &include_paths, &[]).unwrap_err();
+        assert_eq!(
+            error.message,
+            "macro `F` passed 2 arguments, but takes just 1"
+        );
+        assert_eq!(error.line_col(), Some((2, 11)));
+    }
+
+    #[test]
+    fn process_pragmas() {
+        let code = r#"#define PRAGMA(x) _Pragma(#x)
+#warning don't stop here
+#pragma once
+#pragma pack ( 1 )
+PRAGMA(pack(push, 4)) struct S { int x; };
+#pragma STDC FP_CONTRACT ON
+#pragma pack(3)
+int line = __LINE__;
+"#;
+        let expected = r#"# 1 "test.c"
+
+
+
+#pragma pack(1)
+
+#pragma pack(push, 4)
+ struct S { int x; };
+# 6 "test.c"
+
+
+int line = 8;
+"#;
+        let include_paths = IncludePaths::new();
+        assert_eq!(
+            expected,
+            preprocess(code, "test.c", &include_paths, &[]).unwrap().0
+        );
+
+        let error = preprocess("#error \"stop\" here\n", "test.c", &include_paths, &[]);
+        assert!(error
+            .unwrap_err()
+            .to_string()
+            .contains("#error \"stop\" here"));
+        assert!(preprocess("_Pragma(pack)\n", "test.c", &include_paths, &[]).is_err());
+    }
+
+    #[test]
+    fn process_predefined_macros() {
+        let code = r#"
+#define HERE __LINE__
+#if __STDC__ && __STDC_VERSION__ == 199901L && __STDC_HOSTED__ && defined(__cc99__)
+char *file = __FILE__;
+int lines[] = {__LINE__, HERE, __COUNTER__, __COUNTER__};
+#endif
+#if defined(__x86_64__) && defined(__linux__) && !defined(_WIN32)
+int level = LEVEL + EXTRA;
+#endif
+#ifdef GONE
+int gone;
+#endif
+"#;
+        let expected = r#"# 1 "test.c"
+
+
+
+char *file = "test.c";
+int lines[] = {5, 5, 0, 1};
+
+
+int level = 3 + 1;
+
+
+
+
+"#;
+        let include_paths = IncludePaths::new();
+        let mut macros = target_macros("x86_64-unknown-linux-gnu");
+        macros.extend([
+            CommandLineMacro::define("LEVEL=3"),
+            CommandLineMacro::define("GONE"),
+            CommandLineMacro::define("EXTRA"),
+            CommandLineMacro::Undefine("GONE".to_string()),
+        ]);
+        assert_eq!(
+            expected,
+            preprocess(code, "test.c", &include_paths, &macros)
+                .unwrap()
+                .0
+        );
+    }
+
+    #[test]
+    fn predefine_target_macros() {
+        let names = |triple| {
+            target_macros(triple)
+                .into_iter()
+                .map(|macro_| match macro_ {
+                    CommandLineMacro::Define(name, _) => name,
+                    CommandLineMacro::Undefine(_) => unreachable!(),
+                })
+                .collect::<Vec<_>>()
+        };
+        assert!(names("x86_64-pc-linux-gnu").contains(&"__gnu_linux__".to_string()));
+        assert!(names("x86_64-linux-gnu").contains(&"__LP64__".to_string()));
+        assert!(names("aarch64-apple-darwin").contains(&"__APPLE__".to_string()));
+        assert_eq!(
+            names("i686-pc-windows-msvc"),
+            vec!["__i386__", "__i386", "_WIN32"]
+        );
     }
 
     #[test]
@@ -195,14 +766,69 @@ int main() {
     return c;
 }
 "#;
-        let expected = r#"
+        let expected = r#"# 1 "test.c"
+
+
+
 int main() {
     int a = 0;
     int c = a;
     return c;
 }
 "#;
-        let include_dirs = vec![];
-        assert_eq!(expected, preprocess(code, &include_dirs).unwrap());
+        let include_paths = IncludePaths::new();
+        assert_eq!(
+            expected,
+            preprocess(code, "test.c", &include_paths, &[]).unwrap().0
+        );
+    }
+
+    #[test]
+    fn process_token_operators() {
+        // the examples of the C standard
+        let code = r#"#define hash_hash # ## #
+#define mkstr(a) # a
+#define in_between(a) mkstr(a)
+#define join(c, d) in_between(c hash_hash d)
+char p[] = join(x, y);
+#define t(x,y,z) x ## y ## z
+int j[] = { t(1,2,3), t(,4,5), t(6,,7), t(,,) };
+#define str(s) # s
+const char *s = str( "a\n" '\'' );
+#define f(a) a*g
+#define g(a) f(a)
+int k = f(2)(9);
+#define debug(format, ...) printf(format, ## __VA_ARGS__)
+debug("x"); debug("%d", 1);
+"#;
+        let expected = r#"# 1 "test.c"
+
+
+
+
+char p[] = "x ## y";
+
+int j[] = { 123, 45, 67, };
+
+const char *s = "\"a\\n\" '\\''";
+
+
+int k = 2*9*g;
+
+printf("x"); printf("%d", 1);
+"#;
+        let include_paths = IncludePaths::new();
+        assert_eq!(
+            expected,
+            preprocess(code, "test.c", &include_paths, &[]).unwrap().0
+        );
+
+        let code = "#define cat(a, b) a ## b\nint x = cat(+, /);\n";
+        let error = preprocess(code, "test.c", &include_paths, &[]).unwrap_err();
+        assert_eq!(
+            "pasting `+` and `/` does not give a valid preprocessing token",
+            error.message
+        );
+        assert_eq!(Some((2, 13)), error.line_col());
     }
 }
@@ -1,7 +1,25 @@
 pub fn phase2(code: &str) -> String {
-    let mut code = code.replace("\\\n", "");
-    if !code.ends_with('\n') {
-        code.push('\n');
+    // the lines joined by a backslash are kept after the logical line, as empty lines
+    let mut result = String::with_capacity(code.len());
+    let mut joined_lines = 0;
+    let mut lines = code.split('\n').peekable();
+    while let Some(line) = lines.next() {
+        match line.strip_suffix('\\') {
+            Some(line) if lines.peek().is_some() => {
+                result.push_str(line);
+                joined_lines += 1;
+            }
+            _ => {
+                result.push_str(line);
+                if lines.peek().is_some() {
+                    result.push_str(&"\n".repeat(joined_lines + 1));
+                    joined_lines = 0;
+                }
+            }
+        }
     }
-    code
+    if !result.ends_with('\n') {
+        result.push('\n');
+    }
+    result
 }
@@ -4,18 +4,47 @@ use super::*;
 #[grammar = "./preprocess/phase3.pest"]
 struct Phase3Parser;
 
-pub fn phase3(code: &str) -> Result<String, Box<dyn Error>> {
+pub fn phase3(code: &str) -> Result<String, Box<pest::error::Error<Rule>>> {
     let pairs = match Phase3Parser::parse(Rule::cc99, code)?.next() {
         Some(p) => p.into_inner(),
         None => unreachable!(),
     };
     let mut result = String::new();
+    // the lines of a comment are kept where it is, except in a directive, which has to stay on
+    // one line, so they are kept after it as empty lines
+    let mut comment_lines = 0;
+    let mut line_start = true;
+    let mut directive = false;
     for pair in pairs {
         match pair.as_rule() {
-            Rule::cpp_comment => result.push('\n'),
-            Rule::c_comment => result.push(' '),
-            _ => result.push_str(pair.as_str()),
+            Rule::cpp_comment => {
+                result.push_str(&"\n".repeat(comment_lines + 1));
+                comment_lines = 0;
+                (line_start, directive) = (true, false);
+            }
+            Rule::c_comment => {
+                let lines = pair.as_str().matches('\n').count();
+                match (lines, directive) {
+                    (0, _) | (_, true) => {
+                        result.push(' ');
+                        comment_lines += lines;
+                    }
+                    (lines, false) => result.push_str(&"\n".repeat(lines)),
+                }
+            }
+            _ if pair.as_str() == "\n" => {
+                result.push_str(&"\n".repeat(comment_lines + 1));
+                comment_lines = 0;
+                (line_start, directive) = (true, false);
+            }
+            _ => {
+                if line_start && !pair.as_str().trim().is_empty() {
+                    (line_start, directive) = (false, pair.as_str() == "#");
+                }
+                result.push_str(pair.as_str());
+            }
         }
     }
+    result.push_str(&"\n".repeat(comment_lines));
     Ok(result)
 }
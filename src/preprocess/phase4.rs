@@ -1,54 +1,241 @@
+use super::condition::evaluate_condition;
+use super::expand::Expander;
+use super::source_map::{quote, unquote};
+use super::token::*;
 use super::*;
-use escape_string::escape;
-use pest::error::ErrorVariant;
 use pest::iterators::{Pair, Pairs};
-use pest::Span;
-use serde::Serialize;
+use pest::RuleType;
 use std::collections::HashSet;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 #[derive(Parser)]
 #[grammar = "./preprocess/phase4.pest"]
-struct Phase4Parser;
+pub struct Phase4Parser;
 
+#[derive(Clone)]
 pub enum Macro<'a> {
-    Object(Option<Pair<'a, Rule>>),
+    Object(
+        /// body
+        Vec<Token<'a>>,
+    ),
     Function(
-        /// arguments
-        Vec<String>,
+        /// parameters, ending with `__VA_ARGS__` when it is variadic
+        Vec<&'a str>,
         /// is variadic
         bool,
         /// body
-        Option<Vec<Replacement>>,
+        Vec<Token<'a>>,
     ),
 }
 
-#[derive(Serialize, Debug, PartialEq, Clone)]
-pub enum Replacement {
-    Text(String),
-    Parameter(String),
-    Stringizing(String),
-    Concat(String, String),
-    VAArg,
+/// The maximum depth of nested `#include`, as GCC has.
+const MAX_INCLUDE_DEPTH: usize = 200;
+
+/// The state of the preprocessor, shared by a file and all the files it includes.
+#[derive(Default)]
+pub struct State<'a> {
+    pub defined: HashMap<String, Macro<'a>>,
+    /// the files being preprocessed, the innermost last
+    pub include_stack: Vec<String>,
+    /// the lines of the `#include` of each file in the file including it, as set by `#line`
+    include_lines: Vec<isize>,
+    /// the code of the innermost file, where errors are shown
+    pub code: &'a str,
+    /// the offset and the line of the last group expanded in the innermost file, from where the
+    /// line of the next one is counted
+    cursor: (usize, usize),
+    /// the path of the innermost file
+    pub file: PathBuf,
+    /// the directory of the innermost file, where the files it includes with quotes are searched
+    /// first
+    pub directory: PathBuf,
+    /// the index of the directory where the innermost file was found among the directories of
+    /// `#include <file>`, after which `#include_next` searches
+    include_directory: Option<usize>,
+    /// the macros guarding the files wrapped in `#ifndef`
+    include_guards: HashMap<PathBuf, String>,
+    /// the files with `#pragma once`
+    pragma_once: HashSet<PathBuf>,
+    /// whether the innermost file is a system header or is included by one
+    is_system: bool,
+    /// the files read, the first one first
+    pub dependencies: Vec<Dependency>,
+    /// the paths of the files of `dependencies`, which are listed once
+    read_files: HashSet<PathBuf>,
+    /// the line of the group being expanded, in the innermost file
+    line: usize,
+    /// the difference between the lines set by `#line` and the lines of the innermost file
+    line_offset: isize,
+    /// the value of the next `__COUNTER__`
+    counter: usize,
+}
+
+impl<'a> State<'a> {
+    fn is_defined(&self, name: &str) -> bool {
+        self.defined.contains_key(name) || matches!(name, "__FILE__" | "__LINE__" | "__COUNTER__")
+    }
+
+    /// The expansion of the predefined macros whose value depends on where they are.
+    pub fn expand_dynamic_macro(&mut self, name: &str) -> Option<String> {
+        match name {
+            "__FILE__" => Some(format!(
+                "\"{}\"",
+                quote(self.include_stack.last().map_or("", String::as_str))
+            )),
+            "__LINE__" => Some(self.presumed_line(self.line).to_string()),
+            "__COUNTER__" => {
+                self.counter += 1;
+                Some((self.counter - 1).to_string())
+            }
+            _ => None,
+        }
+    }
+
+    /// Point an error to the innermost file: at its span when it is in the code of the file, or at
+    /// the line being expanded when it is in the expansion of a macro.
+    fn locate(&self, error: Box<dyn Error>) -> PreprocessError {
+        let error = match error.downcast::<PreprocessError>() {
+            Ok(error) => return *error,
+            Err(error) => error,
+        };
+        let code = self.code.as_ptr() as usize..self.code.as_ptr() as usize + self.code.len();
+        let line_start = self
+            .code
+            .split_inclusive('\n')
+            .take(self.line.saturating_sub(1))
+            .map(str::len)
+            .sum::<usize>();
+        let line_end = self.code[line_start..]
+            .find('\n')
+            .map_or(self.code.len(), |end| line_start + end);
+        let (message, span) = match error.downcast::<SpanError>() {
+            Ok(error) if code.start <= error.text.start && error.text.end <= code.end => (
+                error.message,
+                error.text.start - code.start..error.text.end - code.start,
+            ),
+            Ok(error) => (error.message, line_start..line_end),
+            Err(error) => match error.downcast::<pest::error::Error<Rule>>() {
+                Ok(error) => (pest_message(&error), line_start..line_end),
+                Err(error) => (error.to_string(), line_start..line_end),
+            },
+        };
+        PreprocessError {
+            included_from: self.included_from(),
+            ..PreprocessError::new(message).at(
+                self.include_stack.last().map_or("", String::as_str),
+                self.code,
+                span,
+            )
+        }
+    }
+
+    /// An error of a pest parser on `code` of the innermost file.
+    pub fn parse_error<R: RuleType>(
+        &self,
+        error: &pest::error::Error<R>,
+        code: &str,
+    ) -> PreprocessError {
+        PreprocessError {
+            included_from: self.included_from(),
+            ..PreprocessError::from_pest(
+                error,
+                self.include_stack.last().map_or("", String::as_str),
+                code,
+            )
+        }
+    }
+
+    /// The files including the innermost file and the lines of their `#include`, the innermost
+    /// first.
+    pub fn included_from(&self) -> Vec<(String, usize)> {
+        self.include_stack
+            .iter()
+            .zip(self.include_lines.iter())
+            .rev()
+            .map(|(file, &line)| (file.clone(), line.max(0) as usize))
+            .collect()
+    }
+
+    /// Print a warning at `text` of the innermost file.
+    fn warn(&self, message: String, text: &str) {
+        PreprocessError {
+            is_warning: true,
+            ..self.locate(SpanError::at(message, text))
+        }
+        .emit();
+    }
+
+    /// Set the line to the one of the group at `offset` of the innermost file.
+    fn move_to(&mut self, offset: usize) {
+        let (last_offset, last_line) = self.cursor;
+        self.line = match last_offset <= offset {
+            true => last_line + self.code[last_offset..offset].matches('\n').count(),
+            false => last_line - self.code[offset..last_offset].matches('\n').count(),
+        };
+        self.cursor = (offset, self.line);
+    }
+
+    /// Set the line to the one of `token` when it is in the innermost file, and not made by the
+    /// preprocessor.
+    pub fn move_to_token(&mut self, token: &Token) {
+        let (start, code) = (token.text.as_ptr() as usize, self.code.as_ptr() as usize);
+        if code <= start && start < code + self.code.len() {
+            self.move_to(start - code);
+        }
+    }
+
+    /// The line of the innermost file as set by `#line`.
+    fn presumed_line(&self, line: usize) -> isize {
+        line as isize + self.line_offset
+    }
+
+    /// Record the innermost file, read from `path`, as a dependency.
+    pub fn add_dependency(&mut self, path: String) {
+        if self.read_files.insert(self.file.clone()) {
+            self.dependencies.push(Dependency {
+                path,
+                is_system: self.is_system,
+            });
+        }
+    }
+
+    /// The line marker telling that the line after it is `line` of the innermost file, with the
+    /// flag `1` when entering a file and `2` when returning to it, as GCC emits.
+    pub fn line_marker(&self, line: usize, flag: Option<u8>) -> String {
+        let file = quote(self.include_stack.last().map_or("", String::as_str));
+        match flag {
+            Some(flag) => format!("# {} \"{}\" {}\n", self.presumed_line(line), file, flag),
+            None => format!("# {} \"{}\"\n", self.presumed_line(line), file),
+        }
+    }
 }
 
 pub fn phase4<'a>(
     code: &'a str,
-    defined: &mut HashMap<String, Macro<'a>>,
-    include_dirs: &[&str],
+    state: &mut State<'a>,
+    include_paths: &IncludePaths,
     code_arena: &'a Arena<String>,
-) -> Result<String, Box<dyn Error>> {
-    let pairs = match Phase4Parser::parse(Rule::cc99, code)?.next() {
-        Some(p) => p.into_inner(),
-        None => unreachable!(),
+) -> Result<String, PreprocessError> {
+    state.code = code;
+    state.cursor = (0, 1);
+    let pairs = match Phase4Parser::parse(Rule::cc99, code) {
+        Ok(mut pairs) => pairs.next().unwrap().into_inner(),
+        Err(error) => {
+            return Err(match unbalanced_conditional(code) {
+                Some((message, directive)) => state.locate(SpanError::at(message, directive)),
+                None => state.parse_error(&error, code),
+            })
+        }
     };
     let mut result = String::new();
-    for pair in pairs {
+    let mut pairs = pairs;
+    while let Some(pair) = pairs.next() {
         match pair.as_rule() {
             Rule::group => {
+                let groups = text_lines(pair, &mut pairs);
                 result.push_str(
-                    build_group(pair, defined, include_dirs, Default::default(), code_arena)?
-                        .as_str(),
+                    &build_file_group(groups, state, include_paths, code_arena)
+                        .map_err(|error| state.locate(error))?,
                 );
             }
             Rule::WHITESPACE | Rule::EOI => {
@@ -60,194 +247,398 @@ pub fn phase4<'a>(
     Ok(result)
 }
 
+/// The first misplaced conditional directive of `code` and its name, like GCC reports them, as
+/// the grammar only tells which rules it expected.
+fn unbalanced_conditional(code: &str) -> Option<(String, &str)> {
+    // the conditionals still open, with whether their `#else` was seen
+    let mut open: Vec<(&str, bool)> = vec![];
+    for line in code.lines() {
+        let rest = match line.trim_start_matches([' ', '\t']).strip_prefix('#') {
+            Some(rest) => rest.trim_start_matches([' ', '\t']),
+            None => continue,
+        };
+        let directive = &rest[..rest
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .unwrap_or(rest.len())];
+        match (directive, open.last_mut()) {
+            ("if" | "ifdef" | "ifndef", _) => open.push((directive, false)),
+            ("elif" | "else" | "endif", None) => {
+                return Some((format!("#{} without #if", directive), directive))
+            }
+            ("elif" | "else", Some((_, true))) => {
+                return Some((format!("#{} after #else", directive), directive))
+            }
+            ("else", Some((_, seen_else))) => *seen_else = true,
+            ("endif", Some(_)) => {
+                open.pop();
+            }
+            _ => {}
+        }
+    }
+    open.pop()
+        .map(|(directive, _)| (format!("unterminated #{}", directive), directive))
+}
+
+/// Whether `group` is a line of text, rather than a directive.
+fn is_text_line(group: &Pair<Rule>) -> bool {
+    let line = group.clone().into_inner().next().unwrap();
+    line.as_rule() == Rule::token_string_line
+        && line
+            .into_inner()
+            .next()
+            .is_none_or(|pair| pair.as_rule() != Rule::empty_line)
+}
+
+/// The groups from `group`: when it is a line of text, the lines of text after it up to the next
+/// directive too, which are expanded together, since the invocation of a macro may span lines.
+fn text_lines<'a>(group: Pair<'a, Rule>, pairs: &mut Pairs<'a, Rule>) -> Vec<Pair<'a, Rule>> {
+    let mut groups = vec![group];
+    if !is_text_line(&groups[0]) {
+        return groups;
+    }
+    loop {
+        let mut rest = pairs.clone();
+        match rest.find(|pair| pair.as_rule() != Rule::WHITESPACE) {
+            Some(pair) if pair.as_rule() == Rule::group && is_text_line(&pair) => {
+                groups.push(pair);
+                *pairs = rest;
+            }
+            _ => return groups,
+        }
+    }
+}
+
+/// Expand a group of the file, or lines of text in a row. When the lines of the result are not the
+/// ones of the code, like when `_Pragma` has split a line, the line after them is marked. Errors
+/// are pointed to the file.
+fn build_file_group<'a>(
+    mut groups: Vec<Pair<'a, Rule>>,
+    state: &mut State<'a>,
+    include_paths: &IncludePaths,
+    code_arena: &'a Arena<String>,
+) -> Result<String, Box<dyn Error>> {
+    let (start, end) = (
+        groups[0].as_span().start(),
+        groups.last().unwrap().as_span().end(),
+    );
+    state.move_to(start);
+    let line = state.line;
+    let lines = state.code[start..end].matches('\n').count();
+    let is_text = is_text_line(&groups[0]);
+    let mut result = match is_text {
+        true => build_text_lines(groups, state, code_arena),
+        false => build_group(groups.pop().unwrap(), state, include_paths, code_arena),
+    }
+    .map_err(|error| state.locate(error))?;
+    if is_text && result.matches('\n').count() != lines {
+        result.push_str(&state.line_marker(line + lines, None));
+    }
+    Ok(result)
+}
+
 fn build_group<'a>(
     pair: Pair<'a, Rule>,
-    defined: &mut HashMap<String, Macro<'a>>,
-    include_dirs: &[&str],
-    mut extracting_macro: HashSet<String>,
+    state: &mut State<'a>,
+    include_paths: &IncludePaths,
     code_arena: &'a Arena<String>,
 ) -> Result<String, Box<dyn Error>> {
-    let mut modified = false;
     let mut result = String::new();
     for token in pair.into_inner() {
         match token.as_rule() {
             Rule::control_line => {
-                modified = true;
-                result.push_str(&build_control_line(token, defined, include_dirs)?);
-            }
-            Rule::token_string_line => {
-                result.push_str(
-                    build_token_string_line(token, defined, &mut extracting_macro, &mut modified)?
-                        .as_str(),
-                );
+                result.push_str(&build_control_line(
+                    token,
+                    state,
+                    include_paths,
+                    code_arena,
+                )?);
             }
             Rule::conditional => {
-                modified = true;
-                result.push_str(
-                    build_conditional(token, defined, include_dirs, &extracting_macro, code_arena)?
-                        .as_str(),
-                );
+                result.push_str(&build_conditional(token, state, include_paths, code_arena)?);
             }
+            // the null directive, a `#` alone
+            Rule::token_string_line => result.push('\n'),
             _ => unreachable!(),
         }
     }
-    match modified {
-        true => {
-            let result = code_arena.alloc(result).as_str();
-            let pairs = match Phase4Parser::parse(Rule::cc99, result).unwrap().next() {
-                Some(p) => p.into_inner(),
-                None => unreachable!(),
-            };
-            let mut result = String::new();
-            for pair in pairs {
-                match pair.as_rule() {
-                    Rule::group => {
-                        result.push_str(
-                            build_group(
-                                pair,
-                                defined,
-                                include_dirs,
-                                extracting_macro.clone(),
-                                code_arena,
-                            )?
-                            .as_str(),
-                        );
-                    }
-                    Rule::WHITESPACE | Rule::EOI => {
-                        result.push_str(pair.as_str());
-                    }
-                    _ => unreachable!(),
-                }
-            }
-            Ok(result)
-        }
-        false => Ok(result),
-    }
+    Ok(result)
 }
 
 fn build_control_line<'a>(
     pair: Pair<'a, Rule>,
-    defined: &mut HashMap<String, Macro<'a>>,
-    include_dirs: &[&str],
+    state: &mut State<'a>,
+    include_paths: &IncludePaths,
+    code_arena: &'a Arena<String>,
 ) -> Result<String, Box<dyn Error>> {
     let pair = pair.into_inner().next().unwrap();
-    let span = pair.as_span();
+    let text = pair.as_str();
 
-    let mut search_current_first = false;
+    let mut quoted = false;
+    let mut include_next = false;
     let mut path: Option<&str> = None;
 
     match pair.as_rule() {
         Rule::function_like_macro => {
-            build_function_like_macro(pair, defined)?;
+            build_function_like_macro(pair, &mut state.defined)?;
         }
         Rule::object_like_macro => {
-            build_object_like_macro(pair, defined)?;
+            build_object_like_macro(pair, &mut state.defined)?;
         }
-        Rule::current_include => {
-            search_current_first = true;
+        Rule::current_include | Rule::standard_include => {
+            quoted = pair.as_rule() == Rule::current_include;
             for token in pair.into_inner() {
-                if token.as_rule() == Rule::path_spec {
-                    path = Some(token.as_str());
+                match token.as_rule() {
+                    Rule::path_spec => path = Some(token.as_str()),
+                    Rule::include_next__ => include_next = true,
+                    _ => {}
                 }
             }
         }
-        Rule::standard_include => {
+        Rule::line_info => {
             for token in pair.into_inner() {
-                if token.as_rule() == Rule::path_spec {
-                    path = Some(token.as_str());
+                match token.as_rule() {
+                    Rule::digit_sequence => {
+                        let line = token.as_str().parse::<isize>().map_err(|_| {
+                            SpanError::at("line number out of range".to_string(), token.as_str())
+                        })?;
+                        // the line after the directive is `line`
+                        state.line_offset = line - state.line as isize - 1;
+                    }
+                    Rule::filename => {
+                        if let Some(file) = state.include_stack.last_mut() {
+                            *file = unquote(token.as_str());
+                        }
+                    }
+                    Rule::line__ | Rule::WHITESPACE => {}
+                    _ => unreachable!(),
                 }
             }
+            return Ok(state.line_marker(state.line + 1, None));
         }
-        Rule::line_info => unimplemented!(),
         Rule::undef_macro => {
             for token in pair.into_inner() {
                 match token.as_rule() {
+                    Rule::undef__ | Rule::WHITESPACE => {}
                     Rule::identifier => {
-                        defined.remove(token.as_str());
+                        state.defined.remove(token.as_str());
                     }
                     _ => unreachable!(),
                 }
             }
         }
-        Rule::error_macro => {}
+        Rule::error_macro => {
+            return Err(SpanError::at(
+                format!("#error {}", directive_message(pair)),
+                text,
+            ));
+        }
+        Rule::warning_macro => {
+            state.warn(format!("#warning {}", directive_message(pair)), text);
+        }
         Rule::pragma_macro => {
-            return Err(Box::new(pest::error::Error::<Rule>::new_from_span(
-                ErrorVariant::CustomError {
-                    message: "unsupported pragma macro".to_string(),
-                },
-                span,
-            )));
-            // TODO: no pragma macro now
+            let tokens = pair
+                .into_inner()
+                .find(|token| token.as_rule() == Rule::token_string)
+                .map(tokenize)
+                .unwrap_or_default();
+            let tokens = tokens.iter().map(|token| token.text).collect::<Vec<_>>();
+            return Ok(build_pragma(&tokens, text, state));
         }
         _ => unreachable!(),
     }
 
     if let Some(path) = path {
-        let mut complete_path: Option<String> = None;
+        // `#include_next` searches after the directory of the current file, or like `#include` when
+        // the current file was not found in the directories of `#include <file>`
+        let found = match (include_next, state.include_directory) {
+            (true, Some(directory)) => include_paths.find_next(path, directory + 1),
+            _ => include_paths.find(path, quoted, &state.directory),
+        };
 
-        // search current
-        if Path::new(path).exists() {
-            complete_path = Some(path.to_string());
-        } else {
-            for &include_dir in include_dirs {
-                let tmp_path = format!("{}/{}", include_dir, path);
-                if Path::new(&tmp_path).exists() {
-                    complete_path = Some(tmp_path);
-                    break;
+        if let Some(found) = found {
+            let file = fs::canonicalize(&found.path).unwrap_or_else(|_| found.path.clone());
+            if state.pragma_once.contains(&file) {
+                return Ok("\n".to_string());
+            }
+            // a file wrapped in `#ifndef` adds nothing once its guard is defined
+            if let Some(guard) = state.include_guards.get(&file) {
+                if state.defined.contains_key(guard) {
+                    return Ok("\n".to_string());
                 }
             }
-        }
-
-        // search standard
-        if !search_current_first {
-            let tmp_path = format!("include/{}", path);
-            if Path::new(&tmp_path).exists() {
-                complete_path = Some(tmp_path);
+            if state.include_stack.len() > MAX_INCLUDE_DEPTH {
+                return Err(SpanError::at(
+                    format!(
+                        "#include nested depth {} exceeds maximum of {}",
+                        state.include_stack.len(),
+                        MAX_INCLUDE_DEPTH
+                    ),
+                    text,
+                ));
             }
-        }
 
-        if let Some(path) = complete_path {
-            let code = fs::read_to_string(&path)
-                .unwrap_or_else(|_| panic!("Unable to read source file {}", path));
-            let code = phase2(&code);
-            let code = phase3(&code)?;
+            let path = found.path.to_string_lossy().to_string();
+            let code = match fs::read_to_string(&path) {
+                Ok(code) => phase2(&code),
+                Err(error) => return Err(SpanError::at(format!("{}: {}", path, error), text)),
+            };
 
-            let mut defined: HashMap<String, Macro> = Default::default();
-            let code_arena = Arena::new();
-            let code = phase4(&code, &mut defined, include_dirs, &code_arena)?;
-            return Ok(code);
+            let (line, line_offset) = (state.line, state.line_offset);
+            let (includer_code, cursor) = (state.code, state.cursor);
+            let includer = (
+                std::mem::replace(&mut state.file, file),
+                std::mem::replace(
+                    &mut state.directory,
+                    found
+                        .path
+                        .parent()
+                        .map(Path::to_path_buf)
+                        .unwrap_or_default(),
+                ),
+                std::mem::replace(&mut state.include_directory, found.directory),
+                state.is_system,
+            );
+            state.is_system |= found.is_system;
+            state.add_dependency(path.clone());
+            state.include_lines.push(state.presumed_line(line));
+            state.include_stack.push(path);
+            state.line_offset = 0;
+            let result = build_included_file(&code, state, include_paths, code_arena);
+            state.include_stack.pop();
+            state.include_lines.pop();
+            (
+                state.file,
+                state.directory,
+                state.include_directory,
+                state.is_system,
+            ) = includer;
+            state.line = line;
+            state.line_offset = line_offset;
+            (state.code, state.cursor) = (includer_code, cursor);
+            let mut result = result?;
+            result.push_str(&state.line_marker(line + 1, Some(2)));
+            return Ok(result);
         } else {
-            return Err(Box::new(pest::error::Error::<Rule>::new_from_span(
-                ErrorVariant::CustomError {
-                    message: "couldn't find such a header file".to_string(),
-                },
-                span,
-            )));
+            return Err(SpanError::at(
+                format!("header file `{}` not found", path),
+                text,
+            ));
+        }
+    }
+
+    // the line of a directive is kept empty
+    Ok("\n".to_string())
+}
+
+/// The code of an included file, whose state is already set, after the line marker entering it.
+fn build_included_file<'a>(
+    code: &str,
+    state: &mut State<'a>,
+    include_paths: &IncludePaths,
+    code_arena: &'a Arena<String>,
+) -> Result<String, PreprocessError> {
+    // the macros of the file refer to its code, which lives as long as the state
+    let code = match phase3(code) {
+        Ok(code) => code_arena.alloc(code).as_str(),
+        Err(error) => return Err(state.parse_error(&error, code)),
+    };
+    if let Some(guard) = include_guard(code) {
+        state.include_guards.insert(state.file.clone(), guard);
+    }
+    let mut result = state.line_marker(1, Some(1));
+    result.push_str(&phase4(code, state, include_paths, code_arena)?);
+    Ok(result)
+}
+
+/// The text of `#error` or `#warning`.
+fn directive_message(pair: Pair<'_, Rule>) -> &str {
+    pair.into_inner()
+        .find(|token| token.as_rule() == Rule::message)
+        .map_or("", |message| message.as_str().trim_end())
+}
+
+/// Apply `#pragma once`, and pass `#pragma pack` to the compiler, which lays out the structs with
+/// it. The other pragmas are ignored with a warning. `text` is the directive or the `_Pragma`
+/// operator.
+fn build_pragma(tokens: &[&str], text: &str, state: &mut State<'_>) -> String {
+    match tokens {
+        [] => {}
+        ["once"] => {
+            state.pragma_once.insert(state.file.clone());
+        }
+        ["pack", "(", arguments @ .., ")"] => {
+            let alignment = |argument: &str| matches!(argument, "1" | "2" | "4" | "8" | "16");
+            match arguments {
+                [] | ["push"] | ["pop"] => {}
+                [n] | ["push", ",", n] if alignment(n) => {}
+                _ => {
+                    state.warn("malformed #pragma pack, ignored".to_string(), text);
+                    return "\n".to_string();
+                }
+            }
+            return format!("#pragma pack({})\n", arguments.join("").replace(',', ", "));
         }
+        [name, ..] => state.warn(format!("unknown #pragma {}, ignored", name), text),
     }
+    "\n".to_string()
+}
 
-    Ok("".to_string())
+/// The macro guarding `code`, when all of it is in a single `#ifndef` without `#elif` or `#else`.
+/// The errors of the code are left to its expansion.
+fn include_guard(code: &str) -> Option<String> {
+    let mut groups = Phase4Parser::parse(Rule::cc99, code)
+        .ok()?
+        .next()
+        .unwrap()
+        .into_inner()
+        .filter(|pair| pair.as_rule() == Rule::group)
+        .map(|pair| pair.into_inner().next().unwrap())
+        // without empty lines
+        .filter(|pair| {
+            pair.as_rule() != Rule::token_string_line
+                || pair
+                    .clone()
+                    .into_inner()
+                    .any(|token| token.as_rule() == Rule::token_string)
+        });
+    let conditional = match (groups.next(), groups.next()) {
+        (Some(conditional), None) if conditional.as_rule() == Rule::conditional => conditional,
+        _ => return None,
+    };
+    let mut lines = conditional
+        .into_inner()
+        .filter(|pair| pair.as_rule() != Rule::group && pair.as_rule() != Rule::WHITESPACE);
+    let if_line = lines.next().unwrap();
+    if lines.next().unwrap().as_rule() != Rule::endif_line {
+        return None;
+    }
+    let mut tokens = if_line
+        .into_inner()
+        .filter(|token| token.as_rule() != Rule::WHITESPACE);
+    match (tokens.next().unwrap().as_rule(), tokens.next()) {
+        (Rule::ifndef__, Some(identifier)) if identifier.as_rule() == Rule::identifier => {
+            Some(identifier.as_str().to_string())
+        }
+        _ => None,
+    }
 }
 
 fn build_object_like_macro<'a>(
     pair: Pair<'a, Rule>,
     defined: &mut HashMap<String, Macro<'a>>,
 ) -> Result<(), Box<dyn Error>> {
-    let mut identifier: String = Default::default();
+    let mut name = "";
+    let mut body = vec![];
     for token in pair.into_inner() {
         match token.as_rule() {
             Rule::define__ | Rule::WHITESPACE => {}
-            Rule::identifier => {
-                identifier = token.as_str().to_string();
-            }
-            Rule::token_string => {
-                defined.insert(identifier, Macro::Object(Some(token)));
-                return Ok(());
-            }
+            Rule::identifier => name = macro_name(token)?,
+            Rule::token_string => body = tokenize(token),
             _ => unreachable!(),
         }
     }
-    defined.insert(identifier, Macro::Object(None));
+    check_macro_body(&body, &[], false, false)?;
+    defined.insert(name.to_string(), Macro::Object(body));
     Ok(())
 }
 
@@ -255,222 +646,186 @@ fn build_function_like_macro<'a>(
     pair: Pair<'a, Rule>,
     defined: &mut HashMap<String, Macro<'a>>,
 ) -> Result<(), Box<dyn Error>> {
-    let mut identifier: Option<String> = None;
-    let mut params: Vec<String> = Default::default();
+    let mut name: Option<&str> = None;
+    let mut params = vec![];
     let mut is_variadic = false;
-
+    let mut body = vec![];
     for token in pair.into_inner() {
         match token.as_rule() {
             Rule::define__ | Rule::WHITESPACE => {}
-            Rule::identifier => {
-                if identifier.is_none() {
-                    identifier = Some(token.as_str().to_string());
-                } else {
-                    params.push(token.as_str().to_string());
+            Rule::identifier => match name {
+                None => name = Some(macro_name(token)?),
+                Some(_) if params.contains(&token.as_str()) => {
+                    return Err(SpanError::at(
+                        format!("duplicate macro parameter `{}`", token.as_str()),
+                        token.as_str(),
+                    ));
                 }
-            }
+                Some(_) => params.push(token.as_str()),
+            },
             Rule::variadic_ => {
                 is_variadic = true;
+                params.push("__VA_ARGS__");
             }
-            Rule::token_string => {
-                let body = build_function_macro_body(token, &params, is_variadic)?;
-                defined.insert(
-                    identifier.unwrap(),
-                    Macro::Function(params, is_variadic, Some(body)),
-                );
-                return Ok(());
-            }
+            Rule::token_string => body = tokenize(token),
             _ => unreachable!(),
         }
     }
+    check_macro_body(&body, &params, is_variadic, true)?;
     defined.insert(
-        identifier.unwrap(),
-        Macro::Function(params, is_variadic, None),
+        name.unwrap().to_string(),
+        Macro::Function(params, is_variadic, body),
     );
     Ok(())
 }
 
-fn build_function_macro_body(
-    pair: Pair<'_, Rule>,
-    params: &[String],
+fn macro_name(pair: Pair<'_, Rule>) -> Result<&str, Box<dyn Error>> {
+    match pair.as_str() {
+        "defined" => Err(SpanError::at(
+            "`defined` cannot be used as a macro name".to_string(),
+            pair.as_str(),
+        )),
+        name => Ok(name),
+    }
+}
+
+/// Check that `##` is between two tokens, and in a function-like macro that `#` is followed by a
+/// parameter, as the macro is expanded.
+fn check_macro_body(
+    body: &[Token],
+    params: &[&str],
     is_variadic: bool,
-) -> Result<Vec<Replacement>, Box<dyn Error>> {
-    let mut result: Vec<Replacement> = Default::default();
-    let mut remaining_text: Option<String> = None;
-    for token in pair.into_inner() {
-        let token = token.into_inner().next().unwrap();
-        match token.as_rule() {
-            Rule::macro_expression => {
-                if let Some(text) = remaining_text {
-                    result.push(Replacement::Text(text));
-                    remaining_text = None;
-                }
-                let token = token.into_inner().next().unwrap();
-                let span = token.as_span();
-                match token.as_rule() {
-                    Rule::token_pasting => {
-                        let mut token_iter = token.into_inner();
-                        let lhs = token_iter.next().unwrap().as_str();
-                        let rhs = token_iter.next().unwrap().as_str();
-                        result.push(Replacement::Concat(lhs.to_string(), rhs.to_string()));
-                    }
-                    Rule::stringizing => {
-                        let token = token.into_inner().next().unwrap().as_str().to_string();
-                        if token == "__VA_ARGS__" {
-                            if is_variadic {
-                                result.push(Replacement::VAArg);
-                            } else {
-                                return Err(Box::new(pest::error::Error::<Rule>::new_from_span(
-                                    ErrorVariant::CustomError {
-                                        message: "__VA_ARGS__ is not allowed in this macro"
-                                            .to_string(),
-                                    },
-                                    span,
-                                )));
-                            }
-                        } else if params.contains(&token) {
-                            result.push(Replacement::Stringizing(token));
-                        } else {
-                            return Err(Box::new(pest::error::Error::<Rule>::new_from_span(
-                                ErrorVariant::CustomError {
-                                    message: "unknown parameter".to_string(),
-                                },
-                                span,
-                            )));
-                        }
-                    }
-                    _ => unreachable!(),
-                }
-            }
-            Rule::keyword | Rule::identifier => {
-                let token = token.as_str().to_string();
-                if params.contains(&token) {
-                    if let Some(text) = remaining_text {
-                        result.push(Replacement::Text(text));
-                        remaining_text = None;
-                    }
-                    result.push(Replacement::Parameter(token));
-                } else if remaining_text.is_some() {
-                    remaining_text.as_mut().unwrap().push_str(token.as_str());
-                } else {
-                    remaining_text = Some(token.as_str().to_string());
-                }
-            }
-            Rule::string_literal | Rule::constant | Rule::punctuator | Rule::WHITESPACE => {
-                if remaining_text.is_some() {
-                    remaining_text.as_mut().unwrap().push_str(token.as_str());
-                } else {
-                    remaining_text = Some(token.as_str().to_string());
-                }
-            }
-            _ => unreachable!(),
-        }
+    is_function: bool,
+) -> Result<(), Box<dyn Error>> {
+    if let Some(token) = [body.first(), body.last()]
+        .into_iter()
+        .flatten()
+        .find(|token| token.is("##"))
+    {
+        return Err(SpanError::at(
+            "`##` cannot appear at either end of a macro expansion".to_string(),
+            token.text,
+        ));
     }
-    if let Some(remaining_text) = remaining_text {
-        result.push(Replacement::Text(remaining_text));
+    for (index, token) in body.iter().enumerate() {
+        if token.kind == Kind::Identifier && token.text == "__VA_ARGS__" && !is_variadic {
+            return Err(SpanError::at(
+                "__VA_ARGS__ is not allowed in this macro".to_string(),
+                token.text,
+            ));
+        }
+        let parameter = body
+            .get(index + 1)
+            .is_some_and(|next| next.kind == Kind::Identifier && params.contains(&next.text));
+        if is_function && token.is("#") && !parameter {
+            return Err(SpanError::at(
+                "`#` is not followed by a macro parameter".to_string(),
+                token.text,
+            ));
+        }
     }
-    Ok(result)
+    Ok(())
 }
 
-fn build_token_string_line<'a>(
-    pair: Pair<'a, Rule>,
-    defined: &mut HashMap<String, Macro<'a>>,
-    extracting_macro: &mut HashSet<String>,
-    modified: &mut bool,
+/// Expand lines of text in a row. The tokens starting a line have the newlines before it as
+/// their whitespace, which the expansion keeps.
+fn build_text_lines<'a>(
+    groups: Vec<Pair<'a, Rule>>,
+    state: &mut State<'a>,
+    code_arena: &'a Arena<String>,
 ) -> Result<String, Box<dyn Error>> {
-    let mut result = String::new();
-    for token in pair.into_inner() {
-        match token.as_rule() {
-            Rule::token_string => {
-                result.push_str(
-                    build_token_string(token, defined, extracting_macro, modified)?.as_str(),
-                );
-            }
-            Rule::empty_line | Rule::WHITESPACE => {
-                result.push_str(token.as_str());
+    let code = state.code;
+    let offset = |text: &str| text.as_ptr() as usize - code.as_ptr() as usize;
+    let end = groups.last().unwrap().as_span().end();
+    // the end of the last token
+    let mut last = groups[0].as_span().start();
+    let mut tokens = vec![];
+    for group in groups {
+        let pair = group.into_inner().next().unwrap();
+        let line = pair.as_str().trim_end();
+        let mut line_tokens = tokenize(pair);
+        // a line starting with `#` and a name which is not a directive, like `#foo`
+        if line_tokens.first().is_some_and(|first| first.is("#")) {
+            return Err(SpanError::at(
+                format!(
+                    "invalid preprocessing directive #{}",
+                    line_tokens.get(1).map_or("", |name| name.text)
+                ),
+                line,
+            ));
+        }
+        if let Some(first) = line_tokens.first_mut() {
+            // the whitespace at the end of the lines is left out
+            let space = &code[last..offset(first.text)];
+            if let Some(newline) = space.find('\n') {
+                first.space = &space[newline..];
             }
-            _ => unreachable!(),
         }
+        if let Some(token) = line_tokens.last() {
+            last = offset(token.text) + token.text.len();
+        }
+        tokens.extend(line_tokens);
     }
-    result.push('\n');
+
+    let tokens = Expander::new(state, code_arena).expand(tokens)?;
+    let mut result = String::new();
+    let mut rest = tokens.as_slice();
+    // the pragmas of the `_Pragma` operators are put on lines of their own
+    while let Some(index) = rest
+        .iter()
+        .position(|token| token.kind == Kind::Identifier && token.text == "_Pragma")
+    {
+        result.push_str(&render(&rest[..index]));
+        result.push('\n');
+        let pragma = pragma_operator(&rest[index..], code_arena)?;
+        result.push_str(&build_pragma(&pragma, rest[index].text, state));
+        rest = &rest[index + 4..];
+    }
+    result.push_str(&render(rest));
+    let rest = &code[last..end];
+    result.push_str(&rest[rest.find('\n').unwrap()..]);
     Ok(result)
 }
 
-fn build_token_string<'a>(
-    pair: Pair<'a, Rule>,
-    defined: &mut HashMap<String, Macro<'a>>,
-    extracting_macro: &mut HashSet<String>,
-    modified: &mut bool,
-) -> Result<String, Box<dyn Error>> {
-    let mut result = String::new();
-    let mut token_iter = pair.into_inner();
-    let mut new_extracted: HashSet<String> = HashSet::new();
-    while let Some(token) = token_iter.next() {
-        match token.as_rule() {
-            Rule::WHITESPACE => {
-                result.push_str(token.as_str());
-            }
-            Rule::token => {
-                let token = token.into_inner().next().unwrap();
-                match token.as_rule() {
-                    Rule::keyword | Rule::string_literal | Rule::constant | Rule::WHITESPACE => {
-                        result.push_str(token.as_str());
-                    }
-                    Rule::identifier => {
-                        if let (Some(macro_), false) = (
-                            defined.get(token.as_str()),
-                            extracting_macro.contains(token.as_str()),
-                        ) {
-                            *modified = true;
-                            new_extracted.insert(token.as_str().to_owned());
-                            match macro_ {
-                                Macro::Object(body) => {
-                                    if let Some(body) = body {
-                                        result.push_str(body.as_str());
-                                    }
-                                }
-                                Macro::Function(params, is_variadic, body) => {
-                                    if let Some(body) = body {
-                                        result.push_str(
-                                            extract_function_like_macro(
-                                                &mut token_iter,
-                                                token.as_span(),
-                                                params,
-                                                *is_variadic,
-                                                body,
-                                            )?
-                                            .as_str(),
-                                        );
-                                    }
-                                }
-                            }
-                        } else {
-                            result.push_str(token.as_str());
-                        }
-                    }
-                    Rule::punctuator => {
-                        result.push_str(token.as_str());
-                    }
-                    _ => unreachable!(),
-                }
-            }
-            _ => unreachable!(),
+/// The tokens of the pragma of a `_Pragma` operator at the start of `tokens`, from the string
+/// literal of its argument.
+fn pragma_operator<'a>(
+    tokens: &[Token<'a>],
+    code_arena: &'a Arena<String>,
+) -> Result<Vec<&'a str>, Box<dyn Error>> {
+    match tokens {
+        [_, open, literal, close, ..]
+            if open.is("(") && literal.kind == Kind::StringLiteral && close.is(")") =>
+        {
+            let literal = literal.text;
+            let literal = &literal[literal.find('"').unwrap() + 1..literal.len() - 1];
+            let text = code_arena
+                .alloc(literal.replace("\\\"", "\"").replace("\\\\", "\\"))
+                .as_str();
+            Ok(match lex(text) {
+                Some(tokens) => tokens.iter().map(|token| token.text).collect(),
+                None => vec![text],
+            })
         }
+        _ => Err(SpanError::at(
+            "_Pragma takes a parenthesized string literal".to_string(),
+            tokens[0].text,
+        )),
     }
-    extracting_macro.extend(new_extracted.to_owned());
-    Ok(result)
 }
 
 fn build_conditional<'a>(
     pair: Pair<'a, Rule>,
-    defined: &mut HashMap<String, Macro<'a>>,
-    include_dirs: &[&str],
-    extracting_macro: &HashSet<String>,
+    state: &mut State<'a>,
+    include_paths: &IncludePaths,
     code_arena: &'a Arena<String>,
 ) -> Result<String, Box<dyn Error>> {
     let mut result = String::new();
+    // whether a group was taken, and whether the current group is
     let mut taken = false;
-    for pair in pair.into_inner() {
+    let mut active = false;
+    let mut pairs = pair.into_inner();
+    while let Some(pair) = pairs.next() {
         match pair.as_rule() {
             Rule::if_line => {
                 let mut negative_predicate = false;
@@ -483,53 +838,46 @@ fn build_conditional<'a>(
                             negative_predicate = true;
                         }
                         Rule::constant_expression => {
-                            taken = negative_predicate ^ build_constant_expression(token, defined)?;
+                            active = negative_predicate
+                                ^ build_constant_expression(token, state, code_arena)?;
                         }
                         Rule::identifier => {
-                            taken = negative_predicate ^ defined.contains_key(token.as_str());
+                            active = negative_predicate ^ state.is_defined(token.as_str());
                         }
                         Rule::WHITESPACE => {}
                         _ => unreachable!(),
                     }
                 }
+                taken = active;
+                result.push('\n');
             }
-            Rule::elif_line => match taken {
-                true => {
-                    // already taken
-                    break;
-                }
-                false => {
+            Rule::elif_line => {
+                active = false;
+                // the conditions after the taken group are not evaluated
+                if !taken {
                     for token in pair.into_inner() {
                         if token.as_rule() == Rule::constant_expression {
-                            taken = build_constant_expression(token, defined)?;
+                            active = build_constant_expression(token, state, code_arena)?;
                         }
                     }
                 }
-            },
-            Rule::else_line => match taken {
+                taken |= active;
+                result.push('\n');
+            }
+            Rule::else_line => {
+                active = !taken;
+                taken = true;
+                result.push('\n');
+            }
+            Rule::endif_line => result.push('\n'),
+            Rule::group => match active {
                 true => {
-                    // already taken
-                    break;
-                }
-                false => {
-                    taken = true;
+                    let groups = text_lines(pair, &mut pairs);
+                    result.push_str(&build_file_group(groups, state, include_paths, code_arena)?);
                 }
+                // the lines of the skipped groups are kept empty
+                false => result.push_str(&"\n".repeat(pair.as_str().matches('\n').count())),
             },
-            Rule::endif_line => {}
-            Rule::group => {
-                if taken {
-                    result.push_str(
-                        build_group(
-                            pair,
-                            defined,
-                            include_dirs,
-                            extracting_macro.clone(),
-                            code_arena,
-                        )?
-                        .as_str(),
-                    );
-                }
-            }
             Rule::WHITESPACE => {}
             _ => unreachable!(),
         }
@@ -537,143 +885,57 @@ fn build_conditional<'a>(
     Ok(result)
 }
 
-fn build_constant_expression(
-    pair: Pair<'_, Rule>,
-    defined: &HashMap<String, Macro>,
+/// The condition of `#if` or `#elif`: the `defined` operators are applied first, then the macros
+/// are expanded, and the tokens are evaluated.
+fn build_constant_expression<'a>(
+    pair: Pair<'a, Rule>,
+    state: &mut State<'a>,
+    code_arena: &'a Arena<String>,
 ) -> Result<bool, Box<dyn Error>> {
-    for token in pair.into_inner() {
-        if token.as_rule() == Rule::identifier {
-            return Ok(defined.contains_key(token.as_str()));
-        }
-    }
-    unreachable!()
-}
+    let span = pair.as_span();
+    let error = |message: &str| SpanError::at(message.to_string(), span.as_str());
 
-fn extract_function_like_macro(
-    token_iter: &mut Pairs<'_, Rule>,
-    span: Span<'_>,
-    params: &[String],
-    is_variadic: bool,
-    body: &[Replacement],
-) -> Result<String, Box<dyn Error>> {
-    // consume '('
-    match token_iter.next() {
-        Some(token) => {
-            if token.as_str() != "(" {
-                return Err(Box::new(pest::error::Error::<Rule>::new_from_span(
-                    ErrorVariant::CustomError {
-                        message: "expected macro function call".to_string(),
-                    },
-                    span,
-                )));
-            }
+    let mut tokens = vec![];
+    let mut input = tokenize(pair).into_iter();
+    while let Some(token) = input.next() {
+        if token.kind != Kind::Identifier || token.text != "defined" {
+            tokens.push(token);
+            continue;
         }
-        None => {
-            return Err(Box::new(pest::error::Error::<Rule>::new_from_span(
-                ErrorVariant::CustomError {
-                    message: "unexpected end of file".to_string(),
-                },
-                span,
-            )));
+        let mut name = input.next();
+        let parenthesized = name.as_ref().is_some_and(|name| name.is("("));
+        if parenthesized {
+            name = input.next();
         }
-    }
-    // get args
-    let mut parenthesis_level = 1;
-    let mut args: Vec<String> = vec!["".to_string()];
-    loop {
-        match token_iter.next() {
-            Some(token) => match token.as_str() {
-                "(" => {
-                    parenthesis_level += 1;
-                    args.last_mut().unwrap().push_str(token.as_str());
-                }
-                ")" => {
-                    parenthesis_level -= 1;
-                    if parenthesis_level == 0 {
-                        break;
-                    }
-                    args.last_mut().unwrap().push_str(token.as_str());
-                }
-                "," => {
-                    if parenthesis_level != 1 {
-                        // old args
-                        args.last_mut().unwrap().push_str(token.as_str());
-                    } else {
-                        // new args
-                        args.push("".to_string());
-                    }
-                }
-                " " => {}
-                _ => {
-                    args.last_mut().unwrap().push_str(token.as_str());
-                }
-            },
-            None => {
-                return Err(Box::new(pest::error::Error::<Rule>::new_from_span(
-                    ErrorVariant::CustomError {
-                        message: "unexpected end of file".to_string(),
-                    },
-                    span,
-                )))
-            }
+        let name = match name {
+            Some(name) if name.kind == Kind::Identifier => name,
+            _ => return Err(error("operator `defined` requires a macro name")),
+        };
+        if parenthesized && !input.next().is_some_and(|token| token.is(")")) {
+            return Err(error("missing `)` after `defined`"));
         }
-    }
-    // replace
-    if !(params.is_empty() && args.len() == 1 && args[0].is_empty()) // function without params
-        && ((!is_variadic && args.len() != params.len())
-            || (is_variadic && args.len() < params.len()))
-    {
-        return Err(Box::new(pest::error::Error::<Rule>::new_from_span(
-            ErrorVariant::CustomError {
-                message: "number of arguments mismatch".to_string(),
+        tokens.push(Token {
+            kind: Kind::Constant,
+            text: match state.is_defined(name.text) {
+                true => "1",
+                false => "0",
             },
-            span,
-        )));
+            ..token
+        });
     }
-    let mut result = String::new();
-    for action in body {
-        match action {
-            Replacement::Text(text) => {
-                result.push_str(text);
-            }
-            Replacement::Parameter(para_name) => {
-                let index = params.iter().position(|r| r == para_name).unwrap();
-                result.push_str(args[index].as_str());
-            }
-            Replacement::Stringizing(para_name) => {
-                let index = params.iter().position(|r| r == para_name).unwrap();
-                result.push('"');
-                result.push_str(&escape(args[index].as_str()));
-                result.push('"');
-            }
-            Replacement::Concat(lhs, rhs) => {
-                let lhs = match params.contains(lhs) {
-                    true => {
-                        let index = params.iter().position(|r| r == lhs).unwrap();
-                        escape(args[index].as_str()).to_string()
-                    }
-                    false => lhs.clone(),
-                };
-                let rhs = match params.contains(rhs) {
-                    true => {
-                        let index = params.iter().position(|r| r == rhs).unwrap();
-                        escape(args[index].as_str()).to_string()
-                    }
-                    false => rhs.clone(),
-                };
-                result.push_str(&format!("{}{}", lhs, rhs));
-            }
-            Replacement::VAArg => {
-                if params.len() == args.len() {
-                    break;
-                }
-                result.push_str(args[params.len()].as_str());
-                for arg in args.iter().skip(params.len() + 1) {
-                    result.push(',');
-                    result.push_str(arg.as_str());
-                }
-            }
-        }
+
+    let tokens = Expander::new(state, code_arena).expand(tokens)?;
+    let code = code_arena.alloc(render(&tokens)).trim();
+    // the macros may expand to nothing
+    if code.is_empty() {
+        return evaluate_condition(vec![], span);
     }
-    Ok(result)
+    let tokens = Phase4Parser::parse(Rule::constant_expression, code)?
+        .next()
+        .unwrap()
+        .into_inner()
+        .filter(|token| token.as_rule() == Rule::token)
+        .map(|token| token.into_inner().next().unwrap())
+        .collect();
+    evaluate_condition(tokens, span)
 }
@@ -1,10 +1,12 @@
 use super::*;
+use pest::error::{Error, ErrorVariant};
+use pest::iterators::Pair;
 
 #[derive(Parser)]
 #[grammar = "./preprocess/phase6.pest"]
 struct Phase6Parser;
 
-pub fn phase6(code: &str) -> Result<String, Box<dyn Error>> {
+pub fn phase6(code: &str) -> Result<String, Box<pest::error::Error<Rule>>> {
     let pairs = match Phase6Parser::parse(Rule::cc99, code)?.next() {
         Some(p) => p.into_inner(),
         None => unreachable!(),
@@ -13,17 +15,90 @@ pub fn phase6(code: &str) -> Result<String, Box<dyn Error>> {
     for pair in pairs {
         match pair.as_rule() {
             Rule::sequence_string_literal => {
-                result.push('"');
-                for literal in pair.into_inner() {
-                    if literal.as_rule() == Rule::string_literal {
-                        let literal = literal.as_str();
-                        result.push_str(&literal[1..literal.len() - 1]);
+                result.push_str(&concatenate(pair.clone())?);
+                // the lines between the literals are kept after them, as empty lines, with their
+                // line markers
+                let mut end = pair.as_span().start();
+                for inner in pair.into_inner() {
+                    let space = &code[end..inner.as_span().start()];
+                    result.push_str(&"\n".repeat(space.matches('\n').count()));
+                    if inner.as_rule() == Rule::line_marker {
+                        result.push_str(inner.as_str());
                     }
+                    end = inner.as_span().end();
                 }
-                result.push('"');
             }
             _ => result.push_str(pair.as_str()),
         }
     }
     Ok(result)
 }
+
+/// Concatenate adjacent string literals into one, which has the encoding prefix of the
+/// prefixed ones (C99 6.4.5p5).
+fn concatenate(sequence: Pair<Rule>) -> Result<String, Box<pest::error::Error<Rule>>> {
+    let mut prefix = "";
+    let mut contents = String::new();
+    // the hexadecimal or octal escape sequence ending the previous literal
+    let mut open_escape: Option<&str> = None;
+    for literal in sequence.into_inner() {
+        if literal.as_rule() != Rule::string_literal {
+            continue;
+        }
+        let mut literal_prefix = "";
+        let mut last_escape = None;
+        for inner in literal.clone().into_inner() {
+            match inner.as_rule() {
+                Rule::encoding_prefix => literal_prefix = inner.as_str(),
+                _ => last_escape = Some(inner),
+            }
+        }
+        match (prefix, literal_prefix) {
+            (_, "") => {}
+            ("", _) => prefix = literal_prefix,
+            _ if prefix == literal_prefix => {}
+            _ => {
+                return Err(Box::new(Error::new_from_span(
+                    ErrorVariant::CustomError {
+                        message: format!(
+                            "cannot concatenate string literals with the prefixes `{}` and `{}`",
+                            prefix, literal_prefix
+                        ),
+                    },
+                    literal.as_span(),
+                )))
+            }
+        }
+
+        let text = literal.as_str();
+        let body = &text[literal_prefix.len() + 1..text.len() - 1];
+        // a digit following an escape sequence would continue it once concatenated, so it is
+        // written as an octal escape sequence itself
+        let continues_escape = match (open_escape, body.chars().next()) {
+            (Some(escape), Some(c)) if escape.starts_with("\\x") => c.is_ascii_hexdigit(),
+            (Some(_), Some(c)) => matches!(c, '0'..='7'),
+            _ => false,
+        };
+        match continues_escape {
+            true => {
+                contents.push_str(&format!("\\{:03o}", body.as_bytes()[0]));
+                contents.push_str(&body[1..]);
+            }
+            false => contents.push_str(body),
+        }
+
+        open_escape = match last_escape {
+            Some(escape) if escape.as_span().end() == literal.as_span().end() - 1 => {
+                let escape = escape.as_str();
+                match escape.as_bytes()[1] {
+                    b'x' | b'0'..=b'7' => Some(escape),
+                    _ => None,
+                }
+            }
+            // an empty literal leaves the escape sequence before it open
+            _ if body.is_empty() => open_escape,
+            _ => None,
+        };
+    }
+    Ok(format!("{}\"{}\"", prefix, contents))
+}
@@ -0,0 +1,118 @@
+use super::CommandLineMacro;
+use std::env;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// The macros predefined by the standard and by cc99. `__FILE__`, `__LINE__` and `__COUNTER__`
+/// change while preprocessing, and are expanded by the state of the preprocessor instead.
+pub fn standard_macros() -> Vec<CommandLineMacro> {
+    let (date, time) = compilation_time();
+    [
+        ("__STDC__", "1".to_string()),
+        ("__STDC_VERSION__", "199901L".to_string()),
+        ("__STDC_HOSTED__", "1".to_string()),
+        ("__cc99__", "1".to_string()),
+        ("__DATE__", format!("\"{}\"", date)),
+        ("__TIME__", format!("\"{}\"", time)),
+    ]
+    .into_iter()
+    .map(|(name, value)| CommandLineMacro::Define(name.to_string(), value))
+    .collect()
+}
+
+/// The macros describing the target, like `__x86_64__` and `__linux__` for
+/// `x86_64-unknown-linux-gnu`.
+pub fn target_macros(triple: &str) -> Vec<CommandLineMacro> {
+    let mut components = triple.split('-');
+    let arch = components.next().unwrap_or_default();
+    // the vendor is optional
+    let rest = components.collect::<Vec<_>>();
+    let (os, env) = match rest.as_slice() {
+        [_, os, env, ..] => (*os, *env),
+        [os, env] if !matches!(*os, "pc" | "unknown" | "apple") => (*os, *env),
+        [_, os] | [os] => (*os, ""),
+        _ => ("", ""),
+    };
+
+    let mut names = vec![];
+    let is_64_bit = match arch {
+        "x86_64" | "amd64" => {
+            names.extend(["__x86_64__", "__x86_64", "__amd64__", "__amd64"]);
+            true
+        }
+        "i386" | "i486" | "i586" | "i686" => {
+            names.extend(["__i386__", "__i386"]);
+            false
+        }
+        "aarch64" | "arm64" => {
+            names.push("__aarch64__");
+            true
+        }
+        "riscv64" => {
+            names.push("__riscv");
+            true
+        }
+        _ => false,
+    };
+    if is_64_bit && !os.starts_with("windows") {
+        names.extend(["__LP64__", "_LP64"]);
+    }
+    if os.starts_with("linux") {
+        names.extend(["__linux__", "__linux", "__unix__", "__unix", "__ELF__"]);
+        if env.starts_with("gnu") {
+            names.push("__gnu_linux__");
+        }
+    } else if os.starts_with("freebsd") {
+        names.extend(["__FreeBSD__", "__unix__", "__unix", "__ELF__"]);
+    } else if os.starts_with("darwin") || os.starts_with("macos") {
+        names.extend(["__APPLE__", "__MACH__"]);
+    } else if os.starts_with("windows") {
+        names.push("_WIN32");
+        if is_64_bit {
+            names.push("_WIN64");
+        }
+    }
+    names
+        .into_iter()
+        .map(|name| CommandLineMacro::Define(name.to_string(), "1".to_string()))
+        .collect()
+}
+
+/// The values of `__DATE__` and `__TIME__`, like `Jan  1 2000` and `00:00:00` in UTC. As GCC does,
+/// `SOURCE_DATE_EPOCH` replaces the current time for reproducible builds.
+fn compilation_time() -> (String, String) {
+    let seconds = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse::<u64>().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_secs())
+        });
+    let (days, seconds) = (seconds / 86400, seconds % 86400);
+
+    // the civil date of the days since 1970-01-01, in the proleptic Gregorian calendar
+    let days = days as i64 + 719468;
+    let era = days / 146097;
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+    (
+        format!("{} {:2} {}", MONTHS[month as usize - 1], day, year),
+        format!(
+            "{:02}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        ),
+    )
+}
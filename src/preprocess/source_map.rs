@@ -0,0 +1,176 @@
+use codespan_reporting::files::{Error as FilesError, Files};
+use pest::error::{Error as PestError, LineColLocation};
+use pest::RuleType;
+use std::ops::Range;
+
+/// Where the lines of preprocessed code come from, read from the line markers like
+/// `# 1 "stdio.h" 1` that the preprocessor leaves where the file or the line changes.
+///
+/// Diagnostics are reported on the code without the markers, which is shared by all the files, so
+/// the id of a file only selects the name shown.
+pub struct SourceMap {
+    /// the preprocessed code without the line markers
+    code: String,
+    /// the names of the files
+    files: Vec<String>,
+    /// the offsets where the lines of the code start
+    line_starts: Vec<usize>,
+    /// the file of each line of the code, and its line there counted from 1
+    lines: Vec<(usize, usize)>,
+}
+
+impl SourceMap {
+    /// The map of `code`, preprocessed from the file named `file_name`.
+    pub fn new(file_name: &str, code: &str) -> SourceMap {
+        let mut source_map = SourceMap {
+            code: String::with_capacity(code.len()),
+            files: vec![file_name.to_string()],
+            line_starts: vec![],
+            lines: vec![],
+        };
+        let (mut file, mut line) = (0, 1);
+        for text in code.split_inclusive('\n') {
+            match line_marker(text) {
+                Some((marker_line, name)) => {
+                    file = match source_map.files.iter().position(|file| *file == name) {
+                        Some(file) => file,
+                        None => {
+                            source_map.files.push(name);
+                            source_map.files.len() - 1
+                        }
+                    };
+                    line = marker_line;
+                }
+                None => {
+                    source_map.line_starts.push(source_map.code.len());
+                    source_map.lines.push((file, line));
+                    source_map.code.push_str(text);
+                    line += 1;
+                }
+            }
+        }
+        source_map
+    }
+
+    /// The preprocessed code without the line markers.
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+
+    /// The id of the file that the code at `offset` comes from.
+    pub fn file_id(&self, offset: usize) -> usize {
+        self.lines
+            .get(self.line_of(offset))
+            .map_or(0, |&(file, _)| file)
+    }
+
+    /// Point an error of a pest parser on the code to the file and the line it comes from.
+    pub fn locate_pest_error<R: RuleType>(&self, error: PestError<R>) -> PestError<R> {
+        let locate = |(line, column): (usize, usize)| match self.lines.get(line - 1) {
+            Some(&(file, line)) => (file, (line, column)),
+            None => (0, (line, column)),
+        };
+        let mut error = error;
+        let (file, line_col) = match error.line_col {
+            LineColLocation::Pos(position) => {
+                let (file, position) = locate(position);
+                (file, LineColLocation::Pos(position))
+            }
+            LineColLocation::Span(start, end) => {
+                let (file, start) = locate(start);
+                (file, LineColLocation::Span(start, locate(end).1))
+            }
+        };
+        error.line_col = line_col;
+        error.with_path(&self.files[file])
+    }
+
+    /// The index of the line containing `offset`.
+    fn line_of(&self, offset: usize) -> usize {
+        match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(line) => line.saturating_sub(1),
+        }
+    }
+}
+
+impl<'a> Files<'a> for SourceMap {
+    type FileId = usize;
+    type Name = &'a str;
+    type Source = &'a str;
+
+    fn name(&'a self, id: usize) -> Result<&'a str, FilesError> {
+        self.files
+            .get(id)
+            .map(String::as_str)
+            .ok_or(FilesError::FileMissing)
+    }
+
+    fn source(&'a self, _: usize) -> Result<&'a str, FilesError> {
+        Ok(&self.code)
+    }
+
+    fn line_index(&'a self, _: usize, byte_index: usize) -> Result<usize, FilesError> {
+        Ok(self.line_of(byte_index))
+    }
+
+    fn line_number(&'a self, _: usize, line_index: usize) -> Result<usize, FilesError> {
+        Ok(self
+            .lines
+            .get(line_index)
+            .map_or(line_index + 1, |&(_, line)| line))
+    }
+
+    fn line_range(&'a self, _: usize, line_index: usize) -> Result<Range<usize>, FilesError> {
+        let start = match self.line_starts.get(line_index) {
+            Some(&start) => start,
+            // the end of code without lines
+            None if line_index == 0 => 0,
+            None => {
+                return Err(FilesError::LineTooLarge {
+                    given: line_index,
+                    max: self.line_starts.len().saturating_sub(1),
+                })
+            }
+        };
+        let end = self
+            .line_starts
+            .get(line_index + 1)
+            .copied()
+            .unwrap_or(self.code.len());
+        Ok(start..end)
+    }
+}
+
+/// The line and the file named by a line marker, like `# 12 "stdio.h" 2`.
+fn line_marker(text: &str) -> Option<(usize, String)> {
+    let (line, rest) = text.trim_end().strip_prefix("# ")?.split_once(' ')?;
+    let line = line.parse().ok()?;
+    let rest = rest.strip_prefix('"')?;
+    let end = rest.rfind('"')?;
+    match rest[end + 1..]
+        .split_whitespace()
+        .all(|flag| matches!(flag, "1" | "2" | "3" | "4"))
+    {
+        true => Some((line, unquote(&rest[..end]))),
+        false => None,
+    }
+}
+
+/// The name of a file escaped to be put in double quotes, as in line markers and `__FILE__`.
+pub fn quote(name: &str) -> String {
+    name.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// The name of a file put in double quotes by [`quote`].
+pub fn unquote(name: &str) -> String {
+    let mut result = String::with_capacity(name.len());
+    let mut chars = name.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => result.extend(chars.next()),
+            _ => result.push(c),
+        }
+    }
+    result
+}
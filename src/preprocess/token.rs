@@ -0,0 +1,149 @@
+use super::phase4::{Phase4Parser, Rule};
+use pest::iterators::Pair;
+use pest::Parser;
+use std::collections::BTreeSet;
+use std::rc::Rc;
+
+/// The kinds of preprocessing tokens.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Kind {
+    /// identifiers and keywords, which are the same to the preprocessor
+    Identifier,
+    /// numbers and character constants
+    Constant,
+    StringLiteral,
+    Punctuator,
+    /// an empty argument of `##`, removed once the operator is applied
+    Placemarker,
+}
+
+/// The names of the macros whose expansion a token comes from, which are not expanded again in
+/// it: the hide set of Prosser's algorithm.
+pub type HideSet<'a> = Rc<BTreeSet<&'a str>>;
+
+/// A preprocessing token. The text of the tokens of the files is in their code, and the text of
+/// the tokens made by the preprocessor is in the arena of the code.
+#[derive(Debug, Clone)]
+pub struct Token<'a> {
+    pub kind: Kind,
+    pub text: &'a str,
+    /// the whitespace before the token
+    pub space: &'a str,
+    pub hide_set: HideSet<'a>,
+}
+
+impl<'a> Token<'a> {
+    pub fn is(&self, punctuator: &str) -> bool {
+        self.kind == Kind::Punctuator && self.text == punctuator
+    }
+}
+
+/// The tokens of `pair`, like a line or the body of a macro.
+pub fn tokenize(pair: Pair<'_, Rule>) -> Vec<Token<'_>> {
+    let (code, start) = (pair.as_str(), pair.as_span().start());
+    let mut end = 0;
+    pair.into_inner()
+        .flatten()
+        .filter(|token| token.as_rule() == Rule::token)
+        .map(|token| {
+            let span = token.as_span();
+            let space = &code[end..span.start() - start];
+            end = span.end() - start;
+            Token {
+                kind: kind(token.into_inner().next().unwrap().as_rule()),
+                text: span.as_str(),
+                space,
+                hide_set: HideSet::default(),
+            }
+        })
+        .collect()
+}
+
+/// The tokens of `code`, unless some of it is not a token.
+pub fn lex(code: &str) -> Option<Vec<Token<'_>>> {
+    let code = code.trim();
+    match code.is_empty() {
+        true => Some(vec![]),
+        false => {
+            let pair = Phase4Parser::parse(Rule::token_string, code).ok()?.next()?;
+            match pair.as_str().len() == code.len() {
+                true => Some(tokenize(pair)),
+                false => None,
+            }
+        }
+    }
+}
+
+fn kind(rule: Rule) -> Kind {
+    match rule {
+        Rule::identifier | Rule::keyword => Kind::Identifier,
+        Rule::constant => Kind::Constant,
+        Rule::string_literal => Kind::StringLiteral,
+        Rule::punctuator => Kind::Punctuator,
+        _ => unreachable!(),
+    }
+}
+
+/// The string literal made by the `#` operator from `tokens`: the whitespace between them becomes a
+/// space, and the string literals and character constants are escaped.
+pub fn stringize(tokens: &[Token]) -> String {
+    let mut result = String::from("\"");
+    for (index, token) in tokens.iter().enumerate() {
+        if index > 0 && !token.space.is_empty() {
+            result.push(' ');
+        }
+        match token.kind {
+            Kind::StringLiteral | Kind::Constant if token.text.contains(['"', '\'']) => {
+                result.push_str(&token.text.replace('\\', "\\\\").replace('"', "\\\""))
+            }
+            _ => result.push_str(token.text),
+        }
+    }
+    result.push('"');
+    result
+}
+
+/// The text of `tokens`. Like GCC, a space is put between the tokens from the expansion of a
+/// macro which would be read as one token otherwise.
+pub fn render(tokens: &[Token]) -> String {
+    let mut result = String::new();
+    let mut previous: Option<&Token> = None;
+    for token in tokens {
+        match previous {
+            Some(previous)
+                if token.space.is_empty()
+                    && !(previous.hide_set.is_empty() && token.hide_set.is_empty())
+                    && would_paste(previous, token) =>
+            {
+                result.push(' ')
+            }
+            _ => result.push_str(token.space),
+        }
+        result.push_str(token.text);
+        previous = Some(token);
+    }
+    result
+}
+
+/// Whether `previous` followed by `next` would be read as another token.
+fn would_paste(previous: &Token, next: &Token) -> bool {
+    let (last, first) = match (previous.text.chars().last(), next.text.chars().next()) {
+        (Some(last), Some(first)) => (last, first),
+        _ => return false,
+    };
+    let word = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    match (previous.kind, next.kind) {
+        (Kind::Punctuator, Kind::Punctuator) => [
+            "++", "--", "->", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "+=", "-=", "*=",
+            "/=", "%=", "^=", "&=", "|=", "##", "//", "/*", "..",
+        ]
+        .contains(&format!("{}{}", last, first).as_str()),
+        // a prefix of a literal, like `L"wide"`
+        (Kind::Identifier, Kind::StringLiteral | Kind::Constant) if matches!(first, '"' | '\'') => {
+            matches!(previous.text, "L" | "u" | "U" | "u8")
+        }
+        (Kind::Constant, Kind::Punctuator) => first == '.',
+        (Kind::Punctuator, Kind::Constant) => last == '.' && first.is_ascii_digit(),
+        _ => word(last) && word(first),
+    }
+}
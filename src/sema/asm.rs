@@ -0,0 +1,123 @@
+use crate::ast::{AsmOperand, AsmStatement};
+use crate::sema::*;
+use crate::utils::asm::{parse_clobber, parse_constraint, translate_template, AsmConstraint};
+use crate::utils::CompileErr as CE;
+
+impl<'a> Sema<'a> {
+    /// Inline assembly is checked as GCC does, as far as it can be without an assembler: the
+    /// constraints, the operands they allow, the clobbered registers and the operand references
+    /// of the template.
+    pub(crate) fn analyze_asm_statement(
+        &self,
+        asm: &AsmStatement,
+    ) -> Result<TypedAsmStatement, CE> {
+        let mut outputs = vec![];
+        let mut output_constraints = vec![];
+        for output in &asm.outputs {
+            let constraint = Self::asm_constraint(output, true, 0)?;
+            let expression = self.analyze_modifiable_lvalue(&output.expression)?;
+            self.check_asm_operand_type(output, &constraint, &expression)?;
+            outputs.push(Self::typed_asm_operand(output, expression));
+            output_constraints.push(constraint);
+        }
+
+        let mut inputs = vec![];
+        for input in &asm.inputs {
+            let constraint = Self::asm_constraint(input, false, asm.outputs.len())?;
+            let expression = match constraint.is_indirect() {
+                true => self.analyze_lvalue(&input.expression)?,
+                false => self.analyze_rvalue(&input.expression)?,
+            };
+            if let Some(index) = constraint.matching_output {
+                if output_constraints[index].is_indirect() {
+                    return Err(CE::invalid_asm_constraint(
+                        input.constraint.to_owned(),
+                        format!("output operand {} is in memory", index),
+                        input.span,
+                    ));
+                }
+            }
+            self.check_asm_operand_type(input, &constraint, &expression)?;
+            inputs.push(Self::typed_asm_operand(input, expression));
+        }
+
+        for (clobber, span) in &asm.clobbers {
+            if parse_clobber(clobber).is_err() {
+                return Err(CE::unknown_asm_clobber(clobber.to_owned(), *span));
+            }
+        }
+
+        if !asm.is_basic {
+            let names = asm
+                .outputs
+                .iter()
+                .chain(asm.inputs.iter())
+                .map(|operand| operand.name.to_owned())
+                .collect::<Vec<_>>();
+            if let Err((reference, reason)) = translate_template(&asm.template, &names, &asm.labels)
+            {
+                return Err(CE::invalid_asm_operand_reference(
+                    reference,
+                    reason,
+                    asm.template_span,
+                ));
+            }
+        }
+
+        Ok(TypedAsmStatement {
+            template: asm.template.to_owned(),
+            is_basic: asm.is_basic,
+            is_volatile: asm.is_volatile,
+            outputs,
+            inputs,
+            clobbers: asm
+                .clobbers
+                .iter()
+                .map(|(clobber, _)| clobber.to_owned())
+                .collect(),
+            labels: asm.labels.to_owned(),
+        })
+    }
+
+    fn asm_constraint(
+        operand: &AsmOperand,
+        is_output: bool,
+        output_count: usize,
+    ) -> Result<AsmConstraint, CE> {
+        parse_constraint(&operand.constraint, is_output, output_count).map_err(|reason| {
+            CE::invalid_asm_constraint(operand.constraint.to_owned(), reason, operand.span)
+        })
+    }
+
+    /// Operands not in memory are values in registers or immediates.
+    fn check_asm_operand_type(
+        &self,
+        operand: &AsmOperand,
+        constraint: &AsmConstraint,
+        expression: &TypedExpression,
+    ) -> Result<(), CE> {
+        if constraint.is_indirect() {
+            return Ok(());
+        }
+        let t = self.resolve_type(&expression.expr_type, operand.span)?;
+        if t.base_type.is_scalar() {
+            return Ok(());
+        }
+        Err(CE::invalid_asm_constraint(
+            operand.constraint.to_owned(),
+            format!(
+                "an operand of type `{}` can only be in memory",
+                expression.expr_type.base_type
+            ),
+            operand.span,
+        ))
+    }
+
+    fn typed_asm_operand(operand: &AsmOperand, expression: TypedExpression) -> TypedAsmOperand {
+        TypedAsmOperand {
+            name: operand.name.to_owned(),
+            constraint: operand.constraint.to_owned(),
+            expression,
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use crate::ast::{Attribute, BaseType, BasicType as BT, ExpressionEnum, IntegerType, StructMember};
+use crate::sema::*;
+use crate::utils::CompileErr as CE;
+
+/// What the attributes of a declaration apply to.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) enum AttributeTarget {
+    Function,
+    GlobalVariable,
+    LocalVariable,
+    Member,
+    Typedef,
+    /// a declaration of a struct or union tag only
+    Tag,
+}
+
+impl AttributeTarget {
+    fn describe(self) -> &'static str {
+        match self {
+            AttributeTarget::Function => "functions",
+            AttributeTarget::GlobalVariable => "file scope variables",
+            AttributeTarget::LocalVariable => "block scope variables",
+            AttributeTarget::Member => "struct members",
+            AttributeTarget::Typedef => "typedef names",
+            AttributeTarget::Tag => "struct declarations",
+        }
+    }
+}
+
+impl<'a> Sema<'a> {
+    /// Check the arguments of known attributes, unknown or misplaced attributes are ignored with
+    /// a warning. `function_type` is the type of the function a `Function` target declares.
+    pub(crate) fn check_attributes(
+        &mut self,
+        attributes: &[Attribute],
+        target: AttributeTarget,
+        function_type: Option<&BT>,
+    ) -> Result<(), CE> {
+        use AttributeTarget::*;
+
+        for attribute in attributes {
+            let targets: &[AttributeTarget] = match attribute.name.as_str() {
+                "packed" => &[Member],
+                "aligned" => &[Function, GlobalVariable, LocalVariable, Member],
+                "unused" => &[Function, GlobalVariable, LocalVariable, Member, Typedef],
+                "noinline" | "always_inline" | "cold" | "noreturn" | "format" | "constructor" => {
+                    &[Function]
+                }
+                "section" | "weak" | "visibility" => &[Function, GlobalVariable],
+                _ => {
+                    self.warnings.push(CE::ignored_attribute(
+                        attribute.name.to_owned(),
+                        "unknown attribute".to_string(),
+                        attribute.span,
+                    ));
+                    continue;
+                }
+            };
+            if !targets.contains(&target) {
+                self.warnings.push(CE::ignored_attribute(
+                    attribute.name.to_owned(),
+                    format!(
+                        "`{}` does not apply to {}",
+                        attribute.name,
+                        target.describe()
+                    ),
+                    attribute.span,
+                ));
+                continue;
+            }
+
+            match attribute.name.as_str() {
+                // the argument of `packed` comes from `#pragma pack`
+                "aligned" | "packed" => {
+                    if attribute.arguments.len() > 1
+                        || attribute.arguments.first().is_some_and(|arg| {
+                            !arg.node.integer_literal().is_some_and(u64::is_power_of_two)
+                        })
+                    {
+                        return Err(invalid_argument(attribute, "an integer power of two"));
+                    }
+                }
+                "section" => {
+                    if !matches!(
+                        attribute.arguments.as_slice(),
+                        [arg] if matches!(arg.node, ExpressionEnum::StringLiteral(..))
+                    ) {
+                        return Err(invalid_argument(attribute, "a string literal"));
+                    }
+                }
+                "visibility" => {
+                    if !matches!(
+                        attribute.arguments.as_slice(),
+                        [arg] if matches!(
+                            string_literal(&arg.node).as_deref(),
+                            Some("default" | "hidden" | "protected" | "internal")
+                        )
+                    ) {
+                        return Err(invalid_argument(
+                            attribute,
+                            "one of \"default\", \"hidden\", \"protected\" or \"internal\"",
+                        ));
+                    }
+                }
+                "constructor" => {
+                    if attribute.arguments.len() > 1
+                        || attribute
+                            .arguments
+                            .first()
+                            .is_some_and(|arg| arg.node.integer_literal().is_none())
+                    {
+                        return Err(invalid_argument(attribute, "an integer priority"));
+                    }
+                }
+                "format" => self.check_format_attribute(attribute, function_type.unwrap())?,
+                _ => {
+                    if !attribute.arguments.is_empty() {
+                        return Err(invalid_argument(attribute, "no argument"));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The attributes of the members of a struct or union, those of the struct or union itself
+    /// are given to each member, but only checked once.
+    pub(crate) fn check_member_attributes(&mut self, members: &[StructMember]) -> Result<(), CE> {
+        let mut attributes: Vec<Attribute> = vec![];
+        for attribute in members.iter().flat_map(|member| member.attributes.iter()) {
+            if !attributes.iter().any(|a| a.span == attribute.span) {
+                attributes.push(attribute.to_owned());
+            }
+        }
+        self.check_attributes(&attributes, AttributeTarget::Member, None)
+    }
+
+    /// `format(archetype, string-index, first-to-check)`: the parameter at `string-index`,
+    /// counted from 1, is the format string, and the arguments to check start at the variadic
+    /// ones, or 0 if they are passed as a `va_list`.
+    fn check_format_attribute(&self, attribute: &Attribute, function_type: &BT) -> Result<(), CE> {
+        let (params, is_variadic) = match function_type.base_type {
+            BaseType::Function(_, ref params, is_variadic) => (params, is_variadic),
+            _ => unreachable!(),
+        };
+        let archetype = match attribute.arguments.first().map(|arg| &arg.node) {
+            Some(ExpressionEnum::Identifier(name)) => name.trim_matches('_'),
+            _ => "",
+        };
+        if !matches!(archetype, "printf" | "scanf" | "strftime" | "strfmon") {
+            return Err(invalid_argument(
+                attribute,
+                "the archetype `printf`, `scanf`, `strftime` or `strfmon`",
+            ));
+        }
+        let indices = attribute.arguments[1..]
+            .iter()
+            .map(|arg| arg.node.integer_literal())
+            .collect::<Vec<_>>();
+        let valid = match indices.as_slice() {
+            [Some(string_index), Some(first_to_check)] => {
+                let is_string = (*string_index as usize)
+                    .checked_sub(1)
+                    .and_then(|i| params.get(i))
+                    .is_some_and(|param| match param.base_type {
+                        BaseType::Pointer(ref pointee) => matches!(
+                            pointee.base_type,
+                            BaseType::SignedInteger(IntegerType::Char)
+                                | BaseType::UnsignedInteger(IntegerType::Char)
+                        ),
+                        _ => false,
+                    });
+                is_string
+                    && (*first_to_check == 0
+                        || is_variadic && *first_to_check as usize == params.len() + 1)
+            }
+            _ => false,
+        };
+        if !valid {
+            return Err(invalid_argument(
+                attribute,
+                "the index of a `char *` parameter, then the index of the first variadic \
+                 argument or 0",
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn invalid_argument(attribute: &Attribute, expected: &str) -> CE {
+    CE::invalid_attribute_argument(
+        attribute.name.to_owned(),
+        format!("expected {}", expected),
+        attribute.span,
+    )
+}
+
+fn string_literal(expr: &ExpressionEnum) -> Option<String> {
+    match expr {
+        ExpressionEnum::StringLiteral(_, code_units) => code_units
+            .iter()
+            .map(|&code_unit| char::from_u32(code_unit))
+            .collect(),
+        _ => None,
+    }
+}
@@ -0,0 +1,622 @@
+use crate::ast::{
+    Attribute, BaseType, BasicType as BT, Declaration, DeclarationEnum, Expression, ExpressionEnum,
+    FunctionSpecifier, Span, StatementEnum, StorageClassSpecifier, Type,
+};
+use crate::sema::attr::AttributeTarget;
+use crate::sema::*;
+use crate::utils::CompileErr as CE;
+
+impl<'a> Sema<'a> {
+    /// Declare a file scope name, function definitions only get their prototype declared.
+    pub(crate) fn analyze_global_declaration(
+        &mut self,
+        declaration: &Declaration,
+    ) -> Result<Option<TypedDeclaration>, CE> {
+        let span = declaration.span;
+        match declaration.node {
+            DeclarationEnum::Declaration(ref type_info, ref identifier, ref initializer) => {
+                self.define_struct(&type_info.basic_type.base_type, span)?;
+                let identifier = match identifier {
+                    Some(identifier) => identifier,
+                    None => {
+                        self.check_attributes(&type_info.attributes, AttributeTarget::Tag, None)?;
+                        return Ok(Some(self.struct_declaration(type_info, span)?));
+                    }
+                };
+
+                let var_type = self.resolve_type(&type_info.basic_type, span)?;
+                if type_info.storage_class_specifier == StorageClassSpecifier::Typedef {
+                    self.check_attributes(&type_info.attributes, AttributeTarget::Typedef, None)?;
+                    self.check_global_name(identifier, span)?;
+                    self.typedef_map
+                        .insert(identifier.to_owned(), var_type.to_owned());
+                    return Ok(Some(TypedDeclaration {
+                        node: TypedDeclarationEnum::Declaration(
+                            Type {
+                                basic_type: self.with_struct_definitions(type_info, var_type),
+                                ..type_info.to_owned()
+                            },
+                            Some(identifier.to_owned()),
+                            None,
+                        ),
+                        span,
+                    }));
+                }
+
+                if let BaseType::Function(ref return_type, ref params, is_variadic) =
+                    var_type.base_type
+                {
+                    if type_info.thread_local {
+                        return Err(CE::invalid_thread_local(identifier.to_string(), span));
+                    }
+                    self.check_attributes(
+                        &type_info.attributes,
+                        AttributeTarget::Function,
+                        Some(&var_type),
+                    )?;
+                    self.declare_function(identifier, return_type, params, is_variadic, span)?;
+                    self.declare_function_specifiers(
+                        identifier,
+                        &type_info.function_specifier,
+                        &type_info.attributes,
+                    );
+                    return Ok(Some(TypedDeclaration {
+                        node: TypedDeclarationEnum::Declaration(
+                            Type {
+                                basic_type: self.declared_function_type(identifier),
+                                ..type_info.to_owned()
+                            },
+                            Some(identifier.to_owned()),
+                            None,
+                        ),
+                        span,
+                    }));
+                }
+
+                self.check_attributes(
+                    &type_info.attributes,
+                    AttributeTarget::GlobalVariable,
+                    None,
+                )?;
+                self.check_global_name(identifier, span)?;
+                let var_type = self.variable_type(type_info, identifier, initializer, span)?;
+                let initializer = match initializer {
+                    Some(initializer) => {
+                        Some(Box::new(self.analyze_initializer(&var_type, initializer)?))
+                    }
+                    None => None,
+                };
+                self.global_variable_map.insert(
+                    identifier.to_owned(),
+                    (var_type.to_owned(), Symbol::Global(span)),
+                );
+                Ok(Some(TypedDeclaration {
+                    node: TypedDeclarationEnum::Declaration(
+                        Type {
+                            basic_type: self.with_struct_definitions(type_info, var_type),
+                            ..type_info.to_owned()
+                        },
+                        Some(identifier.to_owned()),
+                        initializer,
+                    ),
+                    span,
+                }))
+            }
+            DeclarationEnum::FunctionDefinition(
+                ref function_specifier,
+                _,
+                ref attributes,
+                ref return_type,
+                ref identifier,
+                ref params,
+                is_variadic,
+                _,
+            ) => {
+                let params = params
+                    .iter()
+                    .map(|param| self.resolve_type(&param.0, span))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let return_type = self.resolve_type(return_type, span)?;
+                let function_type = BT {
+                    qualifier: vec![],
+                    base_type: BaseType::Function(
+                        Box::new(return_type.to_owned()),
+                        params.to_owned(),
+                        is_variadic,
+                    ),
+                };
+                self.check_attributes(attributes, AttributeTarget::Function, Some(&function_type))?;
+                self.declare_function_specifiers(identifier, function_specifier, attributes);
+                self.declare_function(identifier, &return_type, &params, is_variadic, span)?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// A file scope variable or typedef name must not be declared twice.
+    fn check_global_name(&self, identifier: &str, span: Span) -> Result<(), CE> {
+        if self.global_variable_map.contains_key(identifier) {
+            Err(CE::duplicated_global_variable(identifier.to_string(), span))
+        } else if self.function_map.contains_key(identifier)
+            || self.typedef_map.contains_key(identifier)
+        {
+            Err(CE::duplicated_symbol(identifier.to_string(), span))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub(crate) fn analyze_function_definition(
+        &mut self,
+        declaration: &Declaration,
+    ) -> Result<Option<TypedDeclaration>, Vec<CE>> {
+        let (function_specifier, storage_class, attributes, identifier, params, is_variadic, body) =
+            match declaration.node {
+                DeclarationEnum::FunctionDefinition(
+                    ref function_specifier,
+                    ref storage_class,
+                    ref attributes,
+                    _,
+                    ref identifier,
+                    ref params,
+                    is_variadic,
+                    ref body,
+                ) => (
+                    function_specifier,
+                    storage_class,
+                    attributes,
+                    identifier,
+                    params,
+                    is_variadic,
+                    body,
+                ),
+                _ => unreachable!(),
+            };
+        let (return_type, param_types) = match self.function_map.get(identifier) {
+            Some(function) => (function.0.to_owned(), function.1.to_owned()),
+            // might be CE during the declaration of its prototype
+            None => return Ok(None),
+        };
+        if !self.defined_functions.insert(identifier.to_owned()) {
+            return Err(vec![CE::duplicated_function(
+                identifier.to_string(),
+                declaration.span,
+            )]);
+        }
+
+        let mut errors: Vec<CE> = vec![];
+        self.current_function = Some((identifier.to_owned(), return_type.to_owned(), is_variadic));
+        // parameters share the scope of the outermost block of the body
+        self.scopes.push(HashMap::new());
+
+        let typed_params = param_types
+            .iter()
+            .zip(params.iter())
+            .map(|(param_type, (_, name))| (param_type.to_owned(), name.to_owned()))
+            .collect::<Vec<_>>();
+        for (i, (param_type, name)) in typed_params.iter().enumerate() {
+            if let Some(name) = name {
+                if let Err(e) = self.declare_local(
+                    name,
+                    param_type.to_owned(),
+                    Symbol::Parameter(i),
+                    declaration.span,
+                ) {
+                    errors.push(e);
+                }
+            }
+        }
+
+        let items = match body.node {
+            StatementEnum::Compound(ref items) => items
+                .iter()
+                .filter_map(|item| match self.analyze_block_item(item) {
+                    Ok(item) => Some(item),
+                    Err(e) => {
+                        errors.push(e);
+                        None
+                    }
+                })
+                .collect::<Vec<_>>(),
+            _ => panic!("internal error: func_body is not Statement::Compound"),
+        };
+
+        self.scopes.pop();
+        self.current_function = None;
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        let body = TypedStatement {
+            node: TypedStatementEnum::Compound(items),
+            span: body.span,
+        };
+        let (mut labels, mut jumps) = (HashSet::new(), vec![]);
+        Self::collect_labels(&body, &mut labels, &mut jumps);
+        let errors = jumps
+            .into_iter()
+            .filter(|(label, _)| !labels.contains(label))
+            .map(|(label, span)| CE::undefined_label(label, span))
+            .collect::<Vec<_>>();
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        // the end of a `_Noreturn` function is still assumed unreachable
+        if self.noreturn_functions.contains(identifier) && self.can_complete(&body) {
+            self.warnings.push(CE::noreturn_function_returns(
+                identifier.to_string(),
+                self.closing_brace(body.span),
+            ));
+        } else if return_type.base_type != BaseType::Void
+            // like C99, `main` returns 0 when it reaches its end
+            && identifier != "main"
+            && self.can_complete(&body)
+        {
+            self.warnings.push(CE::missing_return(
+                identifier.to_string(),
+                self.closing_brace(body.span),
+            ));
+        }
+        Ok(Some(TypedDeclaration {
+            node: TypedDeclarationEnum::FunctionDefinition(
+                function_specifier.to_owned(),
+                storage_class.to_owned(),
+                attributes.to_owned(),
+                Box::new(return_type),
+                identifier.to_owned(),
+                typed_params,
+                is_variadic,
+                body,
+            ),
+            span: declaration.span,
+        }))
+    }
+
+    /// The `}` ending a compound statement.
+    fn closing_brace(&self, span: Span) -> Span {
+        let end = self.code[..span.end].rfind('}').map_or(span.end, |i| i + 1);
+        Span::new(end - 1, end)
+    }
+
+    pub(crate) fn analyze_local_declaration(
+        &mut self,
+        declaration: &Declaration,
+    ) -> Result<TypedDeclaration, CE> {
+        let span = declaration.span;
+        let (type_info, identifier, initializer) = match declaration.node {
+            DeclarationEnum::Declaration(ref type_info, ref identifier, ref initializer) => {
+                (type_info, identifier, initializer)
+            }
+            DeclarationEnum::FunctionDefinition(..) => {
+                unreachable!("function definitions are only parsed at file scope")
+            }
+        };
+        self.define_struct(&type_info.basic_type.base_type, span)?;
+        let identifier = match identifier {
+            Some(identifier) => identifier,
+            None => {
+                self.check_attributes(&type_info.attributes, AttributeTarget::Tag, None)?;
+                return self.struct_declaration(type_info, span);
+            }
+        };
+
+        let var_type = self.resolve_type(&type_info.basic_type, span)?;
+        if type_info.storage_class_specifier == StorageClassSpecifier::Typedef {
+            self.check_attributes(&type_info.attributes, AttributeTarget::Typedef, None)?;
+            if self.typedef_map.contains_key(identifier) {
+                return Err(CE::duplicated_symbol(identifier.to_string(), span));
+            }
+            self.typedef_map
+                .insert(identifier.to_owned(), var_type.to_owned());
+            return Ok(TypedDeclaration {
+                node: TypedDeclarationEnum::Declaration(
+                    Type {
+                        basic_type: self.with_struct_definitions(type_info, var_type),
+                        ..type_info.to_owned()
+                    },
+                    Some(identifier.to_owned()),
+                    None,
+                ),
+                span,
+            });
+        }
+        if let BaseType::Function(ref return_type, ref params, is_variadic) = var_type.base_type {
+            if type_info.thread_local {
+                return Err(CE::invalid_thread_local(identifier.to_string(), span));
+            }
+            self.check_attributes(
+                &type_info.attributes,
+                AttributeTarget::Function,
+                Some(&var_type),
+            )?;
+            if !self.function_map.contains_key(identifier) {
+                self.declare_function(identifier, return_type, params, is_variadic, span)?;
+            }
+            self.declare_function_specifiers(
+                identifier,
+                &type_info.function_specifier,
+                &type_info.attributes,
+            );
+            return Ok(TypedDeclaration {
+                node: TypedDeclarationEnum::Declaration(
+                    Type {
+                        basic_type: self.declared_function_type(identifier),
+                        ..type_info.to_owned()
+                    },
+                    Some(identifier.to_owned()),
+                    None,
+                ),
+                span,
+            });
+        }
+
+        // a block scope variable has automatic storage duration unless declared `static` or
+        // `extern`
+        if type_info.thread_local
+            && type_info.storage_class_specifier != StorageClassSpecifier::Static
+            && type_info.storage_class_specifier != StorageClassSpecifier::Extern
+        {
+            return Err(CE::invalid_thread_local(identifier.to_string(), span));
+        }
+        self.check_attributes(&type_info.attributes, AttributeTarget::LocalVariable, None)?;
+        let var_type = self.variable_type(type_info, identifier, initializer, span)?;
+        // the variable is in scope in its own initializer
+        self.declare_local(identifier, var_type.to_owned(), Symbol::Local(span), span)?;
+        let initializer = match initializer {
+            Some(initializer) => Some(Box::new(self.analyze_initializer(&var_type, initializer)?)),
+            None => None,
+        };
+        Ok(TypedDeclaration {
+            node: TypedDeclarationEnum::Declaration(
+                Type {
+                    basic_type: self.with_struct_definitions(type_info, var_type),
+                    ..type_info.to_owned()
+                },
+                Some(identifier.to_owned()),
+                initializer,
+            ),
+            span,
+        })
+    }
+
+    /// The complete type of a variable, whose struct type must have been defined.
+    fn variable_type(
+        &self,
+        type_info: &Type,
+        identifier: &str,
+        initializer: &Option<Box<Expression>>,
+        span: Span,
+    ) -> Result<BT, CE> {
+        let var_type = self.resolve_type(
+            &type_info
+                .basic_type
+                .complete_array_type(identifier, initializer, span)?,
+            span,
+        )?;
+        if let BaseType::Struct(Some(_), _) | BaseType::Union(Some(_), _) = var_type.base_type {
+            self.struct_members(&var_type.base_type, span)?;
+        }
+        Ok(var_type)
+    }
+
+    fn analyze_initializer(
+        &self,
+        var_type: &BT,
+        initializer: &Expression,
+    ) -> Result<TypedExpression, CE> {
+        if let (
+            BaseType::Array(ref element_type, ref dimensions),
+            ExpressionEnum::StringLiteral(prefix, ref code_units),
+        ) = (&var_type.base_type, &initializer.node)
+        {
+            if dimensions.len() != 1 || !prefix.initializes(&element_type.base_type) {
+                return Err(CE::invalid_string_initializer(
+                    var_type.base_type.to_string(),
+                    initializer.span,
+                ));
+            }
+            // the terminating null character is dropped if there is no room for it
+            if let ExpressionEnum::IntegerConstant(length) = dimensions[0].node {
+                if code_units.len() > length as usize {
+                    return Err(CE::string_initializer_too_long(
+                        length as usize,
+                        code_units.len(),
+                        initializer.span,
+                    ));
+                }
+            }
+            return Ok(TypedExpression {
+                node: TypedExpressionEnum::Literal(initializer.node.to_owned()),
+                expr_type: BT {
+                    qualifier: vec![],
+                    base_type: var_type.base_type.to_owned(),
+                },
+                category: ValueCategory::LValue,
+                span: initializer.span,
+            });
+        }
+
+        let value = self.analyze_rvalue(initializer)?;
+        self.check_assignable(&value, &var_type.base_type, initializer.span)?;
+        Ok(self.convert(value, &var_type.base_type))
+    }
+
+    /// A declaration declaring no identifier, e.g. `struct s { int a; };`
+    fn struct_declaration(&self, type_info: &Type, span: Span) -> Result<TypedDeclaration, CE> {
+        let mut basic_type = type_info.basic_type.to_owned();
+        if let BaseType::Struct(_, Some(ref mut members))
+        | BaseType::Union(_, Some(ref mut members)) = basic_type.base_type
+        {
+            *members = self.resolve_members(members, span)?;
+        }
+        Ok(TypedDeclaration {
+            node: TypedDeclarationEnum::Declaration(
+                Type {
+                    basic_type,
+                    ..type_info.to_owned()
+                },
+                None,
+                None,
+            ),
+            span,
+        })
+    }
+
+    /// The resolved type of a declaration, with the members of the struct or union it defines
+    /// listed again, e.g. in `struct s { int a; } *p;`, so that code generation sees the
+    /// definition.
+    fn with_struct_definitions(&self, type_info: &Type, mut var_type: BT) -> BT {
+        let mut declared = &type_info.basic_type.base_type;
+        let mut resolved = &mut var_type.base_type;
+        loop {
+            match (declared, resolved) {
+                (
+                    BaseType::Struct(Some(name), Some(_)) | BaseType::Union(Some(name), Some(_)),
+                    BaseType::Struct(_, members) | BaseType::Union(_, members),
+                ) => {
+                    *members = self.struct_map.get(name).cloned();
+                    break;
+                }
+                (BaseType::Pointer(d), BaseType::Pointer(r))
+                | (BaseType::Array(d, _), BaseType::Array(r, _)) => {
+                    declared = &d.base_type;
+                    resolved = &mut r.base_type;
+                }
+                _ => break,
+            }
+        }
+        var_type
+    }
+
+    /// The type of a declared function, with the parameter types of its first declaration
+    /// adjusted.
+    fn declared_function_type(&self, name: &str) -> BT {
+        let (return_type, params, is_variadic, _) = &self.function_map[name];
+        BT {
+            qualifier: vec![],
+            base_type: BaseType::Function(
+                Box::new(return_type.to_owned()),
+                params.to_owned(),
+                *is_variadic,
+            ),
+        }
+    }
+
+    /// Record the members of a struct or union declared with a tag and a member list.
+    fn define_struct(&mut self, t: &BaseType, span: Span) -> Result<(), CE> {
+        if let BaseType::Struct(Some(ref name), Some(ref members))
+        | BaseType::Union(Some(ref name), Some(ref members)) = t
+        {
+            if self.struct_map.contains_key(name) {
+                return Err(CE::duplicated_struct_definition(name.to_string(), span));
+            }
+            self.check_member_attributes(members)?;
+            let members = self.resolve_members(members, span)?;
+            self.struct_map.insert(name.to_owned(), members);
+        }
+        Ok(())
+    }
+
+    /// A function may be declared again with the same prototype, e.g. `extern` after its inline
+    /// definition; whether it is defined twice is checked with its body.
+    fn declare_function(
+        &mut self,
+        name: &str,
+        return_type: &BT,
+        params: &[BT],
+        is_variadic: bool,
+        span: Span,
+    ) -> Result<(), CE> {
+        if self.global_variable_map.contains_key(name) || self.typedef_map.contains_key(name) {
+            return Err(CE::redefinition_symbol(name.to_string(), span));
+        }
+
+        // `(void)` declares a function without parameters
+        let params = match params {
+            [BT {
+                base_type: BaseType::Void,
+                ..
+            }] => vec![],
+            _ => params.iter().map(Self::adjust_parameter_type).collect(),
+        };
+        if let Some((declared_return_type, declared_params, declared_variadic, _)) =
+            self.function_map.get(name)
+        {
+            return match declared_return_type == return_type
+                && declared_params.len() == params.len()
+                // the qualifiers of a parameter are not part of the function type
+                && declared_params.iter().zip(params.iter()).all(|(declared, param)| {
+                    declared
+                        .base_type
+                        .equal_discarding_qualifiers(&param.base_type, &self.typedef_map)
+                })
+                && *declared_variadic == is_variadic
+            {
+                true => Ok(()),
+                false => Err(CE::duplicated_function(name.to_string(), span)),
+            };
+        }
+        self.function_map.insert(
+            name.to_owned(),
+            (return_type.to_owned(), params, is_variadic, span),
+        );
+        Ok(())
+    }
+
+    /// `_Noreturn`, or the `noreturn` attribute, on any declaration of a function applies to all
+    /// of them.
+    fn declare_function_specifiers(
+        &mut self,
+        name: &str,
+        function_specifier: &[FunctionSpecifier],
+        attributes: &[Attribute],
+    ) {
+        if function_specifier.contains(&FunctionSpecifier::Noreturn)
+            || attributes
+                .iter()
+                .any(|attribute| attribute.name == "noreturn")
+        {
+            self.noreturn_functions.insert(name.to_owned());
+        }
+    }
+
+    /// A parameter declared as array of T is adjusted to pointer to T, and one declared as
+    /// function to pointer to function.
+    fn adjust_parameter_type(param: &BT) -> BT {
+        match param.base_type {
+            BaseType::Array(ref element_type, ref dimensions) => BT {
+                qualifier: param.qualifier.to_owned(),
+                base_type: BaseType::Pointer(match dimensions.len() {
+                    1 => element_type.to_owned(),
+                    _ => Box::new(BT {
+                        qualifier: vec![],
+                        base_type: BaseType::Array(
+                            element_type.to_owned(),
+                            dimensions[1..].to_vec(),
+                        ),
+                    }),
+                }),
+            },
+            BaseType::Function(_, _, _) => BT {
+                qualifier: vec![],
+                base_type: BaseType::Pointer(Box::new(param.to_owned())),
+            },
+            _ => param.to_owned(),
+        }
+    }
+
+    fn declare_local(
+        &mut self,
+        identifier: &str,
+        var_type: BT,
+        symbol: Symbol,
+        span: Span,
+    ) -> Result<(), CE> {
+        let scope = self.scopes.last_mut().unwrap();
+        if scope.contains_key(identifier) {
+            return Err(CE::duplicated_variable(identifier.to_string(), span));
+        }
+        scope.insert(identifier.to_owned(), (var_type, symbol));
+        Ok(())
+    }
+}
@@ -0,0 +1,1147 @@
+use crate::ast::{
+    AssignOperation, BaseType, BasicType as BT, BinaryOperation, BinaryOperationEnum, Expression,
+    ExpressionEnum, IntegerType, Span, UnaryOperation, UnaryOperationEnum,
+};
+use crate::sema::*;
+use crate::utils::builtin::{builtin_signature, is_overflow_builtin};
+use crate::utils::CompileErr as CE;
+
+impl<'a> Sema<'a> {
+    pub(crate) fn analyze_expression(&self, expr: &Expression) -> Result<TypedExpression, CE> {
+        let span = expr.span;
+        match expr.node {
+            ExpressionEnum::Assignment(ref op, ref lhs, ref rhs) => {
+                self.analyze_assignment(op, lhs, rhs, span)
+            }
+            ExpressionEnum::Unary(ref op, ref operand) => self.analyze_unary(op, operand, span),
+            ExpressionEnum::Binary(ref op, ref lhs, ref rhs) => {
+                self.analyze_binary(op, lhs, rhs, span)
+            }
+            ExpressionEnum::FunctionCall(ref function, ref args) => {
+                self.analyze_function_call(function, args, span)
+            }
+            ExpressionEnum::TypeCast(ref cast_type, ref operand) => {
+                let cast_type = self.resolve_type(cast_type, span)?;
+                let operand = self.analyze_rvalue(operand)?;
+                let (from, to) = (&operand.expr_type.base_type, &cast_type.base_type);
+                let pointer_and_floating = matches!(
+                    (from, to),
+                    (BaseType::Pointer(_), BaseType::Float | BaseType::Double)
+                        | (BaseType::Float | BaseType::Double, BaseType::Pointer(_))
+                );
+                let valid = *to == BaseType::Void
+                    || from.equal_discarding_qualifiers(to, &self.typedef_map)
+                    || from.is_scalar() && to.is_scalar() && !pointer_and_floating;
+                if !valid {
+                    return Err(CE::invalid_cast(from.to_string(), to.to_string(), span));
+                }
+                Ok(Self::rvalue(
+                    TypedExpressionEnum::TypeCast(cast_type.to_owned(), Box::new(operand)),
+                    cast_type.base_type,
+                    span,
+                ))
+            }
+            ExpressionEnum::Conditional(ref cond, ref true_expr, ref false_expr) => {
+                self.analyze_conditional(cond, true_expr, false_expr, span)
+            }
+            ExpressionEnum::SizeofType(ref t) => {
+                // `sizeof(x)` is parsed as if `x` was a type name
+                if let BaseType::Identifier(ref name) = t.base_type {
+                    if !self.typedef_map.contains_key(name) {
+                        let operand = Expression {
+                            node: ExpressionEnum::Identifier(name.to_owned()),
+                            span,
+                        };
+                        let op = UnaryOperation {
+                            node: UnaryOperationEnum::SizeofExpr,
+                            span,
+                        };
+                        return self.analyze_unary(&op, &operand, span);
+                    }
+                }
+                let t = self.resolve_type(t, span)?;
+                self.check_sizeof_type(&t.base_type, span)?;
+                Ok(Self::rvalue(
+                    TypedExpressionEnum::SizeofType(t),
+                    Self::size_type(),
+                    span,
+                ))
+            }
+            ExpressionEnum::GenericSelection(ref controlling, ref associations) => {
+                self.analyze_expression(self.select_generic_association(controlling, associations)?)
+            }
+            ExpressionEnum::VaArg(ref va_list, ref argument_type) => {
+                let va_list = self.analyze_va_list(va_list)?;
+                let argument_type = self.resolve_type(argument_type, span)?;
+                Ok(Self::rvalue(
+                    TypedExpressionEnum::VaArg(Box::new(va_list), argument_type.to_owned()),
+                    argument_type.base_type,
+                    span,
+                ))
+            }
+            ExpressionEnum::Offsetof(ref struct_type, ref members) => {
+                let struct_type = self.resolve_type(struct_type, span)?;
+                let mut member_type = struct_type.to_owned();
+                for member in members {
+                    member_type = self.find_member(&member_type, member, span)?;
+                }
+                Ok(Self::rvalue(
+                    TypedExpressionEnum::Offsetof(struct_type, members.to_owned()),
+                    Self::size_type(),
+                    span,
+                ))
+            }
+            ExpressionEnum::MemberOfObject(ref object, ref member) => {
+                let object = self.analyze_expression(object)?;
+                let member_type = self.find_member(&object.expr_type, member, span)?;
+                Ok(TypedExpression {
+                    category: object.category,
+                    node: TypedExpressionEnum::MemberOfObject(Box::new(object), member.to_owned()),
+                    expr_type: member_type,
+                    span,
+                })
+            }
+            ExpressionEnum::MemberOfPointer(ref pointer, ref member) => {
+                let pointer = self.analyze_rvalue(pointer)?;
+                let member_type = match pointer.expr_type.base_type {
+                    BaseType::Pointer(ref pointee) => self.find_member(pointee, member, span)?,
+                    _ => return Err(CE::invalid_dereference(member.to_string(), span)),
+                };
+                Ok(TypedExpression {
+                    node: TypedExpressionEnum::MemberOfPointer(
+                        Box::new(pointer),
+                        member.to_owned(),
+                    ),
+                    expr_type: member_type,
+                    category: ValueCategory::LValue,
+                    span,
+                })
+            }
+            ExpressionEnum::ArraySubscript(ref array, ref indexes) => {
+                self.analyze_array_subscript(array, indexes, span)
+            }
+            ExpressionEnum::Identifier(ref identifier) => self.analyze_identifier(identifier, span),
+            ExpressionEnum::IntegerConstant(_) => Ok(Self::literal(
+                expr,
+                BaseType::SignedInteger(IntegerType::Int),
+            )),
+            ExpressionEnum::UnsignedIntegerConstant(_) => Ok(Self::literal(
+                expr,
+                BaseType::UnsignedInteger(IntegerType::Int),
+            )),
+            ExpressionEnum::LongConstant(_) => Ok(Self::literal(
+                expr,
+                BaseType::SignedInteger(IntegerType::Long),
+            )),
+            ExpressionEnum::UnsignedLongConstant(_) => Ok(Self::literal(
+                expr,
+                BaseType::UnsignedInteger(IntegerType::Long),
+            )),
+            ExpressionEnum::LongLongConstant(_) => Ok(Self::literal(
+                expr,
+                BaseType::SignedInteger(IntegerType::LongLong),
+            )),
+            ExpressionEnum::UnsignedLongLongConstant(_) => Ok(Self::literal(
+                expr,
+                BaseType::UnsignedInteger(IntegerType::LongLong),
+            )),
+            ExpressionEnum::CharacterConstant(prefix, _) => {
+                Ok(Self::literal(expr, prefix.character_type()))
+            }
+            ExpressionEnum::FloatConstant(_) => Ok(Self::literal(expr, BaseType::Float)),
+            ExpressionEnum::DoubleConstant(_) => Ok(Self::literal(expr, BaseType::Double)),
+            ExpressionEnum::StringLiteral(prefix, ref code_units) => {
+                // an array holding the code units and the terminating null character
+                let array_type = BaseType::Array(
+                    Box::new(BT {
+                        qualifier: vec![],
+                        base_type: prefix.element_type(),
+                    }),
+                    vec![Expression {
+                        node: ExpressionEnum::IntegerConstant(code_units.len() as i32 + 1),
+                        span,
+                    }],
+                );
+                Ok(TypedExpression {
+                    category: ValueCategory::LValue,
+                    ..Self::literal(expr, array_type)
+                })
+            }
+            ExpressionEnum::Empty => Ok(Self::rvalue(
+                TypedExpressionEnum::Empty,
+                BaseType::Void,
+                span,
+            )),
+        }
+    }
+
+    /// Analyze an expression whose value is used, arrays and functions are converted to
+    /// pointers.
+    pub(crate) fn analyze_rvalue(&self, expr: &Expression) -> Result<TypedExpression, CE> {
+        Ok(Self::decay(self.analyze_expression(expr)?))
+    }
+
+    /// Array-to-pointer and function-to-pointer conversions, other expressions are kept.
+    fn decay(expr: TypedExpression) -> TypedExpression {
+        let pointer_type = match expr.expr_type.base_type {
+            BaseType::Array(ref element_type, ref dimensions) => {
+                BaseType::Pointer(match dimensions.len() {
+                    1 => element_type.to_owned(),
+                    _ => Box::new(BT {
+                        qualifier: vec![],
+                        base_type: BaseType::Array(
+                            element_type.to_owned(),
+                            dimensions[1..].to_vec(),
+                        ),
+                    }),
+                })
+            }
+            BaseType::Function(_, _, _) => BaseType::Pointer(Box::new(expr.expr_type.to_owned())),
+            _ => return expr,
+        };
+        let span = expr.span;
+        Self::rvalue(
+            TypedExpressionEnum::ImplicitCast(Box::new(expr)),
+            pointer_type,
+            span,
+        )
+    }
+
+    /// Convert the value of `expr` to `dest`, unless it already has that type.
+    pub(crate) fn convert(&self, expr: TypedExpression, dest: &BaseType) -> TypedExpression {
+        if expr
+            .expr_type
+            .base_type
+            .equal_discarding_qualifiers(dest, &self.typedef_map)
+        {
+            return expr;
+        }
+        let span = expr.span;
+        Self::rvalue(
+            TypedExpressionEnum::ImplicitCast(Box::new(expr)),
+            dest.to_owned(),
+            span,
+        )
+    }
+
+    /// Whether `value` may be implicitly converted to `to`, as in assignment. A null pointer
+    /// constant converts to any pointer (C99 6.5.16.1p1).
+    pub(crate) fn check_assignable(
+        &self,
+        value: &TypedExpression,
+        to: &BaseType,
+        span: Span,
+    ) -> Result<(), CE> {
+        match matches!(to, BaseType::Pointer(_)) && Self::is_null_pointer_constant(value) {
+            true => Ok(()),
+            false => self.check_convertible(&value.expr_type.base_type, to, span),
+        }
+    }
+
+    /// Whether a value of type `from` may be implicitly converted to `to`.
+    fn check_convertible(&self, from: &BaseType, to: &BaseType, span: Span) -> Result<(), CE> {
+        if from.is_arithmetic() && to.is_arithmetic() {
+            // any arithmetic type converts to any other, narrowing included (C99 6.5.16.1)
+            Ok(())
+        } else if from.is_scalar() && to.is_scalar() {
+            from.test_cast(to, span, &self.typedef_map)
+        } else if from.equal_discarding_qualifiers(to, &self.typedef_map) {
+            Ok(())
+        } else {
+            Err(CE::invalid_default_cast(
+                from.to_string(),
+                to.to_string(),
+                span,
+            ))
+        }
+    }
+
+    /// `size_t`, the type of `sizeof` and `offsetof`
+    fn size_type() -> BaseType {
+        BaseType::UnsignedInteger(IntegerType::Long)
+    }
+
+    fn rvalue(node: TypedExpressionEnum, base_type: BaseType, span: Span) -> TypedExpression {
+        TypedExpression {
+            node,
+            expr_type: BT {
+                qualifier: vec![],
+                base_type,
+            },
+            category: ValueCategory::RValue,
+            span,
+        }
+    }
+
+    fn literal(expr: &Expression, base_type: BaseType) -> TypedExpression {
+        Self::rvalue(
+            TypedExpressionEnum::Literal(expr.node.to_owned()),
+            base_type,
+            expr.span,
+        )
+    }
+
+    fn analyze_identifier(&self, identifier: &str, span: Span) -> Result<TypedExpression, CE> {
+        if let Some((var_type, symbol)) = self.get_variable(identifier) {
+            return Ok(TypedExpression {
+                node: TypedExpressionEnum::Identifier(identifier.to_owned(), *symbol),
+                expr_type: var_type.to_owned(),
+                category: ValueCategory::LValue,
+                span,
+            });
+        }
+        match self.function_map.get(identifier) {
+            Some((return_type, params, is_variadic, declaration)) => Ok(Self::rvalue(
+                TypedExpressionEnum::Identifier(
+                    identifier.to_owned(),
+                    Symbol::Function(*declaration),
+                ),
+                BaseType::Function(
+                    Box::new(return_type.to_owned()),
+                    params.to_owned(),
+                    *is_variadic,
+                ),
+                span,
+            )),
+            None => Err(CE::missing_variable(identifier.to_string(), span)),
+        }
+    }
+
+    /// An lvalue designating an object, arrays included, which cannot be assigned to.
+    pub(crate) fn analyze_lvalue(&self, expr: &Expression) -> Result<TypedExpression, CE> {
+        let lvalue = self.analyze_expression(expr)?;
+        if lvalue.category != ValueCategory::LValue {
+            return Err(CE::invalid_left_value(
+                self.source_text(expr.span),
+                expr.span,
+            ));
+        }
+        Ok(lvalue)
+    }
+
+    /// A modifiable lvalue, the operand of assignment, `++` and `--`.
+    pub(crate) fn analyze_modifiable_lvalue(
+        &self,
+        expr: &Expression,
+    ) -> Result<TypedExpression, CE> {
+        let lvalue = self.analyze_lvalue(expr)?;
+        if let BaseType::Array(_, _) = lvalue.expr_type.base_type {
+            return Err(CE::invalid_left_value(
+                self.source_text(expr.span),
+                expr.span,
+            ));
+        }
+        if self.is_read_only(&lvalue.expr_type, expr.span)? {
+            return Err(CE::assignment_to_read_only(
+                self.source_text(expr.span),
+                expr.span,
+            ));
+        }
+        Ok(lvalue)
+    }
+
+    /// Const-qualified types, and structs or unions with a const-qualified member.
+    fn is_read_only(&self, t: &BT, span: Span) -> Result<bool, CE> {
+        if t.is_const() {
+            return Ok(true);
+        }
+        match t.base_type {
+            BaseType::Struct(_, _) | BaseType::Union(_, _) => {
+                for member in self.struct_members(&t.base_type, span)? {
+                    if self.is_read_only(&member.member_type, span)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    fn analyze_assignment(
+        &self,
+        op: &AssignOperation,
+        lhs: &Expression,
+        rhs: &Expression,
+        span: Span,
+    ) -> Result<TypedExpression, CE> {
+        let lhs = self.analyze_modifiable_lvalue(lhs)?;
+        let l_t = lhs.expr_type.base_type.to_owned();
+        let rhs_span = rhs.span;
+        let rhs = self.analyze_rvalue(rhs)?;
+
+        let rhs = match op.node.binary_operation() {
+            None => {
+                self.check_assignable(&rhs, &l_t, rhs_span)?;
+                self.convert(rhs, &l_t)
+            }
+            Some(binary_op) => {
+                // the right operand is converted to the type the operation is performed in,
+                // the result of which is then converted back
+                let op = BinaryOperation {
+                    node: binary_op,
+                    span: op.span,
+                };
+                let (_, rhs, result_t) = self.binary_operands(&op, lhs.to_owned(), rhs)?;
+                self.check_convertible(&result_t, &l_t, rhs_span)?;
+                rhs
+            }
+        };
+
+        Ok(Self::rvalue(
+            TypedExpressionEnum::Assignment(op.to_owned(), Box::new(lhs), Box::new(rhs)),
+            l_t,
+            span,
+        ))
+    }
+
+    fn analyze_unary(
+        &self,
+        op: &UnaryOperation,
+        operand: &Expression,
+        span: Span,
+    ) -> Result<TypedExpression, CE> {
+        let operand_span = operand.span;
+        let (operand, result_t) = match op.node {
+            UnaryOperationEnum::Reference => {
+                let operand = self.analyze_expression(operand)?;
+                if operand.category != ValueCategory::LValue
+                    && !matches!(operand.expr_type.base_type, BaseType::Function(_, _, _))
+                {
+                    return Err(CE::invalid_left_value(
+                        self.source_text(operand_span),
+                        operand_span,
+                    ));
+                }
+                let result_t = BaseType::Pointer(Box::new(operand.expr_type.to_owned()));
+                (operand, result_t)
+            }
+            UnaryOperationEnum::Dereference => {
+                let operand = self.analyze_rvalue(operand)?;
+                let pointee = match operand.expr_type.base_type {
+                    BaseType::Pointer(ref pointee) => pointee.as_ref().to_owned(),
+                    _ => {
+                        return Err(CE::invalid_dereference(
+                            self.source_text(operand_span),
+                            span,
+                        ))
+                    }
+                };
+                let category = match pointee.base_type {
+                    BaseType::Function(_, _, _) => ValueCategory::RValue,
+                    _ => ValueCategory::LValue,
+                };
+                return Ok(TypedExpression {
+                    node: TypedExpressionEnum::Unary(op.to_owned(), Box::new(operand)),
+                    expr_type: pointee,
+                    category,
+                    span,
+                });
+            }
+            UnaryOperationEnum::SizeofExpr => {
+                // the operand is never evaluated, and it is not converted
+                let operand = self.analyze_expression(operand)?;
+                self.check_sizeof_type(&operand.expr_type.base_type, operand_span)?;
+                (operand, Self::size_type())
+            }
+            UnaryOperationEnum::PrefixIncrement
+            | UnaryOperationEnum::PrefixDecrement
+            | UnaryOperationEnum::PostfixIncrement
+            | UnaryOperationEnum::PostfixDecrement => {
+                let operand = self.analyze_modifiable_lvalue(operand)?;
+                match operand.expr_type.base_type {
+                    BaseType::SignedInteger(_)
+                    | BaseType::UnsignedInteger(_)
+                    | BaseType::Float
+                    | BaseType::Double
+                    | BaseType::Pointer(_) => {
+                        let result_t = operand.expr_type.base_type.to_owned();
+                        (operand, result_t)
+                    }
+                    _ => return Err(CE::invalid_unary(operand_span)),
+                }
+            }
+            // the operand is promoted
+            UnaryOperationEnum::UnaryPlus | UnaryOperationEnum::UnaryMinus => {
+                let operand = self.analyze_rvalue(operand)?;
+                if !operand.expr_type.base_type.is_arithmetic() {
+                    return Err(CE::invalid_unary(operand_span));
+                }
+                let result_t = operand.expr_type.base_type.promote();
+                (self.convert(operand, &result_t), result_t)
+            }
+            UnaryOperationEnum::BitwiseNot => {
+                let operand = self.analyze_rvalue(operand)?;
+                if !operand.expr_type.base_type.is_integer() {
+                    return Err(CE::invalid_unary(operand_span));
+                }
+                let result_t = operand.expr_type.base_type.promote();
+                (self.convert(operand, &result_t), result_t)
+            }
+            UnaryOperationEnum::LogicalNot => {
+                let operand = self.analyze_rvalue(operand)?;
+                if !operand.expr_type.base_type.is_scalar() {
+                    return Err(CE::invalid_unary(operand_span));
+                }
+                (operand, BaseType::SignedInteger(IntegerType::Int))
+            }
+        };
+        Ok(Self::rvalue(
+            TypedExpressionEnum::Unary(op.to_owned(), Box::new(operand)),
+            result_t,
+            span,
+        ))
+    }
+
+    fn analyze_binary(
+        &self,
+        op: &BinaryOperation,
+        lhs: &Expression,
+        rhs: &Expression,
+        span: Span,
+    ) -> Result<TypedExpression, CE> {
+        if let BinaryOperationEnum::Comma = op.node {
+            // the left operand is evaluated only for its side effects
+            let lhs = self.analyze_expression(lhs)?;
+            let rhs = self.analyze_rvalue(rhs)?;
+            let result_t = rhs.expr_type.base_type.to_owned();
+            return Ok(Self::rvalue(
+                TypedExpressionEnum::Binary(op.to_owned(), Box::new(lhs), Box::new(rhs)),
+                result_t,
+                span,
+            ));
+        }
+        let lhs = self.analyze_rvalue(lhs)?;
+        let rhs = self.analyze_rvalue(rhs)?;
+        let (lhs, rhs, result_t) = self.binary_operands(op, lhs, rhs)?;
+        Ok(Self::rvalue(
+            TypedExpressionEnum::Binary(op.to_owned(), Box::new(lhs), Box::new(rhs)),
+            result_t,
+            span,
+        ))
+    }
+
+    /// Check the operands of a binary operator other than the comma operator, and convert
+    /// arithmetic operands to their common type. Comparisons and logical operators result
+    /// in `int` (C99 6.5.8-6.5.14).
+    fn binary_operands(
+        &self,
+        op: &BinaryOperation,
+        lhs: TypedExpression,
+        rhs: TypedExpression,
+    ) -> Result<(TypedExpression, TypedExpression, BaseType), CE> {
+        use BinaryOperationEnum::*;
+
+        let (l_t, r_t) = (&lhs.expr_type.base_type, &rhs.expr_type.base_type);
+        let is_comparison = matches!(
+            op.node,
+            LessThan | LessThanOrEqual | GreaterThan | GreaterThanOrEqual | Equal | NotEqual
+        );
+
+        let int_t = BaseType::SignedInteger(IntegerType::Int);
+        let result_t = match (&op.node, l_t, r_t) {
+            (LogicalAnd | LogicalOr, _, _) if l_t.is_scalar() && r_t.is_scalar() => int_t,
+            (Addition | Subtraction, BaseType::Pointer(_), _) if r_t.is_integer() => l_t.to_owned(),
+            (Addition, _, BaseType::Pointer(_)) if l_t.is_integer() => r_t.to_owned(),
+            (Subtraction, BaseType::Pointer(_), BaseType::Pointer(_)) => {
+                BaseType::SignedInteger(IntegerType::Long)
+            }
+            (_, BaseType::Pointer(_), BaseType::Pointer(_)) if is_comparison => int_t,
+            // comparison with a null pointer constant
+            (Equal | NotEqual, BaseType::Pointer(_), _) if r_t.is_integer() => int_t,
+            (Equal | NotEqual, _, BaseType::Pointer(_)) if l_t.is_integer() => int_t,
+            (LogicalAnd | LogicalOr, _, _) => return Err(CE::invalid_binary(op.span)),
+            (LeftShift | RightShift, _, _) if l_t.is_integer() && r_t.is_integer() => {
+                // the result has the promoted type of the left operand, which the right one
+                // is converted to as well, the operands of LLVM's shifts having one type
+                let promoted_t = l_t.promote();
+                let lhs = self.convert(lhs, &promoted_t);
+                let rhs = self.convert(rhs, &promoted_t);
+                return Ok((lhs, rhs, promoted_t));
+            }
+            _ if l_t.is_arithmetic() && r_t.is_arithmetic() => {
+                let common_t = BaseType::usual_arithmetic_conversion(l_t, r_t);
+                let integer_only = matches!(
+                    op.node,
+                    Modulo | BitwiseAnd | BitwiseOr | BitwiseXor | LeftShift | RightShift
+                );
+                if integer_only && !common_t.is_integer() {
+                    return Err(CE::invalid_binary(op.span));
+                }
+                let lhs = self.convert(lhs, &common_t);
+                let rhs = self.convert(rhs, &common_t);
+                return Ok(if is_comparison {
+                    (lhs, rhs, int_t)
+                } else {
+                    (lhs, rhs, common_t)
+                });
+            }
+            _ => return Err(CE::invalid_binary(op.span)),
+        };
+        Ok((lhs, rhs, result_t))
+    }
+
+    fn analyze_function_call(
+        &self,
+        function: &Expression,
+        args: &[Expression],
+        span: Span,
+    ) -> Result<TypedExpression, CE> {
+        if let ExpressionEnum::GenericSelection(ref controlling, ref associations) = function.node {
+            let selected = self.select_generic_association(controlling, associations)?;
+            return self.analyze_function_call(selected, args, span);
+        }
+        if let ExpressionEnum::Identifier(ref identifier) = function.node {
+            if self.get_variable(identifier).is_none()
+                && !self.function_map.contains_key(identifier)
+            {
+                if let Some(call) = self.analyze_builtin_call(identifier, args, function.span, span)
+                {
+                    return call;
+                }
+            }
+        }
+
+        let function_span = function.span;
+        let function = self.analyze_rvalue(function)?;
+        let (return_type, params, is_variadic) = match function.expr_type.base_type {
+            BaseType::Pointer(ref pointee) => match pointee.base_type {
+                BaseType::Function(ref return_type, ref params, is_variadic) => (
+                    return_type.as_ref().to_owned(),
+                    params.to_owned(),
+                    is_variadic,
+                ),
+                _ => {
+                    return Err(CE::not_a_function(
+                        self.source_text(function_span),
+                        function.expr_type.base_type.to_string(),
+                        function_span,
+                    ))
+                }
+            },
+            _ => {
+                return Err(CE::not_a_function(
+                    self.source_text(function_span),
+                    function.expr_type.base_type.to_string(),
+                    function_span,
+                ))
+            }
+        };
+
+        if args.len() < params.len() || args.len() > params.len() && !is_variadic {
+            return Err(CE::parameter_count_mismatch(
+                self.source_text(function_span),
+                params.len(),
+                args.len(),
+                span,
+            ));
+        }
+
+        let args = args
+            .iter()
+            .enumerate()
+            .map(|(i, arg)| {
+                let value = self.analyze_rvalue(arg)?;
+                match params.get(i) {
+                    Some(param) => {
+                        self.check_assignable(&value, &param.base_type, arg.span)?;
+                        Ok(self.convert(value, &param.base_type))
+                    }
+                    // variadic, the default argument promotions
+                    None => {
+                        let promoted_t = match value.expr_type.base_type {
+                            BaseType::Float => BaseType::Double,
+                            BaseType::Bool
+                            | BaseType::SignedInteger(IntegerType::Char)
+                            | BaseType::UnsignedInteger(IntegerType::Char)
+                            | BaseType::SignedInteger(IntegerType::Short)
+                            | BaseType::UnsignedInteger(IntegerType::Short) => {
+                                BaseType::SignedInteger(IntegerType::Int)
+                            }
+                            _ => return Ok(value),
+                        };
+                        Ok(self.convert(value, &promoted_t))
+                    }
+                }
+            })
+            .collect::<Result<Vec<_>, CE>>()?;
+
+        Ok(Self::rvalue(
+            TypedExpressionEnum::FunctionCall(Box::new(function), args),
+            return_type.base_type,
+            span,
+        ))
+    }
+
+    /// Analyze a call to a compiler builtin, or return None if `name` is not a builtin.
+    fn analyze_builtin_call(
+        &self,
+        name: &str,
+        args: &[Expression],
+        name_span: Span,
+        span: Span,
+    ) -> Option<Result<TypedExpression, CE>> {
+        let result = match name {
+            "__builtin_va_start" | "__builtin_va_end" | "__builtin_va_copy" => {
+                self.analyze_va_builtin_args(name, args, span)
+            }
+            "__atomic_load_n"
+            | "__atomic_store_n"
+            | "__atomic_exchange_n"
+            | "__atomic_compare_exchange_n"
+            | "__atomic_fetch_add"
+            | "__atomic_fetch_sub"
+            | "__atomic_fetch_and"
+            | "__atomic_fetch_or"
+            | "__atomic_fetch_xor"
+            | "__atomic_thread_fence"
+            | "__atomic_signal_fence" => self.analyze_atomic_builtin_args(name, args, span),
+            _ if is_overflow_builtin(name) => self.analyze_overflow_builtin_args(name, args, span),
+            _ => match builtin_signature(name) {
+                Some((params, return_type)) => {
+                    self.analyze_prototyped_builtin_args(name, &params, return_type, args, span)
+                }
+                None => return None,
+            },
+        };
+        let function = Self::rvalue(
+            TypedExpressionEnum::Identifier(name.to_owned(), Symbol::Builtin),
+            BaseType::Function(Box::default(), vec![], true),
+            name_span,
+        );
+        Some(result.map(|(args, result_t)| {
+            Self::rvalue(
+                TypedExpressionEnum::FunctionCall(Box::new(function), args),
+                result_t,
+                span,
+            )
+        }))
+    }
+
+    fn analyze_va_builtin_args(
+        &self,
+        name: &str,
+        args: &[Expression],
+        span: Span,
+    ) -> Result<(Vec<TypedExpression>, BaseType), CE> {
+        let va_list_count = match name {
+            "__builtin_va_copy" => 2,
+            _ => 1,
+        };
+        let mut param_count = va_list_count;
+        if name == "__builtin_va_start" {
+            let (function_name, _, is_variadic) = self.current_function.as_ref().unwrap();
+            if !is_variadic {
+                return Err(CE::va_start_in_non_variadic_function(
+                    function_name.to_string(),
+                    span,
+                ));
+            }
+            // the second argument only names the last fixed parameter
+            param_count += 1;
+        }
+        if args.len() != param_count {
+            return Err(CE::parameter_count_mismatch(
+                name.to_string(),
+                param_count,
+                args.len(),
+                span,
+            ));
+        }
+        let args = args
+            .iter()
+            .enumerate()
+            .map(|(i, arg)| match i < va_list_count {
+                true => self.analyze_va_list(arg),
+                false => self.analyze_expression(arg),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok((args, BaseType::Void))
+    }
+
+    /// The arguments of a builtin with a signature are converted to the parameter types.
+    fn analyze_prototyped_builtin_args(
+        &self,
+        name: &str,
+        params: &[BT],
+        return_type: BT,
+        args: &[Expression],
+        span: Span,
+    ) -> Result<(Vec<TypedExpression>, BaseType), CE> {
+        if args.len() != params.len() {
+            return Err(CE::parameter_count_mismatch(
+                name.to_string(),
+                params.len(),
+                args.len(),
+                span,
+            ));
+        }
+        let args = args
+            .iter()
+            .zip(params)
+            .map(|(arg, param)| {
+                let value = self.analyze_rvalue(arg)?;
+                self.check_assignable(&value, &param.base_type, arg.span)?;
+                Ok(self.convert(value, &param.base_type))
+            })
+            .collect::<Result<Vec<_>, CE>>()?;
+        Ok((args, return_type.base_type))
+    }
+
+    /// `__builtin_add_overflow(a, b, result)` and the like: the operands are converted to the
+    /// integer type `result` points to, and the call is whether the operation overflowed it.
+    fn analyze_overflow_builtin_args(
+        &self,
+        name: &str,
+        args: &[Expression],
+        span: Span,
+    ) -> Result<(Vec<TypedExpression>, BaseType), CE> {
+        if args.len() != 3 {
+            return Err(CE::parameter_count_mismatch(
+                name.to_string(),
+                3,
+                args.len(),
+                span,
+            ));
+        }
+        let invalid_argument = |arg: &TypedExpression, expected: &str| {
+            CE::invalid_builtin_argument(
+                name.to_string(),
+                arg.expr_type.base_type.to_string(),
+                expected.to_string(),
+                arg.span,
+            )
+        };
+        let result = self.analyze_rvalue(&args[2])?;
+        let result_t = match result.expr_type.base_type {
+            BaseType::Pointer(ref t)
+                if matches!(
+                    t.base_type,
+                    BaseType::SignedInteger(_) | BaseType::UnsignedInteger(_)
+                ) && !t.is_const() =>
+            {
+                t.base_type.to_owned()
+            }
+            _ => {
+                return Err(invalid_argument(
+                    &result,
+                    "the third argument must point to a modifiable integer",
+                ))
+            }
+        };
+        let mut typed_args = vec![];
+        for arg in &args[..2] {
+            let operand = self.analyze_rvalue(arg)?;
+            if !operand.expr_type.base_type.is_integer() {
+                return Err(invalid_argument(&operand, "the operands must be integers"));
+            }
+            typed_args.push(self.convert(operand, &result_t));
+        }
+        typed_args.push(result);
+        Ok((typed_args, BaseType::Bool))
+    }
+
+    /// The `__atomic_*` builtins of GCC take a pointer to the object, the operands, and the
+    /// memory orders last.
+    fn analyze_atomic_builtin_args(
+        &self,
+        name: &str,
+        args: &[Expression],
+        span: Span,
+    ) -> Result<(Vec<TypedExpression>, BaseType), CE> {
+        // (object count, operand count, memory order count)
+        let (object_count, operand_count, order_count) = match name {
+            "__atomic_thread_fence" | "__atomic_signal_fence" => (0, 0, 1),
+            "__atomic_load_n" => (1, 0, 1),
+            "__atomic_compare_exchange_n" => (1, 3, 2),
+            _ => (1, 1, 1),
+        };
+        let param_count = object_count + operand_count + order_count;
+        if args.len() != param_count {
+            return Err(CE::parameter_count_mismatch(
+                name.to_string(),
+                param_count,
+                args.len(),
+                span,
+            ));
+        }
+        let invalid_argument = |arg: &TypedExpression, expected: &str| {
+            CE::invalid_builtin_argument(
+                name.to_string(),
+                arg.expr_type.base_type.to_string(),
+                expected.to_string(),
+                arg.span,
+            )
+        };
+
+        let mut typed_args = vec![];
+        let mut result_t = BaseType::Void;
+        if object_count == 1 {
+            let object = self.analyze_rvalue(&args[0])?;
+            let t = match object.expr_type.base_type {
+                BaseType::Pointer(ref t) if t.base_type.is_scalar() => t.as_ref().to_owned(),
+                _ => {
+                    return Err(invalid_argument(
+                        &object,
+                        "the first argument must point to an arithmetic or pointer object",
+                    ))
+                }
+            };
+            if name != "__atomic_load_n" && t.is_const() {
+                return Err(CE::assignment_to_read_only(
+                    self.source_text(args[0].span),
+                    args[0].span,
+                ));
+            }
+            if name.starts_with("__atomic_fetch_")
+                && !matches!(
+                    t.base_type,
+                    BaseType::SignedInteger(_) | BaseType::UnsignedInteger(_)
+                )
+            {
+                return Err(invalid_argument(
+                    &object,
+                    "the first argument must point to an integer",
+                ));
+            }
+            typed_args.push(object);
+
+            let value = |arg: &Expression| {
+                let value = self.analyze_rvalue(arg)?;
+                self.check_assignable(&value, &t.base_type, arg.span)?;
+                Ok::<_, CE>(self.convert(value, &t.base_type))
+            };
+            result_t = t.base_type.to_owned();
+            if name == "__atomic_compare_exchange_n" {
+                let expected = self.analyze_rvalue(&args[1])?;
+                match expected.expr_type.base_type {
+                    BaseType::Pointer(ref expected_t)
+                        if expected_t
+                            .base_type
+                            .equal_discarding_qualifiers(&t.base_type, &self.typedef_map)
+                            && !expected_t.is_const() => {}
+                    _ => {
+                        return Err(invalid_argument(
+                            &expected,
+                            "the second argument must point to an object of the type the \
+                             first one points to",
+                        ))
+                    }
+                }
+                typed_args.push(expected);
+                typed_args.push(value(&args[2])?);
+                let weak = self.analyze_condition(&args[3])?;
+                typed_args.push(weak);
+                result_t = BaseType::Bool;
+            } else if operand_count == 1 {
+                typed_args.push(value(&args[1])?);
+                if name == "__atomic_store_n" {
+                    result_t = BaseType::Void;
+                }
+            }
+        }
+
+        for arg in &args[param_count - order_count..] {
+            let order = self.analyze_rvalue(arg)?;
+            if !order.expr_type.base_type.is_integer() {
+                return Err(invalid_argument(&order, "a memory order is an integer"));
+            }
+            typed_args.push(order);
+        }
+        Ok((typed_args, result_t))
+    }
+
+    /// A `va_list` is an array of `struct __va_list_tag`, which decays to a pointer when passed
+    /// to a function.
+    fn analyze_va_list(&self, va_list: &Expression) -> Result<TypedExpression, CE> {
+        let va_list_span = va_list.span;
+        let va_list = self.analyze_expression(va_list)?;
+        match va_list.expr_type.base_type {
+            BaseType::Array(ref element_type, _) | BaseType::Pointer(ref element_type)
+                if matches!(
+                    element_type.base_type,
+                    BaseType::Struct(Some(ref name), _) if name == "__va_list_tag"
+                ) => {}
+            _ => {
+                return Err(CE::invalid_va_list(
+                    va_list.expr_type.base_type.to_string(),
+                    va_list_span,
+                ))
+            }
+        }
+        Ok(Self::decay(va_list))
+    }
+
+    fn analyze_conditional(
+        &self,
+        cond: &Expression,
+        true_expr: &Expression,
+        false_expr: &Expression,
+        span: Span,
+    ) -> Result<TypedExpression, CE> {
+        let cond = self.analyze_rvalue(cond)?;
+        if !cond.expr_type.base_type.is_scalar() {
+            return Err(CE::invalid_condition(
+                cond.expr_type.base_type.to_string(),
+                cond.span,
+            ));
+        }
+        let true_expr = self.analyze_rvalue(true_expr)?;
+        let false_expr = self.analyze_rvalue(false_expr)?;
+        let (t_t, f_t) = (
+            &true_expr.expr_type.base_type,
+            &false_expr.expr_type.base_type,
+        );
+        let result_t = match (t_t, f_t) {
+            (t_t, f_t) if t_t.is_arithmetic() && f_t.is_arithmetic() => {
+                BaseType::usual_arithmetic_conversion(t_t, f_t)
+            }
+            // the result points to a type qualified like both operands' pointed-to types
+            (BaseType::Pointer(t_pointee), BaseType::Pointer(f_pointee)) => {
+                let mut pointee = t_pointee.as_ref().to_owned();
+                for q in &f_pointee.qualifier {
+                    if !pointee.qualifier.contains(q) {
+                        pointee.qualifier.push(q.to_owned());
+                    }
+                }
+                BaseType::Pointer(Box::new(pointee))
+            }
+            (t_t, f_t) if t_t.equal_discarding_qualifiers(f_t, &self.typedef_map) => t_t.to_owned(),
+            (BaseType::Pointer(_), f_t) if f_t.is_integer() => t_t.to_owned(),
+            (t_t, BaseType::Pointer(_)) if t_t.is_integer() => f_t.to_owned(),
+            _ => {
+                return Err(CE::conditional_type_mismatch(
+                    t_t.to_string(),
+                    f_t.to_string(),
+                    span,
+                ))
+            }
+        };
+        let true_expr = self.convert(true_expr, &result_t);
+        let false_expr = self.convert(false_expr, &result_t);
+        Ok(Self::rvalue(
+            TypedExpressionEnum::Conditional(
+                Box::new(cond),
+                Box::new(true_expr),
+                Box::new(false_expr),
+            ),
+            result_t,
+            span,
+        ))
+    }
+
+    fn analyze_array_subscript(
+        &self,
+        array: &Expression,
+        indexes: &[Expression],
+        span: Span,
+    ) -> Result<TypedExpression, CE> {
+        let array = self.analyze_expression(array)?;
+        let mut element_type = array.expr_type.to_owned();
+        let indexes = indexes
+            .iter()
+            .map(|index| {
+                let index = self.analyze_rvalue(index)?;
+                if !index.expr_type.base_type.is_integer() {
+                    return Err(CE::non_integer_subscript(
+                        index.expr_type.base_type.to_string(),
+                        index.span,
+                    ));
+                }
+                element_type = match element_type.base_type {
+                    BaseType::Array(ref element_type, ref dimensions) if dimensions.len() > 1 => {
+                        BT {
+                            qualifier: vec![],
+                            base_type: BaseType::Array(
+                                element_type.to_owned(),
+                                dimensions[1..].to_vec(),
+                            ),
+                        }
+                    }
+                    BaseType::Array(ref element_type, _) | BaseType::Pointer(ref element_type) => {
+                        element_type.as_ref().to_owned()
+                    }
+                    ref t => return Err(CE::invalid_subscript(t.to_string(), span)),
+                };
+                Ok(index)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(TypedExpression {
+            node: TypedExpressionEnum::ArraySubscript(Box::new(array), indexes),
+            expr_type: element_type,
+            category: ValueCategory::LValue,
+            span,
+        })
+    }
+
+    /// The type of a member of a struct or union, qualified like the struct or union.
+    fn find_member(&self, struct_type: &BT, member: &str, span: Span) -> Result<BT, CE> {
+        match struct_type.base_type {
+            BaseType::Struct(ref name, _) | BaseType::Union(ref name, _) => {
+                let members = self.struct_members(&struct_type.base_type, span)?;
+                match members.iter().find(|x| x.member_name == member) {
+                    Some(found) => {
+                        let mut member_type = found.member_type.to_owned();
+                        member_type
+                            .qualifier
+                            .extend(struct_type.qualifier.iter().cloned());
+                        Ok(member_type)
+                    }
+                    None => Err(CE::struct_member_not_found(
+                        name.to_owned().unwrap_or_default(),
+                        member.to_string(),
+                        span,
+                    )),
+                }
+            }
+            _ => Err(CE::get_member_from_not_struct(member.to_string(), span)),
+        }
+    }
+
+    fn check_sizeof_type(&self, t: &BaseType, span: Span) -> Result<(), CE> {
+        match t {
+            BaseType::Void => Err(CE::invalid_size_of_type("void".to_string(), span)),
+            BaseType::Function(_, _, _) => {
+                Err(CE::invalid_size_of_type("function".to_string(), span))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn select_generic_association<'e>(
+        &self,
+        controlling: &Expression,
+        associations: &'e [(Option<BT>, Expression)],
+    ) -> Result<&'e Expression, CE> {
+        // the controlling expression is never evaluated, and it goes through lvalue conversion
+        let controlling_type = self.analyze_rvalue(controlling)?.expr_type.base_type;
+
+        let mut selected: Option<&Expression> = None;
+        for (association_type, expression) in associations {
+            if let Some(association_type) = association_type {
+                let association_type = self.resolve_type(association_type, expression.span)?;
+                if controlling_type
+                    .equal_discarding_qualifiers(&association_type.base_type, &self.typedef_map)
+                {
+                    if selected.is_some() {
+                        return Err(CE::generic_selection_multiple_match(
+                            controlling_type.to_string(),
+                            controlling.span,
+                        ));
+                    }
+                    selected = Some(expression);
+                }
+            }
+        }
+        match selected {
+            Some(expression) => Ok(expression),
+            None => associations
+                .iter()
+                .find(|(association_type, _)| association_type.is_none())
+                .map(|(_, expression)| expression)
+                .ok_or_else(|| {
+                    CE::generic_selection_no_match(controlling_type.to_string(), controlling.span)
+                }),
+        }
+    }
+}
@@ -0,0 +1,845 @@
+use crate::ast::{
+    BaseType, BasicType as BT, BinaryOperationEnum, DeclarationEnum, Expression, ExpressionEnum,
+    Span, StructMember, UnaryOperationEnum, AST,
+};
+use crate::utils::CompileErr as CE;
+use std::collections::{HashMap, HashSet};
+
+mod asm;
+mod attr;
+mod decl;
+mod expr;
+mod stmt;
+mod typed;
+
+pub use typed::*;
+
+/// Semantic analysis, run between parsing and code generation: identifiers are resolved to
+/// their declarations, every expression is annotated with its type and value category, and
+/// implicit conversions are made explicit.
+pub struct Sema<'a> {
+    code: &'a str,
+
+    // block scopes of the current function, the innermost one last
+    scopes: Vec<HashMap<String, (BT, Symbol)>>,
+    // file scope variables
+    global_variable_map: HashMap<String, (BT, Symbol)>,
+    // function name -> (return type, parameter types, is variadic, first declaration)
+    function_map: HashMap<String, (BT, Vec<BT>, bool, Span)>,
+    // functions whose body has been analyzed
+    defined_functions: HashSet<String>,
+    // functions declared `_Noreturn`
+    noreturn_functions: HashSet<String>,
+    // diagnostics which do not stop the compilation
+    warnings: Vec<CE>,
+    // typedef name -> the type it stands for, without typedef names inside
+    typedef_map: HashMap<String, BT>,
+    // struct or union tag -> members
+    struct_map: HashMap<String, Vec<StructMember>>,
+    // name, return type and variadic-ness of the function being analyzed
+    current_function: Option<(String, BT, bool)>,
+    // enclosing loop statements, `continue` is only valid inside one
+    loop_depth: usize,
+    // enclosing switch statements, `break` is also valid inside one
+    switch_depth: usize,
+}
+
+impl<'a> Sema<'a> {
+    pub fn new(code: &'a str) -> Sema<'a> {
+        Sema {
+            code,
+            scopes: Vec::new(),
+            global_variable_map: HashMap::new(),
+            function_map: HashMap::new(),
+            defined_functions: HashSet::new(),
+            noreturn_functions: HashSet::new(),
+            warnings: Vec::new(),
+            typedef_map: HashMap::new(),
+            struct_map: HashMap::new(),
+            current_function: None,
+            loop_depth: 0,
+            switch_depth: 0,
+        }
+    }
+
+    pub fn analyze(&mut self, ast: &AST) -> Result<TypedAST, Vec<CE>> {
+        let AST::GlobalDeclaration(ref declarations) = ast;
+
+        let mut errors: Vec<CE> = vec![];
+
+        // first-time scanning, global declarations and function prototypes
+        let mut typed_declarations = declarations
+            .iter()
+            .map(|declaration| {
+                self.analyze_global_declaration(declaration)
+                    .unwrap_or_else(|e| {
+                        errors.push(e);
+                        None
+                    })
+            })
+            .collect::<Vec<_>>();
+
+        // second-time scanning, function bodies
+        for (declaration, typed_declaration) in
+            declarations.iter().zip(typed_declarations.iter_mut())
+        {
+            if let DeclarationEnum::FunctionDefinition(..) = declaration.node {
+                match self.analyze_function_definition(declaration) {
+                    Ok(function) => *typed_declaration = function,
+                    Err(e) => errors.extend(e),
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(TypedAST::GlobalDeclaration(
+                typed_declarations.into_iter().flatten().collect(),
+            ))
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Warnings reported by `analyze`, whether it succeeded or not.
+    pub fn warnings(&self) -> &[CE] {
+        &self.warnings
+    }
+
+    /// The source code covered by `span`, used to name an expression in diagnostics.
+    fn source_text(&self, span: Span) -> String {
+        self.code[span.start..span.end].trim_end().to_string()
+    }
+
+    /// Replace typedef names with the types they stand for, everywhere in `t` but inside the
+    /// members of a tagged struct or union, which are only referred to by tag.
+    fn resolve_type(&self, t: &BT, span: Span) -> Result<BT, CE> {
+        let mut qualifier = t.qualifier.to_owned();
+        let mut base_type = match t.base_type {
+            BaseType::Identifier(ref name) => match self.typedef_map.get(name) {
+                Some(true_type) => {
+                    qualifier.extend(true_type.qualifier.iter().cloned());
+                    true_type.base_type.to_owned()
+                }
+                None => return Err(CE::missing_typedef(name.to_string(), span)),
+            },
+            BaseType::Pointer(ref pointee) => {
+                BaseType::Pointer(Box::new(self.resolve_type(pointee, span)?))
+            }
+            BaseType::Array(ref element_type, ref dimensions) => BaseType::Array(
+                Box::new(self.resolve_type(element_type, span)?),
+                dimensions
+                    .iter()
+                    .map(|dimension| self.array_size(dimension))
+                    .collect::<Result<_, _>>()?,
+            ),
+            BaseType::Function(ref return_type, ref params, is_variadic) => BaseType::Function(
+                Box::new(self.resolve_type(return_type, span)?),
+                params
+                    .iter()
+                    .map(|param| self.resolve_type(param, span))
+                    .collect::<Result<_, _>>()?,
+                is_variadic,
+            ),
+            BaseType::Struct(Some(ref name), _) => BaseType::Struct(Some(name.to_owned()), None),
+            BaseType::Union(Some(ref name), _) => BaseType::Union(Some(name.to_owned()), None),
+            BaseType::Struct(None, Some(ref members)) => {
+                BaseType::Struct(None, Some(self.resolve_members(members, span)?))
+            }
+            BaseType::Union(None, Some(ref members)) => {
+                BaseType::Union(None, Some(self.resolve_members(members, span)?))
+            }
+            ref t => t.to_owned(),
+        };
+        // qualifying an array type, through a typedef name, qualifies its elements instead
+        if let BaseType::Array(ref mut element_type, _) = base_type {
+            element_type.qualifier.append(&mut qualifier);
+        }
+        Ok(BT {
+            qualifier,
+            base_type,
+        })
+    }
+
+    /// An array size folded to an integer literal, which is left empty for an array of unknown
+    /// size.
+    fn array_size(&self, dimension: &Expression) -> Result<Expression, CE> {
+        if dimension.node == ExpressionEnum::Empty {
+            return Ok(dimension.to_owned());
+        }
+        let size = self.analyze_rvalue(dimension)?;
+        let node = match Self::constant_value(&size) {
+            Some(value) if value >= 0 && size.expr_type.base_type.is_integer() => {
+                match i32::try_from(value) {
+                    Ok(value) => ExpressionEnum::IntegerConstant(value),
+                    Err(_) => ExpressionEnum::LongConstant(value),
+                }
+            }
+            _ => return Err(CE::non_constant_array_size(dimension.span)),
+        };
+        Ok(Expression {
+            node,
+            span: dimension.span,
+        })
+    }
+
+    /// The value of an integer constant expression, made of integer and character constants.
+    fn constant_value(expr: &TypedExpression) -> Option<i64> {
+        use BinaryOperationEnum::*;
+        use UnaryOperationEnum::*;
+
+        let value = match expr.node {
+            TypedExpressionEnum::Literal(ExpressionEnum::CharacterConstant(_, value)) => {
+                Some(value)
+            }
+            TypedExpressionEnum::Literal(ref literal) => literal
+                .integer_literal()
+                .and_then(|value| i64::try_from(value).ok()),
+            TypedExpressionEnum::ImplicitCast(ref operand)
+            | TypedExpressionEnum::TypeCast(_, ref operand)
+                if expr.expr_type.base_type.is_integer() =>
+            {
+                Self::constant_value(operand)
+            }
+            TypedExpressionEnum::Unary(ref op, ref operand) => {
+                let value = Self::constant_value(operand)?;
+                match op.node {
+                    UnaryPlus => Some(value),
+                    UnaryMinus => value.checked_neg(),
+                    BitwiseNot => Some(!value),
+                    LogicalNot => Some((value == 0) as i64),
+                    _ => None,
+                }
+            }
+            TypedExpressionEnum::Binary(ref op, ref lhs, ref rhs) => {
+                let (l, r) = (Self::constant_value(lhs)?, Self::constant_value(rhs)?);
+                match op.node {
+                    Addition => l.checked_add(r),
+                    Subtraction => l.checked_sub(r),
+                    Multiplication => l.checked_mul(r),
+                    Division => l.checked_div(r),
+                    Modulo => l.checked_rem(r),
+                    LeftShift => l.checked_shl(u32::try_from(r).ok()?),
+                    RightShift => l.checked_shr(u32::try_from(r).ok()?),
+                    BitwiseAnd => Some(l & r),
+                    BitwiseOr => Some(l | r),
+                    BitwiseXor => Some(l ^ r),
+                    LessThan => Some((l < r) as i64),
+                    LessThanOrEqual => Some((l <= r) as i64),
+                    GreaterThan => Some((l > r) as i64),
+                    GreaterThanOrEqual => Some((l >= r) as i64),
+                    Equal => Some((l == r) as i64),
+                    NotEqual => Some((l != r) as i64),
+                    LogicalAnd => Some((l != 0 && r != 0) as i64),
+                    LogicalOr => Some((l != 0 || r != 0) as i64),
+                    Comma => None,
+                }
+            }
+            TypedExpressionEnum::Conditional(ref cond, ref true_expr, ref false_expr) => {
+                match Self::constant_value(cond)? != 0 {
+                    true => Self::constant_value(true_expr),
+                    false => Self::constant_value(false_expr),
+                }
+            }
+            _ => None,
+        }?;
+        Self::wrap_constant(value, &expr.expr_type.base_type)
+    }
+
+    /// Whether `expr` is a null pointer constant: an integer constant expression with the value
+    /// 0, or one cast to `void *` (C99 6.3.2.3p3).
+    fn is_null_pointer_constant(expr: &TypedExpression) -> bool {
+        match (&expr.node, &expr.expr_type.base_type) {
+            (TypedExpressionEnum::TypeCast(_, operand), BaseType::Pointer(pointee))
+                if pointee.base_type == BaseType::Void && pointee.qualifier.is_empty() =>
+            {
+                Self::is_null_pointer_constant(operand)
+            }
+            (_, t) => t.is_integer() && Self::constant_value(expr) == Some(0),
+        }
+    }
+
+    /// An integer constant converted to the integer type `t`, wrapped around like the
+    /// generated code, or None if it does not fit in an `i64`.
+    fn wrap_constant(value: i64, t: &BaseType) -> Option<i64> {
+        if !t.is_integer() {
+            return Some(value);
+        }
+        let shift = 64 - t.integer_size() as u32 * 8;
+        match t {
+            BaseType::Bool => Some((value != 0) as i64),
+            BaseType::SignedInteger(_) => Some(value << shift >> shift),
+            _ if shift > 0 => Some(((value as u64) << shift >> shift) as i64),
+            _ => (value >= 0).then_some(value),
+        }
+    }
+
+    fn resolve_members(
+        &self,
+        members: &[StructMember],
+        span: Span,
+    ) -> Result<Vec<StructMember>, CE> {
+        members
+            .iter()
+            .map(|member| {
+                Ok(StructMember {
+                    member_name: member.member_name.to_owned(),
+                    member_type: self.resolve_type(&member.member_type, span)?,
+                    attributes: member.attributes.to_owned(),
+                })
+            })
+            .collect()
+    }
+
+    /// Members of a struct or union type, looked up by tag if the type does not list them.
+    fn struct_members(&self, t: &BaseType, span: Span) -> Result<Vec<StructMember>, CE> {
+        match t {
+            BaseType::Struct(_, Some(members)) | BaseType::Union(_, Some(members)) => {
+                Ok(members.to_owned())
+            }
+            BaseType::Struct(Some(name), None) | BaseType::Union(Some(name), None) => {
+                match self.struct_map.get(name) {
+                    Some(members) => Ok(members.to_owned()),
+                    None => Err(CE::struct_not_found(name.to_string(), span)),
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Look up a variable, from the innermost block scope to the file scope.
+    fn get_variable(&self, identifier: &str) -> Option<&(BT, Symbol)> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(identifier))
+            .or_else(|| self.global_variable_map.get(identifier))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::IntegerType;
+    use crate::parse::Parse;
+
+    fn analyze(code: &str) -> Result<TypedAST, Vec<CE>> {
+        let ast = Parse::new().parse(code).unwrap();
+        Sema::new(code).analyze(&ast)
+    }
+
+    fn returned_expression(ast: &TypedAST) -> &TypedExpression {
+        let TypedAST::GlobalDeclaration(ref declarations) = ast;
+        match declarations.last().unwrap().node {
+            TypedDeclarationEnum::FunctionDefinition(_, _, _, _, _, _, _, ref body) => {
+                match body.node {
+                    TypedStatementEnum::Compound(ref items) => match items.last() {
+                        Some(TypedStatementOrDeclaration::Statement(TypedStatement {
+                            node: TypedStatementEnum::Return(Some(ref expr)),
+                            ..
+                        })) => expr,
+                        _ => panic!("expected a return statement"),
+                    },
+                    _ => unreachable!(),
+                }
+            }
+            _ => panic!("expected a function definition"),
+        }
+    }
+
+    #[test]
+    fn implicit_conversions() {
+        let ast = analyze(
+            r#"
+            double f(double x) { return x; }
+            double g() { char c = 'a'; return f(c + 1); }
+            "#,
+        )
+        .unwrap();
+        let call = returned_expression(&ast);
+        assert_eq!(call.expr_type.base_type, BaseType::Double);
+        assert_eq!(call.category, ValueCategory::RValue);
+        match call.node {
+            TypedExpressionEnum::FunctionCall(ref callee, ref args) => {
+                // function-to-pointer conversion of the callee
+                assert!(matches!(callee.node, TypedExpressionEnum::ImplicitCast(_)));
+                // `c + 1` is an `int`, converted to the parameter type
+                match args[0].node {
+                    TypedExpressionEnum::ImplicitCast(ref arg) => {
+                        assert_eq!(
+                            arg.expr_type.base_type,
+                            BaseType::SignedInteger(IntegerType::Int)
+                        );
+                    }
+                    _ => panic!("expected an implicit conversion"),
+                }
+                assert_eq!(args[0].expr_type.base_type, BaseType::Double);
+            }
+            _ => panic!("expected a function call"),
+        }
+    }
+
+    #[test]
+    fn narrowing_conversions() {
+        let ast = analyze(
+            r#"
+            short s = 3;
+            float f = 0.5;
+            char g(long l) { char c = 65; c = l; s = f; return 2.5; }
+            "#,
+        )
+        .unwrap();
+        let value = returned_expression(&ast);
+        assert_eq!(
+            value.expr_type.base_type,
+            BaseType::SignedInteger(IntegerType::Char)
+        );
+        match value.node {
+            TypedExpressionEnum::ImplicitCast(ref operand) => {
+                assert_eq!(operand.expr_type.base_type, BaseType::Double)
+            }
+            _ => panic!("expected an implicit conversion"),
+        }
+
+        let errors = analyze("void h(int *p, double d) { p = d; d = p; }").unwrap_err();
+        assert_eq!(errors.len(), 2);
+    }
+
+    #[test]
+    fn usual_arithmetic_conversions() {
+        use IntegerType::*;
+        let common = |lhs, rhs| BaseType::usual_arithmetic_conversion(&lhs, &rhs);
+        let (signed, unsigned) = (BaseType::SignedInteger, BaseType::UnsignedInteger);
+        assert_eq!(common(unsigned(Char), unsigned(Char)), signed(Int));
+        assert_eq!(common(BaseType::Bool, signed(Short)), signed(Int));
+        assert_eq!(common(signed(Int), unsigned(Int)), unsigned(Int));
+        assert_eq!(common(unsigned(Short), unsigned(Int)), unsigned(Int));
+        assert_eq!(common(signed(Long), unsigned(Int)), signed(Long));
+        assert_eq!(common(unsigned(Long), signed(Int)), unsigned(Long));
+        assert_eq!(common(signed(LongLong), unsigned(Long)), unsigned(LongLong));
+        assert_eq!(common(unsigned(LongLong), BaseType::Float), BaseType::Float);
+
+        let ast = analyze("int f(unsigned char c) { return -c; }").unwrap();
+        let value = returned_expression(&ast);
+        assert_eq!(value.expr_type.base_type, signed(Int));
+        match value.node {
+            TypedExpressionEnum::Unary(_, ref operand) => {
+                assert!(matches!(operand.node, TypedExpressionEnum::ImplicitCast(_)))
+            }
+            _ => panic!("expected a unary operation"),
+        }
+    }
+
+    #[test]
+    fn operator_result_types() {
+        let ast = analyze("unsigned long f(int x) { return sizeof(x < 1 && !x); }").unwrap();
+        let value = returned_expression(&ast);
+        assert_eq!(
+            value.expr_type.base_type,
+            BaseType::UnsignedInteger(IntegerType::Long)
+        );
+        match value.node {
+            TypedExpressionEnum::Unary(_, ref operand) => assert_eq!(
+                operand.expr_type.base_type,
+                BaseType::SignedInteger(IntegerType::Int)
+            ),
+            _ => panic!("expected sizeof"),
+        }
+
+        // constants are folded in the types of the operations
+        assert!(analyze("int a[-1 < 0u ? -1 : 1]; int b[0u - 1 > 0 ? 1 : -1];").is_ok());
+    }
+
+    #[test]
+    fn character_constants_typed_int() {
+        let ast = analyze("char f() { char c = 'a'; return L'b' + 'c'; }").unwrap();
+        let value = returned_expression(&ast);
+        match value.node {
+            TypedExpressionEnum::ImplicitCast(ref operand) => assert_eq!(
+                operand.expr_type.base_type,
+                BaseType::SignedInteger(IntegerType::Int)
+            ),
+            _ => panic!("expected an implicit conversion"),
+        }
+
+        let ast = analyze("int f() { return _Generic('a', char: 1, int: 2); }").unwrap();
+        let value = returned_expression(&ast);
+        assert!(matches!(
+            value.node,
+            TypedExpressionEnum::Literal(ExpressionEnum::IntegerConstant(2))
+        ));
+        // only the qualifiers of the controlling type itself are dropped
+        let ast =
+            analyze("int f(const char *c) { return _Generic(c, char *: 1, const char *: 2); }")
+                .unwrap();
+        let value = returned_expression(&ast);
+        assert!(matches!(
+            value.node,
+            TypedExpressionEnum::Literal(ExpressionEnum::IntegerConstant(2))
+        ));
+    }
+
+    #[test]
+    fn identifiers_resolved() {
+        let code = r#"
+            int x;
+            int f(int x) { int *p = &x; { int x; } return *p; }
+            "#;
+        let ast = analyze(code).unwrap();
+        let dereference = returned_expression(&ast);
+        assert_eq!(dereference.category, ValueCategory::LValue);
+        match dereference.node {
+            TypedExpressionEnum::Unary(_, ref operand) => match operand.node {
+                TypedExpressionEnum::Identifier(ref name, Symbol::Local(span)) => {
+                    assert_eq!(name, "p");
+                    assert_eq!(&code[span.start..span.end], "int *p = &x;");
+                }
+                _ => panic!("expected a local variable"),
+            },
+            _ => panic!("expected a dereference"),
+        }
+    }
+
+    #[test]
+    fn all_errors_reported() {
+        let errors = analyze(
+            r#"
+            int f(int a) {
+                a = y;
+                break;
+                return *a;
+            }
+            int g() { return f(1, 2); }
+            "#,
+        )
+        .unwrap_err();
+        assert_eq!(errors.len(), 4);
+    }
+
+    #[test]
+    fn const_enforced() {
+        let errors = analyze(
+            r#"
+            struct s { const int a; };
+            char *f(const char *s) { return s; }
+            void g(const int *p, struct s v) {
+                const char *ok = "abc";
+                int *q = p;
+                *p = 1;
+                v = v;
+                p++;
+                const int arr[2];
+                arr[ 0 ] = 1;
+            }
+            void h(char **pp, volatile int *v) {
+                const char **cpp = pp;
+                const char *const *ccp = pp;
+                char *const *pcp = pp;
+                int *i = v;
+                const volatile int *cv = v;
+                char *m = 0;
+                int *const pc = 1 - 1;
+                char *n = 1;
+            }
+            "#,
+        )
+        .unwrap_err();
+        let messages = errors.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![
+                "conversion from `const char*` to `char*` discards const qualifier",
+                "conversion from `const int*` to `int*` discards const qualifier",
+                "assignment to read-only location `*p`",
+                "assignment to read-only location `v`",
+                "assignment to read-only location `arr[ 0 ]`",
+                "conversion from `char**` to `const char**` between incompatible pointer types",
+                "conversion from `char**` to `const char* const*` between incompatible pointer \
+                 types",
+                "conversion from `volatile int*` to `int*` discards volatile qualifier",
+                "invalid default cast",
+            ]
+        );
+    }
+
+    #[test]
+    fn atomic_builtins() {
+        let ast =
+            analyze("long f(_Atomic long *p) { return __atomic_fetch_add(p, 'a', 5); }").unwrap();
+        let call = returned_expression(&ast);
+        assert_eq!(
+            call.expr_type.base_type,
+            BaseType::SignedInteger(IntegerType::Long)
+        );
+        match call.node {
+            // the operand is converted to the type of the object
+            TypedExpressionEnum::FunctionCall(_, ref args) => {
+                assert_eq!(
+                    args[1].expr_type.base_type,
+                    BaseType::SignedInteger(IntegerType::Long)
+                );
+            }
+            _ => panic!("expected a builtin call"),
+        }
+
+        let errors = analyze(
+            r#"
+            void g(double *d, const int *c, int *i) {
+                __atomic_fetch_or(d, 1, 5);
+                __atomic_store_n(c, 1, 5);
+                __atomic_compare_exchange_n(i, d, 1, 0, 5, 5);
+                __atomic_load_n(i);
+            }
+            "#,
+        )
+        .unwrap_err();
+        assert_eq!(errors.len(), 4);
+    }
+    #[test]
+    fn noreturn_checked() {
+        let code = r#"
+            _Noreturn void quit();
+            _Noreturn void spin() { for (;;) { } }
+            _Noreturn void stop(int x) { if (x) { quit(); } else { spin(); } }
+            _Noreturn void wait(int x) { while (1) { if (x) break; } }
+            _Noreturn void fail(int x) { if (x) quit(); }
+            _Noreturn void back() { return; }
+            "#;
+        let ast = Parse::new().parse(code).unwrap();
+        let mut sema = Sema::new(code);
+        assert!(sema.analyze(&ast).is_ok());
+        let warnings = sema
+            .warnings()
+            .iter()
+            .map(|warning| warning.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            warnings,
+            vec![
+                "function `wait` declared `_Noreturn` can return",
+                "function `fail` declared `_Noreturn` can return",
+                "function `back` declared `_Noreturn` can return",
+            ]
+        );
+    }
+
+    #[test]
+    fn attributes_checked() {
+        let code = r#"
+            int printf(const char *, ...) __attribute__((format(printf, 1, 2)));
+            int x __attribute__((aligned(16), section(".data.x"), weak, shiny));
+            struct S { char c; int y; } __attribute__((packed, cold));
+            static void init() __attribute__((constructor(101), noinline));
+            int main() {
+                int z __attribute__((unused, visibility("hidden")));
+                return 0;
+            }
+            "#;
+        let ast = Parse::new().parse(code).unwrap();
+        let mut sema = Sema::new(code);
+        assert!(sema.analyze(&ast).is_ok());
+        let warnings = sema
+            .warnings()
+            .iter()
+            .map(|warning| (warning.to_string(), warning.is_warning()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            warnings,
+            vec![
+                ("attribute `shiny` ignored".to_string(), true),
+                ("attribute `cold` ignored".to_string(), true),
+                ("attribute `visibility` ignored".to_string(), true),
+            ]
+        );
+
+        for code in [
+            "int x __attribute__((aligned(3)));",
+            "int x __attribute__((section(1)));",
+            "int x __attribute__((visibility(\"secret\")));",
+            "void f() __attribute__((noinline(1)));",
+            "int f(int, ...) __attribute__((format(printf, 1, 2)));",
+            "int f(const char *, ...) __attribute__((format(printf, 1, 3)));",
+            "int f(const char *, ...) __attribute__((format(echo, 1, 2)));",
+        ] {
+            let errors = analyze(code).unwrap_err();
+            assert_eq!(errors.len(), 1, "{}", code);
+            assert!(errors[0]
+                .to_string()
+                .starts_with("invalid argument to attribute"));
+        }
+    }
+
+    #[test]
+    fn missing_return_warned() {
+        let code = r#"
+            void log(int x) { if (x == 0) { return; } }
+            int sign(int x) { if (x < 0) { return -1; } else { return 1; } }
+            double half(int x) { if (x > 0) { return x / 2.0; } }
+            int spin() { for (;;) {} }
+            int *find(int *p) { p; }
+            int main() { log(0); }
+            "#;
+        let ast = Parse::new().parse(code).unwrap();
+        let mut sema = Sema::new(code);
+        assert!(sema.analyze(&ast).is_ok());
+        let warnings = sema
+            .warnings()
+            .iter()
+            .map(|warning| warning.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            warnings,
+            vec![
+                "control reaches end of non-void function `half`",
+                "control reaches end of non-void function `find`",
+            ]
+        );
+    }
+
+    #[test]
+    fn function_redeclarations_checked() {
+        assert!(analyze(
+            r#"
+            inline int c(void) { return 0; }
+            extern int c(void);
+            int d(const int x);
+            int d(int x) { return x; }
+            int d(int);
+            int main() { return c() + d(1); }
+            "#
+        )
+        .is_ok());
+        let errors = analyze(
+            r#"
+            int c(void) { return 0; }
+            long c(void);
+            int d(int x) { return x; }
+            int d(int x) { return -x; }
+            "#,
+        )
+        .unwrap_err();
+        let messages = errors.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec!["duplicated function `c`", "duplicated function `d`"]
+        );
+    }
+
+    #[test]
+    fn thread_local_checked() {
+        let code = r#"
+            _Thread_local int a;
+            static _Thread_local int b;
+            _Thread_local int f();
+            int main() {
+                static _Thread_local int c;
+                extern _Thread_local int a;
+                _Thread_local int d;
+                return 0;
+            }
+            "#;
+        let errors = analyze(code).unwrap_err();
+        let messages = errors.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![
+                "`f` cannot be `_Thread_local`",
+                "`d` cannot be `_Thread_local`",
+            ]
+        );
+        assert!(Parse::new().parse("typedef _Thread_local int t;").is_err());
+    }
+
+    #[test]
+    fn builtins_checked() {
+        let ast = analyze(
+            r#"
+            struct point { char tag; struct { int x; int y; } at; };
+            _Noreturn void stop() { __builtin_trap(); }
+            int f() { return __builtin_offsetof(struct point, at.y); }
+            "#,
+        )
+        .unwrap();
+        let offset = returned_expression(&ast);
+        assert_eq!(
+            offset.expr_type.base_type,
+            BaseType::SignedInteger(IntegerType::Int)
+        );
+        match offset.node {
+            TypedExpressionEnum::ImplicitCast(ref offset) => {
+                assert_eq!(
+                    offset.expr_type.base_type,
+                    BaseType::UnsignedInteger(IntegerType::Long)
+                );
+                assert!(matches!(offset.node, TypedExpressionEnum::Offsetof(..)));
+            }
+            _ => panic!("expected an implicit conversion"),
+        }
+
+        let code = r#"
+            struct point { int x; };
+            void g(const int *c, double *d, int *i) {
+                __builtin_add_overflow(1, 2, c);
+                __builtin_mul_overflow(1, 2, d);
+                __builtin_popcount(1u, 2u);
+                __builtin_memcpy(c, i, 4ul);
+                __builtin_offsetof(struct point, y);
+            }
+            "#;
+        let errors = analyze(code).unwrap_err();
+        let messages = errors.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![
+                "invalid argument of type `const int*` to `__builtin_add_overflow`",
+                "invalid argument of type `double*` to `__builtin_mul_overflow`",
+                "parameter count mismatch",
+                "conversion from `const int*` to `void*` discards const qualifier",
+                "struct member `y` not found",
+            ]
+        );
+    }
+
+    #[test]
+    fn asm_checked() {
+        let code = r#"
+            struct pair { int a; int b; };
+            void f(int x, const int c, struct pair p) {
+                asm("movl %eax, %eax");
+                asm("addl %1, %0" : "=r"(x) : "ri"(1));
+                asm("movl %[in], %[out]" : [out] "=r"(x) : [in] "r"(x) : "memory", "cc", "%rax");
+                asm goto("jmp %l[done]" : : : : done);
+                asm("" : "r"(x));
+                asm("" : "=r"(c));
+                asm("" : "=m"(x) : "0"(1));
+                asm("" : : "w"(x));
+                asm("" : : "r"(p));
+                asm("" : : : "rxx");
+                asm("movl %1, %0" : "=r"(x));
+                asm("movl %[y], %0" : "=r"(x));
+            done:
+                return;
+            }
+            "#;
+        let errors = analyze(code).unwrap_err();
+        let messages = errors.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![
+                "invalid constraint `r` in asm",
+                "assignment to read-only location `c`",
+                "invalid constraint `0` in asm",
+                "invalid constraint `w` in asm",
+                "invalid constraint `r` in asm",
+                "unknown register name `rxx` in asm clobbers",
+                "invalid operand reference `%1` in asm template",
+                "invalid operand reference `%[y]` in asm template",
+            ]
+        );
+
+        let errors = analyze("void g() { asm goto(\"jmp %l0\" : : : : away); }").unwrap_err();
+        assert_eq!(errors[0].to_string(), "label `away` used but not defined");
+        assert!(Parse::new()
+            .parse("void h() { asm(\"\" : : : : away); }")
+            .is_err());
+    }
+}
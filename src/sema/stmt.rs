@@ -0,0 +1,363 @@
+use crate::ast::{
+    BaseType, Expression, ExpressionEnum, ForInitClause, ForInitClauseEnum, Statement,
+    StatementEnum, StatementOrDeclaration, StatementOrDeclarationEnum,
+};
+use crate::sema::*;
+use crate::utils::CompileErr as CE;
+
+impl<'a> Sema<'a> {
+    pub(crate) fn analyze_statement(
+        &mut self,
+        statement: &Statement,
+    ) -> Result<TypedStatement, CE> {
+        let node = match statement.node {
+            StatementEnum::Labeled(ref label, ref statement) => TypedStatementEnum::Labeled(
+                label.to_owned(),
+                Box::new(self.analyze_statement(statement)?),
+            ),
+            StatementEnum::Case(ref expr, ref statement) => TypedStatementEnum::Case(
+                match expr {
+                    Some(expr) => Some(Box::new(self.analyze_rvalue(expr)?)),
+                    None => None,
+                },
+                Box::new(self.analyze_statement(statement)?),
+            ),
+            StatementEnum::Compound(ref items) => {
+                self.scopes.push(HashMap::new());
+                let items = items
+                    .iter()
+                    .map(|item| self.analyze_block_item(item))
+                    .collect::<Result<Vec<_>, _>>();
+                self.scopes.pop();
+                TypedStatementEnum::Compound(items?)
+            }
+            StatementEnum::Expression(ref expr) => {
+                TypedStatementEnum::Expression(Box::new(self.analyze_expression(expr)?))
+            }
+            StatementEnum::If(ref cond, ref then_stmt, ref else_stmt) => TypedStatementEnum::If(
+                Box::new(self.analyze_condition(cond)?),
+                Box::new(self.analyze_statement(then_stmt)?),
+                match else_stmt {
+                    Some(else_stmt) => Some(Box::new(self.analyze_statement(else_stmt)?)),
+                    None => None,
+                },
+            ),
+            StatementEnum::Switch(ref cond, ref body) => {
+                let cond = self.analyze_rvalue(cond)?;
+                if !cond.expr_type.base_type.is_integer() {
+                    return Err(CE::invalid_condition(
+                        cond.expr_type.base_type.to_string(),
+                        cond.span,
+                    ));
+                }
+                self.switch_depth += 1;
+                let body = self.analyze_statement(body);
+                self.switch_depth -= 1;
+                TypedStatementEnum::Switch(Box::new(cond), Box::new(body?))
+            }
+            StatementEnum::While(ref cond, ref body) => {
+                let cond = self.analyze_condition(cond)?;
+                TypedStatementEnum::While(Box::new(cond), Box::new(self.analyze_loop_body(body)?))
+            }
+            StatementEnum::DoWhile(ref body, ref cond) => {
+                let body = self.analyze_loop_body(body)?;
+                TypedStatementEnum::DoWhile(Box::new(body), Box::new(self.analyze_condition(cond)?))
+            }
+            StatementEnum::For(ref init, ref cond, ref iter, ref body) => {
+                // the declarations in the initialize clause are only visible in the loop
+                self.scopes.push(HashMap::new());
+                let result = self.analyze_for_statement(init, cond, iter, body);
+                self.scopes.pop();
+                result?
+            }
+            StatementEnum::Break => {
+                if self.loop_depth == 0 && self.switch_depth == 0 {
+                    return Err(CE::keyword_not_in_a_loop(
+                        "break".to_string(),
+                        statement.span,
+                    ));
+                }
+                TypedStatementEnum::Break
+            }
+            StatementEnum::Continue => {
+                if self.loop_depth == 0 {
+                    return Err(CE::keyword_not_in_a_loop(
+                        "continue".to_string(),
+                        statement.span,
+                    ));
+                }
+                TypedStatementEnum::Continue
+            }
+            StatementEnum::Return(ref expr) => {
+                let name = &self.current_function.as_ref().unwrap().0;
+                if self.noreturn_functions.contains(name) {
+                    self.warnings.push(CE::noreturn_function_returns(
+                        name.to_string(),
+                        statement.span,
+                    ));
+                }
+                TypedStatementEnum::Return(match expr {
+                    Some(expr) => Some(Box::new(self.analyze_return_value(expr)?)),
+                    None => None,
+                })
+            }
+            StatementEnum::Goto(ref label) => TypedStatementEnum::Goto(label.to_owned()),
+            StatementEnum::Asm(ref asm) => {
+                TypedStatementEnum::Asm(Box::new(self.analyze_asm_statement(asm)?))
+            }
+        };
+        Ok(TypedStatement {
+            node,
+            span: statement.span,
+        })
+    }
+
+    pub(crate) fn analyze_block_item(
+        &mut self,
+        item: &StatementOrDeclaration,
+    ) -> Result<TypedStatementOrDeclaration, CE> {
+        Ok(match item.node {
+            StatementOrDeclarationEnum::Statement(ref statement) => {
+                TypedStatementOrDeclaration::Statement(self.analyze_statement(statement)?)
+            }
+            StatementOrDeclarationEnum::LocalDeclaration(ref declaration) => {
+                TypedStatementOrDeclaration::LocalDeclaration(
+                    self.analyze_local_declaration(declaration)?,
+                )
+            }
+        })
+    }
+
+    fn analyze_for_statement(
+        &mut self,
+        init: &Option<Box<ForInitClause>>,
+        cond: &Option<Box<Expression>>,
+        iter: &Option<Box<Expression>>,
+        body: &Statement,
+    ) -> Result<TypedStatementEnum, CE> {
+        let init = match init {
+            Some(init) => Some(Box::new(match init.node {
+                ForInitClauseEnum::Expression(ref expr) => {
+                    TypedForInitClause::Expression(self.analyze_expression(expr)?)
+                }
+                ForInitClauseEnum::ForDeclaration(ref declarations) => {
+                    TypedForInitClause::ForDeclaration(
+                        declarations
+                            .iter()
+                            .map(|declaration| self.analyze_local_declaration(declaration))
+                            .collect::<Result<_, _>>()?,
+                    )
+                }
+            })),
+            None => None,
+        };
+        let cond = match cond {
+            Some(cond) => Some(Box::new(self.analyze_condition(cond)?)),
+            None => None,
+        };
+        let iter = match iter {
+            Some(iter) => Some(Box::new(self.analyze_expression(iter)?)),
+            None => None,
+        };
+        let body = self.analyze_loop_body(body)?;
+        Ok(TypedStatementEnum::For(init, cond, iter, Box::new(body)))
+    }
+
+    fn analyze_loop_body(&mut self, body: &Statement) -> Result<TypedStatement, CE> {
+        self.loop_depth += 1;
+        let body = self.analyze_statement(body);
+        self.loop_depth -= 1;
+        body
+    }
+
+    /// The controlling expression of `if`, `while`, `do` and `for` is compared with 0.
+    pub(crate) fn analyze_condition(&self, cond: &Expression) -> Result<TypedExpression, CE> {
+        let cond = self.analyze_rvalue(cond)?;
+        if !cond.expr_type.base_type.is_scalar() {
+            return Err(CE::invalid_condition(
+                cond.expr_type.base_type.to_string(),
+                cond.span,
+            ));
+        }
+        Ok(cond)
+    }
+
+    fn analyze_return_value(&self, expr: &Expression) -> Result<TypedExpression, CE> {
+        let (name, return_type, _) = self.current_function.as_ref().unwrap();
+        let value = self.analyze_rvalue(expr)?;
+        match (&return_type.base_type, &value.expr_type.base_type) {
+            (BaseType::Void, BaseType::Void) => Ok(value),
+            (BaseType::Void, _) => Err(CE::return_value_in_void_function(
+                name.to_string(),
+                expr.span,
+            )),
+            (return_type, _) => {
+                self.check_assignable(&value, return_type, expr.span)?;
+                Ok(self.convert(value, return_type))
+            }
+        }
+    }
+
+    /// Whether the end of `statement` can be reached. Jumps are not followed: the statement after
+    /// a `goto` or `return` is unreachable unless it is labeled, loops with a constant nonzero
+    /// condition only complete by `break`, and calls to `_Noreturn` functions never complete.
+    pub(crate) fn can_complete(&self, statement: &TypedStatement) -> bool {
+        match statement.node {
+            TypedStatementEnum::Labeled(_, ref statement)
+            | TypedStatementEnum::Case(_, ref statement) => self.can_complete(statement),
+            TypedStatementEnum::Compound(ref items) => {
+                items.iter().fold(true, |reachable, item| match item {
+                    TypedStatementOrDeclaration::Statement(statement) => {
+                        let labeled = matches!(
+                            statement.node,
+                            TypedStatementEnum::Labeled(..) | TypedStatementEnum::Case(..)
+                        );
+                        (reachable || labeled) && self.can_complete(statement)
+                    }
+                    TypedStatementOrDeclaration::LocalDeclaration(_) => reachable,
+                })
+            }
+            TypedStatementEnum::Expression(ref expr) => !self.is_noreturn_call(expr),
+            TypedStatementEnum::Asm(_) => true,
+            TypedStatementEnum::If(_, ref then_stmt, ref else_stmt) => match else_stmt {
+                Some(else_stmt) => self.can_complete(then_stmt) || self.can_complete(else_stmt),
+                None => true,
+            },
+            TypedStatementEnum::Switch(_, ref body) => {
+                !Self::has_default(body) || self.can_complete(body) || Self::breaks_out(body)
+            }
+            TypedStatementEnum::While(ref cond, ref body)
+            | TypedStatementEnum::DoWhile(ref body, ref cond) => {
+                !Self::is_nonzero_constant(cond) || Self::breaks_out(body)
+            }
+            TypedStatementEnum::For(_, ref cond, _, ref body) => {
+                !cond
+                    .as_ref()
+                    .is_none_or(|cond| Self::is_nonzero_constant(cond))
+                    || Self::breaks_out(body)
+            }
+            TypedStatementEnum::Break
+            | TypedStatementEnum::Continue
+            | TypedStatementEnum::Return(_)
+            | TypedStatementEnum::Goto(_) => false,
+        }
+    }
+
+    /// The labels defined in `statement`, and the labels jumped to with the jumping statements.
+    pub(crate) fn collect_labels(
+        statement: &TypedStatement,
+        defined: &mut HashSet<String>,
+        jumps: &mut Vec<(String, Span)>,
+    ) {
+        match statement.node {
+            TypedStatementEnum::Labeled(ref label, ref statement) => {
+                defined.insert(label.to_owned());
+                Self::collect_labels(statement, defined, jumps);
+            }
+            TypedStatementEnum::Case(_, ref statement)
+            | TypedStatementEnum::Switch(_, ref statement)
+            | TypedStatementEnum::While(_, ref statement)
+            | TypedStatementEnum::DoWhile(ref statement, _)
+            | TypedStatementEnum::For(_, _, _, ref statement) => {
+                Self::collect_labels(statement, defined, jumps)
+            }
+            TypedStatementEnum::Compound(ref items) => {
+                for item in items {
+                    if let TypedStatementOrDeclaration::Statement(statement) = item {
+                        Self::collect_labels(statement, defined, jumps);
+                    }
+                }
+            }
+            TypedStatementEnum::If(_, ref then_stmt, ref else_stmt) => {
+                Self::collect_labels(then_stmt, defined, jumps);
+                if let Some(else_stmt) = else_stmt {
+                    Self::collect_labels(else_stmt, defined, jumps);
+                }
+            }
+            TypedStatementEnum::Goto(ref label) => jumps.push((label.to_owned(), statement.span)),
+            TypedStatementEnum::Asm(ref asm) => jumps.extend(
+                asm.labels
+                    .iter()
+                    .map(|label| (label.to_owned(), statement.span)),
+            ),
+            _ => {}
+        }
+    }
+
+    fn is_noreturn_call(&self, expr: &TypedExpression) -> bool {
+        match expr.node {
+            TypedExpressionEnum::FunctionCall(ref callee, _) => match callee.node {
+                TypedExpressionEnum::ImplicitCast(ref function) => matches!(
+                    function.node,
+                    TypedExpressionEnum::Identifier(ref name, Symbol::Function(_))
+                        if self.noreturn_functions.contains(name)
+                ),
+                TypedExpressionEnum::Identifier(ref name, Symbol::Builtin) => {
+                    matches!(name.as_str(), "__builtin_unreachable" | "__builtin_trap")
+                }
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// Whether a `break` in `statement` leaves it, i.e. is not in a nested loop or switch.
+    fn breaks_out(statement: &TypedStatement) -> bool {
+        match statement.node {
+            TypedStatementEnum::Break => true,
+            TypedStatementEnum::Labeled(_, ref statement)
+            | TypedStatementEnum::Case(_, ref statement) => Self::breaks_out(statement),
+            TypedStatementEnum::Compound(ref items) => items.iter().any(|item| match item {
+                TypedStatementOrDeclaration::Statement(statement) => Self::breaks_out(statement),
+                TypedStatementOrDeclaration::LocalDeclaration(_) => false,
+            }),
+            TypedStatementEnum::If(_, ref then_stmt, ref else_stmt) => {
+                Self::breaks_out(then_stmt)
+                    || else_stmt
+                        .as_ref()
+                        .is_some_and(|else_stmt| Self::breaks_out(else_stmt))
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether a `default` label of a switch statement is in `statement`.
+    fn has_default(statement: &TypedStatement) -> bool {
+        match statement.node {
+            TypedStatementEnum::Case(None, _) => true,
+            TypedStatementEnum::Labeled(_, ref statement)
+            | TypedStatementEnum::Case(_, ref statement) => Self::has_default(statement),
+            TypedStatementEnum::Compound(ref items) => items.iter().any(|item| match item {
+                TypedStatementOrDeclaration::Statement(statement) => Self::has_default(statement),
+                TypedStatementOrDeclaration::LocalDeclaration(_) => false,
+            }),
+            TypedStatementEnum::If(_, ref then_stmt, ref else_stmt) => {
+                Self::has_default(then_stmt)
+                    || else_stmt
+                        .as_ref()
+                        .is_some_and(|else_stmt| Self::has_default(else_stmt))
+            }
+            TypedStatementEnum::While(_, ref body)
+            | TypedStatementEnum::DoWhile(ref body, _)
+            | TypedStatementEnum::For(_, _, _, ref body) => Self::has_default(body),
+            _ => false,
+        }
+    }
+
+    fn is_nonzero_constant(expr: &TypedExpression) -> bool {
+        match expr.node {
+            TypedExpressionEnum::ImplicitCast(ref expr) => Self::is_nonzero_constant(expr),
+            TypedExpressionEnum::Literal(ref literal) => match literal {
+                ExpressionEnum::IntegerConstant(value) => *value != 0,
+                ExpressionEnum::UnsignedIntegerConstant(value) => *value != 0,
+                ExpressionEnum::LongConstant(value) | ExpressionEnum::LongLongConstant(value) => {
+                    *value != 0
+                }
+                ExpressionEnum::UnsignedLongConstant(value)
+                | ExpressionEnum::UnsignedLongLongConstant(value) => *value != 0,
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,250 @@
+use crate::ast::{
+    AssignOperation, Attribute, BasicType, BinaryOperation, ExpressionEnum, FunctionSpecifier,
+    Span, StorageClassSpecifier, Type, UnaryOperation,
+};
+use serde::{Serialize, Serializer};
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Serialize, Debug, PartialEq, Clone)]
+pub enum TypedAST {
+    GlobalDeclaration(Vec<TypedDeclaration>),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct TypedDeclaration {
+    pub node: TypedDeclarationEnum,
+    pub span: Span,
+}
+
+#[derive(Serialize, Debug, PartialEq, Clone)]
+pub enum TypedDeclarationEnum {
+    Declaration(
+        /// typedef names resolved, and array length completed from the initializer
+        Type,
+        /// identifier (if it's a struct/union declaration, it might be None)
+        Option<String>,
+        /// initializer, converted to the declared type
+        Option<Box<TypedExpression>>,
+    ),
+    FunctionDefinition(
+        Vec<FunctionSpecifier>,
+        StorageClassSpecifier,
+        Vec<Attribute>,
+        /// return type
+        Box<BasicType>,
+        /// identifier
+        String,
+        /// parameters and their names, array parameters adjusted to pointers
+        Vec<(BasicType, Option<String>)>,
+        /// is variadic
+        bool,
+        /// body
+        TypedStatement,
+    ),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct TypedStatement {
+    pub node: TypedStatementEnum,
+    pub span: Span,
+}
+
+#[derive(Serialize, Debug, PartialEq, Clone)]
+pub enum TypedStatementEnum {
+    // labeled statement
+    Labeled(String, Box<TypedStatement>),
+    Case(
+        /// None represents default case
+        Option<Box<TypedExpression>>,
+        Box<TypedStatement>,
+    ),
+    // compound statement
+    Compound(Vec<TypedStatementOrDeclaration>),
+    // expression statement
+    Expression(Box<TypedExpression>),
+    // selection statement
+    If(
+        Box<TypedExpression>,
+        /// true statement
+        Box<TypedStatement>,
+        /// false statement
+        Option<Box<TypedStatement>>,
+    ),
+    Switch(Box<TypedExpression>, Box<TypedStatement>),
+    // iteration statement
+    While(Box<TypedExpression>, Box<TypedStatement>),
+    DoWhile(Box<TypedStatement>, Box<TypedExpression>),
+    For(
+        /// initialize clause
+        Option<Box<TypedForInitClause>>,
+        /// condition expression
+        Option<Box<TypedExpression>>,
+        /// iteration expression
+        Option<Box<TypedExpression>>,
+        /// loop statement
+        Box<TypedStatement>,
+    ),
+    // jump statement
+    Break,
+    Continue,
+    /// the value is converted to the return type of the function
+    Return(Option<Box<TypedExpression>>),
+    Goto(String),
+    // assembly statement
+    Asm(Box<TypedAsmStatement>),
+}
+
+#[derive(Serialize, Debug, PartialEq, Clone)]
+pub struct TypedAsmStatement {
+    pub template: String,
+    pub is_basic: bool,
+    pub is_volatile: bool,
+    /// modifiable lvalues
+    pub outputs: Vec<TypedAsmOperand>,
+    /// lvalues for operands in memory, rvalues otherwise
+    pub inputs: Vec<TypedAsmOperand>,
+    pub clobbers: Vec<String>,
+    pub labels: Vec<String>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Clone)]
+pub struct TypedAsmOperand {
+    pub name: Option<String>,
+    pub constraint: String,
+    pub expression: TypedExpression,
+}
+
+#[derive(Serialize, Debug, PartialEq, Clone)]
+pub enum TypedForInitClause {
+    Expression(TypedExpression),
+    ForDeclaration(Vec<TypedDeclaration>),
+}
+
+#[derive(Serialize, Debug, PartialEq, Clone)]
+pub enum TypedStatementOrDeclaration {
+    Statement(TypedStatement),
+    LocalDeclaration(TypedDeclaration),
+}
+
+#[derive(Serialize, Debug, PartialEq, Clone)]
+pub struct TypedExpression {
+    pub node: TypedExpressionEnum,
+    /// typedef names resolved, qualifiers are those of the designated object for an lvalue
+    pub expr_type: BasicType,
+    pub category: ValueCategory,
+    #[serde(skip)]
+    pub span: Span,
+}
+
+#[derive(Serialize, Debug, PartialEq, Clone, Copy)]
+pub enum ValueCategory {
+    /// designates an object, e.g. `a`, `*p`, `s.m`
+    LValue,
+    /// a value, or a function designator
+    RValue,
+}
+
+#[derive(Serialize, Debug, PartialEq, Clone)]
+pub enum TypedExpressionEnum {
+    Assignment(
+        AssignOperation,
+        /// left hand side
+        Box<TypedExpression>,
+        /// right hand side, converted to the type the operation is performed in
+        Box<TypedExpression>,
+    ),
+    Unary(UnaryOperation, Box<TypedExpression>),
+    Binary(
+        BinaryOperation,
+        /// left hand side
+        Box<TypedExpression>,
+        /// right hand side
+        Box<TypedExpression>,
+    ),
+    FunctionCall(
+        /// function
+        Box<TypedExpression>,
+        /// arguments, converted to the parameter types or promoted
+        Vec<TypedExpression>,
+    ),
+    TypeCast(BasicType, Box<TypedExpression>),
+    Conditional(
+        /// condition
+        Box<TypedExpression>,
+        /// true expression
+        Box<TypedExpression>,
+        /// false expression
+        Box<TypedExpression>,
+    ),
+    SizeofType(BasicType),
+    VaArg(
+        /// va_list
+        Box<TypedExpression>,
+        /// type of the argument
+        BasicType,
+    ),
+    Offsetof(
+        /// struct or union type
+        BasicType,
+        /// designator of the member, through nested members
+        Vec<String>,
+    ),
+    MemberOfObject(
+        /// object
+        Box<TypedExpression>,
+        /// member name
+        String,
+    ),
+    MemberOfPointer(
+        /// pointer
+        Box<TypedExpression>,
+        /// member name
+        String,
+    ),
+    ArraySubscript(
+        /// array or pointer, not converted
+        Box<TypedExpression>,
+        /// index
+        Vec<TypedExpression>,
+    ),
+    Identifier(String, Symbol),
+    /// a constant or a string literal, kept as parsed
+    Literal(ExpressionEnum),
+    /// conversion of the operand to `expr_type`, including array-to-pointer and
+    /// function-to-pointer conversions
+    ImplicitCast(Box<TypedExpression>),
+    Empty,
+}
+
+/// The declaration an identifier refers to.
+#[derive(Serialize, Debug, PartialEq, Clone, Copy)]
+pub enum Symbol {
+    /// parameter of the enclosing function, by position
+    Parameter(usize),
+    /// block scope variable, by the span of its declaration
+    Local(Span),
+    /// file scope variable, by the span of its declaration
+    Global(Span),
+    /// function, by the span of its first declaration
+    Function(Span),
+    /// a compiler builtin, like `__builtin_va_start` or `__atomic_load_n`
+    Builtin,
+}
+
+impl Serialize for TypedDeclaration {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.node.serialize(serializer)
+    }
+}
+
+impl Serialize for TypedStatement {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.node.serialize(serializer)
+    }
+}
@@ -0,0 +1,283 @@
+/// An operand constraint of GNU inline assembly, like `=r` or `+m`, for x86-64.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct AsmConstraint {
+    /// `+`, the output operand is also read
+    pub is_read_write: bool,
+    /// `&`, the output operand is written before all inputs are read
+    pub is_early_clobber: bool,
+    /// the output operand sharing the location of an input operand, for a digit
+    pub matching_output: Option<usize>,
+    pub allows_register: bool,
+    pub allows_memory: bool,
+    pub allows_immediate: bool,
+    /// the constraint codes in the syntax of LLVM, without any modifier
+    pub codes: String,
+}
+
+impl AsmConstraint {
+    /// Operands which can only be in memory are passed by address.
+    pub fn is_indirect(&self) -> bool {
+        self.allows_memory && !self.allows_register && !self.allows_immediate
+    }
+}
+
+/// Parse the constraint of an output operand, or of an input operand following `output_count`
+/// outputs. The error is the reason the constraint is invalid.
+pub fn parse_constraint(
+    constraint: &str,
+    is_output: bool,
+    output_count: usize,
+) -> Result<AsmConstraint, String> {
+    let mut result = AsmConstraint::default();
+    let mut chars = constraint.chars().peekable();
+    if is_output {
+        match chars.next() {
+            Some('=') => {}
+            Some('+') => result.is_read_write = true,
+            _ => return Err("output constraints must begin with `=` or `+`".to_string()),
+        }
+    }
+    while let Some(c) = chars.next() {
+        match c {
+            '&' if is_output => result.is_early_clobber = true,
+            // commutative with the next operand, which changes nothing for LLVM
+            '%' if !is_output => {}
+            '=' | '+' | '&' | '%' => {
+                return Err(match is_output {
+                    true => format!("`{}` must begin the constraint", c),
+                    false => format!("`{}` is only allowed in output constraints", c),
+                })
+            }
+            '0'..='9' if !is_output => {
+                let mut index = c.to_digit(10).unwrap() as usize;
+                while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+                    index = index * 10 + digit as usize;
+                    chars.next();
+                }
+                if index >= output_count {
+                    return Err(format!("operand {} is not an output operand", index));
+                }
+                result.matching_output = Some(index);
+            }
+            '0'..='9' => return Err("outputs cannot match other operands".to_string()),
+            'r' | 'q' | 'Q' | 'R' | 'A' | 'f' | 't' | 'u' | 'x' | 'y' => {
+                result.allows_register = true;
+                result.codes.push(c);
+            }
+            'a' | 'b' | 'c' | 'd' | 'S' | 'D' => {
+                result.allows_register = true;
+                result.codes.push_str(match c {
+                    'a' => "{ax}",
+                    'b' => "{bx}",
+                    'c' => "{cx}",
+                    'd' => "{dx}",
+                    'S' => "{si}",
+                    _ => "{di}",
+                });
+            }
+            'p' => {
+                result.allows_register = true;
+                result.codes.push('r');
+            }
+            'm' | 'o' | 'V' => {
+                result.allows_memory = true;
+                result.codes.push(c);
+            }
+            'i' | 'n' | 'I' | 'J' | 'K' | 'L' | 'M' | 'N' | 'e' | 'Z' | 's' => {
+                result.allows_immediate = true;
+                result.codes.push(c);
+            }
+            'g' | 'X' => {
+                result.allows_register = true;
+                result.allows_memory = true;
+                result.allows_immediate = !is_output;
+                result.codes.push_str(match (c, is_output) {
+                    ('g', true) => "rm",
+                    ('g', false) => "imr",
+                    _ => "X",
+                });
+            }
+            ',' => return Err("alternative constraints are not supported".to_string()),
+            _ => return Err(format!("unknown constraint letter `{}`", c)),
+        }
+    }
+
+    if result.matching_output.is_some() {
+        if !result.codes.is_empty() {
+            return Err("a matching constraint cannot be combined with other letters".to_string());
+        }
+    } else if result.codes.is_empty() {
+        return Err("the constraint allows no location for the operand".to_string());
+    } else if is_output && !result.allows_register && !result.allows_memory {
+        return Err("output operands must be in registers or memory".to_string());
+    }
+    Ok(result)
+}
+
+/// The LLVM name of a register or `memory` clobbered by inline assembly, None for `cc` as the
+/// flags are always clobbered on x86-64. The error is the reason the clobber is unknown.
+pub fn parse_clobber(clobber: &str) -> Result<Option<String>, String> {
+    let name = clobber.strip_prefix('%').unwrap_or(clobber);
+    match name {
+        "cc" => Ok(None),
+        "memory" => Ok(Some(name.to_string())),
+        _ if is_register(name) => Ok(Some(name.to_string())),
+        _ => Err(format!("`{}` is not a register", name)),
+    }
+}
+
+fn is_register(name: &str) -> bool {
+    const LEGACY: [&str; 8] = ["ax", "bx", "cx", "dx", "si", "di", "bp", "sp"];
+    const BYTES: [&str; 12] = [
+        "al", "bl", "cl", "dl", "ah", "bh", "ch", "dh", "sil", "dil", "bpl", "spl",
+    ];
+    // the digits of a register number, as written by GCC
+    let number = |digits: &str| {
+        digits
+            .parse::<u32>()
+            .ok()
+            .filter(|n| n.to_string() == digits)
+    };
+    if LEGACY.contains(&name) || BYTES.contains(&name) || name == "st" {
+        return true;
+    }
+    if let Some(rest) = name.strip_prefix('e').or_else(|| name.strip_prefix('r')) {
+        if LEGACY.contains(&rest) {
+            return true;
+        }
+    }
+    // r8 to r15, or their lower bits with a suffix
+    if let Some(rest) = name.strip_prefix('r') {
+        let digits = rest.strip_suffix(['d', 'w', 'b']).unwrap_or(rest);
+        if matches!(number(digits), Some(8..=15)) {
+            return true;
+        }
+    }
+    [("xmm", 16), ("ymm", 16), ("st", 8)]
+        .iter()
+        .any(|&(prefix, count)| {
+            name.strip_prefix(prefix)
+                .and_then(number)
+                .is_some_and(|n| n < count)
+        })
+}
+
+/// The template of basic assembly, which has no operands, in the syntax of LLVM.
+pub fn translate_basic_template(template: &str) -> String {
+    template.replace('$', "$$")
+}
+
+/// Translate the template of extended assembly to the syntax of LLVM, where operands are
+/// referenced as `$0` and `${0:k}` instead of `%0` and `%k0`. Operands are numbered outputs
+/// first, then inputs, then labels; `names` are the symbolic names of the outputs and inputs,
+/// referenced as `%[name]`. The error is the invalid reference and the reason.
+pub fn translate_template(
+    template: &str,
+    names: &[Option<String>],
+    labels: &[String],
+) -> Result<String, (String, String)> {
+    let mut result = String::with_capacity(template.len());
+    let mut chars = template.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        match c {
+            '$' => result.push_str("$$"),
+            '%' => {
+                let modifier = match chars.peek() {
+                    Some(&(_, modifier)) if modifier.is_ascii_alphabetic() => {
+                        chars.next();
+                        Some(modifier)
+                    }
+                    _ => None,
+                };
+                let index = match chars.next() {
+                    Some((_, '%')) if modifier.is_none() => {
+                        result.push('%');
+                        continue;
+                    }
+                    // a number unique to each instance of the assembly
+                    Some((_, '=')) if modifier.is_none() => {
+                        result.push_str("${:uid}");
+                        continue;
+                    }
+                    Some((_, c @ ('{' | '|' | '}'))) if modifier.is_none() => {
+                        result.push(c);
+                        continue;
+                    }
+                    Some((_, '[')) => {
+                        let end = match template[start..].find(']') {
+                            Some(end) => start + end,
+                            None => {
+                                return Err((
+                                    template[start..].to_string(),
+                                    "missing `]`".to_string(),
+                                ))
+                            }
+                        };
+                        while chars.next_if(|&(i, _)| i <= end).is_some() {}
+                        let name = template[start..end].split_once('[').unwrap().1;
+                        let index = match modifier {
+                            Some('l') => labels
+                                .iter()
+                                .position(|label| label == name)
+                                .map(|j| names.len() + j),
+                            _ => names
+                                .iter()
+                                .position(|operand| operand.as_deref() == Some(name)),
+                        };
+                        match index {
+                            Some(index) => index,
+                            None => {
+                                return Err((
+                                    template[start..=end].to_string(),
+                                    format!("no operand is named `{}`", name),
+                                ))
+                            }
+                        }
+                    }
+                    Some((_, digit)) if digit.is_ascii_digit() => {
+                        let mut index = digit.to_digit(10).unwrap() as usize;
+                        while let Some((_, digit)) = chars.next_if(|&(_, c)| c.is_ascii_digit()) {
+                            index = index * 10 + digit.to_digit(10).unwrap() as usize;
+                        }
+                        index
+                    }
+                    next => {
+                        let end = next.map_or(template.len(), |(i, c)| i + c.len_utf8());
+                        return Err((
+                            template[start..end].to_string(),
+                            "expected an operand number or `[name]` after `%`".to_string(),
+                        ));
+                    }
+                };
+                let reference = || {
+                    let end = chars.clone().next().map_or(template.len(), |(i, _)| i);
+                    template[start..end].to_string()
+                };
+                let is_label = modifier == Some('l');
+                let (first, count) = match is_label {
+                    true => (names.len(), labels.len()),
+                    false => (0, names.len()),
+                };
+                if index < first || index >= first + count {
+                    return Err((
+                        reference(),
+                        match is_label {
+                            true => format!("operand {} is not a label", index),
+                            false => format!(
+                                "operand {} is out of range, there are {} operands",
+                                index,
+                                names.len()
+                            ),
+                        },
+                    ));
+                }
+                match modifier {
+                    Some(modifier) => result.push_str(&format!("${{{}:{}}}", index, modifier)),
+                    None => result.push_str(&format!("${}", index)),
+                }
+            }
+            _ => result.push(c),
+        }
+    }
+    Ok(result)
+}
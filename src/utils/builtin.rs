@@ -0,0 +1,61 @@
+use crate::ast::{BaseType, BasicType, IntegerType, TypeQualifier};
+
+/// The parameter types and the return type of a compiler builtin whose arguments are converted
+/// as for a call to a function with a prototype, None for other names. The other builtins, like
+/// `__builtin_va_start` or `__atomic_load_n`, are generic in the types of their arguments.
+pub fn builtin_signature(name: &str) -> Option<(Vec<BasicType>, BasicType)> {
+    let t = |base_type: BaseType| BasicType {
+        qualifier: vec![],
+        base_type,
+    };
+    let int = || t(BaseType::SignedInteger(IntegerType::Int));
+    let long = || t(BaseType::SignedInteger(IntegerType::Long));
+    let unsigned = |int_type: IntegerType| t(BaseType::UnsignedInteger(int_type));
+    let void_ptr = |qualifier: Vec<TypeQualifier>| {
+        t(BaseType::Pointer(Box::new(BasicType {
+            qualifier,
+            base_type: BaseType::Void,
+        })))
+    };
+
+    Some(match name {
+        "__builtin_expect" => (vec![long(), long()], long()),
+        "__builtin_unreachable" | "__builtin_trap" => (vec![], t(BaseType::Void)),
+        "__builtin_popcount" | "__builtin_clz" | "__builtin_ctz" => {
+            (vec![unsigned(IntegerType::Int)], int())
+        }
+        "__builtin_popcountl" | "__builtin_clzl" | "__builtin_ctzl" => {
+            (vec![unsigned(IntegerType::Long)], int())
+        }
+        "__builtin_popcountll" | "__builtin_clzll" | "__builtin_ctzll" => {
+            (vec![unsigned(IntegerType::LongLong)], int())
+        }
+        "__builtin_bswap32" => (vec![unsigned(IntegerType::Int)], unsigned(IntegerType::Int)),
+        "__builtin_bswap64" => (
+            vec![unsigned(IntegerType::Long)],
+            unsigned(IntegerType::Long),
+        ),
+        "__builtin_memcpy" => (
+            vec![
+                void_ptr(vec![]),
+                void_ptr(vec![TypeQualifier::Const]),
+                unsigned(IntegerType::Long),
+            ],
+            void_ptr(vec![]),
+        ),
+        "__builtin_memset" => (
+            vec![void_ptr(vec![]), int(), unsigned(IntegerType::Long)],
+            void_ptr(vec![]),
+        ),
+        _ => return None,
+    })
+}
+
+/// `__builtin_add_overflow` and the like store the result of the operation on their first two
+/// arguments through the third one, and return whether it overflowed the type it points to.
+pub fn is_overflow_builtin(name: &str) -> bool {
+    matches!(
+        name,
+        "__builtin_add_overflow" | "__builtin_sub_overflow" | "__builtin_mul_overflow"
+    )
+}
@@ -148,32 +148,6 @@ impl CompileErr {
         }
     }
 
-    pub fn array_dimension_mismatch(expect: usize, found: usize, span: Span) -> CompileErr {
-        CompileErr {
-            code: "E013".to_string(),
-            message: "array dimension mismatch".to_string(),
-            label: "this array subscript is invalid".to_string(),
-            span,
-            notes: Some(format!(
-                "array has `{}` dimension, but found `{}` subscript",
-                expect, found
-            )),
-        }
-    }
-
-    pub fn pointer_dimension_mismatch(expect: usize, found: usize, span: Span) -> CompileErr {
-        CompileErr {
-            code: "E014".to_string(),
-            message: "pointer dimension mismatch".to_string(),
-            label: "pointer dimension mismatch here".to_string(),
-            span,
-            notes: Some(format!(
-                "pointer dimension mismatch, expect {}, found {}",
-                expect, found
-            )),
-        }
-    }
-
     pub fn invalid_left_value(name: String, span: Span) -> CompileErr {
         CompileErr {
             code: "E015".to_string(),
@@ -288,15 +262,351 @@ impl CompileErr {
         }
     }
 
+    pub fn generic_selection_no_match(type_name: String, span: Span) -> CompileErr {
+        CompileErr {
+            code: "E023".to_string(),
+            message: "no generic association matches the controlling expression".to_string(),
+            label: format!("controlling expression of type `{}` here", type_name),
+            span,
+            notes: Some(format!(
+                "add an association for `{}` or a `default` association",
+                type_name
+            )),
+        }
+    }
+
+    pub fn generic_selection_multiple_match(type_name: String, span: Span) -> CompileErr {
+        CompileErr {
+            code: "E024".to_string(),
+            message: "multiple generic associations match the controlling expression".to_string(),
+            label: format!("controlling expression of type `{}` here", type_name),
+            span,
+            notes: Some(format!(
+                "more than one association is compatible with `{}`",
+                type_name
+            )),
+        }
+    }
+
+    pub fn array_size_missing(name: String, span: Span) -> CompileErr {
+        CompileErr {
+            code: "E025".to_string(),
+            message: format!("array size missing in `{}`", name),
+            label: "array declared here".to_string(),
+            span,
+            notes: Some(
+                "the size may only be omitted when the array is initialized from a string literal"
+                    .to_string(),
+            ),
+        }
+    }
+
+    pub fn invalid_string_initializer(type_name: String, span: Span) -> CompileErr {
+        CompileErr {
+            code: "E026".to_string(),
+            message: "array of inappropriate type initialized from string literal".to_string(),
+            label: format!("cannot initialize `{}` from this string literal", type_name),
+            span,
+            notes: None,
+        }
+    }
+
+    pub fn string_initializer_too_long(expect: usize, found: usize, span: Span) -> CompileErr {
+        CompileErr {
+            code: "E027".to_string(),
+            message: "initializer-string for array is too long".to_string(),
+            label: "string literal here".to_string(),
+            span,
+            notes: Some(format!(
+                "array has {} elements, but the string literal has {}",
+                expect, found
+            )),
+        }
+    }
+
+    pub fn invalid_va_list(type_name: String, span: Span) -> CompileErr {
+        CompileErr {
+            code: "E028".to_string(),
+            message: "expected a `va_list`".to_string(),
+            label: format!("found `{}` here", type_name),
+            span,
+            notes: Some("`va_list` is declared in <stdarg.h>".to_string()),
+        }
+    }
+
+    pub fn va_start_in_non_variadic_function(name: String, span: Span) -> CompileErr {
+        CompileErr {
+            code: "E029".to_string(),
+            message: "`va_start` used in function with fixed arguments".to_string(),
+            label: "`va_start` used here".to_string(),
+            span,
+            notes: Some(format!("function `{}` is not variadic", name)),
+        }
+    }
+
+    pub fn not_a_function(name: String, type_name: String, span: Span) -> CompileErr {
+        CompileErr {
+            code: "E030".to_string(),
+            message: format!("called object `{}` is not a function", name),
+            label: format!("`{}` has type `{}`", name, type_name),
+            span,
+            notes: None,
+        }
+    }
+
+    pub fn invalid_subscript(type_name: String, span: Span) -> CompileErr {
+        CompileErr {
+            code: "E031".to_string(),
+            message: "subscripted value is neither array nor pointer".to_string(),
+            label: format!("value of type `{}` subscripted here", type_name),
+            span,
+            notes: None,
+        }
+    }
+
+    pub fn non_integer_subscript(type_name: String, span: Span) -> CompileErr {
+        CompileErr {
+            code: "E032".to_string(),
+            message: "array subscript is not an integer".to_string(),
+            label: format!("subscript of type `{}` here", type_name),
+            span,
+            notes: None,
+        }
+    }
+
+    pub fn invalid_condition(type_name: String, span: Span) -> CompileErr {
+        CompileErr {
+            code: "E033".to_string(),
+            message: format!("used type `{}` where a scalar is required", type_name),
+            label: "condition here".to_string(),
+            span,
+            notes: None,
+        }
+    }
+
+    pub fn return_value_in_void_function(name: String, span: Span) -> CompileErr {
+        CompileErr {
+            code: "E034".to_string(),
+            message: "`return` with a value in function returning void".to_string(),
+            label: "value returned here".to_string(),
+            span,
+            notes: Some(format!("function `{}` returns `void`", name)),
+        }
+    }
+
+    pub fn conditional_type_mismatch(lhs: String, rhs: String, span: Span) -> CompileErr {
+        CompileErr {
+            code: "E035".to_string(),
+            message: "type mismatch in conditional expression".to_string(),
+            label: "conditional expression here".to_string(),
+            span,
+            notes: Some(format!(
+                "the second operand has type `{}`, but the third has type `{}`",
+                lhs, rhs
+            )),
+        }
+    }
+
+    pub fn assignment_to_read_only(name: String, span: Span) -> CompileErr {
+        CompileErr {
+            code: "E036".to_string(),
+            message: format!("assignment to read-only location `{}`", name),
+            label: "modified here".to_string(),
+            span,
+            notes: None,
+        }
+    }
+
+    pub fn discards_qualifier(
+        from: String,
+        to: String,
+        qualifier: String,
+        span: Span,
+    ) -> CompileErr {
+        CompileErr {
+            code: "E037".to_string(),
+            message: format!(
+                "conversion from `{}` to `{}` discards {} qualifier",
+                from, to, qualifier
+            ),
+            label: "converted here".to_string(),
+            span,
+            notes: None,
+        }
+    }
+
+    pub fn invalid_builtin_argument(
+        name: String,
+        type_name: String,
+        expected: String,
+        span: Span,
+    ) -> CompileErr {
+        CompileErr {
+            code: "E038".to_string(),
+            message: format!("invalid argument of type `{}` to `{}`", type_name, name),
+            label: "argument here".to_string(),
+            span,
+            notes: Some(expected),
+        }
+    }
+
+    pub fn noreturn_function_returns(name: String, span: Span) -> CompileErr {
+        CompileErr {
+            code: "W003".to_string(),
+            message: format!("function `{}` declared `_Noreturn` can return", name),
+            label: "returns here".to_string(),
+            span,
+            notes: Some("the behavior is undefined if it returns".to_string()),
+        }
+    }
+
+    pub fn invalid_attribute_argument(name: String, expected: String, span: Span) -> CompileErr {
+        CompileErr {
+            code: "E040".to_string(),
+            message: format!("invalid argument to attribute `{}`", name),
+            label: "attribute here".to_string(),
+            span,
+            notes: Some(expected),
+        }
+    }
+
+    pub fn ignored_attribute(name: String, reason: String, span: Span) -> CompileErr {
+        CompileErr {
+            code: "W001".to_string(),
+            message: format!("attribute `{}` ignored", name),
+            label: "ignored here".to_string(),
+            span,
+            notes: Some(reason),
+        }
+    }
+
+    pub fn invalid_thread_local(name: String, span: Span) -> CompileErr {
+        CompileErr {
+            code: "E041".to_string(),
+            message: format!("`{}` cannot be `_Thread_local`", name),
+            label: "declared here".to_string(),
+            span,
+            notes: Some(
+                "only variables with static storage duration are thread-local, at block scope \
+                 they must be declared `static` or `extern`"
+                    .to_string(),
+            ),
+        }
+    }
+
+    pub fn non_constant_initializer(name: String, span: Span) -> CompileErr {
+        CompileErr {
+            code: "E042".to_string(),
+            message: format!("initializer of `{}` is not a constant expression", name),
+            label: "not a constant".to_string(),
+            span,
+            notes: Some(
+                "variables with static storage duration are initialized before the program starts"
+                    .to_string(),
+            ),
+        }
+    }
+
+    pub fn invalid_asm_constraint(constraint: String, reason: String, span: Span) -> CompileErr {
+        CompileErr {
+            code: "E043".to_string(),
+            message: format!("invalid constraint `{}` in asm", constraint),
+            label: "operand here".to_string(),
+            span,
+            notes: Some(reason),
+        }
+    }
+
+    pub fn invalid_asm_operand_reference(
+        reference: String,
+        reason: String,
+        span: Span,
+    ) -> CompileErr {
+        CompileErr {
+            code: "E044".to_string(),
+            message: format!("invalid operand reference `{}` in asm template", reference),
+            label: "template here".to_string(),
+            span,
+            notes: Some(reason),
+        }
+    }
+
+    pub fn unknown_asm_clobber(clobber: String, span: Span) -> CompileErr {
+        CompileErr {
+            code: "E045".to_string(),
+            message: format!("unknown register name `{}` in asm clobbers", clobber),
+            label: "clobbered here".to_string(),
+            span,
+            notes: Some("clobbers are x86-64 registers, `memory` or `cc`".to_string()),
+        }
+    }
+
+    pub fn undefined_label(label: String, span: Span) -> CompileErr {
+        CompileErr {
+            code: "E046".to_string(),
+            message: format!("label `{}` used but not defined", label),
+            label: "jumps to the label here".to_string(),
+            span,
+            notes: None,
+        }
+    }
+
+    pub fn non_constant_array_size(span: Span) -> CompileErr {
+        CompileErr {
+            code: "E047".to_string(),
+            message: "size of array is not a nonnegative integer constant".to_string(),
+            label: "array size here".to_string(),
+            span,
+            notes: Some("variable length arrays are not supported".to_string()),
+        }
+    }
+
+    pub fn incompatible_pointer_types(from: String, to: String, span: Span) -> CompileErr {
+        CompileErr {
+            code: "E048".to_string(),
+            message: format!(
+                "conversion from `{}` to `{}` between incompatible pointer types",
+                from, to
+            ),
+            label: "converted here".to_string(),
+            span,
+            notes: Some(
+                "the types pointed to by the pointed-to types must be qualified alike".to_string(),
+            ),
+        }
+    }
+
+    pub fn missing_return(name: String, span: Span) -> CompileErr {
+        CompileErr {
+            code: "W002".to_string(),
+            message: format!("control reaches end of non-void function `{}`", name),
+            label: "function ends here".to_string(),
+            span,
+            notes: Some("the value returned is undefined".to_string()),
+        }
+    }
+
+    /// Warnings have a code starting with `W`, they do not stop the compilation.
+    pub fn is_warning(&self) -> bool {
+        self.code.starts_with('W')
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
     pub fn to_diagnostic<FileId>(&self, file_id: FileId) -> Diagnostic<FileId> {
-        Diagnostic::error()
-            .with_message(self.message.clone())
-            .with_code(self.code.clone())
-            .with_labels(vec![Label::primary(
-                file_id,
-                (self.span.start)..(self.span.end),
-            )
-            .with_message(self.label.clone())])
-            .with_notes(vec![self.notes.clone().unwrap_or_else(|| "".to_string())])
+        match self.is_warning() {
+            true => Diagnostic::warning(),
+            false => Diagnostic::error(),
+        }
+        .with_message(self.message.clone())
+        .with_code(self.code.clone())
+        .with_labels(vec![Label::primary(
+            file_id,
+            (self.span.start)..(self.span.end),
+        )
+        .with_message(self.label.clone())])
+        .with_notes(vec![self.notes.clone().unwrap_or_default()])
     }
 }
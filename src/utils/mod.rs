@@ -1,3 +1,5 @@
+pub mod asm;
+pub mod builtin;
 pub mod error;
 
 pub use error::CompileErr;
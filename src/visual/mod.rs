@@ -2,14 +2,8 @@ use serde::Serialize;
 
 use super::ast::AST;
 use super::parse::Parse;
-use super::preprocess::preprocess;
+use super::preprocess::{preprocess, IncludePaths, SourceMap};
 
-#[derive(Debug, Serialize)]
-struct ParseTreeNode {
-    id: String,
-    label: String,
-    children: Vec<ParseTreeNode>,
-}
 #[derive(Debug, Serialize)]
 struct VisualResult {
     error: bool,
@@ -23,9 +17,8 @@ pub fn compile_result(code: &str) -> String {
         message: String::from(""),
         ast: Box::new(AST::GlobalDeclaration(vec![])),
     };
-    let include_dirs = vec![];
-    match preprocess(code, &include_dirs) {
-        Ok(code) => match Parse::new().parse(&code) {
+    match preprocess(code, "<stdin>", &IncludePaths::new(), &[]) {
+        Ok((code, _)) => match Parse::new().parse(SourceMap::new("<stdin>", &code).code()) {
             Ok(ast) => {
                 result.ast = ast;
             }
@@ -0,0 +1,57 @@
+#include <stdio.h>
+
+int counter;
+
+int add(int a, int b) {
+    int sum;
+    asm("movl %1, %0\n\t"
+        "addl %2, %0"
+        : "=&r"(sum)
+        : "r"(a), "r"(b));
+    return sum;
+}
+
+long swap_bytes(long x) {
+    asm("bswap %q[value]" : [value] "+r"(x));
+    return x;
+}
+
+void increment(int *p) {
+    asm volatile("incl %0" : "+m"(*p) : : "memory");
+}
+
+int cpuid_max() {
+    int eax;
+    int ebx;
+    int ecx;
+    int edx;
+    asm volatile("cpuid" : "=a"(eax), "=b"(ebx), "=c"(ecx), "=d"(edx) : "a"(0));
+    return eax > 0;
+}
+
+int is_zero(int x) {
+    asm goto("testl %0, %0\n\t"
+             "jz %l[zero]"
+             :
+             : "r"(x)
+             : "cc"
+             : zero);
+    return 0;
+zero:
+    return 1;
+}
+
+int main() {
+    int shifted;
+    asm("nop");
+    __asm__ __volatile__("" : : : "memory");
+
+    increment(&counter);
+    increment(&counter);
+    asm("shll $%c1, %0" : "=r"(shifted) : "i"(4), "0"(3));
+
+    printf("%d %d %d\n", add(40, 2), counter, shifted);
+    printf("%lx\n", swap_bytes(0x1122334455667788l));
+    printf("%d %d %d\n", is_zero(0), is_zero(5), cpuid_max());
+    return 0;
+}
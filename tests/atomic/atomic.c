@@ -0,0 +1,52 @@
+#include <stdatomic.h>
+#include <stdio.h>
+
+volatile int status;
+atomic_int counter;
+atomic_bool ready;
+_Atomic double total;
+int *_Atomic head;
+
+struct device {
+    int control;
+    int data;
+};
+
+int poll(volatile struct device *dev) {
+    dev->control = 1;
+    while (dev->control != 1) {
+    }
+    return dev->data;
+}
+
+int main() {
+    int values[3];
+    int expected = 5;
+    struct device dev;
+    dev.data = 42;
+    values[1] = 7;
+
+    counter++;
+    ++counter;
+    counter += 3;
+    counter *= 2;
+    counter <<= 1;
+    printf("%d\n", counter);
+
+    atomic_store(&counter, 5);
+    printf("%d %d\n", atomic_fetch_add(&counter, 2), atomic_load(&counter));
+    printf("%d\n", atomic_exchange(&counter, 9));
+    printf("%d %d\n", atomic_compare_exchange_strong(&counter, &expected, 1), expected);
+    printf("%d %d\n", atomic_compare_exchange_strong(&counter, &expected, 1), counter);
+    atomic_fetch_or_explicit(&counter, 6, memory_order_relaxed);
+    atomic_thread_fence(memory_order_seq_cst);
+
+    ready = counter > 0;
+    total += 1.5;
+    total -= 0.25;
+    head = values;
+    head++;
+    status = poll(&dev);
+    printf("%d %f %d %d\n", ready, total, *head, status);
+    return 0;
+}
@@ -0,0 +1,40 @@
+#include <stdio.h>
+
+struct Packed {
+    char tag;
+    int value;
+} __attribute__((packed));
+
+struct Aligned {
+    char tag;
+    int value __attribute__((aligned(8)));
+};
+
+int counter __attribute__((aligned(16), section(".data.counter"))) = 1;
+int fallback __attribute__((weak)) = 7;
+
+void log_message(const char *format, ...) __attribute__((format(printf, 1, 2), noinline));
+
+__attribute__((constructor)) static void setup() {
+    counter = counter + 10;
+}
+
+__attribute__((cold)) void report(int value) {
+    printf("report %d\n", value);
+}
+
+int main() {
+    struct Packed p;
+    struct Aligned a;
+    long buffer[2] __attribute__((aligned(32)));
+    p.tag = 'p';
+    p.value = 42;
+    a.tag = 'a';
+    a.value = 7;
+    buffer[0] = 1;
+    printf("%d %d\n", sizeof(struct Packed), sizeof(struct Aligned));
+    printf("%c %d %c %d\n", p.tag, p.value, a.tag, a.value);
+    printf("%d %d\n", counter, fallback);
+    report(counter + fallback);
+    return 0;
+}
@@ -0,0 +1,63 @@
+#include <stdio.h>
+
+struct header {
+    char tag;
+    int length;
+    struct {
+        short kind;
+        long id;
+    } owner;
+};
+
+int checked_add(int a, int b) {
+    int sum;
+    if (__builtin_add_overflow(a, b, &sum) == 1) {
+        __builtin_trap();
+    }
+    return sum;
+}
+
+int sign(int x) {
+    if (x > 0) {
+        return 1;
+    }
+    if (x < 0) {
+        return -1;
+    }
+    if (x == 0) {
+        return 0;
+    }
+    __builtin_unreachable();
+}
+
+int main() {
+    int product;
+    unsigned int big;
+    char buffer[8];
+    char copy[8];
+    unsigned long n = 3ul;
+    unsigned int bits = 255u;
+
+    if (__builtin_expect(n == 3, 1) == 1) {
+        printf("%d %d\n", checked_add(40, 2), sign(-7));
+    }
+
+    printf("%d ", __builtin_mul_overflow(65536, 65536, &product));
+    printf("%d ", __builtin_add_overflow(4000000000u, 400000000u, &big));
+    printf("%u %d\n", big, __builtin_sub_overflow(5, 3, &product));
+
+    printf("%d %d %d ", __builtin_popcount(bits), __builtin_clz(bits), __builtin_ctz(bits << 3));
+    printf("%d %d\n", __builtin_popcountll(n), __builtin_ctzl(n << 40));
+    printf("%x ", __builtin_bswap32(0x11223344u));
+    printf("%lx\n", __builtin_bswap64(0x1122334455667788ul));
+
+    __builtin_memset(buffer, 'a', 7);
+    __builtin_memset(buffer + 7, 0, 1);
+    __builtin_memcpy(copy, buffer, 8);
+    printf("%s\n", copy);
+
+    printf("%d %d %d\n", __builtin_offsetof(struct header, length),
+           __builtin_offsetof(struct header, owner),
+           __builtin_offsetof(struct header, owner.id));
+    return 0;
+}
@@ -0,0 +1,30 @@
+#include <stdio.h>
+
+int counter = 0;
+
+int next() {
+    counter++;
+    return counter;
+}
+
+int main() {
+    int i;
+    int j;
+    int n = 5;
+    int x;
+    int arr[3];
+    int *p;
+    arr[0] = 7;
+    arr[1] = 8;
+    arr[2] = 9;
+    for (i = 0, j = n; i < j; i++, j--) {
+        printf("%d %d\n", i, j);
+    }
+    x = (next(), next(), next() * 10);
+    printf("%d %d\n", x, counter);
+    p = (x++, arr);
+    printf("%d %d\n", p[1], x);
+    x = 1, n = 2;
+    printf("%d %d\n", x, n);
+    return 0;
+}
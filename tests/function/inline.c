@@ -0,0 +1,27 @@
+#include <stdio.h>
+#include <stdlib.h>
+
+static inline int square(int x) { return x * x; }
+
+// an inline definition, any other translation unit may define `cube` too
+inline int cube(int x) { return x * x * x; }
+
+inline int twice(int x);
+int twice(int x) { return x + x; }
+
+// `extern` makes this inline definition the external definition of `half`
+inline int half(int x) { return x / 2; }
+extern int half(int x);
+
+_Noreturn void fail(const char *message) {
+    printf("%s\n", message);
+    exit(1);
+}
+
+int main() {
+    printf("%d %d %d %d\n", square(3), cube(2), twice(5), half(8));
+    if (square(2) != 4) {
+        fail("square");
+    }
+    return 0;
+}
@@ -0,0 +1,13 @@
+#include <stdio.h>
+
+// warned about (W002), but still compiled: the value returned is undefined
+double half(int x) {
+    if (x > 0) {
+        return x / 2.0;
+    }
+}
+
+// `main` returns 0 when it reaches its end
+int main() {
+    printf("%f\n", half(5));
+}
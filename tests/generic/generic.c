@@ -0,0 +1,42 @@
+#include <stdio.h>
+
+#define abs(x) _Generic((x), int: iabs, double: dabs, default: labs)(x)
+#define type_name(x) _Generic((x), char: "char", int: "int", long: "long", double: "double", char *: "pointer to char", default: "other")
+#define constness(x) _Generic((x), char *: "char *", const char *: "const char *")
+
+int iabs(int x) {
+    if (x < 0) {
+        return -x;
+    }
+    return x;
+}
+
+double dabs(double x) {
+    if (x < 0.0) {
+        return -x;
+    }
+    return x;
+}
+
+long labs(long x) {
+    if (x < 0l) {
+        return -x;
+    }
+    return x;
+}
+
+int main() {
+    int i = -3;
+    double d = -2.5;
+    long l = -7l;
+    char s[10];
+    printf("%d %f %ld\n", abs(i), abs(d), abs(l));
+    printf("%s %s %s\n", type_name(i), type_name(s), type_name(1.0f));
+    printf("%d\n", _Generic(i++, int: 1, default: 0));
+    printf("%d\n", i);
+    printf("%s %d\n", type_name('a'), sizeof('a') == sizeof(int));
+    const char *c = "c";
+    char a[2];
+    printf("%s %s\n", constness(c), constness(a));
+    return 0;
+}
@@ -0,0 +1,27 @@
+#include <stdio.h>
+
+_Thread_local int counter = 10;
+static _Thread_local long total;
+_Thread_local int shared = 3;
+
+int next_id() {
+    static int id = 100;
+    static _Thread_local int calls;
+    calls = calls + 1;
+    id = id + calls;
+    return id;
+}
+
+int read_shared() {
+    extern _Thread_local int shared;
+    return shared;
+}
+
+int main() {
+    int first = next_id();
+    int second = next_id();
+    counter = counter + 1;
+    total = total + counter;
+    printf("%d %d %d %ld %d\n", first, second, counter, total, read_shared());
+    return 0;
+}
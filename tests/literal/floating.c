@@ -0,0 +1,12 @@
+#include <stdio.h>
+
+int main() {
+    double a = 0x1.8p3;
+    double b = 0x.8p1;
+    float c = 0x1.000003p0f;
+    double c_promoted = c;
+    double d = 1.5 + 0x1p-2;
+    printf("%f %f %.10f %f\n", a, b, c_promoted, d);
+    printf("%a %a\n", 0x1.fffffffffffff8p0, 0x1p-1074);
+    return 0;
+}
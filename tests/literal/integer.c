@@ -0,0 +1,17 @@
+#include <stdio.h>
+
+int main() {
+    long a = 3000000000;
+    int e = -1;
+    printf("%ld %ld %ld %ld\n", sizeof(3000000000), sizeof(0xFFFFFFFF), sizeof(10ll), sizeof(1));
+    printf("%ld %u %lu %lld\n", a, 0xFFFFFFFF, 0xFFFFFFFFFFFFFFFF, 0x7fffffffffffffffll);
+    if (0xFFFFFFFF > e) {
+        printf("signed\n");
+    } else {
+        printf("unsigned\n");
+    }
+    if (10ll + 1 == 11l) {
+        printf("long long\n");
+    }
+    return 0;
+}
@@ -0,0 +1,32 @@
+#include <stdio.h>
+
+char greeting[] = "hello";
+char padded[8] = "abc";
+
+int main() {
+    char s[] = "café";
+    char t[4] = "xyz";
+    int w[] = L"été";
+    unsigned short u[] = u"\U0001F600";
+    unsigned int v[] = U"\U0001F600";
+    char *p = u8"\xff\101";
+    int i = 0;
+    char a = 'a';
+    int b = '\xff';
+    int c0 = p[0];
+    int c1 = p[1];
+    int c2 = p[2];
+    printf("%s %ld %s %ld\n", greeting, sizeof(greeting), padded, sizeof(padded));
+    printf("%s %ld %s %ld\n", s, sizeof(s), t, sizeof(t));
+    printf("%d %d %d %d %ld\n", w[0], w[1], w[2], w[3], sizeof(w));
+    printf("%x %x %x %ld\n", u[0], u[1], u[2], sizeof(u));
+    printf("%x %x %ld\n", v[0], v[1], sizeof(v));
+    printf("%d %d %d\n", c0, c1, c2);
+    printf("%d %d %d %d\n", a + 0, b, 'ab', L'é');
+    printf("%ld %ld %ld %ld\n", sizeof('ab'), sizeof(L'a'), sizeof(u'a'), sizeof(U'a'));
+    while (s[i] != 0) {
+        i++;
+    }
+    printf("%d\n", i);
+    return 0;
+}
@@ -4,13 +4,14 @@ extern crate cc99;
 mod tests {
     use crate::*;
     use cc99::generator::Generator;
+    use cc99::sema::Sema;
     use cc99::*;
     use inkwell::context::Context;
     use walkdir::WalkDir;
 
     #[test]
     fn parse_test_file() {
-        let include_dirs = vec![];
+        let include_paths = IncludePaths::new();
         for entry in WalkDir::new("./tests")
             .into_iter()
             .filter_map(Result::ok)
@@ -27,8 +28,9 @@ mod tests {
             }
             println!(">>> Start compiling {} <<<", source_path);
 
-            let res = preprocess_file(source_path, &include_dirs).unwrap();
-            let ast = Parse::new().parse(&res).unwrap();
+            let res = preprocess_file(source_path, &include_paths, &[]).unwrap().0;
+            let source_map = SourceMap::new(source_path, &res);
+            let ast = Parse::new().parse(source_map.code()).unwrap();
             println!("{}", serde_json::to_string(&ast).unwrap());
             println!(">>> Finish Parsing <<<");
         }
@@ -36,15 +38,24 @@ mod tests {
 
     #[test]
     fn test_gen() {
-        let code = preprocess_file("./tests/global/decl2.c", vec![].as_slice()).unwrap();
+        let code = preprocess_file("./tests/global/decl2.c", &IncludePaths::new(), &[])
+            .unwrap()
+            .0;
+        let source_map = SourceMap::new("./tests/global/decl2.c", &code);
+        let code = source_map.code();
         let ast = Parse::new()
-            .parse(&code)
+            .parse(code)
             .unwrap_or_else(|e| panic!("Parse failed:\n{}", e));
+        let typed_ast = Sema::new(code)
+            .analyze(&ast)
+            .unwrap_or_else(|e| panic!("Semantic analysis failed:\n{:?}", e));
 
         let context = Context::create();
-        let mut code_gen = Generator::new(&context, "./tests/global/decl2.c", &code);
-        code_gen.gen(&ast);
-        code_gen.out_asm_or_obj(false, None, inkwell::OptimizationLevel::None);
+        let mut code_gen = Generator::new(&context, "./tests/global/decl2.c", &source_map);
+        code_gen.gen(&typed_ast);
+        code_gen
+            .out_asm_or_obj(false, None, inkwell::OptimizationLevel::None)
+            .unwrap();
         code_gen.out_bc(None);
     }
 }
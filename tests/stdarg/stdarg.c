@@ -0,0 +1,45 @@
+#include <stdarg.h>
+#include <stdio.h>
+
+int vsum(int count, va_list ap) {
+    int total = 0;
+    int i;
+    for (i = 0; i < count; i++) {
+        total += va_arg(ap, int);
+    }
+    return total;
+}
+
+int sum(int count, ...) {
+    va_list ap;
+    int total;
+    va_start(ap, count);
+    total = vsum(count, ap);
+    va_end(ap);
+    return total;
+}
+
+double average(int count, ...) {
+    va_list ap;
+    va_list copy;
+    double total = 0;
+    int i;
+    va_start(ap, count);
+    va_copy(copy, ap);
+    for (i = 0; i < count; i++) {
+        total += va_arg(copy, double);
+    }
+    va_end(copy);
+    va_end(ap);
+    return total / count;
+}
+
+int main() {
+    float f = 2.5f;
+    char c = '\x04';
+    double avg = average(3, 1.0, f, 6.5);
+    printf("%d\n", sum(5, 1, 2, 3, c, 5));
+    printf("%d\n", sum(10, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10));
+    printf("%f %f\n", avg, f);
+    return 0;
+}
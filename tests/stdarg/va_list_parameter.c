@@ -0,0 +1,29 @@
+#include <stdarg.h>
+#include <stdio.h>
+
+// `va_arg` on a `va_list` received as a parameter, which LLVM's `va_arg` instruction
+// miscompiles at -O0: the scalars must be fetched from the register save area and the
+// overflow area by hand
+double vmix(int count, va_list ap) {
+    double total = 0;
+    int i;
+    for (i = 0; i < count; i++) {
+        total += va_arg(ap, int) * va_arg(ap, double);
+    }
+    return total;
+}
+
+double mix(int count, ...) {
+    va_list ap;
+    double total;
+    va_start(ap, count);
+    total = vmix(count, ap);
+    va_end(ap);
+    return total;
+}
+
+int main() {
+    // more arguments than registers, so that some are passed in memory
+    printf("%f\n", mix(10, 1, 0.5, 2, 1.5, 3, 2.5, 4, 3.5, 5, 4.5, 6, 5.5, 7, 6.5, 8, 7.5, 9, 8.5, 10, 9.5));
+    return 0;
+}
@@ -0,0 +1,28 @@
+#include <stdio.h>
+
+int calls = 0;
+
+int next_index() {
+    calls++;
+    return 1;
+}
+
+int main() {
+    int i = 5;
+    int a[3];
+    int *p;
+    int size;
+    a[0] = 0;
+    a[1] = 0;
+    a[2] = 0;
+    ++i;
+    printf("%d\n", i);
+    a[next_index()] += 4;
+    printf("%d %d\n", a[1], calls);
+    p = a;
+    *p++ = 9;
+    printf("%d %d\n", a[0], *p);
+    size = sizeof(i = next_index());
+    printf("%d %d %d\n", size, i, calls);
+    return 0;
+}